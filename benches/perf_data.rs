@@ -0,0 +1,57 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use icingaplugin_rs::check::{CheckResult, Metric, PerfData};
+use std::hint::black_box;
+
+/// The numbers of metrics benchmarked, up to a per-interface check of a large switch stack
+const SIZES: [usize; 3] = [10, 1_000, 10_000];
+
+
+/// Returns the metric of interface `i`
+fn metric(i: usize) -> Metric {
+    Metric::numeric(format!("eth{}_rx", i), i as u64 * 1024).uom("B").warning("1000000").critical("2000000")
+}
+
+
+fn construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("construction");
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::new("push", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut pd = PerfData::new();
+                for i in 0..size {
+                    pd.push(metric(i));
+                }
+                black_box(pd)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("with_capacity", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut pd = PerfData::with_capacity(size);
+                for i in 0..size {
+                    pd.push(metric(i));
+                }
+                black_box(pd)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("collect", size), &size, |b, &size| {
+            b.iter(|| black_box((0..size).map(metric).collect::<PerfData>()))
+        });
+    }
+    group.finish();
+}
+
+
+fn display(c: &mut Criterion) {
+    let mut group = c.benchmark_group("display");
+    for size in SIZES {
+        let cr = CheckResult::ok("all interfaces up").set_perf_data((0..size).map(metric).collect());
+        group.bench_with_input(BenchmarkId::new("check_result", size), &cr, |b, cr| {
+            b.iter(|| black_box(cr.to_string()))
+        });
+    }
+    group.finish();
+}
+
+
+criterion_group!(benches, construction, display);
+criterion_main!(benches);
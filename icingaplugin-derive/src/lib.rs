@@ -0,0 +1,219 @@
+//! Derive macros for `icingaplugin-rs`, re-exported by its `derive` feature
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, GenericArgument, Lit, LitChar, LitStr,
+          PathArguments, Type};
+
+/// The attributes mapping a field to a standard argument, with the name of the `StandardArgs`
+/// field and whether it is optional there
+const STANDARD: [(&str, bool); 6] = [
+    ("warning", true),
+    ("critical", true),
+    ("timeout", false),
+    ("hostname", true),
+    ("verbose", false),
+    ("output_format", false),
+];
+
+
+/// Implements `icingaplugin_rs::cli::PluginArgs` for a struct with named fields, see the
+/// documentation of the trait
+#[proc_macro_derive(PluginArgs, attributes(warning, critical, timeout, hostname, verbose, output_format, arg))]
+pub fn derive_plugin_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+
+/// Generates the `PluginArgs` implementation of `input`
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(name, "PluginArgs requires named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "PluginArgs can only be derived for structs")),
+    };
+
+    let about = doc_comment(&input.attrs);
+    let about = if about.is_empty() { quote!() } else { quote!(let cmd = cmd.about(#about);) };
+
+    let mut args = Vec::new();
+    let mut values = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let standard = field
+            .attrs
+            .iter()
+            .find_map(|a| STANDARD.iter().find(|(s, _)| a.path().is_ident(s)));
+
+        match standard {
+            Some((standard, optional)) => {
+                let source = syn::Ident::new(standard, ident.span());
+                let value = if *optional && option_inner(&field.ty).is_none() {
+                    let missing = format!("missing {}", standard.replace('_', " "));
+                    quote!(standard.#source.clone().ok_or_else(|| ::std::string::String::from(#missing))?)
+                } else {
+                    quote!(standard.#source.clone())
+                };
+                values.push(quote!(#ident: #value));
+            }
+            None => {
+                let (arg, value) = custom_arg(field, ident)?;
+                args.push(arg);
+                values.push(quote!(#ident: #value));
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl ::icingaplugin_rs::cli::PluginArgs for #name {
+            fn command() -> ::icingaplugin_rs::cli::clap::Command {
+                let cmd = ::icingaplugin_rs::cli::command(&::icingaplugin_rs::plugin_info!());
+                #about
+                let cmd = ::icingaplugin_rs::cli::standard_args(cmd) #(.arg(#args))*;
+                ::icingaplugin_rs::cli::guideline_help(cmd, &[])
+            }
+
+            fn from_matches(
+                standard: &::icingaplugin_rs::cli::StandardArgs,
+                matches: &::icingaplugin_rs::cli::clap::ArgMatches,
+            ) -> ::std::result::Result<Self, ::std::string::String> {
+                let _ = (standard, matches);
+                ::std::result::Result::Ok(Self { #(#values),* })
+            }
+        }
+    })
+}
+
+
+/// The options of a field given by `#[arg(...)]`
+#[derive(Default)]
+struct ArgOptions {
+    /// the short flag
+    short: Option<LitChar>,
+    /// the long flag, the field name with dashes by default
+    long: Option<LitStr>,
+    /// the default value
+    default: Option<LitStr>,
+    /// the environment variable the value is read from if it is missing
+    env: Option<LitStr>,
+}
+
+
+/// Generates the `Arg` of a field which is not a standard argument and the expression reading
+/// its value from the matches
+fn custom_arg(field: &syn::Field, ident: &syn::Ident) -> syn::Result<(TokenStream2, TokenStream2)> {
+    let mut options = ArgOptions::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("short") {
+                options.short = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("long") {
+                options.long = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("default") {
+                options.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("env") {
+                options.env = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `short`, `long`, `default` or `env`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let id = ident.to_string();
+    let long = options.long.map(|l| l.value()).unwrap_or_else(|| id.replace('_', "-"));
+    let value_name = id.to_uppercase();
+    let help = doc_comment(&field.attrs);
+
+    let mut arg = quote!(::icingaplugin_rs::cli::clap::Arg::new(#id).long(#long));
+    if let Some(short) = options.short {
+        arg.extend(quote!(.short(#short)));
+    }
+    if !help.is_empty() {
+        arg.extend(quote!(.help(#help)));
+    }
+    if let Some(env) = options.env {
+        arg.extend(quote!(.env(#env)));
+    }
+
+    let parser = |ty: &Type| {
+        quote!(.value_name(#value_name).value_parser(|s: &str| s.parse::<#ty>().map_err(|e| e.to_string())))
+    };
+    let value = if is_bool(&field.ty) {
+        arg.extend(quote!(.action(::icingaplugin_rs::cli::clap::ArgAction::SetTrue)));
+        quote!(matches.get_flag(#id))
+    } else if let Some(inner) = option_inner(&field.ty) {
+        arg.extend(parser(inner));
+        quote!(matches.get_one::<#inner>(#id).cloned())
+    } else if let Some(inner) = generic_inner(&field.ty, "Vec") {
+        arg.extend(parser(inner));
+        arg.extend(quote!(.action(::icingaplugin_rs::cli::clap::ArgAction::Append)));
+        quote!(matches.get_many::<#inner>(#id).map(|v| v.cloned().collect()).unwrap_or_default())
+    } else {
+        let ty = &field.ty;
+        arg.extend(parser(ty));
+        match options.default {
+            Some(default) => arg.extend(quote!(.default_value(#default))),
+            None => arg.extend(quote!(.required(true))),
+        }
+        let missing = format!("missing --{}", long);
+        quote!(matches.get_one::<#ty>(#id).cloned().ok_or_else(|| ::std::string::String::from(#missing))?)
+    };
+    Ok((arg, value))
+}
+
+
+/// Returns the doc comment of an item, its lines joined with spaces
+fn doc_comment(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta.require_name_value().ok()?.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(s) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.join(" ")
+}
+
+
+/// Returns whether `ty` is `bool`
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.qself.is_none() && p.path.is_ident("bool"))
+}
+
+
+/// Returns `T` if `ty` is `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    generic_inner(ty, "Option")
+}
+
+
+/// Returns `T` if `ty` is `wrapper<T>`
+fn generic_inner<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match ty {
+        Type::Path(p) if p.qself.is_none() => p.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match args.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,501 @@
+use crate::check::{CheckResult, State};
+use serde_json::json;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ureq::tls::{Certificate, ClientCert, PemItem, PrivateKey, RootCerts, TlsConfig};
+
+/// The object a passive `CheckResult` is submitted for
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Target {
+    /// A host, identified by its name, which is UP for OK results and DOWN for all others
+    Host(String),
+    /// A service, identified by its host's name and its own name
+    Service { host: String, service: String },
+}
+
+
+/// A client for the Icinga2 REST API, submitting passive check results
+#[derive(Clone, Debug)]
+pub struct ApiClient {
+    /// the base URL of the API, e.g. `https://icinga2.example.com:5665`
+    url: String,
+    /// the HTTP agent, configured for TLS
+    agent: ureq::Agent,
+    /// the value of the `Authorization` header, if basic auth is used
+    authorization: Option<String>,
+    /// the `check_source` reported to Icinga2, if set
+    check_source: Option<String>,
+}
+
+
+impl ApiClient {
+    /// Returns an `ApiClientBuilder` for the API at `url`
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the base URL of the API, e.g. `https://icinga2.example.com:5665`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::api::{ApiClient, Target};
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    ///
+    /// let client = ApiClient::builder("https://icinga2.example.com:5665")
+    ///     .basic_auth("collector", "secret")
+    ///     .ca_file("/var/lib/icinga2/certs/ca.crt")
+    ///     .build()
+    ///     .unwrap();
+    /// let target = Target::Service { host: String::from("db01"), service: String::from("backup") };
+    /// let cr = CheckResult::new(State::OK).set_info(String::from("backup finished"));
+    /// client.submit(&target, &cr).unwrap();
+    /// ```
+    pub fn builder(url: &str) -> ApiClientBuilder {
+        ApiClientBuilder {
+            url: url.trim_end_matches('/').to_string(),
+            basic_auth: None,
+            ca_file: None,
+            client_cert: None,
+            insecure: false,
+            timeout: Duration::from_secs(10),
+            check_source: None,
+        }
+    }
+
+    /// Submits `result` as passive check result for `target` using the
+    /// `actions/process-check-result` endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - the host or service the result belongs to
+    /// * `result` - the check result
+    pub fn submit(&self, target: &Target, result: &CheckResult) -> Result<(), ApiError> {
+        let body = process_check_result_body(target, result, self.check_source.as_deref());
+        let mut request = self
+            .agent
+            .post(&format!("{}/v1/actions/process-check-result", self.url))
+            .header("Accept", "application/json");
+        if let Some(auth) = &self.authorization {
+            request = request.header("Authorization", auth);
+        }
+
+        let mut response = request
+            .content_type("application/json")
+            .send(body.to_string())
+            .map_err(|e| ApiError::Http(e.to_string()))?;
+        let status = response.status().as_u16();
+        if status == 200 {
+            return Ok(());
+        }
+
+        let text = response.body_mut().read_to_string().unwrap_or_default();
+        let message = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| first_status(&v))
+            .unwrap_or(text);
+        Err(ApiError::Status(status, message))
+    }
+
+    /// Queries whether `target` is currently in a downtime or its problem is acknowledged, using
+    /// the `objects/hosts` or `objects/services` endpoint. Run it before an expensive check to
+    /// skip probing during planned maintenance, see `ObjectStatus::skipped()`, or to annotate
+    /// the result, see `ObjectStatus::annotate()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - the host or service to query
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::api::{ApiClient, Target};
+    /// use icingaplugin_rs::check::CheckResult;
+    ///
+    /// let client = ApiClient::builder("https://icinga2.example.com:5665")
+    ///     .basic_auth("monitoring", "secret")
+    ///     .build()
+    ///     .unwrap();
+    /// let target = Target::Service { host: String::from("db01"), service: String::from("backup") };
+    /// let status = client.status(&target).unwrap();
+    /// let cr = match status.skipped() {
+    ///     Some(cr) => cr,
+    ///     None => status.annotate(CheckResult::ok("backup finished")),
+    /// };
+    /// ```
+    pub fn status(&self, target: &Target) -> Result<ObjectStatus, ApiError> {
+        let (endpoint, mut body) = match target {
+            Target::Host(host) => ("hosts", json!({
+                "filter": "host.name==host_name",
+                "filter_vars": { "host_name": host },
+            })),
+            Target::Service { host, service } => ("services", json!({
+                "filter": "host.name==host_name && service.name==service_name",
+                "filter_vars": { "host_name": host, "service_name": service },
+            })),
+        };
+        body["attrs"] = json!(["state", "downtime_depth", "acknowledgement"]);
+        // the query is sent as body, which requires a POST overriding the method
+        let mut request = self
+            .agent
+            .post(&format!("{}/v1/objects/{}", self.url, endpoint))
+            .header("Accept", "application/json")
+            .header("X-HTTP-Method-Override", "GET");
+        if let Some(auth) = &self.authorization {
+            request = request.header("Authorization", auth);
+        }
+
+        let mut response = request
+            .content_type("application/json")
+            .send(body.to_string())
+            .map_err(|e| ApiError::Http(e.to_string()))?;
+        let status = response.status().as_u16();
+        let text = response.body_mut().read_to_string().unwrap_or_default();
+        let parsed = serde_json::from_str::<serde_json::Value>(&text).ok();
+        if status != 200 {
+            let message = parsed.as_ref().and_then(first_status).unwrap_or(text);
+            return Err(ApiError::Status(status, message));
+        }
+        parsed
+            .as_ref()
+            .and_then(|v| object_status(target, v))
+            .ok_or_else(|| ApiError::Status(status, String::from("no matching object found")))
+    }
+}
+
+
+/// Whether a host or service is in a downtime or acknowledged, as returned by
+/// `ApiClient::status()`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ObjectStatus {
+    /// the current state of the object in Icinga2, CRITICAL for DOWN hosts
+    pub state: State,
+    /// whether at least one downtime is active
+    pub in_downtime: bool,
+    /// whether the current problem is acknowledged
+    pub acknowledged: bool,
+}
+
+
+impl ObjectStatus {
+    /// Returns a result keeping the current state of the object if it is in a downtime, so the
+    /// check can skip probing during planned maintenance, or `None` if the check should run
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::api::ObjectStatus;
+    /// use icingaplugin_rs::check::State;
+    /// let status = ObjectStatus { state: State::Warning, in_downtime: true, acknowledged: false };
+    /// assert_eq!(status.skipped().unwrap().to_string(), "WARNING - check skipped, in downtime");
+    /// ```
+    pub fn skipped(&self) -> Option<CheckResult> {
+        match self.in_downtime {
+            true => Some(CheckResult::new(self.state).set_info(String::from("check skipped, in downtime"))),
+            false => None,
+        }
+    }
+
+    /// Appends `(in downtime)`, `(acknowledged)` or both to the info line of `cr`. Returns `cr`
+    /// unchanged if neither applies.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - the result of the check
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::api::ObjectStatus;
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let status = ObjectStatus { state: State::Critical, in_downtime: false, acknowledged: true };
+    /// let cr = status.annotate(CheckResult::critical("backup failed"));
+    /// assert_eq!(cr.to_string(), "CRITICAL - backup failed (acknowledged)");
+    /// ```
+    pub fn annotate(&self, cr: CheckResult) -> CheckResult {
+        let notes: Vec<_> = [(self.in_downtime, "in downtime"), (self.acknowledged, "acknowledged")]
+            .iter()
+            .filter(|(applies, _)| *applies)
+            .map(|(_, note)| *note)
+            .collect();
+        if notes.is_empty() {
+            return cr;
+        }
+        let info = match cr.info() {
+            Some(info) if !info.is_empty() => format!("{} ({})", info, notes.join(", ")),
+            _ => notes.join(", "),
+        };
+        cr.set_info(info)
+    }
+}
+
+
+/// Configures an `ApiClient`
+#[derive(Clone, Debug)]
+pub struct ApiClientBuilder {
+    /// the base URL of the API, without a trailing `/`
+    url: String,
+    /// the API user and its password, if any
+    basic_auth: Option<(String, String)>,
+    /// the PEM file with the CA certificates the API's certificate is verified against, if any
+    ca_file: Option<PathBuf>,
+    /// the PEM files with the client certificate (chain) and its private key, if any
+    client_cert: Option<(PathBuf, PathBuf)>,
+    /// whether the API's certificate is accepted without verification
+    insecure: bool,
+    /// the time a request may take in total
+    timeout: Duration,
+    /// the `check_source` reported to Icinga2, if any
+    check_source: Option<String>,
+}
+
+
+impl ApiClientBuilder {
+    /// Authenticates using an API user and its password
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - the name of the `ApiUser` object
+    /// * `password` - its password
+    pub fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.basic_auth = Some((user.to_string(), password.to_string()));
+        self
+    }
+
+    /// Verifies the API's certificate against the CA in `path` instead of the system's roots,
+    /// usually `/var/lib/icinga2/certs/ca.crt`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - a PEM file containing one or more CA certificates
+    pub fn ca_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.ca_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Authenticates using a client certificate, whose common name must match an `ApiUser`
+    ///
+    /// # Arguments
+    ///
+    /// * `cert` - a PEM file containing the certificate (chain)
+    /// * `key` - a PEM file containing the private key
+    pub fn client_cert<P: AsRef<Path>>(mut self, cert: P, key: P) -> Self {
+        self.client_cert = Some((cert.as_ref().to_path_buf(), key.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Disables verification of the API's certificate. Only use this for testing.
+    ///
+    /// # Arguments
+    ///
+    /// * `insecure` - `true` to accept any certificate
+    pub fn insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+
+    /// Sets the time a request may take in total, 10 seconds by default
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the request timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the `check_source` reported to Icinga2, e.g. the name of the collector
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - the name of the submitting system
+    pub fn check_source(mut self, source: &str) -> Self {
+        self.check_source = Some(source.to_string());
+        self
+    }
+
+    /// Builds the `ApiClient`, reading the configured certificate files. Fails if a file cannot
+    /// be read or the CA file or the client certificate file contains no certificate.
+    pub fn build(self) -> Result<ApiClient, ApiError> {
+        let mut tls = TlsConfig::builder().disable_verification(self.insecure);
+
+        if let Some(path) = &self.ca_file {
+            let pem = read(path)?;
+            let certs: Vec<Certificate<'static>> = ureq::tls::parse_pem(&pem)
+                .filter_map(|item| match item {
+                    Ok(PemItem::Certificate(c)) => Some(c),
+                    _ => None,
+                })
+                .collect();
+            if certs.is_empty() {
+                return Err(ApiError::Tls(format!("no certificates found in {}", path.display())));
+            }
+            tls = tls.root_certs(RootCerts::new_with_certs(&certs));
+        }
+
+        if let Some((cert_path, key_path)) = &self.client_cert {
+            let cert_pem = read(cert_path)?;
+            let chain: Vec<Certificate<'static>> = ureq::tls::parse_pem(&cert_pem)
+                .filter_map(|item| match item {
+                    Ok(PemItem::Certificate(c)) => Some(c),
+                    _ => None,
+                })
+                .collect();
+            if chain.is_empty() {
+                return Err(ApiError::Tls(format!("no certificates found in {}", cert_path.display())));
+            }
+            let key = PrivateKey::from_pem(&read(key_path)?)
+                .map_err(|e| ApiError::Tls(format!("{}: {}", key_path.display(), e)))?;
+            tls = tls.client_cert(Some(ClientCert::new_with_certs(&chain, key)));
+        }
+
+        let agent = ureq::Agent::config_builder()
+            .tls_config(tls.build())
+            .timeout_global(Some(self.timeout))
+            .http_status_as_error(false)
+            .build()
+            .new_agent();
+
+        Ok(ApiClient {
+            url: self.url,
+            agent,
+            authorization: self
+                .basic_auth
+                .map(|(user, password)| format!("Basic {}", base64(format!("{}:{}", user, password).as_bytes()))),
+            check_source: self.check_source,
+        })
+    }
+}
+
+
+/// An error occurring while talking to the Icinga2 API
+#[derive(Debug)]
+pub enum ApiError {
+    /// A certificate file could not be read
+    Io(String, io::Error),
+    /// A certificate or key could not be used
+    Tls(String),
+    /// The request failed, e.g. because the API was unreachable
+    Http(String),
+    /// The API answered with an error status and message
+    Status(u16, String),
+}
+
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Io(path, e) => write!(f, "failed to read {}: {}", path, e),
+            ApiError::Tls(e) => write!(f, "TLS setup failed: {}", e),
+            ApiError::Http(e) => write!(f, "request to Icinga2 API failed: {}", e),
+            ApiError::Status(code, msg) => write!(f, "Icinga2 API returned {}: {}", code, msg),
+        }
+    }
+}
+
+
+impl Error for ApiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApiError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+
+/// Builds the request body for the `actions/process-check-result` endpoint
+pub(crate) fn process_check_result_body(target: &Target, result: &CheckResult, check_source: Option<&str>)
+    -> serde_json::Value {
+    let mut output = match result.info() {
+        Some(info) => info.to_string(),
+        None => result.state().to_string(),
+    };
+    for line in result.long_output() {
+        output.push('\n');
+        output.push_str(line);
+    }
+
+    let mut body = match target {
+        Target::Host(host) => json!({
+            "type": "Host",
+            "filter": "host.name==host_name",
+            "filter_vars": { "host_name": host },
+            // hosts only know UP (0) and DOWN (1), Icinga2 rejects any other exit status
+            "exit_status": match result.state() {
+                State::OK => 0,
+                _ => 1,
+            },
+        }),
+        Target::Service { host, service } => json!({
+            "type": "Service",
+            "filter": "host.name==host_name && service.name==service_name",
+            "filter_vars": { "host_name": host, "service_name": service },
+            "exit_status": i32::from(result.state()),
+        }),
+    };
+    body["plugin_output"] = json!(output);
+    if let Some(pd) = result.perf_data() {
+        body["performance_data"] = json!(pd.to_string().trim_end());
+    }
+    if let Some(source) = check_source {
+        body["check_source"] = json!(source);
+    }
+    body
+}
+
+
+/// Extracts the `ObjectStatus` of the first object in a response of the `objects` endpoints
+pub(crate) fn object_status(target: &Target, response: &serde_json::Value) -> Option<ObjectStatus> {
+    let attrs = &response["results"][0]["attrs"];
+    let state = attrs["state"].as_f64()? as i32;
+    let state = match target {
+        // hosts are either UP (0) or DOWN (1)
+        Target::Host(_) if state == 0 => State::OK,
+        Target::Host(_) => State::Critical,
+        Target::Service { .. } => State::try_from(state).ok()?,
+    };
+    Some(ObjectStatus {
+        state,
+        in_downtime: attrs["downtime_depth"].as_f64()? > 0.0,
+        acknowledged: attrs["acknowledgement"].as_f64()? != 0.0,
+    })
+}
+
+
+/// Extracts the status message of the first result in an API response
+fn first_status(response: &serde_json::Value) -> Option<String> {
+    response["results"][0]["status"]
+        .as_str()
+        .or_else(|| response["status"].as_str())
+        .map(String::from)
+}
+
+
+fn read(path: &Path) -> Result<Vec<u8>, ApiError> {
+    fs::read(path).map_err(|e| ApiError::Io(path.display().to_string(), e))
+}
+
+
+/// Encodes `input` as standard base64 with padding, as used by HTTP basic auth
+pub(crate) fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
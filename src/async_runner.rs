@@ -0,0 +1,286 @@
+use crate::check::{CheckResult, Metric};
+use crate::runner::{catch_panic, exit_with, Error, OutputFormat};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Executes async checks on the current tokio runtime, enforcing a timeout and converting errors
+/// and panics into UNKNOWN results. Checks running into the timeout are cancelled. Several
+/// checks, e.g. querying many endpoints, can be executed concurrently with `execute_all()`, or
+/// with `execute_timed()` to find out which of them are slow.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AsyncRunner {
+    /// the time each check is given to produce a result
+    timeout: Duration,
+    /// the verbosity level applied to the results
+    verbosity: u8,
+    /// the format the result is printed in
+    output_format: OutputFormat,
+}
+
+
+impl AsyncRunner {
+    /// Creates a new `AsyncRunner` with the default timeout of 10 seconds
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::async_runner::AsyncRunner;
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::runner::Error;
+    /// use std::time::Duration;
+    ///
+    /// async fn check_answer() -> Result<CheckResult, Error> {
+    ///     tokio::time::sleep(Duration::from_millis(1)).await;
+    ///     Ok(CheckResult::new(State::OK).set_info(String::from("the answer is 42")))
+    /// }
+    ///
+    /// let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// let cr = rt.block_on(AsyncRunner::new().execute(check_answer()));
+    /// assert_eq!(cr.to_string(), "OK - the answer is 42");
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            verbosity: 0,
+            output_format: OutputFormat::Text,
+        }
+    }
+
+    /// Sets the time each check is given to produce a result
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the timeout of each check
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the verbosity level (0-3) deciding which debug lines of the results are displayed
+    ///
+    /// # Arguments
+    ///
+    /// * `verbosity` - the verbosity level
+    pub fn verbosity(mut self, verbosity: u8) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Sets the format the result is printed in by `run()`
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - the output format
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Executes `check` and returns its `CheckResult`. Errors, panics and timeouts result in an
+    /// UNKNOWN `CheckResult` describing the problem.
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - the check to execute
+    pub async fn execute<F>(&self, check: F) -> CheckResult
+    where F: Future<Output = Result<CheckResult, Error>> + Send + 'static {
+        let started = Instant::now();
+        self.join(spawn(check), started, started + self.timeout).await.0
+    }
+
+    /// Executes all `checks` concurrently and returns their `CheckResult`s in the same order,
+    /// e.g. to be combined with `Overall`. All checks start at once, so each is given the full timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `checks` - the checks to execute
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::async_runner::AsyncRunner;
+    /// use icingaplugin_rs::check::{CheckResult, Overall, State};
+    /// use icingaplugin_rs::runner::Error;
+    ///
+    /// async fn check_endpoint(url: &'static str) -> Result<CheckResult, Error> {
+    ///     match url.starts_with("https") {
+    ///         true => Ok(CheckResult::new(State::OK).set_info(format!("{} is up", url))),
+    ///         false => Err(format!("{} is not encrypted", url).into()),
+    ///     }
+    /// }
+    ///
+    /// let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// let checks = vec![check_endpoint("https://a.example.com"), check_endpoint("http://b.example.com")];
+    /// let results = rt.block_on(AsyncRunner::new().execute_all(checks));
+    /// let overall: Overall = results.into_iter().collect();
+    /// assert_eq!(overall.finish().to_string(), "UNKNOWN - 1 unknown, 1 ok\n\
+    ///     \\_ [OK] https://a.example.com is up\n\
+    ///     \\_ [UNKNOWN] http://b.example.com is not encrypted");
+    /// ```
+    pub async fn execute_all<I, F>(&self, checks: I) -> Vec<CheckResult>
+    where I: IntoIterator<Item = F>, F: Future<Output = Result<CheckResult, Error>> + Send + 'static {
+        let started = Instant::now();
+        let handles: Vec<_> = checks.into_iter().map(spawn).collect();
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            results.push(self.join(handle, started, started + self.timeout).await.0);
+        }
+        results
+    }
+
+    /// Executes all named `checks` concurrently like `execute_all()` and returns their
+    /// `CheckResult`s in the same order, together with the time each check took, so slow targets
+    /// within an aggregate check can be identified. Checks running into the timeout took the
+    /// full timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `checks` - the checks to execute with their names, e.g. the endpoints they query
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::async_runner::AsyncRunner;
+    /// use icingaplugin_rs::check::{CheckResult, Overall};
+    /// use icingaplugin_rs::runner::Error;
+    /// use std::time::Duration;
+    ///
+    /// async fn check_endpoint(millis: u64) -> Result<CheckResult, Error> {
+    ///     tokio::time::sleep(Duration::from_millis(millis)).await;
+    ///     Ok(CheckResult::ok(format!("responded after {}ms", millis)))
+    /// }
+    ///
+    /// let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+    /// let checks = vec![("db01", check_endpoint(1)), ("db02", check_endpoint(50))];
+    /// let (results, timings) = rt.block_on(AsyncRunner::new().execute_timed(checks));
+    /// assert!(timings.get("db02").unwrap() >= Duration::from_millis(50));
+    ///
+    /// let overall: Overall = results.into_iter().collect();
+    /// let cr = timings.attach(overall.finish());
+    /// assert!(cr.perf_data().unwrap().to_string().starts_with("'db01_time'="));
+    /// ```
+    pub async fn execute_timed<I, N, F>(&self, checks: I) -> (Vec<CheckResult>, Timings)
+    where I: IntoIterator<Item = (N, F)>, N: Into<String>,
+          F: Future<Output = Result<CheckResult, Error>> + Send + 'static {
+        let started = Instant::now();
+        let handles: Vec<_> = checks.into_iter().map(|(name, check)| (name.into(), spawn(check))).collect();
+        let mut results = Vec::with_capacity(handles.len());
+        let mut timings = Timings::default();
+        for (name, handle) in handles {
+            let (cr, took) = self.join(handle, started, started + self.timeout).await;
+            results.push(cr);
+            timings.durations.push((name, took));
+        }
+        (results, timings)
+    }
+
+    /// Executes `check`, prints its `CheckResult` and exits with the corresponding exit code
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - the check to execute
+    pub async fn run<F>(&self, check: F) -> !
+    where F: Future<Output = Result<CheckResult, Error>> + Send + 'static {
+        crate::platform::init();
+        exit_with(self.execute(check).await, self.output_format, None)
+    }
+
+    /// Awaits a check spawned at `started`, cancelling it if `deadline` passes, and returns its
+    /// result with the time it took
+    async fn join(&self, mut handle: JoinHandle<(Result<CheckResult, Error>, Duration)>, started: Instant,
+                  deadline: Instant) -> (CheckResult, Duration) {
+        let (cr, took) = match tokio::time::timeout_at(deadline, &mut handle).await {
+            Ok(Ok((Ok(cr), took))) => (cr, took),
+            Ok(Ok((Err(e), took))) => (CheckResult::unknown(e.to_string()), took),
+            Ok(Err(_)) => (CheckResult::unknown(String::from("check was cancelled")), started.elapsed()),
+            Err(_) => {
+                handle.abort();
+                let cr = CheckResult::unknown(format!("check timed out after {}s", self.timeout.as_secs_f64()));
+                (cr, started.elapsed())
+            }
+        };
+        (cr.set_verbosity(self.verbosity), took)
+    }
+}
+
+
+/// Spawns `check` on the tokio runtime, measuring the time until it completes
+fn spawn<F>(check: F) -> JoinHandle<(Result<CheckResult, Error>, Duration)>
+where F: Future<Output = Result<CheckResult, Error>> + Send + 'static {
+    let started = Instant::now();
+    tokio::spawn(async move {
+        let result = CatchPanic(Box::pin(check)).await;
+        (result, started.elapsed())
+    })
+}
+
+
+/// The time each check executed by `AsyncRunner::execute_timed()` took, in the order the checks
+/// were given
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings {
+    /// the name of each check with the time it took
+    durations: Vec<(String, Duration)>,
+}
+
+
+impl Timings {
+    /// Returns the time the check `name` took, if it was executed
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the check
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.durations.iter().find(|(n, _)| n == name).map(|(_, took)| *took)
+    }
+
+    /// Returns an iterator over the names of the checks and the time each took
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Duration)> {
+        self.durations.iter().map(|(name, took)| (name.as_str(), *took))
+    }
+
+    /// Adds the timings to `cr` and returns it: the time each check took as a `<name>_time`
+    /// metric in seconds, and a table of all timings as debug lines, displayed with `-v`
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - the result combining the checks, e.g. built with `Overall`
+    pub fn attach(&self, cr: CheckResult) -> CheckResult {
+        if self.durations.is_empty() {
+            return cr;
+        }
+        let width = self.durations.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("check".len());
+        let cr = cr.add_debug(1, format!("{:<width$}  {:>10}", "check", "time", width = width));
+        self.durations.iter().fold(cr, |cr, (name, took)| {
+            let secs = took.as_secs_f64();
+            cr.add_debug(1, format!("{:<width$}  {:>9.3}s", name, secs, width = width))
+                .add_metric(Metric::new(format!("{}_time", name), secs).uom("s").precision(6).min("0"))
+        })
+    }
+}
+
+
+impl Default for AsyncRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// A check whose panics are caught while it is polled, on the worker thread polling it, and
+/// turned into an UNKNOWN `CheckResult`
+struct CatchPanic<F>(Pin<Box<F>>);
+
+
+impl<F: Future<Output = Result<CheckResult, Error>>> Future for CatchPanic<F> {
+    type Output = Result<CheckResult, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let check = &mut self.0;
+        catch_panic(|| check.as_mut().poll(cx)).unwrap_or_else(|panic| Poll::Ready(Ok(CheckResult::unknown(panic))))
+    }
+}
@@ -0,0 +1,221 @@
+use crate::check::{Metric, State};
+use crate::export::as_f64;
+use crate::history::History;
+use crate::persist::StateFile;
+use crate::pipeline::Evaluation;
+use crate::thresholds::{Range, Thresholds};
+use crate::utils::{apply_thresholds, Direction};
+
+/// How the thresholds are derived from the recorded values
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+    /// `mean ± k·stddev`, with one factor for warning and one for critical
+    StdDev { warning: f64, critical: f64 },
+    /// the band between the `100 - p`th and the `p`th percentile
+    Percentile { warning: f64, critical: f64 },
+}
+
+
+/// Thresholds derived from the previous values of a metric instead of being configured. The
+/// values are recorded in a `StateFile` as a `History`. During the learning period, until
+/// `learning` values are recorded, the metric is OK and reported without thresholds. Afterwards
+/// the derived thresholds are applied and filled into the performance data, so they can be
+/// graphed next to the value.
+///
+/// Every value is recorded after it was evaluated, including alerting ones, so the baseline
+/// follows lasting changes. Only the last `window` values are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Baseline {
+    /// how the thresholds are derived
+    method: Method,
+    /// the number of values required before thresholds are derived
+    learning: usize,
+    /// the number of values the thresholds are derived from
+    window: usize,
+    /// the direction in which values raise an alert, both if not set
+    direction: Option<Direction>,
+}
+
+
+impl Baseline {
+    /// Creates a new `Baseline` raising an alert for values further than `warning` or
+    /// `critical` standard deviations away from the mean of the recorded values. It learns for
+    /// 10 values and keeps the last 100 by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `warning` - the factor of the standard deviation for the warning band, e.g. `2.0`
+    /// * `critical` - the factor of the standard deviation for the critical band, e.g. `3.0`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::baseline::Baseline;
+    /// use icingaplugin_rs::check::{Metric, State};
+    /// use icingaplugin_rs::persist::StateFile;
+    ///
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+    /// let baseline = Baseline::stddev(2.0, 3.0).learning(4);
+    /// for queued in &[10, 12, 8, 10] {
+    ///     let evaluation = baseline.evaluate(&mut state, &Metric::numeric("queued", *queued));
+    ///     assert_eq!(evaluation.state(), State::OK);
+    /// }
+    ///
+    /// let evaluation = baseline.evaluate(&mut state, &Metric::numeric("queued", 14));
+    /// assert_eq!(evaluation.state(), State::Warning);
+    /// assert_eq!(evaluation.metric().to_string(), "'queued'=14;7.17:12.83;5.76:14.24;;");
+    /// ```
+    pub fn stddev(warning: f64, critical: f64) -> Self {
+        Self::with_method(Method::StdDev { warning, critical })
+    }
+
+    /// Creates a new `Baseline` raising an alert for values outside of the band between the
+    /// `100 - p`th and the `p`th percentile of the recorded values. It learns for 10 values and
+    /// keeps the last 100 by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `warning` - the percentile of the warning band, e.g. `95.0`
+    /// * `critical` - the percentile of the critical band, e.g. `99.0`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::baseline::Baseline;
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::history::History;
+    ///
+    /// let mut history = History::new(100);
+    /// for latency in 1..=100 {
+    ///     history.push(latency as f64, State::OK);
+    /// }
+    ///
+    /// let thresholds = Baseline::percentile(90.0, 99.0).thresholds(&history).unwrap();
+    /// assert_eq!(thresholds.warning_range().unwrap().to_string(), "10.9:90.1");
+    /// assert_eq!(thresholds.critical_range().unwrap().to_string(), "1.99:99.01");
+    /// ```
+    pub fn percentile(warning: f64, critical: f64) -> Self {
+        Self::with_method(Method::Percentile { warning, critical })
+    }
+
+    /// Creates a new `Baseline` with the default learning period and window
+    fn with_method(method: Method) -> Self {
+        Self { method, learning: 10, window: 100, direction: None }
+    }
+
+    /// Sets the number of values recorded before thresholds are derived
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - the length of the learning period, at least two
+    pub fn learning(mut self, values: usize) -> Self {
+        self.learning = values.max(2);
+        self
+    }
+
+    /// Sets the number of recent values the thresholds are derived from
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - the size of the window, at least the length of the learning period
+    pub fn window(mut self, values: usize) -> Self {
+        self.window = values;
+        self
+    }
+
+    /// Only raises an alert for values deviating in `direction`, e.g. `Direction::Above` for
+    /// response times. By default deviations in both directions raise an alert.
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - the direction in which values raise an alert
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Derives the thresholds from the values in `history`. Returns `None` during the learning
+    /// period. The bounds are rounded to two decimal places.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - the recorded values
+    pub fn thresholds(&self, history: &History) -> Option<Thresholds> {
+        if history.len() < self.learning {
+            return None;
+        }
+        let mut values: Vec<f64> = history.values().collect();
+        let (warning, critical) = match self.method {
+            Method::StdDev { warning, critical } => {
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let stddev = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+                let band = |k: f64| (mean - k * stddev, mean + k * stddev);
+                (band(warning), band(critical))
+            }
+            Method::Percentile { warning, critical } => {
+                values.sort_by(f64::total_cmp);
+                let band = |p: f64| {
+                    let p = p.clamp(50.0, 100.0);
+                    (percentile(&values, 100.0 - p), percentile(&values, p))
+                };
+                (band(warning), band(critical))
+            }
+        };
+        Some(Thresholds::new().warning(self.range(warning)).critical(self.range(critical)))
+    }
+
+    /// Returns the `Range` raising an alert outside of `band` in the configured direction
+    fn range(&self, (lower, upper): (f64, f64)) -> Range {
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+        match self.direction {
+            Some(Direction::Above) => Range::new(f64::NEG_INFINITY, round(upper)),
+            Some(Direction::Below) => Range::new(round(lower), f64::INFINITY),
+            None => Range::new(round(lower), round(upper)),
+        }
+    }
+
+    /// Evaluates `metric` against the thresholds derived from its previous values, which are
+    /// stored in `state` under the label of the metric, and records the value. During the
+    /// learning period the metric is OK, with a hint how many values are still missing. Metrics
+    /// without a numeric value are UNKNOWN and not recorded. The change is persisted by
+    /// `StateFile::save()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state file
+    /// * `metric` - the metric to evaluate
+    pub fn evaluate(&self, state: &mut StateFile, metric: &Metric) -> Evaluation {
+        let label = metric.label();
+        let value = as_f64(metric.value()).filter(|v| v.is_finite());
+        let mut history = History::load(state, label, self.window.max(self.learning));
+        let evaluation = match self.thresholds(&history) {
+            Some(thresholds) => {
+                let (s, metric) = apply_thresholds(metric, &thresholds);
+                Evaluation::new(metric, s)
+            }
+            None => {
+                let s = if value.is_some() { State::OK } else { State::Unknown };
+                Evaluation::new(metric.clone(), s).set_hint(format!(
+                    "{} is learning its baseline ({} of {} values)",
+                    label, history.len(), self.learning
+                ))
+            }
+        };
+
+        if let Some(value) = value {
+            history.push(value, evaluation.state());
+            history.store(state, label);
+        }
+        evaluation
+    }
+}
+
+
+/// Returns the `p`th percentile of the sorted, non-empty `values`, interpolating linearly
+/// between the closest ranks
+fn percentile(values: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (values.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    values[below] + (values[above] - values[below]) * (rank - below as f64)
+}
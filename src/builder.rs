@@ -0,0 +1,208 @@
+use crate::check::{CheckResult, Metric, PerfData, State};
+use crate::thresholds::Thresholds;
+use crate::utils::{apply_thresholds, worst_first};
+use std::fmt;
+use std::sync::Arc;
+
+/// A custom evaluation deciding the state of a metric, see `CheckBuilder::context()`
+type Evaluator = Arc<dyn Fn(&Metric) -> State + Send + Sync>;
+
+/// A value registered with a `CheckBuilder`
+#[derive(Clone, Debug, PartialEq)]
+struct Item {
+    metric: Metric,
+    thresholds: Thresholds,
+    template: String,
+}
+
+
+/// Builds a complete `CheckResult` from named values, their thresholds and message templates:
+/// the state is the worst state of all values, the info line consists of the messages of the
+/// values raising an alert, worst first, or of all values if none does, and every value is
+/// reported as performance data.
+///
+/// Templates may contain the placeholders `{label}`, `{value}`, `{uom}`, `{state}`, `{warning}`
+/// and `{critical}`.
+#[derive(Clone, Default)]
+pub struct CheckBuilder {
+    /// the registered values in the order they were added
+    items: Vec<Item>,
+    /// the custom evaluations by metric label, replacing the thresholds
+    contexts: Vec<(String, Evaluator)>,
+    /// the info line used if no value raises an alert
+    ok_message: Option<String>,
+}
+
+
+impl fmt::Debug for CheckBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckBuilder")
+            .field("items", &self.items)
+            .field("contexts", &self.contexts.iter().map(|(label, _)| label).collect::<Vec<_>>())
+            .field("ok_message", &self.ok_message)
+            .finish()
+    }
+}
+
+
+impl PartialEq for CheckBuilder {
+    /// Custom evaluations are only equal if they are the same closure
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+            && self.ok_message == other.ok_message
+            && self.contexts.len() == other.contexts.len()
+            && self
+                .contexts
+                .iter()
+                .zip(&other.contexts)
+                .all(|((l1, e1), (l2, e2))| l1 == l2 && Arc::ptr_eq(e1, e2))
+    }
+}
+
+
+impl CheckBuilder {
+    /// Creates a new, empty `CheckBuilder`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::builder::CheckBuilder;
+    /// use icingaplugin_rs::check::{Metric, State};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let disk = Thresholds::new().warning("80".parse().unwrap()).critical("90".parse().unwrap());
+    /// let inodes = Thresholds::new().critical("95".parse().unwrap());
+    /// let cr = CheckBuilder::new()
+    ///     .value(Metric::numeric("/var", 93).uom(String::from("%")), disk, "{label} is {value}{uom} full")
+    ///     .value(Metric::numeric("/var inodes", 12).uom(String::from("%")), inodes, "{value}{uom} inodes used")
+    ///     .finish();
+    /// assert_eq!(cr.state(), State::Critical);
+    /// assert_eq!(cr.to_string(), "CRITICAL - /var is 93% full \
+    ///     | '/var'=93%;80;90;; '/var inodes'=12%;;95;; \n\
+    ///     [CRITICAL] /var is 93% full\n[OK] 12% inodes used");
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a value with its thresholds and the message describing it
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - the value, its label and UOM
+    /// * `thresholds` - the thresholds the value is evaluated against
+    /// * `template` - the message, e.g. `"{label} is {value}{uom} full"`
+    pub fn value(mut self, metric: Metric, thresholds: Thresholds, template: &str) -> Self {
+        self.items.push(Item { metric, thresholds, template: template.to_string() });
+        self
+    }
+
+    /// Registers a custom evaluation deciding the state of the values labeled `label` instead of
+    /// their thresholds, e.g. for conditions which are not numeric. The thresholds are still
+    /// reported as performance data. A custom evaluation registered before for the same label is
+    /// replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label of the values
+    /// * `evaluate` - returns the state of a value, given the metric with its thresholds
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::builder::CheckBuilder;
+    /// use icingaplugin_rs::check::{Metric, State};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let read_only = true;
+    /// let disk = Thresholds::new().critical("90".parse().unwrap());
+    /// let cr = CheckBuilder::new()
+    ///     .value(Metric::numeric("/var", 42).uom("%"), disk, "{label} is {value}{uom} full ({state})")
+    ///     .context("/var", move |_| if read_only { State::Critical } else { State::OK })
+    ///     .finish();
+    /// assert_eq!(cr.state(), State::Critical);
+    /// assert_eq!(cr.info(), Some("/var is 42% full (CRITICAL)"));
+    /// assert_eq!(cr.perf_data().unwrap().to_string(), "'/var'=42%;;90;; ");
+    /// ```
+    pub fn context<L, F>(mut self, label: L, evaluate: F) -> Self
+    where
+        L: Into<String>,
+        F: Fn(&Metric) -> State + Send + Sync + 'static,
+    {
+        let label = label.into();
+        self.contexts.retain(|(l, _)| *l != label);
+        self.contexts.push((label, Arc::new(evaluate)));
+        self
+    }
+
+    /// Sets the info line used if no value raises an alert, instead of the messages of all values
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the info line
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::builder::CheckBuilder;
+    /// use icingaplugin_rs::check::Metric;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let cr = CheckBuilder::new()
+    ///     .value(Metric::numeric("load1", 0.4), Thresholds::new(), "load is {value}")
+    ///     .ok_message(String::from("system is idle"))
+    ///     .finish();
+    /// assert_eq!(cr.to_string(), "OK - system is idle | 'load1'=0.4;;;; \n[OK] load is 0.4");
+    /// ```
+    pub fn ok_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.ok_message = Some(message.into());
+        self
+    }
+
+    /// Evaluates all values and returns the resulting `CheckResult`. The message of every value
+    /// is added to the long output, prefixed by its state. Without any value, the result is
+    /// UNKNOWN.
+    pub fn finish(self) -> CheckResult {
+        if self.items.is_empty() {
+            return CheckResult::new(State::Unknown).set_info(String::from("no values to evaluate"));
+        }
+
+        let mut metrics = Vec::with_capacity(self.items.len());
+        let mut messages = Vec::with_capacity(self.items.len());
+        for item in &self.items {
+            let (mut state, metric) = apply_thresholds(&item.metric, &item.thresholds);
+            if let Some((_, evaluate)) = self.contexts.iter().find(|(label, _)| *label == metric.label) {
+                state = evaluate(&metric);
+            }
+            messages.push((state, render(&item.template, &metric, state)));
+            metrics.push(metric);
+        }
+
+        let state = State::worst(messages.iter().map(|(s, _)| *s));
+        let long_output = messages.iter().map(|(s, m)| format!("[{}] {}", s, m)).collect();
+
+        let alerting = worst_first(messages.iter().map(|(s, m)| (*s, m.as_str())));
+        let info = match (alerting.is_empty(), self.ok_message) {
+            (true, Some(ok)) => ok,
+            (true, None) => messages.iter().map(|(_, m)| m.as_str()).collect::<Vec<_>>().join(", "),
+            (false, _) => alerting.join(", "),
+        };
+
+        CheckResult::new(state)
+            .set_info(info)
+            .set_perf_data(PerfData::from_metrics(metrics))
+            .set_long_output(long_output)
+    }
+}
+
+
+/// Replaces the placeholders of `template` with the properties of `metric`
+fn render(template: &str, metric: &Metric, state: State) -> String {
+    template
+        .replace("{label}", &metric.label)
+        .replace("{value}", &metric.formatted_value())
+        .replace("{uom}", metric.uom.as_deref().unwrap_or(""))
+        .replace("{state}", &state.to_string())
+        .replace("{warning}", metric.warning.as_deref().unwrap_or(""))
+        .replace("{critical}", metric.critical.as_deref().unwrap_or(""))
+}
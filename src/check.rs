@@ -1,7 +1,16 @@
+use crate::secret::redact;
+use crate::thresholds::{Range, Thresholds};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
+use std::io;
+use std::str::FromStr;
 
 /// Represents a complete CheckResult from Icinga2's POV
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CheckResult {
     /// CheckResults know about their state (OK|Warning|Critical|Unknown)
     state: State,
@@ -10,23 +19,161 @@ pub struct CheckResult {
     /// CheckResults know about their performance data, to be parsed by Icinga2 and displayed by
     /// Icingaweb2
     perf_data: Option<PerfData>,
+    /// CheckResults know about additional lines of output, displayed below the info string
+    long_output: Vec<String>,
+    /// CheckResults know about debug lines and the verbosity level (0-3) each one requires
+    debug: Vec<(u8, String)>,
+    /// CheckResults know about the verbosity level the plugin was invoked with
+    verbosity: u8,
+    /// CheckResults know about the name of the service they prefix their output with, if any
+    service: Option<String>,
+    /// CheckResults know about the maximum length of their first line, metrics not fitting into
+    /// it are displayed after the long output
+    first_line_limit: Option<usize>,
+    /// CheckResults know about the maximum length of their displayed output, exceeding long
+    /// output is truncated
+    max_output_bytes: Option<usize>,
 }
 
 
 impl fmt::Display for CheckResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Some(i) = &self.info {
-            if let Some(pd) = &self.perf_data {
-                write!(f, "{} - {} | {}", self.state, i, pd)
-            } else {
-                write!(f, "{} - {}", self.state, i)
-            }
-        } else if let Some(pd) = &self.perf_data {
-            write!(f, "{} | {}", self.state, pd)
-        } else {
-            write!(f, "{}", self.state)
+        write_check_result(f, self, false)
+    }
+}
+
+
+/// Writes `cr` to `out`, shared by the `Display` output and `CheckResult::to_colored_string()`.
+/// If `colored` is set, the state and the metrics breaching their thresholds are wrapped in ANSI
+/// escape sequences, which do not count towards the length limits.
+fn write_check_result<W: fmt::Write>(out: &mut W, cr: &CheckResult, colored: bool) -> fmt::Result {
+    use fmt::Write as _;
+
+    fn paint(s: &str, state: State, colored: bool) -> Cow<'_, str> {
+        match colored {
+            true => Cow::Owned(colorize(s, state)),
+            false => Cow::Borrowed(s),
+        }
+    }
+
+    let mut prefix = String::new();
+    if let Some(service) = &cr.service {
+        prefix.push_str(&sanitize_output(service).replace('\n', " "));
+        prefix.push(' ');
+    }
+    let state = cr.state.to_string();
+    let mut first_line = format!("{}{}", prefix, state);
+
+    let info = cr.info.as_deref().map(sanitize_output);
+    let mut info_lines = info.as_deref().map(|i| i.split('\n'));
+    if let Some(i) = info_lines.as_mut().and_then(Iterator::next) {
+        first_line.push_str(" - ");
+        first_line.push_str(i);
+    }
+
+    // every metric is formatted once, into a single buffer
+    let metrics = cr.perf_data.as_ref().map_or(&[][..], |pd| &pd.metrics[..]);
+    let mut formatted = String::new();
+    let mut bounds = Vec::with_capacity(metrics.len());
+    for metric in metrics {
+        let start = formatted.len();
+        write!(formatted, "{}", metric)?;
+        bounds.push(start..formatted.len());
+    }
+    let paint_metric = |i: usize| {
+        let text = &formatted[bounds[i].clone()];
+        // the thresholds are only evaluated if the state is displayed
+        match colored.then(|| metrics[i].threshold_state()) {
+            Some(state) if state != State::OK => paint(text, state, colored),
+            _ => Cow::Borrowed(text),
+        }
+    };
+
+    // the metrics fitting into the first line, the others follow the long output
+    let split = match cr.first_line_limit {
+        Some(limit) => {
+            let mut len = first_line.len() + 3;
+            bounds
+                .iter()
+                .position(|b| {
+                    len += b.len() + 1;
+                    len > limit
+                })
+                .unwrap_or(metrics.len())
+        }
+        None => metrics.len(),
+    };
+    let head_perf_data = cr.perf_data.is_some() && (split > 0 || metrics.is_empty());
+    let head_len = match head_perf_data {
+        true => first_line.len() + 3 + bounds[..split].iter().map(|b| b.len() + 1).sum::<usize>(),
+        false => first_line.len(),
+    };
+    let trailing = split..metrics.len();
+
+    let mut lines: Vec<String> = info_lines.into_iter().flatten().map(String::from).collect();
+    let long_output = cr.long_output.iter().map(|l| sanitize_output(l));
+    let debug = cr.debug.iter().filter(|(level, _)| *level <= cr.verbosity).map(|(_, l)| sanitize_output(l));
+    for line in long_output.chain(debug) {
+        lines.extend(line.split('\n').map(String::from));
+    }
+
+    // the first line and the performance data are never truncated, only the long output
+    if let Some(max) = cr.max_output_bytes {
+        let fixed = head_len + bounds[trailing.clone()].iter().map(|b| b.len() + 3).sum::<usize>();
+        let budget = max.saturating_sub(fixed);
+        if lines.iter().map(|l| l.len() + 1).sum::<usize>() > budget {
+            let reserved = format!("\n(output truncated, {} lines omitted)", lines.len()).len();
+            let mut used = reserved;
+            let kept = lines
+                .iter()
+                .position(|l| {
+                    used += l.len() + 1;
+                    used > budget
+                })
+                .unwrap_or(lines.len());
+            let omitted = lines.len() - kept;
+            lines.truncate(kept);
+            lines.push(format!("(output truncated, {} lines omitted)", omitted));
+        }
+    }
+
+    write!(out, "{}{}{}", prefix, paint(&state, cr.state, colored), &first_line[prefix.len() + state.len()..])?;
+    if head_perf_data {
+        write!(out, " | ")?;
+        for i in 0..split {
+            write!(out, "{} ", paint_metric(i))?;
+        }
+    }
+    for line in &lines {
+        write!(out, "\n{}", line)?;
+    }
+    for i in trailing {
+        match (i == split, lines.is_empty()) {
+            (true, false) => write!(out, " | {}", paint_metric(i))?,
+            (true, true) => write!(out, "\n| {}", paint_metric(i))?,
+            _ => write!(out, "\n{}", paint_metric(i))?,
         }
     }
+    Ok(())
+}
+
+
+/// Wraps `s` in the ANSI escape sequences for the color of `state`
+pub(crate) fn colorize(s: &str, state: State) -> String {
+    let color = match state {
+        State::OK => 32,
+        State::Warning => 33,
+        State::Critical => 31,
+        State::Unknown => 35,
+    };
+    format!("\x1b[{}m{}\x1b[0m", color, s)
+}
+
+
+/// Replaces `|`, which would start the performance data, with `¦`, normalizes line endings and
+/// redacts secrets
+fn sanitize_output(s: &str) -> String {
+    redact(s).replace("\r\n", "\n").replace('\r', "\n").replace('|', "\u{a6}")
 }
 
 
@@ -70,17 +217,89 @@ impl CheckResult {
     /// ```
     pub fn new(state: State) -> Self {
         Self {
-            state: state,
+            state,
             info: None,
             perf_data: None,
+            long_output: Vec::new(),
+            debug: Vec::new(),
+            verbosity: 0,
+            service: None,
+            first_line_limit: None,
+            max_output_bytes: None,
         }
     }
 
-    /// Sets the info string for a CheckResult and returns the CheckResult
+    /// Returns a new OK CheckResult with the info string `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the info string, a `String` or `&str`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// assert_eq!(CheckResult::ok("all fine").to_string(), "OK - all fine");
+    /// assert_eq!(CheckResult::warning("load is high").state(), State::Warning);
+    /// assert_eq!(CheckResult::critical("host is down").state(), State::Critical);
+    /// assert_eq!(CheckResult::unknown("no data").state(), State::Unknown);
+    /// ```
+    pub fn ok<S: Into<String>>(text: S) -> Self {
+        Self::new(State::OK).set_info(text)
+    }
+
+    /// Returns a new WARNING CheckResult with the info string `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the info string, a `String` or `&str`
+    pub fn warning<S: Into<String>>(text: S) -> Self {
+        Self::new(State::Warning).set_info(text)
+    }
+
+    /// Returns a new CRITICAL CheckResult with the info string `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the info string, a `String` or `&str`
+    pub fn critical<S: Into<String>>(text: S) -> Self {
+        Self::new(State::Critical).set_info(text)
+    }
+
+    /// Returns a new UNKNOWN CheckResult with the info string `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the info string, a `String` or `&str`
+    pub fn unknown<S: Into<String>>(text: S) -> Self {
+        Self::new(State::Unknown).set_info(text)
+    }
+
+    /// Replaces the state of a CheckResult and returns the CheckResult
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - A State struct that represents the state of the check
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let check_result = CheckResult::from(0).set_state(State::Critical);
+    /// assert_eq!(check_result.state(), State::Critical);
+    /// ```
+    pub fn set_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Sets the info string for a CheckResult and returns the CheckResult. When displayed, `|`
+    /// is replaced by `¦`, so it cannot be mistaken for the start of the performance data, and
+    /// all lines but the first are moved to the long output.
     ///
     /// # Arguments
     ///
-    /// * `text` - An owned String, passed to the function
+    /// * `text` - the info string, a `String` or `&str`
     ///
     /// # Examples
     ///
@@ -88,12 +307,40 @@ impl CheckResult {
     /// use icingaplugin_rs::check::{CheckResult, State};
     /// let check_result = CheckResult::new(State::OK).set_info(String::from("Everything fine."));
     /// assert_eq!(check_result.to_string(), String::from("OK - Everything fine."));
+    ///
+    /// let check_result = CheckResult::new(State::OK).set_info(String::from("a|b\r\nc"));
+    /// assert_eq!(check_result.to_string(), "OK - a\u{a6}b\nc");
+    /// ```
+    pub fn set_info<S: Into<String>>(mut self, text: S) -> Self {
+        self.info = Some(text.into());
+        self
+    }
+
+    /// Sets the name of the service and returns the CheckResult. The name prefixes the displayed
+    /// output, as many plugins do, while the state and exit code stay the same.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the service, e.g. `DISK`
+    ///
+    /// # Examples
+    ///
     /// ```
-    pub fn set_info(mut self, text: String) -> Self {
-        self.info = Some(text);
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let check_result = CheckResult::ok("all disks fine").set_service("DISK");
+    /// assert_eq!(check_result.to_string(), "DISK OK - all disks fine");
+    /// assert_eq!(check_result.state(), State::OK);
+    /// ```
+    pub fn set_service<S: Into<String>>(mut self, name: S) -> Self {
+        self.service = Some(name.into());
         self
     }
 
+    /// Returns the name of the service, if set
+    pub fn service(&self) -> Option<&str> {
+        self.service.as_deref()
+    }
+
     /// Sets the performance data for a CheckResult and returns the CheckResult
     /// 
     /// # Arguments
@@ -113,241 +360,1537 @@ impl CheckResult {
         self
     }
 
-    /// Returns the `state` field of a `CheckResult`
+    /// Limits the length of the first line of the displayed output to `bytes` and returns the
+    /// CheckResult. Metrics which do not fit into it, and all following ones, are displayed after
+    /// the long output, one per line, as the Nagios plugin API allows. Classic Nagios reads only
+    /// the first 1024 bytes of a line.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the maximum length of the first line
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::{CheckResult, State};
-    /// assert_eq!(CheckResult::from(0).state(), State::OK);
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// let check_result = CheckResult::ok("2 interfaces up")
+    ///     .add_metric(Metric::new("eth0", 1))
+    ///     .add_metric(Metric::new("eth1", 1))
+    ///     .add_metric(Metric::new("eth2", 0))
+    ///     .add_long_output("eth2 is not connected")
+    ///     .set_first_line_limit(40);
+    /// assert_eq!(check_result.to_string(), "OK - 2 interfaces up | 'eth0'=1;;;; \n\
+    ///     eth2 is not connected | 'eth1'=1;;;;\n'eth2'=0;;;;");
     /// ```
-    pub fn state(&self) -> State {
-        self.state
+    pub fn set_first_line_limit(mut self, bytes: usize) -> Self {
+        self.first_line_limit = Some(bytes);
+        self
     }
 
-    /// Prints the formatted `CheckResult` and returns the corresponding exit code
+    /// Sorts the metrics of the performance data by label and returns the CheckResult, so the
+    /// output is the same regardless of the order the metrics were collected in, see
+    /// `PerfData::sort_by_label()`
     ///
     /// # Examples
     ///
-    /// ``` 
-    /// use icingaplugin_rs::check::CheckResult;
-    /// assert_eq!(CheckResult::from(0).promote(), 0);
-    pub fn promote(&self) -> i32 {
-        println!("{}", self);
-
-        self.state.into()
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// let cr = CheckResult::ok("2 disks").add_metric(Metric::new("sdb", 2)).add_metric(Metric::new("sda", 1));
+    /// assert_eq!(cr.sort_perf_data().to_string(), "OK - 2 disks | 'sda'=1;;;; 'sdb'=2;;;; ");
+    /// ```
+    pub fn sort_perf_data(mut self) -> Self {
+        if let Some(pd) = self.perf_data.as_mut() {
+            pd.sort_by_label();
+        }
+        self
     }
-}
-
-
-/// A struct for collecting `metrics` to be embedded into a `CheckResult`
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct PerfData {
-    /// A list of `Metric` objects to propagate to Icinga2
-    metrics: Vec<Metric>,
-}
-
 
-impl PerfData {
-    /// Creates a new `PerfData` struct, wrapping a single `Metric`
+    /// Resolves metrics of the performance data with the same label according to `policy` and
+    /// returns the CheckResult, see `PerfData::dedup()`. With `DedupPolicy::Error`, duplicates
+    /// turn the CheckResult UNKNOWN.
     ///
     /// # Arguments
     ///
-    /// * `single_metric` - a single Metric to be wrapped by the struct. It will be converted into
-    /// a `Vec`
+    /// * `policy` - how duplicates are resolved
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::{PerfData, Metric};
-    /// let metric = Metric::new(String::from("label"), String::from("value"));
-    /// let pd = PerfData::from_metric(metric);
-    /// assert_eq!(pd.to_string(), "'label'=value;;;; ");
-    pub fn from_metric(single_metric: Metric) -> Self {
-        Self {
-            metrics: vec![single_metric],
+    /// use icingaplugin_rs::check::{CheckResult, DedupPolicy, Metric};
+    /// let cr = CheckResult::ok("fine").add_metric(Metric::new("rx", 1)).add_metric(Metric::new("rx", 2));
+    /// assert_eq!(cr.clone().dedup_perf_data(DedupPolicy::KeepLast).to_string(), "OK - fine | 'rx'=2;;;; ");
+    /// assert_eq!(cr.dedup_perf_data(DedupPolicy::Error).to_string(),
+    ///     "UNKNOWN - duplicate metric label 'rx' | 'rx'=1;;;; 'rx'=2;;;; ");
+    /// ```
+    pub fn dedup_perf_data(mut self, policy: DedupPolicy) -> Self {
+        if let Some(Err(e)) = self.perf_data.as_mut().map(|pd| pd.dedup(policy)) {
+            self.state = State::Unknown;
+            self.info = Some(e.to_string());
         }
+        self
     }
 
-    /// Creates a new `PerfData` struct, wrapping a collection of `metrics`
+    /// Limits the length of the displayed output to `bytes` and returns the CheckResult. Long
+    /// output exceeding the limit is cut off at a line boundary and replaced by a
+    /// `(output truncated, N lines omitted)` marker, so Icinga2 never cuts off the output in
+    /// the middle of a metric. The first line and the performance data are never truncated.
     ///
     /// # Arguments
     ///
-    /// * `multiple_metrics` - a `Vec` containing a collection of `Metric` structurist
+    /// * `bytes` - the maximum length of the output
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::{PerfData, Metric};
-    /// let metrics = vec![Metric::new(String::from("label1"), String::from("value1")),
-    /// Metric::new(String::from("label2"), String::from("value2"))];
-    /// assert_eq!(PerfData::from_metrics(metrics).to_string(), String::from("'label1'=value1;;;; 'label2'=value2;;;; "));
-    pub fn from_metrics(multiple_metrics: Vec<Metric>) -> Self {
-        Self {
-            metrics: multiple_metrics,
-        }
-    }
-}
-
-
-impl fmt::Display for PerfData {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut output = String::new();
-        for metric in &self.metrics {
-            output += &(format!("{}", metric) + &String::from(" "));
-        }
-        write!(f, "{}", output)
-    }
-}
-
-
-/// A struct representing performance metrics in a format parsable for Icinga2
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Metric {
-    /// the `Metric` name
-    label: String,
-    /// the `Metric` value
-    value: String,
-    /// the `warning` threshold, if specified
-    warning: Option<String>,
-    /// the `critical` threshold, if specified
-    critical: Option<String>,
-    /// the `min`imum `value`, obsolete if `value` is UOM='%'
-    min: Option<String>,
-    /// the `max`imum `value`, obsolete if `value` is UOM='%'
-    max: Option<String>,
-}
-
-
-impl fmt::Display for Metric {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "'{}'={};{};{};{};{}", self.label, self.value,
-               self.warning.as_ref().unwrap_or(&String::from("")),
-               self.critical.as_ref().unwrap_or(&String::from("")),
-               self.min.as_ref().unwrap_or(&String::from("")),
-               self.max.as_ref().unwrap_or(&String::from(""))
-               )
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// let check_result = CheckResult::critical("5 files too old")
+    ///     .add_metric(Metric::new("old_files", 5))
+    ///     .set_long_output((1..=5).map(|i| format!("/var/spool/queue/{}", i)).collect())
+    ///     .set_max_output_bytes(120);
+    /// assert_eq!(check_result.to_string(), "CRITICAL - 5 files too old | 'old_files'=5;;;; \n\
+    ///     /var/spool/queue/1\n(output truncated, 4 lines omitted)");
+    /// ```
+    pub fn set_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = Some(bytes);
+        self
     }
-}
 
-
-impl Metric {
-    /// Creates a new `Metric` struct with a `label` and `value`
+    /// Appends a `Metric` to the performance data of a CheckResult and returns the CheckResult
     ///
     /// # Arguments
     ///
-    /// * `label` - the name of this `Metric`
-    /// * `value` - the value of this `Metric`
+    /// * `metric` - the `Metric` to append
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::Metric;
-    /// let metric = Metric::new(String::from("label"), String::from("value"));
-    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;;"));
+    /// use icingaplugin_rs::check::{CheckResult, Metric, State};
+    /// let check_result = CheckResult::new(State::OK)
+    ///     .add_metric(Metric::numeric("load1", 0.42))
+    ///     .add_metric(Metric::numeric("load5", 0.3));
+    /// assert_eq!(check_result.to_string(), "OK | 'load1'=0.42;;;; 'load5'=0.3;;;; ");
     /// ```
-    pub fn new(label: String, value: String) -> Self {
-        Metric {
-            label: label,
-            value: value,
-            warning: None,
-            critical: None,
-            min: None,
-            max: None,
-        }
+    pub fn add_metric(mut self, metric: Metric) -> Self {
+        self.perf_data.get_or_insert_with(PerfData::new).push(metric);
+        self
     }
 
-    /// Adds a `warning` threshold to the `Metric` struct
+    /// Appends a line to the long output of a CheckResult and returns the CheckResult. Long output
+    /// is displayed below the first line of the plugin output.
     ///
     /// # Arguments
     ///
-    /// * `warning` - the threshold to be set 
+    /// * `line` - the line, a `String` or `&str`
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::Metric;
-    /// let metric = Metric::new(String::from("label"),
-    /// String::from("value")).warning(5.to_string());
-    /// assert_eq!(metric.to_string(), String::from("'label'=value;5;;;"));
+    /// use icingaplugin_rs::check::{CheckResult, PerfData, Metric, State};
+    /// let check_result = CheckResult::new(State::Warning)
+    ///     .set_info(String::from("1 of 2 disks almost full"))
+    ///     .set_perf_data(PerfData::from_metric(Metric::numeric("/var", 93)))
+    ///     .add_long_output(String::from("/var is 93% full"))
+    ///     .add_long_output(String::from("/home is 12% full"));
+    /// assert_eq!(check_result.to_string(), "WARNING - 1 of 2 disks almost full | '/var'=93;;;; \n\
+    /// /var is 93% full\n/home is 12% full");
     /// ```
-    pub fn warning(mut self, warning: String) -> Self {
-        self.warning = Some(warning);
+    pub fn add_long_output<S: Into<String>>(mut self, line: S) -> Self {
+        self.long_output.push(line.into());
         self
     }
 
-    /// Adds a `critical` threshold to the `Metric` struct
+    /// Replaces the long output of a CheckResult and returns the CheckResult
     ///
     /// # Arguments
     ///
-    /// * `critical` - the threshold to be set 
+    /// * `lines` - The lines to display below the first line of the plugin output
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::Metric;
-    /// let metric = Metric::new(String::from("label"),
-    /// String::from("value")).critical(5.to_string());
-    /// assert_eq!(metric.to_string(), String::from("'label'=value;;5;;"));
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let check_result = CheckResult::new(State::OK)
+    ///     .set_long_output(vec![String::from("a"), String::from("b")]);
+    /// assert_eq!(check_result.to_string(), "OK\na\nb");
     /// ```
-    pub fn critical(mut self, critical: String) -> Self {
-        self.critical = Some(critical);
+    pub fn set_long_output(mut self, lines: Vec<String>) -> Self {
+        self.long_output = lines;
         self
     }
 
-    /// Adds a `min` threshold to the `Metric` struct
+    /// Returns the info string of a `CheckResult`, if set
+    pub fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
+    /// Returns the performance data of a `CheckResult`, if set
+    pub fn perf_data(&self) -> Option<&PerfData> {
+        self.perf_data.as_ref()
+    }
+
+    /// Returns the long output lines of a `CheckResult`
+    pub fn long_output(&self) -> &[String] {
+        &self.long_output
+    }
+
+    /// Adds a debug line to a CheckResult and returns the CheckResult. Debug lines are displayed
+    /// below the long output if the verbosity of the CheckResult is at least `level`, following
+    /// the Monitoring Plugins guidelines:
+    ///
+    /// * `1` - a single line of additional information (`-v`)
+    /// * `2` - configuration debug output, e.g. commands used (`-vv`)
+    /// * `3` - plugin problem diagnosis (`-vvv`)
     ///
     /// # Arguments
     ///
-    /// * `min` - the threshold to be set 
+    /// * `level` - the minimum verbosity required to display the line
+    /// * `line` - the line, a `String` or `&str`
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::Metric;
-    /// let metric = Metric::new(String::from("label"),
-    /// String::from("value")).min(5.to_string());
-    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;5;"));
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let check_result = CheckResult::new(State::OK)
+    ///     .add_debug(1, String::from("connected to db01:5432"))
+    ///     .add_debug(3, String::from("query: SELECT 1"));
+    /// assert_eq!(check_result.clone().to_string(), "OK");
+    /// assert_eq!(check_result.clone().set_verbosity(1).to_string(), "OK\nconnected to db01:5432");
+    /// assert_eq!(check_result.set_verbosity(3).to_string(),
+    ///     "OK\nconnected to db01:5432\nquery: SELECT 1");
     /// ```
-    pub fn min(mut self, min: String) -> Self {
-        self.min = Some(min);
+    pub fn add_debug<S: Into<String>>(mut self, level: u8, line: S) -> Self {
+        self.debug.push((level, line.into()));
         self
     }
 
-    /// Adds a `max` threshold to the `Metric` struct
+    /// Sets the verbosity level (0-3) of a CheckResult and returns the CheckResult. Levels above
+    /// 3 are capped.
     ///
     /// # Arguments
     ///
-    /// * `max` - the threshold to be set 
+    /// * `verbosity` - the number of `-v` flags the plugin was invoked with
+    pub fn set_verbosity(mut self, verbosity: u8) -> Self {
+        self.verbosity = verbosity.min(3);
+        self
+    }
+
+    /// Returns the verbosity level of a `CheckResult`
+    pub fn verbosity(&self) -> u8 {
+        self.verbosity
+    }
+
+    /// Returns the `state` field of a `CheckResult`
     ///
     /// # Examples
     ///
     /// ```
-    /// use icingaplugin_rs::check::Metric;
-    /// let metric = Metric::new(String::from("label"),
-    /// String::from("value")).max(5.to_string());
-    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;;5"));
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// assert_eq!(CheckResult::from(0).state(), State::OK);
     /// ```
-    pub fn max(mut self, max: String) -> Self {
-        self.max = Some(max);
-        self
+    pub fn state(&self) -> State {
+        self.state
     }
-}
 
+    /// Returns the result with the worst state of `results`, ranking states like
+    /// `State::worst()`. Of several results with the same state, the first one is returned.
+    /// Returns an UNKNOWN result if `results` is empty. Use `Overall` to combine all results
+    /// into a summary instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `results` - the results to collapse
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let results = vec![
+    ///     CheckResult::new(State::Warning).set_info(String::from("/var is 91% full")),
+    ///     CheckResult::new(State::Critical).set_info(String::from("/home is 99% full")),
+    ///     CheckResult::new(State::Critical).set_info(String::from("/tmp is 98% full")),
+    /// ];
+    /// assert_eq!(CheckResult::worst_of(results).to_string(), "CRITICAL - /home is 99% full");
+    /// assert_eq!(CheckResult::worst_of(Vec::new()).state(), State::Unknown);
+    /// ```
+    pub fn worst_of(results: Vec<CheckResult>) -> CheckResult {
+        let mut worst: Option<CheckResult> = None;
+        for result in results {
+            match &worst {
+                Some(w) if w.state >= result.state => {}
+                _ => worst = Some(result),
+            }
+        }
+        worst.unwrap_or_else(|| CheckResult::new(State::Unknown).set_info(String::from("no results")))
+    }
 
-/// An enum representing check states known to Icinga2
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum State {
-    OK,
-    Warning,
-    Critical,
-    Unknown,
-}
+    /// Prints the formatted `CheckResult` and returns the corresponding exit code
+    ///
+    /// # Examples
+    ///
+    /// ``` 
+    /// use icingaplugin_rs::check::CheckResult;
+    /// assert_eq!(CheckResult::from(0).promote(), 0);
+    pub fn promote(&self) -> i32 {
+        let _ = self.write_to(&mut io::stdout().lock());
 
+        self.state.into()
+    }
 
-impl fmt::Display for State {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self {
+    /// Writes the formatted `CheckResult`, followed by a line break, to `out` instead of stdout
+    /// and returns the corresponding exit code, e.g. to capture the output in tests or to send
+    /// it elsewhere from a daemon
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - the writer the output is written to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// let mut out = Vec::new();
+    /// assert_eq!(CheckResult::warning("load is high").write_to(&mut out).unwrap(), 1);
+    /// assert_eq!(out, b"WARNING - load is high\n");
+    /// ```
+    pub fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<i32> {
+        writeln!(out, "{}", self)?;
+        out.flush()?;
+        Ok(self.state.into())
+    }
+
+    /// Prints the formatted `CheckResult` and exits the process with the corresponding exit
+    /// code, so nothing can be printed after the plugin output
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// fn main() {
+    ///     CheckResult::new(State::OK).set_info(String::from("all fine")).exit()
+    /// }
+    /// ```
+    pub fn exit(self) -> ! {
+        crate::platform::exit(self.promote())
+    }
+
+    /// Returns the formatted `CheckResult` with ANSI colors for reading it in a terminal: the state
+    /// and the metrics breaching their thresholds are colored by their state. Apart from the
+    /// escape sequences, the output is identical to the `Display` output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// let check_result = CheckResult::warning("load is high")
+    ///     .add_metric(Metric::new("load1", 4.2).warning("4"))
+    ///     .add_metric(Metric::new("load5", 1.3).warning("4"));
+    /// assert_eq!(check_result.to_colored_string(),
+    ///     "\x1b[33mWARNING\x1b[0m - load is high | \x1b[33m'load1'=4.2;4;;;\x1b[0m 'load5'=1.3;4;;; ");
+    /// ```
+    pub fn to_colored_string(&self) -> String {
+        let mut out = String::new();
+        // writing into a `String` never fails
+        let _ = write_check_result(&mut out, self, true);
+        out
+    }
+
+    /// Returns the `CheckResult` as a JSON object containing the state, exit code, info string,
+    /// long output (including the debug lines visible at the current verbosity) and the
+    /// structured performance data
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric, PerfData, State};
+    /// let check_result = CheckResult::new(State::Warning)
+    ///     .set_info(String::from("load is \"high\""))
+    ///     .set_perf_data(PerfData::from_metric(Metric::numeric("load1", 4.2).warning(String::from("4"))))
+    ///     .add_long_output(String::from("4 cores"));
+    /// assert_eq!(check_result.to_json(), r#"{"state":"WARNING","exit_code":1,"info":"load is \"high\"","long_output":["4 cores"],"perfdata":[{"label":"load1","value":4.2,"uom":null,"warning":"4","critical":null,"min":null,"max":null}]}"#);
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"state\":");
+        write_json_string(&mut out, &self.state.to_string());
+        out += &format!(",\"exit_code\":{},\"info\":", i32::from(self.state));
+        write_json_option(&mut out, self.info.as_deref());
+
+        out += ",\"long_output\":[";
+        let debug = self.debug.iter().filter(|(level, _)| *level <= self.verbosity).map(|(_, l)| l);
+        for (i, line) in self.long_output.iter().chain(debug).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, line);
+        }
+
+        out += "],\"perfdata\":[";
+        if let Some(pd) = &self.perf_data {
+            for (i, metric) in pd.metrics.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                metric.write_json(&mut out);
+            }
+        }
+        out += "]}";
+        out
+    }
+}
+
+
+/// Aggregates the partial `CheckResult`s of a check looking at multiple items (e.g. one per disk
+/// or per URL) into a single `CheckResult`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overall {
+    /// the partial results in the order they were added
+    results: Vec<CheckResult>,
+}
+
+
+impl Overall {
+    /// Creates a new, empty `Overall`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a partial `CheckResult`
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - the result of a single item
+    pub fn push(&mut self, result: CheckResult) {
+        self.results.push(result);
+    }
+
+    /// Returns the number of partial results
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns whether no partial results were added
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Returns the worst `State` of all partial results, ranking Unknown between Warning and
+    /// Critical. Returns OK if no results were added.
+    pub fn state(&self) -> State {
+        State::worst(self.results.iter().map(CheckResult::state))
+    }
+
+    /// Collapses the partial results into a single `CheckResult`. Its state is the worst state of
+    /// all partial results, the info string counts the results per state and every partial result
+    /// is listed in the long output. The performance data of all partial results is merged.
+    /// Without any partial results, the `CheckResult` is UNKNOWN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric, Overall, PerfData, State};
+    /// let mut overall = Overall::new();
+    /// overall.push(CheckResult::new(State::OK).set_info(String::from("/ is 40% full"))
+    ///     .set_perf_data(PerfData::from_metric(Metric::numeric("/", 40))));
+    /// overall.push(CheckResult::new(State::Critical).set_info(String::from("/var is 97% full"))
+    ///     .set_perf_data(PerfData::from_metric(Metric::numeric("/var", 97))));
+    /// overall.push(CheckResult::new(State::OK).set_info(String::from("/home is 12% full")));
+    /// assert_eq!(overall.finish().to_string(), "CRITICAL - 1 critical, 2 ok | '/'=40;;;; '/var'=97;;;; \n\
+    /// \\_ [OK] / is 40% full\n\\_ [CRITICAL] /var is 97% full\n\\_ [OK] /home is 12% full");
+    /// ```
+    pub fn finish(self) -> CheckResult {
+        if self.results.is_empty() {
+            return CheckResult::new(State::Unknown).set_info(String::from("no results"));
+        }
+
+        let state = self.state();
+        let mut counts = [0usize; 4];
+        let mut metrics = Vec::new();
+        let mut long_output = Vec::new();
+
+        for result in self.results {
+            counts[usize::from(result.state.severity())] += 1;
+            long_output.push(match &result.info {
+                Some(info) => format!("\\_ [{}] {}", result.state, info),
+                None => format!("\\_ [{}]", result.state),
+            });
+            long_output.extend(result.long_output.into_iter().map(|l| format!("    {}", l)));
+            if let Some(pd) = result.perf_data {
+                metrics.extend(pd.metrics);
+            }
+        }
+
+        // most severe first, OK last
+        let summary: Vec<String> = [
+            (counts[3], "critical"),
+            (counts[2], "unknown"),
+            (counts[1], "warning"),
+            (counts[0], "ok"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| format!("{} {}", count, name))
+        .collect();
+
+        let mut result = CheckResult::new(state)
+            .set_info(summary.join(", "))
+            .set_long_output(long_output);
+        if !metrics.is_empty() {
+            result = result.set_perf_data(PerfData::from_metrics(metrics));
+        }
+        result
+    }
+}
+
+
+impl std::iter::FromIterator<CheckResult> for Overall {
+    fn from_iter<I: IntoIterator<Item = CheckResult>>(iter: I) -> Self {
+        Self {
+            results: iter.into_iter().collect(),
+        }
+    }
+}
+
+
+impl Extend<CheckResult> for Overall {
+    fn extend<I: IntoIterator<Item = CheckResult>>(&mut self, iter: I) {
+        self.results.extend(iter);
+    }
+}
+
+
+/// A struct for collecting `metrics` to be embedded into a `CheckResult`. Metrics are displayed
+/// in the order they were added, use `sort_by_label()` for an order independent of how they
+/// were collected, e.g. from a `HashMap` or by parallel threads.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PerfData {
+    /// A list of `Metric` objects to propagate to Icinga2
+    pub(crate) metrics: Vec<Metric>,
+}
+
+
+impl PerfData {
+    /// Creates a new `PerfData` struct, wrapping a single `Metric`
+    ///
+    /// # Arguments
+    ///
+    /// * `single_metric` - a single Metric to be wrapped by the struct. It will be converted into
+    ///   a `Vec`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{PerfData, Metric};
+    /// let metric = Metric::new(String::from("label"), String::from("value"));
+    /// let pd = PerfData::from_metric(metric);
+    /// assert_eq!(pd.to_string(), "'label'=value;;;; ");
+    pub fn from_metric(single_metric: Metric) -> Self {
+        Self {
+            metrics: vec![single_metric],
+        }
+    }
+
+    /// Creates a new `PerfData` struct, wrapping a collection of `metrics`
+    ///
+    /// # Arguments
+    ///
+    /// * `multiple_metrics` - a `Vec` containing a collection of `Metric` structurist
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{PerfData, Metric};
+    /// let metrics = vec![Metric::new(String::from("label1"), String::from("value1")),
+    /// Metric::new(String::from("label2"), String::from("value2"))];
+    /// assert_eq!(PerfData::from_metrics(metrics).to_string(), String::from("'label1'=value1;;;; 'label2'=value2;;;; "));
+    pub fn from_metrics(multiple_metrics: Vec<Metric>) -> Self {
+        Self {
+            metrics: multiple_metrics,
+        }
+    }
+
+    /// Creates a new, empty `PerfData` struct, to which metrics can be added while the check
+    /// progresses
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let mut pd = PerfData::new();
+    /// assert!(pd.is_empty());
+    /// pd.push(Metric::numeric("rta", 0.25));
+    /// pd.extend(vec![Metric::numeric("pl", 0), Metric::numeric("ttl", 57)]);
+    /// assert_eq!(pd.len(), 3);
+    /// let labels: Vec<_> = pd.iter().map(|m| m.to_string()).collect();
+    /// assert_eq!(labels, ["'rta'=0.25;;;;", "'pl'=0;;;;", "'ttl'=57;;;;"]);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            metrics: Vec::new(),
+        }
+    }
+
+    /// Creates a new, empty `PerfData` struct with room for `capacity` metrics, so plugins
+    /// reporting thousands of metrics, e.g. per interface or queue, push them without
+    /// reallocating
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the number of metrics expected
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let mut pd = PerfData::with_capacity(512);
+    /// for queue in 0..512 {
+    ///     pd.push(Metric::numeric(format!("queue{}", queue), 0));
+    /// }
+    /// assert!(pd.capacity() >= 512);
+    /// assert_eq!(pd.len(), 512);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            metrics: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of metrics the `PerfData` can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.metrics.capacity()
+    }
+
+    /// Reserves room for at least `additional` more metrics
+    ///
+    /// # Arguments
+    ///
+    /// * `additional` - the number of metrics about to be added
+    pub fn reserve(&mut self, additional: usize) {
+        self.metrics.reserve(additional);
+    }
+
+    /// Appends a `Metric`
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - the `Metric` to append
+    pub fn push(&mut self, metric: Metric) {
+        self.metrics.push(metric);
+    }
+
+    /// Returns the number of metrics
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Returns whether there are no metrics
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Returns an iterator over the metrics
+    pub fn iter(&self) -> std::slice::Iter<'_, Metric> {
+        self.metrics.iter()
+    }
+
+    /// Sorts the metrics by label, keeping the order of metrics with the same label
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let mut pd = PerfData::from_metrics(vec![Metric::new("sdb", 2), Metric::new("sda", 1), Metric::new("nvme0n1", 3)]);
+    /// pd.sort_by_label();
+    /// assert_eq!(pd.to_string(), "'nvme0n1'=3;;;; 'sda'=1;;;; 'sdb'=2;;;; ");
+    /// ```
+    pub fn sort_by_label(&mut self) {
+        self.metrics.sort_by(|a, b| a.label.cmp(&b.label));
+    }
+
+    /// Resolves metrics with the same label according to `policy`. Icinga2 keeps only one of
+    /// them, and which one is unpredictable.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - how duplicates are resolved
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{DedupPolicy, Metric, MetricError, PerfData};
+    /// let pd = PerfData::from_metrics(vec![Metric::new("rx", 1), Metric::new("tx", 2), Metric::new("rx", 3)]);
+    ///
+    /// let mut first = pd.clone();
+    /// first.dedup(DedupPolicy::KeepFirst).unwrap();
+    /// assert_eq!(first.to_string(), "'rx'=1;;;; 'tx'=2;;;; ");
+    ///
+    /// let mut last = pd.clone();
+    /// last.dedup(DedupPolicy::KeepLast).unwrap();
+    /// assert_eq!(last.to_string(), "'tx'=2;;;; 'rx'=3;;;; ");
+    ///
+    /// let mut suffixed = pd.clone();
+    /// suffixed.dedup(DedupPolicy::Suffix).unwrap();
+    /// assert_eq!(suffixed.to_string(), "'rx'=1;;;; 'tx'=2;;;; 'rx_2'=3;;;; ");
+    ///
+    /// assert_eq!(pd.clone().dedup(DedupPolicy::Error), Err(MetricError::DuplicateLabel(String::from("rx"))));
+    /// ```
+    pub fn dedup(&mut self, policy: DedupPolicy) -> Result<(), MetricError> {
+        let mut seen = std::collections::HashSet::new();
+        match policy {
+            DedupPolicy::Error => {
+                if let Some(m) = self.metrics.iter().find(|m| !seen.insert(m.label.clone())) {
+                    return Err(MetricError::DuplicateLabel(m.label.to_string()));
+                }
+            }
+            DedupPolicy::KeepFirst => self.metrics.retain(|m| seen.insert(m.label.clone())),
+            DedupPolicy::KeepLast => {
+                self.metrics.reverse();
+                self.metrics.retain(|m| seen.insert(m.label.clone()));
+                self.metrics.reverse();
+            }
+            DedupPolicy::Suffix => {
+                let labels: std::collections::HashSet<_> = self.metrics.iter().map(|m| m.label.clone()).collect();
+                for metric in &mut self.metrics {
+                    if seen.insert(metric.label.clone()) {
+                        continue;
+                    }
+                    // the first free suffix, also avoiding labels which only occur later
+                    let label = (2..)
+                        .map(|i| format!("{}_{}", metric.label, i))
+                        .find(|l| !labels.contains(l.as_str()) && !seen.contains(l.as_str()))
+                        .unwrap_or_default();
+                    seen.insert(Cow::Owned(label.clone()));
+                    metric.label = Cow::Owned(label);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl Default for PerfData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Extend<Metric> for PerfData {
+    fn extend<I: IntoIterator<Item = Metric>>(&mut self, iter: I) {
+        self.metrics.extend(iter);
+    }
+}
+
+
+impl std::iter::FromIterator<Metric> for PerfData {
+    /// Collects the metrics in one allocation if the iterator knows its length, e.g. when
+    /// mapping over a `Vec` or a range
+    fn from_iter<I: IntoIterator<Item = Metric>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut pd = Self::with_capacity(iter.size_hint().0);
+        pd.metrics.extend(iter);
+        pd
+    }
+}
+
+
+impl IntoIterator for PerfData {
+    type Item = Metric;
+    type IntoIter = std::vec::IntoIter<Metric>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.metrics.into_iter()
+    }
+}
+
+
+impl<'a> IntoIterator for &'a PerfData {
+    type Item = &'a Metric;
+    type IntoIter = std::slice::Iter<'a, Metric>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.metrics.iter()
+    }
+}
+
+
+impl fmt::Display for PerfData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for metric in &self.metrics {
+            write!(f, "{} ", metric)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// How `PerfData::dedup()` resolves metrics with the same label
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DedupPolicy {
+    /// Fail with `MetricError::DuplicateLabel`
+    Error,
+    /// Keep the metric added first
+    KeepFirst,
+    /// Keep the metric added last, at its position
+    KeepLast,
+    /// Keep all metrics, appending `_2`, `_3` and so on to the labels of later duplicates
+    Suffix,
+}
+
+
+/// A struct representing performance metrics in a format parsable for Icinga2
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metric {
+    /// the `Metric` name, borrowed if created by `Metric::from_static`
+    pub(crate) label: Cow<'static, str>,
+    /// the `Metric` value
+    pub(crate) value: Value,
+    /// the unit of measurement of `value`, if any
+    pub(crate) uom: Option<String>,
+    /// the `warning` threshold, if specified
+    pub(crate) warning: Option<String>,
+    /// the `critical` threshold, if specified
+    pub(crate) critical: Option<String>,
+    /// the `min`imum `value`, obsolete if `value` is UOM='%'
+    pub(crate) min: Option<String>,
+    /// the `max`imum `value`, obsolete if `value` is UOM='%'
+    pub(crate) max: Option<String>,
+    /// the number of decimal places a float `value` is displayed with, if fixed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) precision: Option<usize>,
+}
+
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("'")?;
+        // single quotes within the label are escaped by doubling them
+        for (i, part) in self.label.split('\'').enumerate() {
+            if i > 0 {
+                f.write_str("''")?;
+            }
+            f.write_str(part)?;
+        }
+        f.write_str("'=")?;
+        match (&self.value, self.precision) {
+            (Value::Float(v), Some(_)) if v.is_finite() => f.write_str(&format_float(*v, self.precision))?,
+            (value, _) => write!(f, "{}", value)?,
+        }
+        f.write_str(self.uom.as_deref().unwrap_or(""))?;
+        for field in [&self.warning, &self.critical, &self.min, &self.max] {
+            f.write_str(";")?;
+            f.write_str(field.as_deref().unwrap_or(""))?;
+        }
+        Ok(())
+    }
+}
+
+
+impl Metric {
+    /// Creates a new `Metric` struct with a `label` and `value`. Strings are passed through as is,
+    /// see `Metric::try_new` to parse a number from them.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`, a `String` or `&str`
+    /// * `value` - the value of this `Metric`, a number or a string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new(String::from("label"), String::from("value"));
+    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;;"));
+    /// assert_eq!(Metric::new("load1", 0.42).uom("s").warning("1").to_string(), "'load1'=0.42s;1;;;");
+    ///
+    /// let mount = String::from("/var");
+    /// assert_eq!(Metric::new(&mount, 12).to_string(), "'/var'=12;;;;");
+    /// ```
+    pub fn new<L: Into<String>, V: Into<Value>>(label: L, value: V) -> Self {
+        Metric::with_label(Cow::Owned(label.into()), value.into())
+    }
+
+    /// Creates a new `Metric` struct like `Metric::new`, but stores the `label` without copying
+    /// it, which saves an allocation per metric for plugins reporting many metrics with fixed
+    /// names
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`, a string literal
+    /// * `value` - the value of this `Metric`, a number or a string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// assert_eq!(Metric::from_static("load1", 0.42), Metric::new("load1", 0.42));
+    /// ```
+    pub fn from_static<V: Into<Value>>(label: &'static str, value: V) -> Self {
+        Metric::with_label(Cow::Borrowed(label), value.into())
+    }
+
+    fn with_label(label: Cow<'static, str>, value: Value) -> Self {
+        Metric {
+            label,
+            value,
+            uom: None,
+            warning: None,
+            critical: None,
+            min: None,
+            max: None,
+            precision: None,
+        }
+    }
+
+    /// Creates a new `Metric` struct with a numeric `value`, which is always formatted with `.` as
+    /// decimal separator and without exponent
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`
+    /// * `value` - the value of this `Metric`, an integer or float
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// assert_eq!(Metric::numeric("load", 1.73).to_string(), "'load'=1.73;;;;");
+    /// assert_eq!(Metric::numeric("procs", 312).to_string(), "'procs'=312;;;;");
+    /// assert_eq!(Metric::numeric("tiny", 0.0000001).to_string(), "'tiny'=0.0000001;;;;");
+    /// ```
+    pub fn numeric<L: Into<String>, V: Into<Value>>(label: L, value: V) -> Self {
+        Metric::new(label, value)
+    }
+
+    /// Creates a new `Metric` struct whose value could not be determined, e.g. because a single
+    /// item of a check could not be queried, so the set of metrics reported stays the same. The
+    /// value is displayed as `U`, exported as NaN to Prometheus and OpenMetrics, as `null` in JSON
+    /// and skipped by the other exporters.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric, PerfData, State};
+    /// let metric = Metric::unknown("/mnt/nfs").uom(String::from("%")).warning(String::from("80"));
+    /// assert_eq!(metric.to_string(), "'/mnt/nfs'=U%;80;;;");
+    ///
+    /// let cr = CheckResult::new(State::Unknown).set_perf_data(PerfData::from_metric(metric));
+    /// assert!(cr.to_json().contains(r#""value":null"#));
+    /// ```
+    pub fn unknown<L: Into<String>>(label: L) -> Self {
+        Metric::numeric(label, Value::Unknown)
+    }
+
+    /// Creates a new `Metric` struct from a textual value consisting of a number and an optional
+    /// unit of measurement, e.g. when passing on values read from another program's output.
+    /// Fails if the value does not start with a number or the label is invalid, see
+    /// `Metric::validate_label()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`
+    /// * `value` - the value of this `Metric`, e.g. `"42"`, `"-0.5"` or `"512MB"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, MetricError, Value};
+    /// let metric = Metric::try_new("used", "512MB").unwrap();
+    /// assert_eq!(metric.value(), &Value::Integer(512));
+    /// assert_eq!(metric.to_string(), "'used'=512MB;;;;");
+    /// assert_eq!(Metric::try_new("rta", "0.25ms").unwrap().value(), &Value::Float(0.25));
+    /// assert_eq!(Metric::try_new("used", "1,5GB"), Err(MetricError::InvalidValue(String::from("1,5GB"))));
+    /// assert_eq!(Metric::try_new("a=b", "1"), Err(MetricError::InvalidLabel(String::from("a=b"))));
+    /// ```
+    pub fn try_new<L: Into<String>>(label: L, value: &str) -> Result<Self, MetricError> {
+        let label = label.into();
+        Metric::validate_label(&label)?;
+        let invalid = || MetricError::InvalidValue(value.to_string());
+        let split = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(value.len());
+        let (number, uom) = value.split_at(split);
+        if !uom.chars().all(|c| c.is_alphabetic() || c == '%') {
+            return Err(invalid());
+        }
+
+        let parsed = match number.parse::<i64>() {
+            Ok(i) => Value::Integer(i),
+            Err(_) => match number.parse::<f64>() {
+                Ok(f) if f.is_finite() => Value::Float(f),
+                _ => return Err(invalid()),
+            },
+        };
+
+        let metric = Metric::numeric(label, parsed);
+        if uom.is_empty() {
+            Ok(metric)
+        } else {
+            Ok(metric.uom(uom.to_string()))
+        }
+    }
+
+    /// Checks whether `label` can be used as a `Metric` label. Labels must not be empty and must
+    /// neither contain `=` nor control characters like newlines, which Icinga2 cannot parse.
+    /// Single quotes and spaces are allowed, labels are always quoted and single quotes are
+    /// escaped as `''` when displayed. Use `sanitize_label()` to turn any string into a valid
+    /// label.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label to check
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, MetricError};
+    /// assert_eq!(Metric::validate_label("it's /var"), Ok(()));
+    /// assert_eq!(Metric::numeric("it's /var", 1).to_string(), "'it''s /var'=1;;;;");
+    /// assert_eq!(Metric::validate_label("x=1"), Err(MetricError::InvalidLabel(String::from("x=1"))));
+    /// assert!(Metric::validate_label("").is_err());
+    /// ```
+    pub fn validate_label(label: &str) -> Result<(), MetricError> {
+        if label.is_empty() || label.contains(|c: char| c == '=' || c.is_control()) {
+            return Err(MetricError::InvalidLabel(label.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sets the unit of measurement of the `Metric`'s value
+    ///
+    /// # Arguments
+    ///
+    /// * `uom` - the unit, e.g. `%`, `s`, `B` or `c`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::numeric("disk", 87.5).uom(String::from("%"));
+    /// assert_eq!(metric.to_string(), "'disk'=87.5%;;;;");
+    /// ```
+    pub fn uom<S: Into<String>>(mut self, uom: S) -> Self {
+        self.uom = Some(uom.into());
+        self
+    }
+
+    /// Sets the number of decimal places a float value is displayed with, e.g. to avoid
+    /// reporting noise in the last digits. Integer and text values are displayed as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `decimals` - the number of decimal places
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// assert_eq!(Metric::numeric("load", 1.73456).precision(2).to_string(), "'load'=1.73;;;;");
+    /// assert_eq!(Metric::numeric("load", 2.0).precision(2).to_string(), "'load'=2.00;;;;");
+    /// assert_eq!(Metric::numeric("procs", 312).precision(2).to_string(), "'procs'=312;;;;");
+    /// ```
+    pub fn precision(mut self, decimals: usize) -> Self {
+        self.precision = Some(decimals);
+        self
+    }
+
+    /// Returns the `value` formatted with the configured precision
+    pub(crate) fn formatted_value(&self) -> String {
+        match (&self.value, self.precision) {
+            (Value::Float(v), Some(_)) if v.is_finite() => format_float(*v, self.precision),
+            (value, _) => value.to_string(),
+        }
+    }
+
+    /// Returns the state of the `value` according to the `warning` and `critical` thresholds.
+    /// Thresholds which are not valid ranges and non-numeric values are ignored.
+    pub(crate) fn threshold_state(&self) -> State {
+        let value = match crate::export::as_f64(&self.value) {
+            Some(v) if !v.is_nan() => v,
+            _ => return State::OK,
+        };
+        let range = |r: &Option<String>| r.as_deref().and_then(|r| r.parse::<Range>().ok());
+        let mut thresholds = Thresholds::new();
+        if let Some(w) = range(&self.warning) {
+            thresholds = thresholds.warning(w);
+        }
+        if let Some(c) = range(&self.critical) {
+            thresholds = thresholds.critical(c);
+        }
+        thresholds.state(value)
+    }
+
+    /// Returns the label of the `Metric`
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the `value` of the `Metric`
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Appends the `Metric` as a JSON object to `out`
+    fn write_json(&self, out: &mut String) {
+        out.push_str("{\"label\":");
+        write_json_string(out, &self.label);
+        out.push_str(",\"value\":");
+        match &self.value {
+            Value::Integer(i) => out.push_str(&i.to_string()),
+            Value::Float(f) if f.is_finite() => out.push_str(&f.to_string()),
+            Value::Float(_) | Value::Unknown => out.push_str("null"),
+            Value::Text(t) => write_json_string(out, t),
+        }
+        for (key, field) in [
+            ("uom", &self.uom),
+            ("warning", &self.warning),
+            ("critical", &self.critical),
+            ("min", &self.min),
+            ("max", &self.max),
+        ].iter() {
+            out.push_str(&format!(",\"{}\":", key));
+            write_json_option(out, field.as_deref());
+        }
+        out.push('}');
+    }
+
+    /// Adds a `warning` threshold to the `Metric` struct
+    ///
+    /// # Arguments
+    ///
+    /// * `warning` - the threshold to be set 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new(String::from("label"),
+    /// String::from("value")).warning(5.to_string());
+    /// assert_eq!(metric.to_string(), String::from("'label'=value;5;;;"));
+    /// ```
+    pub fn warning<S: Into<String>>(mut self, warning: S) -> Self {
+        self.warning = Some(warning.into());
+        self
+    }
+
+    /// Adds a `critical` threshold to the `Metric` struct
+    ///
+    /// # Arguments
+    ///
+    /// * `critical` - the threshold to be set 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new(String::from("label"),
+    /// String::from("value")).critical(5.to_string());
+    /// assert_eq!(metric.to_string(), String::from("'label'=value;;5;;"));
+    /// ```
+    pub fn critical<S: Into<String>>(mut self, critical: S) -> Self {
+        self.critical = Some(critical.into());
+        self
+    }
+
+    /// Adds a `min` threshold to the `Metric` struct
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - the threshold to be set 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new(String::from("label"),
+    /// String::from("value")).min(5.to_string());
+    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;5;"));
+    /// ```
+    pub fn min<S: Into<String>>(mut self, min: S) -> Self {
+        self.min = Some(min.into());
+        self
+    }
+
+    /// Adds a `max` threshold to the `Metric` struct
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - the threshold to be set 
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new(String::from("label"),
+    /// String::from("value")).max(5.to_string());
+    /// assert_eq!(metric.to_string(), String::from("'label'=value;;;;5"));
+    /// ```
+    pub fn max<S: Into<String>>(mut self, max: S) -> Self {
+        self.max = Some(max.into());
+        self
+    }
+
+    /// Fills in `min` and `max` where they follow from the UOM or thresholds, so graphs are
+    /// scaled correctly. Bounds which are already set are kept.
+    ///
+    /// * `%` values range from 0 to 100
+    /// * counters (`c`) never drop below 0
+    /// * numeric values start at 0 if neither they nor any end of their warning and critical
+    ///   ranges are negative, e.g. `10` or `20:80`, but not `-5:5`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::numeric("/var", 87.5).uom(String::from("%")).infer_bounds();
+    /// assert_eq!(metric.to_string(), "'/var'=87.5%;;;0;100");
+    ///
+    /// let metric = Metric::numeric("procs", 312).warning(String::from("400")).infer_bounds();
+    /// assert_eq!(metric.to_string(), "'procs'=312;400;;0;");
+    ///
+    /// let metric = Metric::numeric("temp", 21).warning(String::from("-5:30")).infer_bounds();
+    /// assert_eq!(metric.to_string(), "'temp'=21;-5:30;;;");
+    /// ```
+    pub fn infer_bounds(mut self) -> Self {
+        let value = match self.value {
+            Value::Integer(i) => i as f64,
+            Value::Float(f) if f.is_finite() => f,
+            _ => return self,
+        };
+
+        match self.uom.as_deref() {
+            Some("%") => {
+                self.min.get_or_insert_with(|| String::from("0"));
+                self.max.get_or_insert_with(|| String::from("100"));
+            }
+            Some("c") => {
+                self.min.get_or_insert_with(|| String::from("0"));
+            }
+            _ => {
+                let ranges: Option<Vec<Range>> = [&self.warning, &self.critical]
+                    .iter()
+                    .filter_map(|t| t.as_deref())
+                    .map(|t| t.parse().ok())
+                    .collect();
+                let non_negative = ranges.is_some_and(|ranges| {
+                    ranges.iter().all(|r| [r.start(), r.end()].iter().all(|b| b.is_infinite() || *b >= 0.0))
+                });
+                if value >= 0.0 && non_negative {
+                    self.min.get_or_insert_with(|| String::from("0"));
+                }
+            }
+        }
+        self
+    }
+}
+
+
+/// The value of a `Metric`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Value {
+    /// An integer value
+    Integer(i64),
+    /// A floating point value
+    Float(f64),
+    /// A value passed through as is, see `Metric::new`
+    Text(String),
+    /// A value which could not be determined, displayed as `U`, see `Metric::unknown`
+    Unknown,
+}
+
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            // `Display` for floats never uses an exponent and is locale-independent
+            Value::Float(v) if v.is_finite() => write!(f, "{}", v),
+            // Icinga2 rejects NaN and infinity
+            Value::Float(_) | Value::Unknown => write!(f, "U"),
+            Value::Text(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+
+macro_rules! value_from_integer {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(value: $t) -> Self {
+                    Value::Integer(i64::from(value))
+                }
+            }
+        )*
+    };
+}
+
+value_from_integer!(i8, i16, i32, i64, u8, u16, u32);
+
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        match i64::try_from(value) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::Float(value as f64),
+        }
+    }
+}
+
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float(f64::from(value))
+    }
+}
+
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+
+/// Formats `value` the way Icinga2 parses performance data: always with `.` as decimal separator
+/// and never in scientific notation, which `{:e}` or other languages' defaults would produce.
+/// Use it to format numeric thresholds consistently with the metric value.
+///
+/// # Arguments
+///
+/// * `value` - the number to format
+/// * `decimals` - the number of decimal places, `None` for as many as needed
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{format_float, Metric};
+/// assert_eq!(format_float(0.0000001, None), "0.0000001");
+/// assert_eq!(format_float(1e21, None), "1000000000000000000000");
+/// assert_eq!(format_float(2.0 / 3.0, Some(3)), "0.667");
+///
+/// let metric = Metric::numeric("rta", 0.0125).precision(3).warning(format_float(0.1, Some(3)));
+/// assert_eq!(metric.to_string(), "'rta'=0.013;0.100;;;");
+/// ```
+pub fn format_float(value: f64, decimals: Option<usize>) -> String {
+    match decimals {
+        Some(d) => format!("{:.*}", d, value),
+        // `Display` for floats never uses an exponent and is locale-independent
+        None => value.to_string(),
+    }
+}
+
+
+/// Turns `label` into a valid `Metric` label by replacing `=` and control characters with `_`.
+/// An empty label becomes `_`.
+///
+/// # Arguments
+///
+/// * `label` - the label to sanitize, e.g. a mount point or interface name read from the system
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::sanitize_label;
+/// assert_eq!(sanitize_label("load=1\n"), "load_1_");
+/// assert_eq!(sanitize_label("it's /var"), "it's /var");
+/// assert_eq!(sanitize_label(""), "_");
+/// ```
+pub fn sanitize_label(label: &str) -> String {
+    if label.is_empty() {
+        return String::from("_");
+    }
+    label.chars().map(|c| if c == '=' || c.is_control() { '_' } else { c }).collect()
+}
+
+
+/// An error occurring while building a `Metric`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MetricError {
+    /// The value is not a number followed by an optional unit of measurement
+    InvalidValue(String),
+    /// The label is empty or contains `=` or control characters
+    InvalidLabel(String),
+    /// Several metrics have the same label, see `DedupPolicy::Error`
+    DuplicateLabel(String),
+}
+
+
+impl fmt::Display for MetricError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricError::InvalidValue(v) => write!(f, "invalid metric value '{}'", v),
+            MetricError::InvalidLabel(l) => write!(f, "invalid metric label '{}'", l),
+            MetricError::DuplicateLabel(l) => write!(f, "duplicate metric label '{}'", l),
+        }
+    }
+}
+
+
+impl Error for MetricError {}
+
+
+/// An error occurring while converting a value into a `State`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateError {
+    /// The string is neither the name nor the exit code of a state
+    InvalidName(String),
+    /// The exit code is outside of 0..=3
+    InvalidCode(i32),
+}
+
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidName(s) => write!(f, "invalid state '{}'", s),
+            StateError::InvalidCode(c) => write!(f, "exit code {} does not denote a state", c),
+        }
+    }
+}
+
+
+impl Error for StateError {}
+
+
+/// Appends `s` as a quoted and escaped JSON string to `out`
+pub(crate) fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in redact(s).chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+
+/// Appends `s` as JSON string to `out`, or `null` if it is not set
+pub(crate) fn write_json_option(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => write_json_string(out, s),
+        None => out.push_str("null"),
+    }
+}
+
+
+/// An enum representing check states known to Icinga2. States are ordered by severity, see
+/// `Ord`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum State {
+    OK,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+
+impl State {
+    /// Returns the worst of `states` by severity, where Unknown ranks between Warning and
+    /// Critical (see `Ord`). Returns OK if `states` is empty, as nothing is wrong.
+    ///
+    /// # Arguments
+    ///
+    /// * `states` - the states to collapse
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// assert_eq!(State::worst(vec![State::OK, State::Unknown, State::Warning]), State::Unknown);
+    /// assert_eq!(State::worst(Vec::new()), State::OK);
+    /// ```
+    pub fn worst<I: IntoIterator<Item = State>>(states: I) -> State {
+        states.into_iter().max().unwrap_or(State::OK)
+    }
+
+    /// Maps an exit code to its `State`, treating every code outside of 0..=3 as Unknown, like
+    /// Icinga2 does. Use `State::try_from()` to tell these codes apart from a genuine UNKNOWN.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - the exit code of a plugin
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// assert_eq!(State::from_exit_code_lossy(2), State::Critical);
+    /// assert_eq!(State::from_exit_code_lossy(127), State::Unknown);
+    /// ```
+    pub fn from_exit_code_lossy(code: i32) -> State {
+        State::try_from(code).unwrap_or(State::Unknown)
+    }
+
+    /// Ranks states by severity for aggregation: OK < Warning < Unknown < Critical
+    pub(crate) fn severity(self) -> u8 {
+        match self {
+            State::OK => 0,
+            State::Warning => 1,
+            State::Unknown => 2,
+            State::Critical => 3,
+        }
+    }
+}
+
+
+impl Ord for State {
+    /// Orders states by severity: OK < Warning < Unknown < Critical. Unknown ranks below
+    /// Critical, as a failed check says less about the service than a confirmed problem. This
+    /// differs from the order of the exit codes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// let states = [State::Warning, State::Critical, State::Unknown];
+    /// assert_eq!(states.iter().max(), Some(&State::Critical));
+    /// assert!(State::OK < State::Warning && State::Warning < State::Unknown);
+    /// ```
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.severity().cmp(&other.severity())
+    }
+}
+
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
             State::OK => "OK",
             State::Warning => "WARNING",
             State::Critical => "CRITICAL",
@@ -357,6 +1900,69 @@ impl fmt::Display for State {
 }
 
 
+impl FromStr for State {
+    type Err = StateError;
+
+    /// Parses a `State` from its name, a common abbreviation or its exit code, ignoring case
+    /// and surrounding whitespace: `OK`, `WARNING`/`WARN`, `CRITICAL`/`CRIT`, `UNKNOWN`/`UNKN`
+    /// and `0` to `3`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// assert_eq!("warn".parse(), Ok(State::Warning));
+    /// assert_eq!(" CRITICAL ".parse(), Ok(State::Critical));
+    /// assert_eq!("3".parse(), Ok(State::Unknown));
+    /// assert!("fine".parse::<State>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "OK" | "0" => Ok(State::OK),
+            "WARNING" | "WARN" | "1" => Ok(State::Warning),
+            "CRITICAL" | "CRIT" | "2" => Ok(State::Critical),
+            "UNKNOWN" | "UNKN" | "3" => Ok(State::Unknown),
+            _ => Err(StateError::InvalidName(s.to_string())),
+        }
+    }
+}
+
+
+impl TryFrom<&str> for State {
+    type Error = StateError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+
+impl TryFrom<i32> for State {
+    type Error = StateError;
+
+    /// Maps an exit code to its `State`, failing for codes outside of 0..=3, e.g. 127 for a
+    /// command which could not be found
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{State, StateError};
+    /// use std::convert::TryFrom;
+    /// assert_eq!(State::try_from(3), Ok(State::Unknown));
+    /// assert_eq!(State::try_from(127), Err(StateError::InvalidCode(127)));
+    /// ```
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(State::OK),
+            1 => Ok(State::Warning),
+            2 => Ok(State::Critical),
+            3 => Ok(State::Unknown),
+            _ => Err(StateError::InvalidCode(code)),
+        }
+    }
+}
+
+
 impl From<State> for i32 {
     fn from(state: State) -> Self {
         match state {
@@ -0,0 +1,595 @@
+use crate::extra_opts;
+use crate::runner::{self, OutputFormat, PluginInfo};
+use crate::thresholds::Range;
+use crate::units::parse_duration;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use std::ffi::OsString;
+use std::process;
+use std::time::Duration;
+
+#[doc(hidden)]
+pub use clap;
+#[cfg(feature = "derive")]
+pub use icingaplugin_derive::PluginArgs;
+
+/// The exit code used for usage errors, `--help` and `--version`, as the Monitoring Plugins
+/// guidelines demand
+const EXIT_UNKNOWN: i32 = 3;
+
+/// The standard monitoring plugin arguments, parsed into the crate's types
+#[derive(Clone, Debug, PartialEq)]
+pub struct StandardArgs {
+    /// the warning threshold given by `-w/--warning`
+    pub warning: Option<Range>,
+    /// the critical threshold given by `-c/--critical`
+    pub critical: Option<Range>,
+    /// the timeout given by `-t/--timeout`, 10 seconds by default
+    pub timeout: Duration,
+    /// the host given by `-H/--hostname`
+    pub hostname: Option<String>,
+    /// the number of `-v/--verbose` flags, at most 3
+    pub verbose: u8,
+    /// the output format given by `--output-format`, `Text` by default
+    pub output_format: OutputFormat,
+    /// the interval given by `--watch`, see `Runner::watch()`
+    pub watch: Option<Duration>,
+    /// whether `--dry-run` was given, see `Runner::dry_run()`
+    pub dry_run: bool,
+}
+
+
+impl StandardArgs {
+    /// Reads the standard arguments from `matches`. The `Command` the matches stem from must have
+    /// been built with `standard_args()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - the parsed command line
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clap::Command;
+    /// use icingaplugin_rs::cli::{standard_args, StandardArgs};
+    /// use icingaplugin_rs::runner::OutputFormat;
+    /// use std::time::Duration;
+    /// let matches = standard_args(Command::new("check_foo"))
+    ///     .get_matches_from(["check_foo", "-w", "10:", "-c", "@0:5", "-t", "30", "-vv"]);
+    /// let args = StandardArgs::from_matches(&matches);
+    /// assert_eq!(args.warning.unwrap().to_string(), "10:");
+    /// assert!(args.critical.unwrap().alert(3));
+    /// assert_eq!(args.timeout, Duration::from_secs(30));
+    /// assert_eq!(args.hostname, None);
+    /// assert_eq!(args.verbose, 2);
+    /// assert_eq!(args.output_format, OutputFormat::Text);
+    /// ```
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            warning: matches.get_one::<Range>("warning").copied(),
+            critical: matches.get_one::<Range>("critical").copied(),
+            timeout: matches
+                .get_one::<Duration>("timeout")
+                .copied()
+                .unwrap_or(Duration::from_secs(10)),
+            hostname: matches.get_one::<String>("hostname").cloned(),
+            verbose: matches.get_count("verbose").min(3),
+            output_format: matches
+                .get_one::<OutputFormat>("output_format")
+                .copied()
+                .unwrap_or(OutputFormat::Text),
+            watch: matches.get_one::<Duration>("watch").copied(),
+            dry_run: matches.get_flag("dry_run"),
+        }
+    }
+}
+
+
+/// Expands to a complete `main` function for a plugin: parses the command line with the standard
+/// arguments, runs the check function with a `Runner` enforcing `--timeout` and turning errors
+/// and panics into UNKNOWN results, prints the result in the chosen output format and exits
+/// with its exit code. `-V/--version`, `--help`, `--extra-opts` and `--dump-icinga2-command`
+/// work as usual.
+///
+/// The check function gets the parsed arguments, `StandardArgs` by default or the given type
+/// implementing `PluginArgs`, and returns a `Result<CheckResult, E>`, where `E` is any error
+/// convertible into a boxed error. The metadata defaults to `plugin_info!()`.
+///
+/// ```text
+/// plugin!(check);
+/// plugin! { info: plugin_info!().set_author("NETWAYS GmbH"), check: check }
+/// plugin! { args: Args, check: |args: &Args| ... }
+/// plugin! { info: ..., args: Args, check: ... }
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{CheckResult, Metric};
+/// use icingaplugin_rs::cli::StandardArgs;
+///
+/// fn check(args: &StandardArgs) -> Result<CheckResult, std::io::Error> {
+///     let uptime: f64 = std::fs::read_to_string("/proc/uptime")?
+///         .split_whitespace().next().unwrap_or_default().parse().unwrap_or_default();
+///     Ok(CheckResult::ok(format!("hello from {}", args.hostname.as_deref().unwrap_or("localhost")))
+///         .add_metric(Metric::new("uptime", uptime).uom("s")))
+/// }
+///
+/// icingaplugin_rs::plugin!(check);
+/// ```
+#[macro_export]
+macro_rules! plugin {
+    (info: $info:expr, args: $args:ty, check: $check:expr $(,)?) => {
+        fn main() {
+            let info = $info;
+            let (standard, matches) = $crate::cli::parse(
+                <$args as $crate::cli::PluginArgs>::command().name(info.name().to_string()),
+            );
+            let args = match <$args as $crate::cli::PluginArgs>::from_matches(&standard, &matches) {
+                ::std::result::Result::Ok(args) => args,
+                ::std::result::Result::Err(e) => {
+                    println!("UNKNOWN - {}", e);
+                    ::std::process::exit(3)
+                }
+            };
+            $crate::plugin!(@run info, standard, args, $check)
+        }
+    };
+    (args: $args:ty, check: $check:expr $(,)?) => {
+        $crate::plugin! { info: $crate::plugin_info!(), args: $args, check: $check }
+    };
+    (info: $info:expr, check: $check:expr $(,)?) => {
+        fn main() {
+            let info = $info;
+            let cmd = $crate::cli::guideline_help($crate::cli::standard_args($crate::cli::command(&info)), &[]);
+            let (standard, _) = $crate::cli::parse(cmd);
+            let args = standard.clone();
+            $crate::plugin!(@run info, standard, args, $check)
+        }
+    };
+    (check: $check:expr $(,)?) => {
+        $crate::plugin! { info: $crate::plugin_info!(), check: $check }
+    };
+    (@run $info:ident, $standard:ident, $args:ident, $check:expr) => {{
+        let check = $check;
+        let plugin = move || -> ::std::result::Result<$crate::check::CheckResult, $crate::runner::Error> {
+            check(&$args).map_err(::std::convert::Into::into)
+        };
+        let runner = $crate::runner::Runner::new(plugin)
+            .timeout($standard.timeout)
+            .verbosity($standard.verbose)
+            .output_format($standard.output_format)
+            .dry_run($standard.dry_run)
+            .info($info);
+        match $standard.watch {
+            ::std::option::Option::Some(interval) => runner.watch(interval).run(),
+            ::std::option::Option::None => runner.run(),
+        }
+    }};
+    ($check:expr $(,)?) => {
+        $crate::plugin! { check: $check }
+    };
+}
+
+
+/// A struct holding the arguments of a plugin. With the `derive` feature, it can be derived for
+/// structs with named fields:
+///
+/// * Fields marked `#[warning]`, `#[critical]`, `#[timeout]`, `#[hostname]`, `#[verbose]` or
+///   `#[output_format]` take the value of the standard argument, see `StandardArgs`. If a
+///   `#[warning]`, `#[critical]` or `#[hostname]` field is not an `Option`, the argument is
+///   required.
+/// * Other fields become `--field-name` options parsed with `FromStr`, their doc comment is the
+///   help text. `bool` fields are flags, `Option` fields are optional, `Vec` fields may be given
+///   several times and all others are required. `#[arg(short = 'p', long = "port",
+///   default = "5432", env = "CHECK_DB_PORT")]` customizes them.
+///
+/// The name, version and author are taken from the plugin's `Cargo.toml`, the doc comment of
+/// the struct is the description in `--help`.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use icingaplugin_rs::cli::PluginArgs;
+/// use icingaplugin_rs::thresholds::Range;
+/// use std::time::Duration;
+///
+/// /// Checks the replication lag of a database
+/// #[derive(PluginArgs)]
+/// struct Args {
+///     #[warning]
+///     warning: Range,
+///     #[critical]
+///     critical: Option<Range>,
+///     #[timeout]
+///     timeout: Duration,
+///     /// Database port
+///     #[arg(short = 'p', default = "5432")]
+///     port: u16,
+///     /// Connect using TLS
+///     ssl: bool,
+///     /// Replicas to check
+///     replica: Vec<String>,
+/// }
+///
+/// let args = Args::try_parse_from(["check_lag", "-w", "10", "--replica", "db02", "--replica", "db03"]).unwrap();
+/// assert_eq!(args.warning.to_string(), "10");
+/// assert_eq!(args.critical, None);
+/// assert_eq!(args.timeout, Duration::from_secs(10));
+/// assert_eq!((args.port, args.ssl), (5432, false));
+/// assert_eq!(args.replica, vec!["db02", "db03"]);
+///
+/// assert_eq!(Args::try_parse_from(["check_lag"]).err().unwrap(), "missing warning");
+/// assert!(Args::try_parse_from(["check_lag", "-w", "10", "-p", "postgres"]).is_err());
+/// assert!(Args::command().render_help().to_string().contains("Checks the replication lag of a database"));
+/// # }
+/// ```
+pub trait PluginArgs: Sized {
+    /// Returns the `Command` describing the arguments, including the standard arguments
+    fn command() -> Command;
+
+    /// Builds the arguments from the parsed command line. Fails if a required value is missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `standard` - the standard arguments
+    /// * `matches` - the parsed command line
+    fn from_matches(standard: &StandardArgs, matches: &ArgMatches) -> Result<Self, String>;
+
+    /// Parses the command line of the process like `parse()`. If a required value is missing,
+    /// prints an UNKNOWN result and exits.
+    fn parse() -> Self {
+        let (standard, matches) = parse(Self::command());
+        match Self::from_matches(&standard, &matches) {
+            Ok(args) => args,
+            Err(e) => {
+                println!("UNKNOWN - {}", e);
+                process::exit(EXIT_UNKNOWN)
+            }
+        }
+    }
+
+    /// Parses `args`, expanding `--extra-opts`, without exiting the process on errors
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - the command line, starting with the program name
+    fn try_parse_from<I, T>(args: I) -> Result<Self, String>
+    where I: IntoIterator<Item = T>, T: Into<OsString> {
+        let args = extra_opts::expand(args).map_err(|e| e.to_string())?;
+        let matches = Self::command().try_get_matches_from(args).map_err(|e| e.to_string())?;
+        Self::from_matches(&StandardArgs::from_matches(&matches), &matches)
+    }
+}
+
+
+/// Creates the `Command` of the plugin described by `info`, with its name, version and author,
+/// so `-V/--version`, `--help` and the `CheckCommand` export show them. `-V` prints the first
+/// line of the banner, `--version` the complete banner, see `PluginInfo::banner()`.
+///
+/// # Arguments
+///
+/// * `info` - the metadata of the plugin, see `plugin_info!()`
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::cli::{command, icinga2_command, standard_args};
+/// use icingaplugin_rs::runner::PluginInfo;
+/// let info = PluginInfo::new("check_foo", "1.2.0").set_license("MIT");
+/// let cmd = standard_args(command(&info));
+/// assert_eq!(cmd.render_version(), "check_foo v1.2.0\n");
+/// assert_eq!(cmd.render_long_version(), "check_foo v1.2.0\nLicense: MIT\n");
+/// assert!(icinga2_command(&cmd).starts_with("// check_foo v1.2.0\nobject CheckCommand \"foo\" {"));
+/// ```
+pub fn command(info: &PluginInfo) -> Command {
+    // clap prefixes the version with the name
+    let banner = info.banner();
+    let banner = &banner[info.name().len() + 1..];
+    let mut cmd = Command::new(info.name().to_string())
+        .version(banner.lines().next().unwrap_or_default().to_string())
+        .long_version(banner.to_string());
+    if let Some(author) = info.author() {
+        cmd = cmd.author(author.to_string());
+    }
+    cmd
+}
+
+
+/// Adds the standard monitoring plugin arguments `-w/--warning`, `-c/--critical`,
+/// `-t/--timeout`, `-H/--hostname`, `-v/--verbose`, `--output-format`, `--dry-run`, `--watch`
+/// and `--extra-opts` to `cmd`. Thresholds are parsed as `Range`s. `-V/--version` is available
+/// if a version is set on `cmd`. Options given several times override each other, so command
+/// line options can override those read from `--extra-opts`.
+///
+/// Options missing on the command line are read from the environment, so containerized checks
+/// can be configured without wrapper scripts: the thresholds from `<NAME>_WARNING` and
+/// `<NAME>_CRITICAL`, where `<NAME>` is the upper case name of `cmd`, e.g. `CHECK_FOO_WARNING`,
+/// the timeout from `ICINGA_TIMEOUT`, the host from the `NAGIOS_HOSTNAME` macro and the output
+/// format from `ICINGA_OUTPUT_FORMAT`.
+///
+/// # Arguments
+///
+/// * `cmd` - the plugin's `Command`, possibly with additional arguments
+pub fn standard_args(cmd: Command) -> Command {
+    let prefix = cmd.get_name().to_uppercase().replace('-', "_");
+    cmd.args_override_self(true)
+    .arg(
+        Arg::new("warning")
+            .short('w')
+            .long("warning")
+            .value_name("RANGE")
+            .help("Warning threshold")
+            .env(format!("{}_WARNING", prefix))
+            .value_parser(|s: &str| s.parse::<Range>()),
+    )
+    .arg(
+        Arg::new("critical")
+            .short('c')
+            .long("critical")
+            .value_name("RANGE")
+            .help("Critical threshold")
+            .env(format!("{}_CRITICAL", prefix))
+            .value_parser(|s: &str| s.parse::<Range>()),
+    )
+    .arg(
+        Arg::new("timeout")
+            .short('t')
+            .long("timeout")
+            .value_name("DURATION")
+            .help("Time before the check times out, in seconds or e.g. 1m30s")
+            .env(runner::TIMEOUT_VAR)
+            .default_value("10")
+            .value_parser(|s: &str| parse_duration(s)),
+    )
+    .arg(
+        Arg::new("hostname")
+            .short('H')
+            .long("hostname")
+            .value_name("ADDRESS")
+            .help("Host name or IP address to check")
+            .env("NAGIOS_HOSTNAME")
+            .value_parser(value_parser!(String)),
+    )
+    .arg(
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Increase verbosity, may be given up to 3 times")
+            .action(ArgAction::Count),
+    )
+    .arg(
+        Arg::new("output_format")
+            .long("output-format")
+            .value_name("FORMAT")
+            .help("Output format, text, json or color")
+            .env(runner::OUTPUT_FORMAT_VAR)
+            .default_value("text")
+            .value_parser(|s: &str| s.parse::<OutputFormat>()),
+    )
+    .arg(
+        Arg::new("dry_run")
+            .long("dry-run")
+            .help("Report the actions the plugin would take instead of taking them")
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new("watch")
+            .long("watch")
+            .value_name("INTERVAL")
+            .help("Run the check repeatedly and show what changed between runs, for development")
+            .value_parser(|s: &str| parse_duration(s)),
+    )
+    .arg(
+        Arg::new("extra_opts")
+            .long("extra-opts")
+            .value_name("[SECTION][@FILE]")
+            .help("Read default options from a section of an ini file")
+            .num_args(0..=1)
+            .require_equals(true)
+            .action(ArgAction::Append),
+    )
+    .arg(
+        Arg::new("dump_icinga2_command")
+            .long("dump-icinga2-command")
+            .help("Print an Icinga2 CheckCommand definition for this plugin and exit")
+            .action(ArgAction::SetTrue),
+    )
+}
+
+
+/// Explains the range syntax of thresholds in the `--help` output
+const THRESHOLD_HELP: &str = "\
+Thresholds:
+  Thresholds are ranges in the format [@][start:][end], start defaults to 0 and
+  end to infinity, ~ denotes negative infinity. An alert is raised if the value
+  is outside of the range, or inside of it if the range starts with @.
+
+    10       < 0 or > 10
+    10:      < 10
+    ~:10     > 10
+    10:20    < 10 or > 20
+    @10:20   >= 10 and <= 20";
+
+
+/// Formats the `--help` output of `cmd` following the Monitoring Plugins developer guidelines:
+/// the plugin name and version, the author, the description, the usage, the options, an
+/// explanation of the threshold syntax and `examples`, each a command line with its description.
+///
+/// # Arguments
+///
+/// * `cmd` - the plugin's `Command`
+/// * `examples` - example command lines and what they check
+///
+/// # Examples
+///
+/// ```
+/// use clap::Command;
+/// use icingaplugin_rs::cli::{guideline_help, standard_args};
+///
+/// let cmd = guideline_help(standard_args(Command::new("check_load")).version("1.2.0")
+///     .about("Checks the system load"), &[("check_load -w 4 -c 8", "alert if load1 exceeds 4 or 8")]);
+/// let help = cmd.clone().render_help().to_string();
+/// assert!(help.starts_with("check_load 1.2.0\n"));
+/// assert!(help.contains("Checks the system load\n\nUsage: check_load [OPTIONS]\n"));
+/// assert!(help.contains("  -w, --warning <RANGE>"));
+/// assert!(help.contains("Thresholds:\n"));
+/// assert!(help.ends_with("Examples:\n  check_load -w 4 -c 8\n      alert if load1 exceeds 4 or 8\n"));
+/// ```
+pub fn guideline_help(cmd: Command, examples: &[(&str, &str)]) -> Command {
+    let mut after_help = String::from(THRESHOLD_HELP);
+    if !examples.is_empty() {
+        after_help += "\n\nExamples:";
+        for (command, description) in examples {
+            after_help += &format!("\n  {}\n      {}", command, description);
+        }
+    }
+
+    let author = if cmd.get_author().is_some() { "{author}\n" } else { "" };
+    let template = format!("{{name}} {{version}}\n{}\n{{about}}\n\n{{usage-heading}} {{usage}}\n\n{{all-args}}{{after-help}}", author);
+    cmd.help_template(template).after_help(after_help)
+}
+
+
+/// Returns an Icinga2 `CheckCommand` object for the plugin described by `cmd`, ready to be
+/// imported into the Icinga2 configuration. Every argument is mapped to a custom variable named
+/// after the plugin without its `check_` prefix and the argument's id, e.g. `$foo_warning$`.
+/// Flags are set with `set_if`, positional arguments are passed without key. `--help`,
+/// `--version`, `--extra-opts`, `--dump-icinga2-command` and `--watch` are left out.
+///
+/// Plugins using `parse()` print it when invoked with `--dump-icinga2-command`.
+///
+/// # Arguments
+///
+/// * `cmd` - the plugin's `Command`
+///
+/// # Examples
+///
+/// ```
+/// use clap::{Arg, ArgAction, Command};
+/// use icingaplugin_rs::cli::icinga2_command;
+///
+/// let cmd = Command::new("check_foo")
+///     .arg(Arg::new("warning").short('w').long("warning").help("Warning threshold"))
+///     .arg(Arg::new("ssl").long("ssl").help("Use \"TLS\"").action(ArgAction::SetTrue))
+///     .arg(Arg::new("database").required(true));
+/// assert_eq!(icinga2_command(&cmd).replace('\t', "    "), r#"object CheckCommand "foo" {
+///     command = [ PluginDir + "/check_foo" ]
+///
+///     arguments = {
+///         "-w" = {
+///             value = "$foo_warning$"
+///             description = "Warning threshold"
+///         }
+///         "--ssl" = {
+///             set_if = "$foo_ssl$"
+///             description = "Use \"TLS\""
+///         }
+///         "database" = {
+///             value = "$foo_database$"
+///             skip_key = true
+///             required = true
+///         }
+///     }
+/// }
+/// "#);
+/// ```
+pub fn icinga2_command(cmd: &Command) -> String {
+    let plugin = cmd.get_name();
+    let name = plugin.strip_prefix("check_").unwrap_or(plugin);
+    let prefix = name.replace('-', "_");
+
+    let mut out = match cmd.get_version() {
+        Some(version) => format!("// {} {}\n", plugin, version),
+        None => String::new(),
+    };
+    out += &format!("object CheckCommand {} {{\n", quote(name));
+    out += &format!("\tcommand = [ PluginDir + {} ]\n\n\targuments = {{\n", quote(&format!("/{}", plugin)));
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        if ["help", "version", "extra_opts", "dump_icinga2_command", "watch"].contains(&id) {
+            continue;
+        }
+
+        let key = match (arg.get_short(), arg.get_long()) {
+            (Some(short), _) => format!("-{}", short),
+            (None, Some(long)) => format!("--{}", long),
+            (None, None) => id.to_string(),
+        };
+        let var = format!("${}_{}$", prefix, id);
+        out += &format!("\t\t{} = {{\n", quote(&key));
+        if arg.get_action().takes_values() {
+            out += &format!("\t\t\tvalue = {}\n", quote(&var));
+        } else {
+            out += &format!("\t\t\tset_if = {}\n", quote(&var));
+        }
+        if arg.is_positional() {
+            out += "\t\t\tskip_key = true\n";
+        }
+        if let ArgAction::Append = arg.get_action() {
+            out += "\t\t\trepeat_key = true\n";
+        }
+        if arg.is_required_set() {
+            out += "\t\t\trequired = true\n";
+        }
+        if let Some(help) = arg.get_help() {
+            out += &format!("\t\t\tdescription = {}\n", quote(&help.to_string()));
+        }
+        out += "\t\t}\n";
+    }
+    out += "\t}\n}\n";
+    out
+}
+
+
+/// Quotes `s` as an Icinga2 DSL string
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+
+/// Parses the command line of the process with `cmd`, which must have been built with
+/// `standard_args()`. `--extra-opts` are expanded first, options given on the command line take
+/// precedence. On usage errors, `--help`, `--version` and `--dump-icinga2-command` the message
+/// is printed and the process exits with UNKNOWN (3).
+///
+/// # Arguments
+///
+/// * `cmd` - the plugin's `Command`
+pub fn parse(cmd: Command) -> (StandardArgs, ArgMatches) {
+    parse_from(cmd, std::env::args_os())
+}
+
+
+/// Like `parse()`, but reads the arguments from `args` instead of the process' command line. The
+/// first item is the program name.
+///
+/// # Arguments
+///
+/// * `cmd` - the plugin's `Command`
+/// * `args` - the command line
+pub fn parse_from<I, T>(cmd: Command, args: I) -> (StandardArgs, ArgMatches) where
+I: IntoIterator<Item = T>,
+T: Into<OsString> + Clone
+{
+    let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    if args.iter().skip(1).any(|a| a == "--dump-icinga2-command") {
+        print!("{}", icinga2_command(&cmd));
+        process::exit(EXIT_UNKNOWN)
+    }
+
+    let args = match extra_opts::expand(args) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("UNKNOWN - {}", e);
+            process::exit(EXIT_UNKNOWN)
+        }
+    };
+    match cmd.try_get_matches_from(args) {
+        Ok(matches) => (StandardArgs::from_matches(&matches), matches),
+        Err(e) => {
+            let _ = e.print();
+            process::exit(EXIT_UNKNOWN)
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The source of the current time, e.g. for the age of persisted values, counter rates and
+/// expiry dates. Checks take a `Clock` instead of calling `SystemTime::now()`, so tests can use a
+/// `MockClock` instead of sleeping.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> SystemTime;
+
+    /// Returns how long ago `earlier` was, or zero if it is in the future
+    ///
+    /// # Arguments
+    ///
+    /// * `earlier` - a point in time, e.g. when a value was stored
+    fn since(&self, earlier: SystemTime) -> Duration {
+        self.now().duration_since(earlier).unwrap_or_default()
+    }
+
+    /// Returns how long until `later`, or zero if it has passed, e.g. the remaining validity
+    /// of a certificate
+    ///
+    /// # Arguments
+    ///
+    /// * `later` - a point in time, e.g. an expiry date
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::clock::{Clock, MockClock};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(1700000000));
+    /// let not_after = UNIX_EPOCH + Duration::from_secs(1700000000 + 14 * 86400);
+    /// assert_eq!(clock.until(not_after).as_secs() / 86400, 14);
+    /// clock.advance(Duration::from_secs(15 * 86400));
+    /// assert_eq!(clock.until(not_after), Duration::from_secs(0));
+    /// ```
+    fn until(&self, later: SystemTime) -> Duration {
+        later.duration_since(self.now()).unwrap_or_default()
+    }
+}
+
+
+/// The `Clock` of the operating system
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SystemClock;
+
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+
+/// A `Clock` standing still until it is moved explicitly, for tests. Clones share their time, so
+/// a test can keep a clone to advance the clock passed to the code under test.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::clock::{Clock, MockClock};
+/// use icingaplugin_rs::rate::{rate, CounterWidth, Sample};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::default();
+/// let previous = Sample::at(1_000, clock.now());
+/// clock.advance(Duration::from_secs(60));
+/// let current = Sample::at(7_000, clock.now());
+/// assert_eq!(rate(&previous, &current, CounterWidth::Bits64), Ok(100.0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    /// the current time, shared by all clones
+    now: Arc<Mutex<SystemTime>>,
+}
+
+
+impl MockClock {
+    /// Creates a new `MockClock` showing `now`
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - the initial time
+    pub fn new(now: SystemTime) -> Self {
+        Self { now: Arc::new(Mutex::new(now)) }
+    }
+
+    /// Moves the clock forward by `duration`
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - the time passing
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += duration;
+    }
+
+    /// Sets the clock to `now`, which may be in the past, e.g. to simulate the clock being
+    /// turned back
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - the new time
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+}
+
+
+impl Default for MockClock {
+    /// Creates a new `MockClock` showing 2023-11-14 22:13:20 UTC, 1700000000 seconds after the
+    /// epoch
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    }
+}
+
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::PluginError;
+use serde::de::DeserializeOwned;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Returns the path of the configuration file: the one given by a command line flag, e.g.
+/// `--config`, or else the one named by the environment variable `var`
+///
+/// # Arguments
+///
+/// * `flag` - the path given on the command line, if any
+/// * `var` - the environment variable naming the file, e.g. `CHECK_DB_CONFIG`
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::config::config_path;
+/// use std::path::{Path, PathBuf};
+/// std::env::set_var("CHECK_FOO_CONFIG", "/etc/check_foo.toml");
+/// assert_eq!(config_path(None, "CHECK_FOO_CONFIG"), Some(PathBuf::from("/etc/check_foo.toml")));
+/// assert_eq!(config_path(Some(Path::new("foo.toml")), "CHECK_FOO_CONFIG"), Some(PathBuf::from("foo.toml")));
+/// assert_eq!(config_path(None, "CHECK_BAR_CONFIG"), None);
+/// ```
+pub fn config_path(flag: Option<&Path>, var: &str) -> Option<PathBuf> {
+    flag.map(Path::to_path_buf).or_else(|| env::var_os(var).map(PathBuf::from))
+}
+
+
+/// Loads the TOML file at `path` into the plugin's configuration struct
+///
+/// # Arguments
+///
+/// * `path` - the configuration file
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::config::load;
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Config {
+///     host: String,
+///     port: Option<u16>,
+/// }
+///
+/// let path = std::env::temp_dir().join("config_load_doctest.toml");
+/// std::fs::write(&path, "host = \"db01\"\n").unwrap();
+/// assert_eq!(load::<Config>(&path).unwrap(), Config { host: String::from("db01"), port: None });
+///
+/// std::fs::write(&path, "port = 5432\n").unwrap();
+/// assert!(load::<Config>(&path).unwrap_err().to_string().contains("missing field `host`"));
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.message().to_string()))
+}
+
+
+/// Loads the configuration file chosen by `config_path()`, or returns the default configuration
+/// if neither the flag nor the environment variable is given
+///
+/// # Arguments
+///
+/// * `flag` - the path given on the command line, if any
+/// * `var` - the environment variable naming the file
+pub fn load_optional<T: DeserializeOwned + Default>(flag: Option<&Path>, var: &str) -> Result<T, ConfigError> {
+    match config_path(flag, var) {
+        Some(path) => load(&path),
+        None => Ok(T::default()),
+    }
+}
+
+
+/// Resolves a single setting following the precedence command line > environment > file: the
+/// value given on the command line, or else the value of the environment variable `var`, or else
+/// the value of the configuration file
+///
+/// # Arguments
+///
+/// * `cli` - the value given on the command line, if any
+/// * `var` - the environment variable overriding the file
+/// * `file` - the value of the configuration file, if any
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::config::layered;
+/// std::env::set_var("CHECK_FOO_PORT", "5433");
+/// assert_eq!(layered(Some(5434), "CHECK_FOO_PORT", Some(5432)).unwrap(), Some(5434));
+/// assert_eq!(layered(None, "CHECK_FOO_PORT", Some(5432)).unwrap(), Some(5433));
+/// assert_eq!(layered(None, "CHECK_FOO_HOST", Some(String::from("db01"))).unwrap(), Some(String::from("db01")));
+///
+/// std::env::set_var("CHECK_FOO_PORT", "postgres");
+/// assert!(layered::<u16>(None, "CHECK_FOO_PORT", None).is_err());
+/// ```
+pub fn layered<T: FromStr>(cli: Option<T>, var: &str, file: Option<T>) -> Result<Option<T>, ConfigError> {
+    if cli.is_some() {
+        return Ok(cli);
+    }
+    match env::var(var) {
+        Ok(value) => match value.parse() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(ConfigError::Env(var.to_string(), value)),
+        },
+        Err(_) => Ok(file),
+    }
+}
+
+
+/// An error occurring while loading the configuration
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    Io(PathBuf, io::Error),
+    /// The configuration file is no valid TOML or does not match the configuration struct
+    Parse(PathBuf, String),
+    /// An environment variable has an invalid value
+    Env(String, String),
+}
+
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            ConfigError::Parse(path, msg) => write!(f, "invalid configuration file {}: {}", path.display(), msg.trim_end()),
+            ConfigError::Env(var, value) => write!(f, "invalid value '{}' of {}", value, var),
+        }
+    }
+}
+
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+
+impl From<ConfigError> for PluginError {
+    fn from(e: ConfigError) -> Self {
+        PluginError::Config(e.to_string())
+    }
+}
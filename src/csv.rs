@@ -0,0 +1,300 @@
+use crate::check::{CheckResult, Metric, PerfData, State};
+use crate::utils::{evaluate_opt, EvalError};
+use std::error::Error;
+use std::fmt;
+
+/// Describes how a CSV report is laid out, so every row can be evaluated into a `CheckResult`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CsvEvaluator {
+    /// the column holding the `Metric` label
+    label: usize,
+    /// the column holding the `Metric` value
+    value: usize,
+    /// the column holding the `warning` threshold, if any
+    warning: Option<usize>,
+    /// the column holding the `critical` threshold, if any
+    critical: Option<usize>,
+    /// the column holding the `max`imum value, if any
+    max: Option<usize>,
+    /// the character separating the fields of a row
+    delimiter: char,
+    /// whether the first non-empty line is a header and has to be skipped
+    header: bool,
+}
+
+
+impl CsvEvaluator {
+    /// Creates a new `CsvEvaluator` reading labels and values from the given (zero-based) columns.
+    /// Fields are separated by `,` and no header line is expected by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the column holding the label of each row
+    /// * `value` - the column holding the numeric value of each row
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::csv::CsvEvaluator;
+    /// let cr = CsvEvaluator::new(0, 1).evaluate("queue_a,5\nqueue_b,7").unwrap();
+    /// assert_eq!(cr.state(), State::OK);
+    /// assert_eq!(cr.to_string(), "OK - 0 critical, 0 warning, 2 ok | 'queue_a'=5;;;; 'queue_b'=7;;;; ");
+    /// ```
+    pub fn new(label: usize, value: usize) -> Self {
+        Self {
+            label,
+            value,
+            warning: None,
+            critical: None,
+            max: None,
+            delimiter: ',',
+            header: false,
+        }
+    }
+
+    /// Sets the column holding the `warning` threshold of each row
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - the zero-based column index
+    pub fn warning(mut self, column: usize) -> Self {
+        self.warning = Some(column);
+        self
+    }
+
+    /// Sets the column holding the `critical` threshold of each row
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - the zero-based column index
+    pub fn critical(mut self, column: usize) -> Self {
+        self.critical = Some(column);
+        self
+    }
+
+    /// Sets the column holding the `max`imum value of each row, which is passed on to the
+    /// performance data
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - the zero-based column index
+    pub fn max(mut self, column: usize) -> Self {
+        self.max = Some(column);
+        self
+    }
+
+    /// Sets the character separating the fields of a row
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter` - e.g. `;` or `\t`
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Defines whether the first non-empty line is a header line which must not be evaluated
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - `true` if the input starts with a header line
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Evaluates every row of `input` and aggregates the rows into a single `CheckResult`.
+    /// The resulting state is the worst state of all rows, the info string counts the rows per
+    /// state and every row is added as a numeric `Metric` to the performance data. Rows are evaluated
+    /// like `evaluate_opt`: a row with only a `warning` or only a `critical` field alerts if its
+    /// value reaches it, a row without thresholds counts as OK.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - the CSV formatted report
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::csv::CsvEvaluator;
+    /// let report = "name;used;warn;crit;size\n/;80;70;90;100\n/var;95;70;90;100\n/tmp;10;;;";
+    /// let cr = CsvEvaluator::new(0, 1).warning(2).critical(3).max(4).delimiter(';').header(true)
+    ///     .evaluate(report).unwrap();
+    /// assert_eq!(cr.state(), State::Critical);
+    /// assert_eq!(cr.to_string(), "CRITICAL - 1 critical, 1 warning, 1 ok | \
+    /// '/'=80;70;90;;100 '/var'=95;70;90;;100 '/tmp'=10;;;; ");
+    /// ```
+    ///
+    /// ```
+    /// use icingaplugin_rs::csv::{CsvError, CsvEvaluator};
+    /// let err = CsvEvaluator::new(0, 1).evaluate("a,none").unwrap_err();
+    /// assert_eq!(err, CsvError::InvalidNumber { line: 1, field: String::from("none") });
+    /// ```
+    pub fn evaluate(&self, input: &str) -> Result<CheckResult, CsvError> {
+        let mut metrics = Vec::new();
+        let mut counts = [0usize; 4];
+        let mut worst = State::OK;
+        let mut header = self.header;
+
+        for (i, line) in input.lines().enumerate() {
+            let line_no = i + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = split_line(line, self.delimiter, line_no)?;
+            if header {
+                header = false;
+                continue;
+            }
+
+            let label = field(&fields, self.label, line_no)?;
+            let value = field(&fields, self.value, line_no)?;
+            let v_64 = parse_number(value, line_no)?;
+            let warning = optional_field(&fields, self.warning, line_no)?;
+            let critical = optional_field(&fields, self.critical, line_no)?;
+            let max = optional_field(&fields, self.max, line_no)?;
+
+            let w_64 = warning.map(|w| parse_number(w, line_no)).transpose()?;
+            let c_64 = critical.map(|c| parse_number(c, line_no)).transpose()?;
+            let m_64 = max.map(|m| parse_number(m, line_no)).transpose()?;
+            let state = evaluate_opt(v_64, w_64, c_64)
+                .map_err(|e| CsvError::Evaluation { line: line_no, error: e })?
+                .state();
+
+            match state {
+                State::Critical => counts[0] += 1,
+                State::Unknown => counts[1] += 1,
+                State::Warning => counts[2] += 1,
+                State::OK => counts[3] += 1,
+            }
+            worst = worst.max(state);
+
+            let mut metric = Metric::new(label.to_string(), v_64);
+            if let Some(w) = w_64 {
+                metric = metric.warning(w.to_string());
+            }
+            if let Some(c) = c_64 {
+                metric = metric.critical(c.to_string());
+            }
+            if let Some(m) = m_64 {
+                metric = metric.max(m.to_string());
+            }
+            metrics.push(metric);
+        }
+
+        if metrics.is_empty() {
+            return Err(CsvError::Empty);
+        }
+
+        // unknown rows are only mentioned if there are any
+        let unknown = match counts[1] {
+            0 => String::new(),
+            n => format!("{} unknown, ", n),
+        };
+        Ok(CheckResult::new(worst)
+            .set_info(format!("{} critical, {}{} warning, {} ok", counts[0], unknown, counts[2], counts[3]))
+            .set_perf_data(PerfData::from_metrics(metrics)))
+    }
+}
+
+
+/// An error occurring while reading a CSV report
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CsvError {
+    /// The input did not contain a single row to evaluate
+    Empty,
+    /// A row is shorter than the configured columns require
+    MissingColumn { line: usize, column: usize },
+    /// A value or threshold field could not be parsed as a number
+    InvalidNumber { line: usize, field: String },
+    /// A quoted field was not terminated
+    UnterminatedQuote { line: usize },
+    /// The value of a row could not be evaluated against its thresholds
+    Evaluation { line: usize, error: EvalError },
+}
+
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Empty => write!(f, "no rows to evaluate"),
+            CsvError::MissingColumn { line, column } => {
+                write!(f, "line {}: missing column {}", line, column)
+            }
+            CsvError::InvalidNumber { line, field } => {
+                write!(f, "line {}: '{}' is not a number", line, field)
+            }
+            CsvError::UnterminatedQuote { line } => write!(f, "line {}: unterminated quote", line),
+            CsvError::Evaluation { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+
+impl Error for CsvError {}
+
+
+/// Splits a single CSV line into its fields, honoring double-quoted fields and `""` escapes
+fn split_line(line: &str, delimiter: char, line_no: usize) -> Result<Vec<String>, CsvError> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    current.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                current.push(c);
+            }
+        } else if c == '"' && current.trim().is_empty() {
+            current.clear();
+            quoted = true;
+        } else if c == delimiter {
+            fields.push(current.trim().to_string());
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+
+    if quoted {
+        return Err(CsvError::UnterminatedQuote { line: line_no });
+    }
+    fields.push(current.trim().to_string());
+    Ok(fields)
+}
+
+
+fn field(fields: &[String], column: usize, line_no: usize) -> Result<&str, CsvError> {
+    fields
+        .get(column)
+        .map(|f| f.as_str())
+        .ok_or(CsvError::MissingColumn { line: line_no, column })
+}
+
+
+/// Returns the content of an optional column, treating empty fields as unset
+fn optional_field(fields: &[String], column: Option<usize>, line_no: usize)
+    -> Result<Option<&str>, CsvError> {
+    match column {
+        Some(c) => field(fields, c, line_no).map(|f| if f.is_empty() { None } else { Some(f) }),
+        None => Ok(None),
+    }
+}
+
+
+fn parse_number(field: &str, line_no: usize) -> Result<f64, CsvError> {
+    field.parse::<f64>().map_err(|_| CsvError::InvalidNumber {
+        line: line_no,
+        field: field.to_string(),
+    })
+}
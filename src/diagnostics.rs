@@ -0,0 +1,174 @@
+use crate::check::{CheckResult, Metric};
+use std::fmt::{self, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The span timings and events recorded by a `CheckLayer`
+#[derive(Debug, Default)]
+struct Recorded {
+    /// the total time spent in each span name, in the order the spans were first closed
+    timings: Vec<(String, Duration)>,
+    /// the formatted events with the verbosity required to display them
+    events: Vec<(u8, String)>,
+}
+
+
+/// Collects the diagnostics of a check recorded by its `CheckLayer`s: the time spent in each
+/// span, e.g. the phases `connect`, `query` and `parse` of a database check, and the events
+/// emitted while the check ran. Clones share the recorded diagnostics.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    /// the diagnostics recorded so far
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+
+impl Diagnostics {
+    /// Creates a new `Diagnostics` collector without any recorded diagnostics
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::diagnostics::Diagnostics;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    ///
+    /// let diagnostics = Diagnostics::new();
+    /// let subscriber = tracing_subscriber::registry().with(diagnostics.layer());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _connect = tracing::info_span!("connect").entered();
+    ///     tracing::debug!(host = "db01", "connected");
+    /// });
+    ///
+    /// let cr = diagnostics.attach(CheckResult::ok("database is up"));
+    /// assert!(cr.perf_data().unwrap().to_string().starts_with("'connect_duration'="));
+    /// assert!(cr.long_output()[0].starts_with("connect took "));
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a `tracing_subscriber` layer recording into this `Diagnostics` collector
+    pub fn layer(&self) -> CheckLayer {
+        CheckLayer { recorded: Arc::clone(&self.recorded) }
+    }
+
+    /// Adds the diagnostics recorded since the last call to `cr` and returns it: the time spent
+    /// in each span as a long output line and as a `<span>_duration` metric in seconds, and the
+    /// events as debug lines. `error`, `warn` and `info` events are displayed with `-v`, `debug`
+    /// events with `-vv` and `trace` events with `-vvv`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - the result of the check
+    pub fn attach(&self, cr: CheckResult) -> CheckResult {
+        let recorded = match self.recorded.lock() {
+            Ok(mut recorded) => mem::take(&mut *recorded),
+            Err(_) => return cr,
+        };
+
+        let cr = recorded.timings.into_iter().fold(cr, |cr, (name, duration)| {
+            let secs = duration.as_secs_f64();
+            cr.add_long_output(format!("{} took {:.3}s", name, secs))
+                .add_metric(Metric::new(format!("{}_duration", name), secs).uom("s").precision(6).min("0"))
+        });
+        recorded.events.into_iter().fold(cr, |cr, (verbosity, line)| cr.add_debug(verbosity, line))
+    }
+}
+
+
+/// A `tracing_subscriber` layer recording span timings and events into a `Diagnostics`
+/// collector, see `Diagnostics::layer()`
+#[derive(Clone, Debug)]
+pub struct CheckLayer {
+    /// the diagnostics recorded so far, shared with the `Diagnostics` collector
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+
+/// The time a span was created, stored in its extensions
+struct Opened(Instant);
+
+
+impl<S> Layer<S> for CheckLayer where S: Subscriber + for<'a> LookupSpan<'a> {
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let verbosity = match *metadata.level() {
+            Level::ERROR | Level::WARN | Level::INFO => 1,
+            Level::DEBUG => 2,
+            Level::TRACE => 3,
+        };
+
+        let mut visitor = Formatter::default();
+        event.record(&mut visitor);
+        let fields = format!("{}{}", visitor.message, visitor.fields);
+        let line = format!("[{} {}] {}", metadata.level(), metadata.target(), fields.trim_start());
+        if let Ok(mut recorded) = self.recorded.lock() {
+            recorded.events.push((verbosity, line));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+        let elapsed = match span.extensions().get::<Opened>() {
+            Some(Opened(opened)) => opened.elapsed(),
+            None => return,
+        };
+
+        if let Ok(mut recorded) = self.recorded.lock() {
+            // spans entered repeatedly, e.g. once per host, are summed up
+            match recorded.timings.iter_mut().find(|(name, _)| name == span.name()) {
+                Some((_, total)) => *total += elapsed,
+                None => recorded.timings.push((span.name().to_string(), elapsed)),
+            }
+        }
+    }
+}
+
+
+/// Formats the fields of an event as its message followed by ` key=value` pairs
+#[derive(Default)]
+struct Formatter {
+    /// the `message` field
+    message: String,
+    /// the other fields
+    fields: String,
+}
+
+
+impl Visit for Formatter {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name => {
+                let _ = write!(self.fields, " {}={}", name, value);
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{:?}", value);
+            }
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use crate::check::{CheckResult, State};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// The states errors are reported with, see `ErrorStates::install()`
+static STATES: RwLock<ErrorStates> = RwLock::new(ErrorStates::new());
+
+/// An error aborting a check. Converting it into a `CheckResult` yields the state configured in
+/// `ErrorStates` for its kind, UNKNOWN by default, so check functions can use `?` throughout.
+#[derive(Debug)]
+pub enum PluginError {
+    /// The check did not finish in time
+    Timeout(Duration),
+    /// An I/O operation failed
+    Io(io::Error),
+    /// Data gathered by the check could not be parsed
+    Parse(String),
+    /// The check was denied access to the monitored system
+    Auth(String),
+    /// The check was configured incorrectly, e.g. by invalid arguments
+    Config(String),
+    /// Any other error
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+
+impl PluginError {
+    /// Returns the state this error is reported with, according to the installed `ErrorStates`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::error::PluginError;
+    /// assert_eq!(PluginError::Auth(String::from("invalid token")).state(), State::Unknown);
+    /// ```
+    pub fn state(&self) -> State {
+        match STATES.read() {
+            Ok(states) => states.state(self),
+            Err(poisoned) => poisoned.into_inner().state(self),
+        }
+    }
+}
+
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Timeout(d) => write!(f, "check timed out after {}s", d.as_secs_f64()),
+            PluginError::Io(e) => write!(f, "I/O error: {}", e),
+            PluginError::Parse(msg) => write!(f, "parse error: {}", msg),
+            PluginError::Auth(msg) => write!(f, "authentication failed: {}", msg),
+            PluginError::Config(msg) => write!(f, "invalid configuration: {}", msg),
+            PluginError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+
+impl Error for PluginError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PluginError::Io(e) => Some(e),
+            PluginError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+
+impl From<io::Error> for PluginError {
+    fn from(e: io::Error) -> Self {
+        PluginError::Io(e)
+    }
+}
+
+
+impl From<ParseIntError> for PluginError {
+    fn from(e: ParseIntError) -> Self {
+        PluginError::Parse(e.to_string())
+    }
+}
+
+
+impl From<ParseFloatError> for PluginError {
+    fn from(e: ParseFloatError) -> Self {
+        PluginError::Parse(e.to_string())
+    }
+}
+
+
+impl From<String> for PluginError {
+    fn from(msg: String) -> Self {
+        PluginError::Other(msg.into())
+    }
+}
+
+
+impl From<&str> for PluginError {
+    fn from(msg: &str) -> Self {
+        PluginError::Other(msg.into())
+    }
+}
+
+
+impl From<Box<dyn Error + Send + Sync>> for PluginError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        PluginError::Other(e)
+    }
+}
+
+
+impl From<PluginError> for CheckResult {
+    /// Converts a `PluginError` into a `CheckResult` with the state configured for its kind and
+    /// the error message as info
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::error::PluginError;
+    ///
+    /// fn read_load() -> Result<CheckResult, PluginError> {
+    ///     let load: f64 = "high".parse()?;
+    ///     Ok(CheckResult::from(0).set_info(format!("load is {}", load)))
+    /// }
+    ///
+    /// let cr = read_load().unwrap_or_else(CheckResult::from);
+    /// assert_eq!(cr.to_string(), "UNKNOWN - parse error: invalid float literal");
+    /// ```
+    fn from(e: PluginError) -> Self {
+        CheckResult::new(e.state()).set_info(e.to_string())
+    }
+}
+
+
+/// The states `PluginError`s are reported with, by kind. All kinds map to UNKNOWN by default,
+/// as the check could not determine the state of the monitored system. Plugins for which e.g. a
+/// refused login already is a problem of the monitored system can map it to CRITICAL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ErrorStates {
+    timeout: State,
+    io: State,
+    parse: State,
+    auth: State,
+    config: State,
+    other: State,
+}
+
+
+impl ErrorStates {
+    /// Creates a new `ErrorStates` mapping every kind to UNKNOWN
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::error::{ErrorStates, PluginError};
+    /// use std::time::Duration;
+    ///
+    /// let states = ErrorStates::new().timeout(State::Critical);
+    /// assert_eq!(states.state(&PluginError::Timeout(Duration::from_secs(10))), State::Critical);
+    /// assert_eq!(states.state(&PluginError::Config(String::from("no host"))), State::Unknown);
+    /// ```
+    pub const fn new() -> Self {
+        Self {
+            timeout: State::Unknown,
+            io: State::Unknown,
+            parse: State::Unknown,
+            auth: State::Unknown,
+            config: State::Unknown,
+            other: State::Unknown,
+        }
+    }
+
+    /// Sets the state of `PluginError::Timeout`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn timeout(mut self, state: State) -> Self {
+        self.timeout = state;
+        self
+    }
+
+    /// Sets the state of `PluginError::Io`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn io(mut self, state: State) -> Self {
+        self.io = state;
+        self
+    }
+
+    /// Sets the state of `PluginError::Parse`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn parse(mut self, state: State) -> Self {
+        self.parse = state;
+        self
+    }
+
+    /// Sets the state of `PluginError::Auth`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn auth(mut self, state: State) -> Self {
+        self.auth = state;
+        self
+    }
+
+    /// Sets the state of `PluginError::Config`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn config(mut self, state: State) -> Self {
+        self.config = state;
+        self
+    }
+
+    /// Sets the state of `PluginError::Other`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state to report
+    pub fn other(mut self, state: State) -> Self {
+        self.other = state;
+        self
+    }
+
+    /// Returns the state `error` is reported with
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - the error
+    pub fn state(&self, error: &PluginError) -> State {
+        match error {
+            PluginError::Timeout(_) => self.timeout,
+            PluginError::Io(_) => self.io,
+            PluginError::Parse(_) => self.parse,
+            PluginError::Auth(_) => self.auth,
+            PluginError::Config(_) => self.config,
+            PluginError::Other(_) => self.other,
+        }
+    }
+
+    /// Makes this mapping the one used by `PluginError::state()` and the conversion into a
+    /// `CheckResult` for the rest of the process
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::error::{ErrorStates, PluginError};
+    ///
+    /// ErrorStates::new().auth(State::Critical).install();
+    /// let cr = CheckResult::from(PluginError::Auth(String::from("login refused")));
+    /// assert_eq!(cr.to_string(), "CRITICAL - authentication failed: login refused");
+    /// ```
+    pub fn install(self) {
+        match STATES.write() {
+            Ok(mut states) => *states = self,
+            Err(poisoned) => *poisoned.into_inner() = self,
+        }
+    }
+}
+
+
+impl Default for ErrorStates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
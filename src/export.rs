@@ -0,0 +1,344 @@
+use crate::check::{Metric, PerfData, Value};
+
+/// The base unit a `Metric` is converted to for export
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Unit {
+    /// `s`, `ms`, `us` and `ns`
+    Seconds,
+    /// `B` and its decimal and binary multiples
+    Bytes,
+    /// `%`, exported as a ratio between 0 and 1
+    Ratio,
+    /// `c`, a continuously increasing counter
+    Counter,
+    /// no or an unknown UOM, the value is exported as is
+    None,
+}
+
+
+impl Unit {
+    /// The suffix appended to metric names, as the Prometheus naming conventions demand
+    fn suffix(self) -> Option<&'static str> {
+        match self {
+            Unit::Seconds => Some("seconds"),
+            Unit::Bytes => Some("bytes"),
+            Unit::Ratio => Some("ratio"),
+            Unit::Counter | Unit::None => None,
+        }
+    }
+}
+
+
+/// A `Metric` converted to its base unit with a sanitized name
+struct Sample<'a> {
+    metric: &'a Metric,
+    name: String,
+    unit: Unit,
+    value: f64,
+}
+
+
+impl Sample<'_> {
+    /// Returns the label set of the sample, `{group="..."}` for grouped metrics
+    fn labels(&self) -> String {
+        match self.metric.group() {
+            Some(group) => format!("{{group=\"{}\"}}", escape_label_value(group)),
+            None => String::new(),
+        }
+    }
+}
+
+
+impl PerfData {
+    /// Converts the numeric metrics to the Prometheus text exposition format. Names are
+    /// sanitized and suffixed with their base unit, values are converted to seconds, bytes or
+    /// ratios. Metrics with the UOM `c` become counters, all others gauges. Unknown values are
+    /// exported as `NaN`, other non-numeric values are skipped. The group of grouped metrics,
+    /// see `MetricGroup`, becomes the `group` label, so metrics of all groups share their name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let pd = PerfData::from_metrics(vec![
+    ///     Metric::numeric("rta", 250).uom(String::from("ms")),
+    ///     Metric::numeric("/var used", 2).uom(String::from("KiB")),
+    /// ]);
+    /// let lines: Vec<_> = pd.to_prometheus().lines().map(String::from).collect();
+    /// assert_eq!(lines, ["# HELP rta_seconds rta", "# TYPE rta_seconds gauge", "rta_seconds 0.25",
+    ///     "# HELP _var_used_bytes /var used", "# TYPE _var_used_bytes gauge", "_var_used_bytes 2048"]);
+    ///
+    /// let pd = PerfData::from_metrics(vec![Metric::numeric("eth0::rx", 1), Metric::numeric("eth1::rx", 2)]);
+    /// let lines: Vec<_> = pd.to_prometheus().lines().map(String::from).collect();
+    /// assert_eq!(lines, ["# HELP rx rx", "# TYPE rx gauge", "rx{group=\"eth0\"} 1", "rx{group=\"eth1\"} 2"]);
+    /// ```
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        for family in self.families() {
+            let first = &family[0];
+            let kind = if first.unit == Unit::Counter { "counter" } else { "gauge" };
+            out.push_str(&format!("# HELP {} {}\n", first.name, escape_help(first.metric.name())));
+            out.push_str(&format!("# TYPE {} {}\n", first.name, kind));
+            for sample in &family {
+                out.push_str(&format!("{}{} {}\n", sample.name, sample.labels(), format_float(sample.value)));
+            }
+        }
+        out
+    }
+
+    /// Converts the numeric metrics to the OpenMetrics text format. In addition to the
+    /// Prometheus format, the base unit is announced in `# UNIT` lines, counter samples carry the
+    /// `_total` suffix and the output ends with `# EOF`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let pd = PerfData::from_metrics(vec![
+    ///     Metric::numeric("uptime", 2).uom(String::from("s")),
+    ///     Metric::numeric("packets", 42).uom(String::from("c")),
+    /// ]);
+    /// let lines: Vec<_> = pd.to_openmetrics().lines().map(String::from).collect();
+    /// assert_eq!(lines, ["# TYPE uptime_seconds gauge", "# UNIT uptime_seconds seconds",
+    ///     "# HELP uptime_seconds uptime", "uptime_seconds 2",
+    ///     "# TYPE packets counter", "# HELP packets packets", "packets_total 42", "# EOF"]);
+    /// ```
+    pub fn to_openmetrics(&self) -> String {
+        let mut out = String::new();
+        for family in self.families() {
+            let first = &family[0];
+            let help = escape_help(first.metric.name()).replace('"', "\\\"");
+            if first.unit == Unit::Counter {
+                let name = first.name.strip_suffix("_total").unwrap_or(&first.name);
+                out.push_str(&format!("# TYPE {} counter\n", name));
+                out.push_str(&format!("# HELP {} {}\n", name, help));
+                for sample in &family {
+                    out.push_str(&format!("{}_total{} {}\n", name, sample.labels(), format_float(sample.value)));
+                }
+            } else {
+                out.push_str(&format!("# TYPE {} gauge\n", first.name));
+                if let Some(unit) = first.unit.suffix() {
+                    out.push_str(&format!("# UNIT {} {}\n", first.name, unit));
+                }
+                out.push_str(&format!("# HELP {} {}\n", first.name, help));
+                for sample in &family {
+                    out.push_str(&format!("{}{} {}\n", sample.name, sample.labels(), format_float(sample.value)));
+                }
+            }
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// Converts the numeric metrics to InfluxDB line protocol, one line per metric, the way
+    /// Icinga2's InfluxdbWriter flattens performance data: `tags` are extended by the `metric`
+    /// label and its `unit`, the fields are the `value` and all thresholds which are plain
+    /// numbers. Grouped metrics, see `MetricGroup`, carry their name as `metric` and their group
+    /// as `group` tag. Values are written as is, without unit conversion. Lines carry no timestamp, so
+    /// the output can be fed to telegraf's `exec` input.
+    ///
+    /// # Arguments
+    ///
+    /// * `measurement` - the measurement name, Icinga2 uses the check command
+    /// * `tags` - additional tags, e.g. `hostname` and `service`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let pd = PerfData::from_metric(Metric::numeric("/var", 87.5).uom(String::from("%"))
+    ///     .warning(String::from("80")).critical(String::from("~:90")).max(String::from("100")));
+    /// assert_eq!(pd.to_influx("disk", &[("hostname", "db 01")]),
+    ///     "disk,hostname=db\\ 01,metric=/var,unit=% value=87.5,warn=80,max=100\n");
+    /// ```
+    pub fn to_influx(&self, measurement: &str, tags: &[(&str, &str)]) -> String {
+        let mut out = String::new();
+        for metric in &self.metrics {
+            let value = match as_f64(&metric.value) {
+                Some(v) if v.is_finite() => v,
+                _ => continue,
+            };
+
+            out.push_str(&escape_influx(measurement, &[',', ' ']));
+            for (key, val) in tags {
+                out.push_str(&format!(",{}={}", escape_influx(key, &[',', '=', ' ']),
+                    escape_influx(val, &[',', '=', ' '])));
+            }
+            if let Some(group) = metric.group() {
+                out.push_str(&format!(",group={}", escape_influx(group, &[',', '=', ' '])));
+            }
+            out.push_str(&format!(",metric={}", escape_influx(metric.name(), &[',', '=', ' '])));
+            if let Some(uom) = metric.uom.as_deref().filter(|u| !u.is_empty()) {
+                out.push_str(&format!(",unit={}", escape_influx(uom, &[',', '=', ' '])));
+            }
+
+            out.push_str(&format!(" value={}", value));
+            for (key, threshold) in [
+                ("warn", &metric.warning),
+                ("crit", &metric.critical),
+                ("min", &metric.min),
+                ("max", &metric.max),
+            ].iter() {
+                if let Some(t) = threshold.as_deref().and_then(|t| t.parse::<f64>().ok()).filter(|t| t.is_finite()) {
+                    out.push_str(&format!(",{}={}", key, t));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Converts the numeric metrics to the Graphite plaintext protocol, one `prefix.label value
+    /// timestamp` line per metric. Labels are sanitized like Icinga2's GraphiteWriter does, every
+    /// character other than letters, digits, `-` and `_` becomes `_`, so labels never introduce
+    /// additional path segments. Values are written as is, without unit conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - the path the metrics are stored under, e.g. `icinga2.db01.services.disk`
+    /// * `timestamp` - the time of the check in seconds since the epoch
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// let pd = PerfData::from_metrics(vec![Metric::numeric("/var", 87.5), Metric::numeric("load 1", 2)]);
+    /// assert_eq!(pd.to_graphite("icinga2.db01.disk", 1700000000),
+    ///     "icinga2.db01.disk._var 87.5 1700000000\nicinga2.db01.disk.load_1 2 1700000000\n");
+    /// ```
+    pub fn to_graphite(&self, prefix: &str, timestamp: u64) -> String {
+        let mut out = String::new();
+        for metric in &self.metrics {
+            let value = match as_f64(&metric.value) {
+                Some(v) if v.is_finite() => v,
+                _ => continue,
+            };
+            let label: String = metric.label
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            if prefix.is_empty() {
+                out.push_str(&format!("{} {} {}\n", label, value, timestamp));
+            } else {
+                out.push_str(&format!("{}.{} {} {}\n", prefix.trim_end_matches('.'), label, value, timestamp));
+            }
+        }
+        out
+    }
+
+    /// Returns the samples grouped by name, in the order their names first occur, so every
+    /// metric family is announced once
+    fn families(&self) -> Vec<Vec<Sample<'_>>> {
+        let mut families: Vec<Vec<Sample<'_>>> = Vec::new();
+        for sample in self.samples() {
+            match families.iter_mut().find(|f| f[0].name == sample.name) {
+                Some(family) => family.push(sample),
+                None => families.push(vec![sample]),
+            }
+        }
+        families
+    }
+
+    /// Converts the numeric metrics to their base unit and sanitizes their names, the names of
+    /// grouped metrics without their group
+    fn samples(&self) -> impl Iterator<Item = Sample<'_>> {
+        self.metrics.iter().filter_map(|metric| {
+            let (unit, value) = convert(as_f64(&metric.value)?, metric.uom.as_deref().unwrap_or(""));
+            let mut name = sanitize(metric.name());
+            if let Some(suffix) = unit.suffix() {
+                if !name.ends_with(suffix) {
+                    name.push('_');
+                    name.push_str(suffix);
+                }
+            }
+            Some(Sample { metric, name, unit, value })
+        })
+    }
+}
+
+
+/// Returns the numeric value of a `Metric`, NaN for `Value::Unknown` and `None` for `Value::Text`
+pub(crate) fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        Value::Unknown => Some(f64::NAN),
+        Value::Text(_) => None,
+    }
+}
+
+
+/// Converts `value` from `uom` to its base unit. Sub-units are divided rather than multiplied
+/// by a fraction, which keeps e.g. `250ms` at exactly `0.25`.
+pub(crate) fn convert(value: f64, uom: &str) -> (Unit, f64) {
+    const KIB: f64 = 1024.0;
+    match uom {
+        "s" => (Unit::Seconds, value),
+        "ms" => (Unit::Seconds, value / 1e3),
+        "us" => (Unit::Seconds, value / 1e6),
+        "ns" => (Unit::Seconds, value / 1e9),
+        "B" => (Unit::Bytes, value),
+        "KB" | "kB" => (Unit::Bytes, value * 1e3),
+        "MB" => (Unit::Bytes, value * 1e6),
+        "GB" => (Unit::Bytes, value * 1e9),
+        "TB" => (Unit::Bytes, value * 1e12),
+        "PB" => (Unit::Bytes, value * 1e15),
+        "KiB" => (Unit::Bytes, value * KIB),
+        "MiB" => (Unit::Bytes, value * KIB * KIB),
+        "GiB" => (Unit::Bytes, value * KIB * KIB * KIB),
+        "TiB" => (Unit::Bytes, value * KIB * KIB * KIB * KIB),
+        "PiB" => (Unit::Bytes, value * KIB * KIB * KIB * KIB * KIB),
+        "%" => (Unit::Ratio, value / 100.0),
+        "c" => (Unit::Counter, value),
+        _ => (Unit::None, value),
+    }
+}
+
+
+/// Replaces every character not allowed in Prometheus metric names with `_`
+fn sanitize(label: &str) -> String {
+    let mut name: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+
+fn escape_help(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+
+/// Escapes backslashes, double quotes and line breaks in a Prometheus label value
+fn escape_label_value(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+
+/// Escapes `chars` and backslashes with a backslash, as line protocol demands
+fn escape_influx(s: &str, chars: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || chars.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+
+/// Formats a float the way Prometheus expects, including its special values
+fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        String::from("NaN")
+    } else if f.is_infinite() {
+        String::from(if f > 0.0 { "+Inf" } else { "-Inf" })
+    } else {
+        f.to_string()
+    }
+}
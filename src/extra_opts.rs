@@ -0,0 +1,203 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The files searched for the section if `--extra-opts` names no file and `NAGIOS_CONFIG_PATH`
+/// is not set, as defined by the Monitoring Plugins
+const DEFAULT_FILES: [&str; 7] = [
+    "/etc/nagios/plugins.ini",
+    "/usr/local/nagios/etc/plugins.ini",
+    "/usr/local/etc/nagios/plugins.ini",
+    "/etc/opt/nagios/plugins.ini",
+    "/etc/nagios-plugins.ini",
+    "/usr/local/etc/nagios-plugins.ini",
+    "/etc/opt/nagios-plugins.ini",
+];
+
+
+/// Expands the `--extra-opts[=[section][@file]]` arguments of `args` following the Monitoring
+/// Plugins convention: every `key=value` line of the ini section becomes a `--key=value` argument
+/// (`-k value` for single character keys, a key without value becomes a flag). The options read
+/// are inserted before all other arguments, so arguments given on the command line take
+/// precedence when parsed with clap.
+///
+/// The section defaults to the plugin name, i.e. the file name of the first argument. Without a
+/// file, the directories in `NAGIOS_CONFIG_PATH` are searched for `plugins.ini`,
+/// `nagios-plugins.ini` and `nagios.ini`, or, if it is not set, the default locations like
+/// `/etc/nagios/plugins.ini`. `--extra-opts` may be given several times.
+///
+/// # Arguments
+///
+/// * `args` - the command line, starting with the program name
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::extra_opts::expand;
+/// use std::ffi::OsString;
+///
+/// let ini = std::env::temp_dir().join("extra_opts_doctest.ini");
+/// std::fs::write(&ini, "[check_db]\n; shared credentials\nuser = monitoring\np=secret\nssl\n").unwrap();
+///
+/// let spec = format!("--extra-opts=check_db@{}", ini.display());
+/// let args = expand(vec![OsString::from("check_db"), spec.into(), "-w".into(), "5".into()]).unwrap();
+/// let expected: Vec<OsString> = vec!["check_db", "--user=monitoring", "-p", "secret", "--ssl", "-w", "5"]
+///     .into_iter().map(OsString::from).collect();
+/// assert_eq!(args, expected);
+/// # std::fs::remove_file(ini).unwrap();
+/// ```
+pub fn expand<I, T>(args: I) -> Result<Vec<OsString>, ExtraOptsError>
+where I: IntoIterator<Item = T>, T: Into<OsString> {
+    let mut args = args.into_iter().map(Into::into);
+    let program = match args.next() {
+        Some(p) => p,
+        None => return Ok(Vec::new()),
+    };
+    let plugin = Path::new(&program)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut extra = Vec::new();
+    let mut rest = Vec::new();
+    for arg in args {
+        match arg.to_str() {
+            Some("--extra-opts") => extra.extend(read_section(&plugin, "")?),
+            Some(a) if a.starts_with("--extra-opts=") => {
+                extra.extend(read_section(&plugin, &a["--extra-opts=".len()..])?)
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut expanded = vec![program];
+    expanded.extend(extra);
+    expanded.extend(rest);
+    Ok(expanded)
+}
+
+
+/// Reads the section named by `spec` (`[section][@file]`) and returns it as arguments
+fn read_section(plugin: &str, spec: &str) -> Result<Vec<OsString>, ExtraOptsError> {
+    let (section, file) = match spec.split_once('@') {
+        Some((section, file)) => (section, Some(PathBuf::from(file))),
+        None => (spec, None),
+    };
+    let section = if section.is_empty() { plugin } else { section };
+
+    let path = match file {
+        Some(f) => f,
+        None => default_file().ok_or(ExtraOptsError::NoFile)?,
+    };
+    let content = fs::read_to_string(&path).map_err(|e| ExtraOptsError::Io(path.clone(), e))?;
+
+    let options = parse_section(&content, section)
+        .map_err(|line| ExtraOptsError::Syntax(path.clone(), line))?
+        .ok_or_else(|| ExtraOptsError::MissingSection(section.to_string(), path.clone()))?;
+
+    let mut args = Vec::new();
+    for (key, value) in options {
+        match value {
+            Some(v) if key.chars().count() == 1 => {
+                args.push(OsString::from(format!("-{}", key)));
+                args.push(OsString::from(v));
+            }
+            Some(v) => args.push(OsString::from(format!("--{}={}", key, v))),
+            None => args.push(OsString::from(format!("--{}", key))),
+        }
+    }
+    Ok(args)
+}
+
+
+/// Returns the first existing ini file of the configured or default locations
+fn default_file() -> Option<PathBuf> {
+    match env::var_os("NAGIOS_CONFIG_PATH") {
+        Some(dirs) => env::split_paths(&dirs)
+            .flat_map(|dir| ["plugins.ini", "nagios-plugins.ini", "nagios.ini"].iter().map(move |f| dir.join(f)))
+            .find(|f| f.is_file()),
+        None => DEFAULT_FILES.iter().map(PathBuf::from).find(|f| f.is_file()),
+    }
+}
+
+
+/// The options of an ini section, keys with their optional values
+type Options = Vec<(String, Option<String>)>;
+
+
+/// Returns the options of `section` in `content`, or `None` if there is no such section. Options
+/// of a section given several times are merged. Fails with the number of the first line which
+/// is neither a section header, an option, a comment nor empty.
+fn parse_section(content: &str, section: &str) -> Result<Option<Options>, usize> {
+    let mut current = None;
+    let mut found = false;
+    let mut options = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let name = line.strip_suffix(']').ok_or(i + 1)?[1..].trim();
+            found |= name == section;
+            current = Some(name.to_string());
+            continue;
+        }
+        if current.as_deref() != Some(section) {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim().to_string())),
+            None => (line, None),
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(i + 1);
+        }
+        options.push((key.to_string(), value));
+    }
+
+    Ok(if found { Some(options) } else { None })
+}
+
+
+/// An error occurring while reading `--extra-opts`
+#[derive(Debug)]
+pub enum ExtraOptsError {
+    /// No file was given and none of the default locations exists
+    NoFile,
+    /// The file could not be read
+    Io(PathBuf, io::Error),
+    /// The file contains an invalid line
+    Syntax(PathBuf, usize),
+    /// The file does not contain the section
+    MissingSection(String, PathBuf),
+}
+
+
+impl fmt::Display for ExtraOptsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtraOptsError::NoFile => write!(f, "no extra-opts file found"),
+            ExtraOptsError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            ExtraOptsError::Syntax(path, line) => write!(f, "invalid line {} in {}", line, path.display()),
+            ExtraOptsError::MissingSection(section, path) => {
+                write!(f, "section '{}' not found in {}", section, path.display())
+            }
+        }
+    }
+}
+
+
+impl Error for ExtraOptsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExtraOptsError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
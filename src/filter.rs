@@ -0,0 +1,325 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A single pattern matching item names like mount points, interfaces or process names
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// A shell style glob supporting `*`, `?` and `[...]` character classes
+    Glob(String),
+    /// A regular expression, matching anywhere in the item unless anchored
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+
+impl Pattern {
+    /// Returns whether `item` is matched by this `Pattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - the name to match
+    /// * `ignore_case` - whether letter case is ignored. Regular expressions have to be compiled
+    ///   case-insensitive instead, see `FilterSet::ignore_case()`.
+    fn matches(&self, item: &str, ignore_case: bool) -> bool {
+        match self {
+            Pattern::Glob(glob) if ignore_case => {
+                glob_match(&glob.to_lowercase(), &item.to_lowercase())
+            }
+            Pattern::Glob(glob) => glob_match(glob, item),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(re) => re.is_match(item),
+        }
+    }
+
+    /// Returns the pattern used for matching with `ignore_case`. Regular expressions are wrapped
+    /// in a case-insensitive group, flags written by the user are left as they are.
+    #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+    fn with_case(&self, ignore_case: bool) -> Self {
+        match self {
+            #[cfg(feature = "regex")]
+            Pattern::Regex(re) if ignore_case => {
+                // the pattern compiled before, wrapping it in a group keeps it valid
+                let folded = regex::Regex::new(&format!("(?i:{})", re.as_str()));
+                Pattern::Regex(folded.unwrap_or_else(|_| re.clone()))
+            }
+            other => other.clone(),
+        }
+    }
+}
+
+
+impl FromStr for Pattern {
+    type Err = FilterError;
+
+    /// Parses a pattern from its command line representation. Arguments prefixed with `re:` or
+    /// `regex:` are regular expressions (requires the `regex` feature), arguments prefixed with
+    /// `glob:` or without a prefix are globs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::filter::Pattern;
+    /// let pattern: Pattern = "/var/*".parse().unwrap();
+    /// let literal: Pattern = "glob:re:tmp".parse().unwrap();
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(glob) = s.strip_prefix("glob:") {
+            return Ok(Pattern::Glob(glob.to_string()));
+        }
+
+        match s.strip_prefix("re:").or_else(|| s.strip_prefix("regex:")) {
+            #[cfg(feature = "regex")]
+            Some(re) => regex::Regex::new(re)
+                .map(Pattern::Regex)
+                .map_err(|e| FilterError::InvalidRegex(e.to_string())),
+            #[cfg(not(feature = "regex"))]
+            Some(_) => Err(FilterError::RegexUnsupported),
+            None => Ok(Pattern::Glob(s.to_string())),
+        }
+    }
+}
+
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Glob(glob) => write!(f, "{}", glob),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(re) => write!(f, "re:{}", re.as_str()),
+        }
+    }
+}
+
+
+/// A set of include and exclude patterns deciding which items a check looks at. An item is
+/// selected if it matches any include pattern (or no include patterns are given) and does not
+/// match any exclude pattern.
+#[derive(Clone, Debug, Default)]
+pub struct FilterSet {
+    /// patterns selecting items, an empty list selects everything
+    include: Vec<Selector>,
+    /// patterns removing items from the selection
+    exclude: Vec<Selector>,
+    /// whether letter case is ignored when matching
+    ignore_case: bool,
+}
+
+
+/// A `Pattern` of a `FilterSet` as it was given, and as it is matched with the case sensitivity
+/// of the `FilterSet`
+#[derive(Clone, Debug)]
+struct Selector {
+    /// the pattern as given, never rewritten
+    given: Pattern,
+    /// the pattern used for matching
+    effective: Pattern,
+}
+
+
+impl Selector {
+    fn new(given: Pattern, ignore_case: bool) -> Self {
+        Self { effective: given.with_case(ignore_case), given }
+    }
+}
+
+
+impl FilterSet {
+    /// Creates a new, empty `FilterSet` selecting every item
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::filter::FilterSet;
+    /// assert!(FilterSet::new().matches("anything"));
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a `FilterSet` from command line arguments, e.g. repeated `--include`/`--exclude`
+    /// options. See `Pattern` for the accepted syntax.
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - the values given for the include option
+    /// * `exclude` - the values given for the exclude option
+    /// * `ignore_case` - whether letter case is ignored when matching
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::filter::FilterSet;
+    /// let set = FilterSet::from_args(&["eth*", "en?[0-9]"], &["*.100"], true).unwrap();
+    /// assert!(set.matches("ETH0"));
+    /// assert!(set.matches("eno1"));
+    /// assert!(!set.matches("eth0.100"));
+    /// assert!(!set.matches("lo"));
+    /// ```
+    pub fn from_args<S: AsRef<str>>(include: &[S], exclude: &[S], ignore_case: bool)
+        -> Result<Self, FilterError> {
+        let mut set = FilterSet::new();
+        for pattern in include {
+            set = set.include(pattern.as_ref().parse()?);
+        }
+        for pattern in exclude {
+            set = set.exclude(pattern.as_ref().parse()?);
+        }
+        Ok(set.ignore_case(ignore_case))
+    }
+
+    /// Adds an include `Pattern` to the `FilterSet`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the pattern selecting items
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.include.push(Selector::new(pattern, self.ignore_case));
+        self
+    }
+
+    /// Adds an exclude `Pattern` to the `FilterSet`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the pattern removing items
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(Selector::new(pattern, self.ignore_case));
+        self
+    }
+
+    /// Defines whether letter case is ignored, applying to all patterns of the `FilterSet`
+    ///
+    /// # Arguments
+    ///
+    /// * `ignore_case` - `true` to match case-insensitively
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        for selector in self.include.iter_mut().chain(self.exclude.iter_mut()) {
+            selector.effective = selector.given.with_case(ignore_case);
+        }
+        self
+    }
+
+    /// Returns whether `item` is selected by the `FilterSet`
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - the name to match
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::filter::FilterSet;
+    /// let set = FilterSet::new().exclude("/run/*".parse().unwrap());
+    /// assert!(set.matches("/var"));
+    /// assert!(!set.matches("/run/user/1000"));
+    /// ```
+    pub fn matches(&self, item: &str) -> bool {
+        let included = self.include.is_empty()
+            || self.include.iter().any(|s| s.effective.matches(item, self.ignore_case));
+        included && !self.exclude.iter().any(|s| s.effective.matches(item, self.ignore_case))
+    }
+
+    /// Returns whether the `FilterSet` has neither include nor exclude patterns
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+
+/// An error occurring while parsing a `Pattern`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FilterError {
+    /// The regular expression could not be compiled
+    InvalidRegex(String),
+    /// A regular expression was given, but the crate was built without the `regex` feature
+    RegexUnsupported,
+}
+
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::InvalidRegex(e) => write!(f, "invalid regular expression: {}", e),
+            FilterError::RegexUnsupported => write!(f, "regular expressions are not supported"),
+        }
+    }
+}
+
+
+impl Error for FilterError {}
+
+
+/// Matches `text` against a shell style `glob`
+fn glob_match(glob: &str, text: &str) -> bool {
+    let pattern: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` in the pattern and the text position it currently covers
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match_class(&pattern[p..], text[t]),
+            Some(c) if *c == text[t] => Some(1),
+            _ => None,
+        };
+
+        match step {
+            Some(len) => {
+                p += len;
+                t += 1;
+            }
+            None => match backtrack {
+                Some((star, covered)) => {
+                    p = star + 1;
+                    t = covered + 1;
+                    backtrack = Some((star, covered + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+
+/// Matches `c` against the character class at the start of `class`, returning the length of the
+/// class in the pattern if it matches. Unterminated classes match a literal `[`.
+fn match_class(class: &[char], c: char) -> Option<usize> {
+    let end = match class.iter().skip(2).position(|x| *x == ']') {
+        Some(pos) => pos + 2,
+        None => return if c == '[' { Some(1) } else { None },
+    };
+
+    let (negated, members) = match class[1] {
+        '!' | '^' => (true, &class[2..end]),
+        _ => (false, &class[1..end]),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            found |= members[i] <= c && c <= members[i + 2];
+            i += 3;
+        } else {
+            found |= members[i] == c;
+            i += 1;
+        }
+    }
+
+    if found != negated {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
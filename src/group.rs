@@ -0,0 +1,126 @@
+use crate::check::{Metric, Value};
+
+/// The separator between the group and the name of a metric label, e.g. `eth0::rx_bytes`
+pub const SEPARATOR: &str = "::";
+
+
+/// A namespace for the metrics of one object among several alike, e.g. an interface, disk or
+/// queue. Its metrics are labeled `group::name`, so all objects report the same names. The
+/// exporters turn the group into a label or tag instead of a part of the name, see
+/// `PerfData::to_prometheus()` and `PerfData::to_influx()`.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::PerfData;
+/// use icingaplugin_rs::group::group;
+///
+/// let mut pd = PerfData::new();
+/// for (interface, rx) in [("eth0", 1024), ("eth1", 2048)] {
+///     let g = group(interface);
+///     pd.push(g.metric("rx_bytes", rx).uom("B"));
+/// }
+/// assert_eq!(pd.to_string(), "'eth0::rx_bytes'=1024B;;;; 'eth1::rx_bytes'=2048B;;;; ");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MetricGroup {
+    /// the group labels are prefixed with, groups nested into each other joined by `::`
+    prefix: String,
+}
+
+
+impl MetricGroup {
+    /// Creates a new `MetricGroup` named `name`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the group, e.g. the interface name
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self { prefix: name.into() }
+    }
+
+    /// Returns a group nested into this one, e.g. a queue of an interface
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the nested group
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::group::group;
+    /// let queue = group("eth0").group("rx0");
+    /// assert_eq!(queue.name(), "eth0::rx0");
+    /// assert_eq!(queue.metric("drops", 3).to_string(), "'eth0::rx0::drops'=3;;;;");
+    /// ```
+    pub fn group(&self, name: &str) -> Self {
+        Self::new(self.label(name))
+    }
+
+    /// Returns the name of the group, including the groups it is nested into
+    pub fn name(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the label of the metric `name` within the group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the metric
+    pub fn label(&self, name: &str) -> String {
+        format!("{}{}{}", self.prefix, SEPARATOR, name)
+    }
+
+    /// Creates a new `Metric` named `name` within the group
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the metric
+    /// * `value` - the value of the metric
+    pub fn metric<V: Into<Value>>(&self, name: &str, value: V) -> Metric {
+        Metric::new(self.label(name), value)
+    }
+}
+
+
+/// Creates a new `MetricGroup` named `name`, see `MetricGroup::new()`
+///
+/// # Arguments
+///
+/// * `name` - the name of the group
+pub fn group<S: Into<String>>(name: S) -> MetricGroup {
+    MetricGroup::new(name)
+}
+
+
+impl Metric {
+    /// Returns the group the `Metric` belongs to, the part of its label before the last `::`,
+    /// if any
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// let metric = Metric::new("eth0::rx0::drops", 3);
+    /// assert_eq!(metric.group(), Some("eth0::rx0"));
+    /// assert_eq!(metric.name(), "drops");
+    /// assert_eq!(Metric::new("load1", 0.5).group(), None);
+    /// ```
+    pub fn group(&self) -> Option<&str> {
+        self.split_label().0
+    }
+
+    /// Returns the name of the `Metric` within its group, the part of its label after the last
+    /// `::`
+    pub fn name(&self) -> &str {
+        self.split_label().1
+    }
+
+    /// Splits the label into the group, if any, and the name
+    fn split_label(&self) -> (Option<&str>, &str) {
+        match self.label.rsplit_once(SEPARATOR) {
+            Some((group, name)) if !group.is_empty() && !name.is_empty() => (Some(group), name),
+            _ => (None, &self.label),
+        }
+    }
+}
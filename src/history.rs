@@ -0,0 +1,186 @@
+use crate::check::{format_float, CheckResult, State};
+use crate::persist::StateFile;
+use std::collections::VecDeque;
+
+/// The levels of a sparkline, from the lowest to the highest value
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The most recent values of a metric and their states, kept between check invocations in a
+/// `StateFile`. Once `capacity` entries are recorded, recording another one drops the oldest.
+/// The history gives operators context in the info line, e.g. `+12% since last run` and a
+/// sparkline of the trend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct History {
+    /// the values and their states, oldest first
+    entries: VecDeque<(f64, State)>,
+    /// the maximum number of entries
+    capacity: usize,
+}
+
+
+impl History {
+    /// Creates a new, empty `History` keeping at most `capacity` entries
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - the maximum number of entries, at least one
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self { entries: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Loads the history stored for `key` by `store()` from `state`. A missing or invalid entry
+    /// results in an empty `History`, entries exceeding `capacity` are dropped, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state file
+    /// * `key` - the key the history is stored under, e.g. the label of the metric
+    /// * `capacity` - the maximum number of entries, at least one
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::history::History;
+    /// use icingaplugin_rs::persist::StateFile;
+    ///
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+    /// for queued in &[20.0, 30.0, 25.0] {
+    ///     let mut history = History::load(&state, "queued", 10);
+    ///     history.push(*queued, State::OK);
+    ///     history.store(&mut state, "queued");
+    /// }
+    ///
+    /// let history = History::load(&state, "queued", 10);
+    /// assert_eq!(history.values().collect::<Vec<_>>(), vec![20.0, 30.0, 25.0]);
+    /// let cr = history.annotate(CheckResult::ok("25 messages queued"));
+    /// assert_eq!(cr.to_string(), "OK - 25 messages queued (-17% since last run, trend ▁█▅)");
+    /// ```
+    pub fn load(state: &StateFile, key: &str, capacity: usize) -> Self {
+        let mut history = Self::new(capacity);
+        let parsed: Option<Vec<_>> = state.get(key).map_or(Some(Vec::new()), |stored| {
+            stored
+                .split_whitespace()
+                .map(|entry| {
+                    let (value, state) = entry.split_once(':')?;
+                    Some((value.parse::<f64>().ok()?, state.parse::<State>().ok()?))
+                })
+                .collect()
+        });
+        for (value, state) in parsed.unwrap_or_default() {
+            history.push(value, state);
+        }
+        history
+    }
+
+    /// Stores the history for `key` in `state`. The change is persisted by `StateFile::save()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state file
+    /// * `key` - the key the history is stored under
+    pub fn store(&self, state: &mut StateFile, key: &str) {
+        let entries: Vec<_> = self.entries.iter().map(|(v, s)| format!("{}:{}", format_float(*v, None), s)).collect();
+        state.set(key, entries.join(" "));
+    }
+
+    /// Records `value` with its `state`, dropping the oldest entry if the history is full.
+    /// Values which are not finite are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the current value of the metric
+    /// * `state` - the current state of the metric
+    pub fn push(&mut self, value: f64, state: State) {
+        if !value.is_finite() {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((value, state));
+    }
+
+    /// Returns the number of recorded entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no entries were recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the recorded values, oldest first
+    pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        self.entries.iter().map(|(v, _)| *v)
+    }
+
+    /// Returns the recorded states, oldest first
+    pub fn states(&self) -> impl Iterator<Item = State> + '_ {
+        self.entries.iter().map(|(_, s)| *s)
+    }
+
+    /// Describes the change of the latest value since the one before, e.g. `+12% since last
+    /// run`, or the absolute change if the value before was zero. Returns `None` if less than
+    /// two values are recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::history::History;
+    /// let mut history = History::new(5);
+    /// history.push(0.0, State::OK);
+    /// history.push(4.0, State::OK);
+    /// assert_eq!(history.delta().as_deref(), Some("+4 since last run"));
+    /// history.push(5.0, State::OK);
+    /// assert_eq!(history.delta().as_deref(), Some("+25% since last run"));
+    /// ```
+    pub fn delta(&self) -> Option<String> {
+        let mut latest = self.entries.iter().rev().map(|(v, _)| *v);
+        let (current, previous) = (latest.next()?, latest.next()?);
+        let change = current - previous;
+        let sign = if change < 0.0 { "" } else { "+" };
+        Some(if previous == 0.0 {
+            format!("{}{} since last run", sign, format_float(change, None))
+        } else {
+            // adding zero turns a negative zero into a positive one
+            format!("{}{}% since last run", sign, (change / previous.abs() * 100.0).round() + 0.0)
+        })
+    }
+
+    /// Returns a sparkline of the recorded values, one character per value scaled between the
+    /// lowest and the highest one, e.g. `▁▃▅█`
+    pub fn sparkline(&self) -> String {
+        let min = self.values().fold(f64::INFINITY, f64::min);
+        let max = self.values().fold(f64::NEG_INFINITY, f64::max);
+        self.values()
+            .map(|v| match max - min {
+                range if range > 0.0 => SPARKS[((v - min) / range * (SPARKS.len() - 1) as f64).round() as usize],
+                _ => SPARKS[0],
+            })
+            .collect()
+    }
+
+    /// Appends the `delta()` and the `sparkline()` to the info line of `cr`, e.g.
+    /// `(+12% since last run, trend ▁▃▅█)`. Returns `cr` unchanged if less than two values are
+    /// recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `cr` - the result of the check, after the current value was recorded
+    pub fn annotate(&self, cr: CheckResult) -> CheckResult {
+        let delta = match self.delta() {
+            Some(delta) => delta,
+            None => return cr,
+        };
+        let note = format!("{}, trend {}", delta, self.sparkline());
+        let info = match cr.info() {
+            Some(info) if !info.is_empty() => format!("{} ({})", info, note),
+            _ => note,
+        };
+        cr.set_info(info)
+    }
+}
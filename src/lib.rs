@@ -1,6 +1,105 @@
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "tokio")]
+pub mod async_runner;
+#[cfg(feature = "persist")]
+pub mod baseline;
+pub mod builder;
 pub mod check;
+pub mod clock;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod csv;
+#[cfg(feature = "tracing")]
+pub mod diagnostics;
+pub mod error;
+pub mod export;
+pub mod extra_opts;
+pub mod filter;
+pub mod group;
+#[cfg(feature = "persist")]
+pub mod history;
+#[cfg(feature = "log")]
+pub mod logging;
+#[cfg(feature = "nrpe")]
+pub mod nrpe;
+#[cfg(feature = "nsca")]
+pub mod nsca;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod parse;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod pipeline;
+mod platform;
+pub mod probes;
+pub mod rate;
+pub mod runner;
+pub mod secret;
+pub mod spool;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+#[cfg(feature = "ssh")]
+pub mod ssh;
+pub mod testing;
+pub mod thresholds;
+pub mod units;
 pub mod utils;
 
+// lets the code generated by the derive macros refer to this crate by name within it as well
+extern crate self as icingaplugin_rs;
+
+use check::CheckResult;
+use error::PluginError;
+
+
+/// Runs `check`, prints its `CheckResult` and exits with the corresponding exit code. Errors are
+/// converted into a `CheckResult` via `PluginError`, panics result in an UNKNOWN `CheckResult`.
+/// Like `runner::Runner::run()`, stdout is redirected to stderr while `check` runs. Use
+/// `runner::Runner` to also enforce a timeout or print JSON.
+///
+/// # Arguments
+///
+/// * `check` - the check to run
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::check::{CheckResult, State};
+/// use icingaplugin_rs::error::PluginError;
+///
+/// fn check_load() -> Result<CheckResult, PluginError> {
+///     let loadavg = std::fs::read_to_string("/proc/loadavg")?;
+///     let load: f64 = loadavg.split_whitespace().next().unwrap_or("").parse()?;
+///     Ok(CheckResult::new(State::OK).set_info(format!("load is {}", load)))
+/// }
+///
+/// fn main() {
+///     icingaplugin_rs::run(check_load)
+/// }
+/// ```
+pub fn run<F, E>(check: F) -> !
+where F: FnOnce() -> Result<CheckResult, E>, E: Into<PluginError> {
+    platform::init();
+    let cr = {
+        let _stdout = platform::redirect_stdout();
+        execute(check)
+    };
+    cr.exit()
+}
+
+
+/// Runs `check` like `run()` does, returning its `CheckResult` instead of exiting
+pub(crate) fn execute<F, E>(check: F) -> CheckResult
+where F: FnOnce() -> Result<CheckResult, E>, E: Into<PluginError> {
+    let cr = utils::guard(|| check().unwrap_or_else(|e| CheckResult::from(e.into())));
+    #[cfg(feature = "log")]
+    let cr = logging::attach_captured(cr);
+    cr
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -38,4 +137,1807 @@ mod tests {
         let cr2 = utils::evaluate(1.11, 1.12, 1.13);
         assert_eq!(cr2.state(), check::State::OK);
     }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let cr = csv::CsvEvaluator::new(0, 1).warning(2).critical(3)
+            .evaluate("\"disk, C:\",42,50,60\n\"disk \"\"D\"\"\",55,50,60").unwrap();
+        assert_eq!(cr.state(), check::State::Warning);
+        assert_eq!(cr.to_string(), "WARNING - 0 critical, 1 warning, 1 ok | 'disk, C:'=42;50;60;; 'disk \"D\"'=55;50;60;; ");
+    }
+
+    #[test]
+    fn csv_errors() {
+        let ev = csv::CsvEvaluator::new(0, 1).warning(2).critical(3);
+        assert_eq!(ev.evaluate("\n\n"), Err(csv::CsvError::Empty));
+        assert_eq!(ev.evaluate("a,1,2"), Err(csv::CsvError::MissingColumn { line: 1, column: 3 }));
+        assert_eq!(ev.evaluate("a,1,2,2"), Err(csv::CsvError::Evaluation { line: 1, error: utils::EvalError::EqualThresholds }));
+        assert_eq!(ev.evaluate("\"a,1,2,3"), Err(csv::CsvError::UnterminatedQuote { line: 1 }));
+        let ev = ev.max(4);
+        assert_eq!(ev.evaluate("a,1,2,3,full"), Err(csv::CsvError::InvalidNumber { line: 1, field: String::from("full") }));
+    }
+
+    #[test]
+    fn csv_single_threshold() {
+        let cr = csv::CsvEvaluator::new(0, 1).warning(2).critical(3)
+            .evaluate("a,95,,90\nb,85,80,\nc,10,,").unwrap();
+        assert_eq!(cr.to_string(), "CRITICAL - 1 critical, 1 warning, 1 ok | 'a'=95;;90;; 'b'=85;80;;; 'c'=10;;;; ");
+    }
+
+    #[test]
+    fn csv_numeric_values() {
+        let cr = csv::CsvEvaluator::new(0, 1).warning(2).max(3)
+            .evaluate("a,1e3,2e3,4e3\nb,2.5,,").unwrap();
+        assert_eq!(cr.to_string(), "OK - 0 critical, 0 warning, 2 ok | 'a'=1000;2000;;;4000 'b'=2.5;;;; ");
+        let metric = cr.perf_data().unwrap().iter().next().unwrap();
+        assert_eq!(metric.value(), &check::Value::Float(1000.0));
+    }
+
+    #[test]
+    fn filter_globs() {
+        let set = filter::FilterSet::new()
+            .include("/var/*/log".parse().unwrap())
+            .include("/dev/sd[a-c]?".parse().unwrap())
+            .exclude("*[!0-9]".parse().unwrap());
+        assert!(set.matches("/dev/sda1"));
+        assert!(!set.matches("/dev/sdd1"));
+        assert!(!set.matches("/dev/sdab"));
+        assert!(!set.matches("/var/lib/log"));
+        assert!(filter::FilterSet::new().include("a*b*c".parse().unwrap()).matches("aXbYbZc"));
+        assert!(filter::FilterSet::new().include("[abc".parse().unwrap()).matches("[abc"));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn filter_regex() {
+        let set = filter::FilterSet::from_args(&["re:^sd[a-z]$", "nvme*"], &["regex:b"], true).unwrap();
+        assert!(set.matches("SDA"));
+        assert!(!set.matches("sdb"));
+        assert!(set.matches("nvme0n1"));
+        assert!(!set.matches("xsda"));
+        assert!(filter::FilterSet::from_args(&["re:("], &[], false).is_err());
+
+        // flags written by the user are kept, whatever the case sensitivity of the set
+        let set = filter::FilterSet::from_args(&["re:(?i)eth"], &[], false).unwrap();
+        assert!(set.matches("ETH0"));
+        let set = set.ignore_case(true).ignore_case(false).include("re:^lo$".parse().unwrap());
+        assert!(set.matches("Eth1"));
+        assert!(!set.matches("LO"));
+        let set = set.ignore_case(true);
+        assert!(set.matches("LO"));
+        assert!(!set.matches("wlan0"));
+    }
+
+    #[test]
+    fn range_syntax() {
+        let parse = |s: &str| s.parse::<thresholds::Range>();
+        assert_eq!(parse("10"), Ok(thresholds::Range::new(0.0, 10.0)));
+        assert_eq!(parse("10:"), Ok(thresholds::Range::new(10.0, f64::INFINITY)));
+        assert_eq!(parse("~:10"), Ok(thresholds::Range::new(f64::NEG_INFINITY, 10.0)));
+        assert_eq!(parse("10:20"), Ok(thresholds::Range::new(10.0, 20.0)));
+        assert_eq!(parse("@10:20"), Ok(thresholds::Range::inside(10.0, 20.0)));
+        assert_eq!(parse("@"), Err(thresholds::RangeError::Empty));
+        assert_eq!(parse("a:5"), Err(thresholds::RangeError::InvalidNumber(String::from("a"))));
+        for s in &["10", "10:", "~:10", "10:20", "@10:20", "@~:1.5", "-5:-1"] {
+            assert_eq!(parse(s).unwrap().to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn evaluate_inside_ranges() {
+        let warn: thresholds::Range = "@10:20".parse().unwrap();
+        let crit: thresholds::Range = "@15:17".parse().unwrap();
+        assert_eq!(utils::evaluate_range(9, &warn, &crit), check::State::OK);
+        assert_eq!(utils::evaluate_range(12, &warn, &crit), check::State::Warning);
+        assert_eq!(utils::evaluate_range(16, &warn, &crit), check::State::Critical);
+        assert_eq!(utils::evaluate_range(f64::NAN, &warn, &crit), check::State::Unknown);
+        assert_eq!(thresholds::Thresholds::new().critical(crit).state(f64::NAN), check::State::Unknown);
+    }
+
+    #[test]
+    fn overall_roll_up() {
+        let empty = check::Overall::new().finish();
+        assert_eq!(empty.state(), check::State::Unknown);
+
+        let overall: check::Overall = vec![
+            check::CheckResult::from(1).set_long_output(vec![String::from("detail")]),
+            check::CheckResult::from(3),
+            check::CheckResult::from(0),
+        ].into_iter().collect();
+        assert_eq!(overall.state(), check::State::Unknown);
+        assert_eq!(overall.finish().to_string(),
+            "UNKNOWN - 1 unknown, 1 warning, 1 ok\n\\_ [WARNING]\n    detail\n\\_ [UNKNOWN]\n\\_ [OK]");
+    }
+
+    #[test]
+    fn runner_catches_panics() {
+        let plugin = || -> Result<check::CheckResult, runner::Error> { panic!("index out of bounds") };
+        let cr = runner::Runner::new(plugin).execute();
+        assert_eq!(cr.state(), check::State::Unknown);
+        assert!(cr.to_string().starts_with("UNKNOWN - check panicked: index out of bounds (src/lib.rs:"));
+    }
+
+    #[test]
+    fn concurrent_runners_report_own_panics() {
+        // each check panics with the line it panics on, which has to match the recorded location
+        let threads: Vec<_> = (0..8)
+            .map(|i| std::thread::spawn(move || {
+                for _ in 0..20 {
+                    let cr = match i % 2 {
+                        0 => runner::Runner::new(|| -> Result<check::CheckResult, runner::Error> { panic!("{}", line!()) }).execute(),
+                        _ => runner::Runner::new(|| -> Result<check::CheckResult, runner::Error> {
+                            panic!("{}", line!())
+                        }).execute(),
+                    };
+                    let output = cr.to_string();
+                    let line = output["UNKNOWN - check panicked: ".len()..].split(' ').next().unwrap().to_string();
+                    assert!(output.contains(&format!("(src/lib.rs:{}:", line)), "{}", output);
+                }
+            }))
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn runner_watch_diff() {
+        let previous = check::CheckResult::ok("fine")
+            .add_metric(check::Metric::new("load1", 0.5).critical("1"))
+            .add_metric(check::Metric::new("procs", 312))
+            .add_metric(check::Metric::new("users", 3));
+        let current = check::CheckResult::critical("overloaded")
+            .add_metric(check::Metric::new("load1", 1.25).critical("1"))
+            .add_metric(check::Metric::new("procs", 300))
+            .add_metric(check::Metric::new("rta", 12).uom("ms"));
+        assert_eq!(runner::watch_diff(&previous, &current, false), "state: OK -> CRITICAL\n\
+            load1: 0.5 -> 1.25 (+0.75)\nprocs: 312 -> 300 (-12)\nrta: new, 12ms\nusers: removed");
+        assert_eq!(runner::watch_diff(&previous, &previous, false), "no changes");
+        assert_eq!(runner::watch_diff(&previous, &current, true).lines().nth(1),
+            Some("\x1b[31mload1: 0.5 -> 1.25 (+0.75)\x1b[0m"));
+    }
+
+    #[test]
+    fn runner_dry_run() {
+        let plugin = || -> Result<check::CheckResult, runner::Error> {
+            Ok(check::CheckResult::ok(format!("dry run: {}", runner::dry_run())))
+        };
+        assert_eq!(runner::Runner::new(plugin).execute().to_string(), "OK - dry run: false");
+        assert_eq!(runner::Runner::new(plugin).dry_run(true).execute().to_string(), "OK - [dry run] dry run: true");
+        // the mode does not leak into plugins executed later on the same thread
+        assert!(!runner::dry_run());
+        let silent = || -> Result<check::CheckResult, runner::Error> { Ok(check::CheckResult::from(0)) };
+        assert_eq!(runner::Runner::new(silent).dry_run(true).execute().to_string(), "OK - [dry run]");
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn cli_rejects_invalid_thresholds() {
+        let cmd = cli::standard_args(clap::Command::new("check_foo"));
+        assert!(cmd.clone().try_get_matches_from(["check_foo", "-w", "20:10"]).is_err());
+        assert!(cmd.clone().try_get_matches_from(["check_foo", "-t", "-1"]).is_err());
+        let matches = cmd.clone().try_get_matches_from(["check_foo", "-t", "1m30s"]).unwrap();
+        assert_eq!(cli::StandardArgs::from_matches(&matches).timeout, std::time::Duration::from_secs(90));
+        let matches = cmd.clone().try_get_matches_from(["check_foo", "--watch", "2s"]).unwrap();
+        assert_eq!(cli::StandardArgs::from_matches(&matches).watch, Some(std::time::Duration::from_secs(2)));
+        assert!(!cli::icinga2_command(&cmd).contains("watch"));
+        let matches = cmd.clone().try_get_matches_from(["check_foo", "--dry-run"]).unwrap();
+        assert!(cli::StandardArgs::from_matches(&matches).dry_run);
+        let matches = cmd.try_get_matches_from(["check_foo", "-H", "db01", "-vvvv"]).unwrap();
+        let args = cli::StandardArgs::from_matches(&matches);
+        assert_eq!(args.hostname.as_deref(), Some("db01"));
+        assert_eq!(args.verbose, 3);
+        assert_eq!(args.warning, None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let cr = check::CheckResult::new(check::State::Critical)
+            .set_info(String::from("disk full"))
+            .set_perf_data(check::PerfData::from_metrics(vec![
+                check::Metric::numeric("used", 98).uom(String::from("%")),
+                check::Metric::numeric("load", 0.5),
+                check::Metric::new(String::from("raw"), String::from("1;2")),
+            ]))
+            .add_long_output(String::from("/var"));
+        let json = serde_json::to_string(&cr).unwrap();
+        assert!(json.contains(r#""value":98,"uom":"%""#));
+        assert_eq!(serde_json::from_str::<check::CheckResult>(&json).unwrap(), cr);
+    }
+
+    #[test]
+    fn parse_check_output() {
+        let output = "OK - all fine | 'it''s'=5s;1:;~:10 b=U\nline 1\nline 2 | c=3;;;0;100\nd=4%";
+        let cr: check::CheckResult = output.parse().unwrap();
+        assert_eq!(cr.state(), check::State::OK);
+        assert_eq!(cr.long_output(), ["line 1", "line 2"]);
+        assert_eq!(cr.to_string(),
+            "OK - all fine | 'it''s'=5s;1:;~:10;; 'b'=U;;;; 'c'=3;;;0;100 'd'=4%;;;; \nline 1\nline 2");
+
+        assert_eq!("".parse::<check::CheckResult>(), Err(parse::ParseError::Empty));
+        assert_eq!("fine | a=1".parse::<check::CheckResult>(),
+            Err(parse::ParseError::MissingState(String::from("fine | a=1"))));
+        assert_eq!("OK | a=1 b c=2".parse::<check::CheckResult>(),
+            Err(parse::ParseError::InvalidMetric(String::from("b"))));
+        assert_eq!("OK | a=1;2;3;4;5;6".parse::<check::CheckResult>(),
+            Err(parse::ParseError::InvalidMetric(String::from("a=1;2;3;4;5;6"))));
+        assert_eq!("UNKNOWN".parse::<check::CheckResult>(), Ok(check::CheckResult::from(3)));
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn api_request_body() {
+        let cr = check::CheckResult::new(check::State::Critical)
+            .set_info(String::from("backup failed"))
+            .add_long_output(String::from("exit code 1"))
+            .set_perf_data(check::PerfData::from_metric(
+                check::Metric::numeric("duration", 12).uom(String::from("s"))));
+        let target = api::Target::Service { host: String::from("db01"), service: String::from("backup") };
+        assert_eq!(api::process_check_result_body(&target, &cr, Some("collector01")), serde_json::json!({
+            "type": "Service",
+            "filter": "host.name==host_name && service.name==service_name",
+            "filter_vars": { "host_name": "db01", "service_name": "backup" },
+            "exit_status": 2,
+            "plugin_output": "backup failed\nexit code 1",
+            "performance_data": "'duration'=12s;;;;",
+            "check_source": "collector01",
+        }));
+
+        let body = api::process_check_result_body(&api::Target::Host(String::from("db01")),
+            &check::CheckResult::from(0), None);
+        assert_eq!(body["plugin_output"], "OK");
+        assert_eq!(body["exit_status"], 0);
+        assert!(body.get("performance_data").is_none());
+
+        // hosts are either UP or DOWN
+        for state in &[check::State::Warning, check::State::Critical, check::State::Unknown] {
+            let body = api::process_check_result_body(&api::Target::Host(String::from("db01")),
+                &check::CheckResult::new(*state), None);
+            assert_eq!(body["type"], "Host");
+            assert_eq!(body["exit_status"], 1);
+        }
+
+        assert_eq!(api::base64(b"root:icinga"), "cm9vdDppY2luZ2E=");
+        assert_eq!(api::base64(b"ab"), "YWI=");
+        assert_eq!(api::base64(b"abc"), "YWJj");
+
+        let empty = std::env::temp_dir().join(format!("icingaplugin-api-{}.pem", std::process::id()));
+        std::fs::write(&empty, "no certificate").unwrap();
+        let result = api::ApiClient::builder("https://localhost:5665").client_cert(&empty, &empty).build();
+        assert!(matches!(result, Err(api::ApiError::Tls(e)) if e.starts_with("no certificates found in ")));
+        let result = api::ApiClient::builder("https://localhost:5665").ca_file(&empty).build();
+        assert!(matches!(result, Err(api::ApiError::Tls(_))));
+        std::fs::remove_file(empty).unwrap();
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn api_object_status() {
+        let service = api::Target::Service { host: String::from("db01"), service: String::from("backup") };
+        let response = serde_json::json!({ "results": [{
+            "attrs": { "state": 2.0, "downtime_depth": 1.0, "acknowledgement": 0.0 },
+            "name": "db01!backup",
+        }]});
+        let status = api::object_status(&service, &response).unwrap();
+        assert_eq!(status, api::ObjectStatus { state: check::State::Critical, in_downtime: true, acknowledged: false });
+        assert_eq!(status.annotate(check::CheckResult::from(2)).to_string(), "CRITICAL - in downtime");
+
+        // hosts are UP or DOWN, acknowledgements may be sticky (2)
+        let host = api::Target::Host(String::from("db01"));
+        let response = serde_json::json!({ "results": [{
+            "attrs": { "state": 1.0, "downtime_depth": 0.0, "acknowledgement": 2.0 },
+        }]});
+        let status = api::object_status(&host, &response).unwrap();
+        assert_eq!(status, api::ObjectStatus { state: check::State::Critical, in_downtime: false, acknowledged: true });
+        assert!(status.skipped().is_none());
+        let cr = api::ObjectStatus { in_downtime: true, ..status }.annotate(check::CheckResult::critical("down"));
+        assert_eq!(cr.to_string(), "CRITICAL - down (in downtime, acknowledged)");
+
+        assert!(api::object_status(&host, &serde_json::json!({ "results": [] })).is_none());
+        let unchanged = api::ObjectStatus { state: check::State::OK, in_downtime: false, acknowledged: false };
+        assert_eq!(unchanged.annotate(check::CheckResult::ok("up")).to_string(), "OK - up");
+    }
+
+    #[cfg(feature = "ssh")]
+    #[test]
+    fn ssh_destination_is_no_option() {
+        let ssh = ssh::Ssh::new("-oProxyCommand=touch /tmp/pwned").option("LogLevel=ERROR");
+        let args: Vec<_> = ssh.command("uptime").get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(args[args.len() - 3..], ["--", "-oProxyCommand=touch /tmp/pwned", "uptime"]);
+    }
+
+    #[cfg(feature = "nsca")]
+    #[test]
+    fn nsca_packet() {
+        assert_eq!(utils::crc32(b"123456789"), 0xCBF4_3926);
+
+        let sender = nsca::Nsca::new(String::from("localhost")).output_length(512);
+        let cr = check::CheckResult::new(check::State::Warning).set_info(String::from("disk 93% full"));
+        let packet = sender.packet("db01", "disk", &cr, &cr.to_string(), 1_700_000_000).unwrap();
+        assert_eq!(packet.len(), 720);
+        assert_eq!(&packet[0..2], &[0, 3]);
+        assert_eq!(&packet[8..12], &1_700_000_000u32.to_be_bytes());
+        assert_eq!(&packet[12..14], &[0, 1]);
+        assert_eq!(&packet[14..19], b"db01\0");
+        assert_eq!(&packet[78..83], b"disk\0");
+        assert_eq!(&packet[206..230], b"WARNING - disk 93% full\0");
+        let mut zeroed = packet.clone();
+        zeroed[4..8].copy_from_slice(&[0; 4]);
+        assert_eq!(&packet[4..8], &utils::crc32(&zeroed).to_be_bytes());
+        assert!(sender.packet(&"a".repeat(64), "disk", &cr, "", 0).is_err());
+        // a zero output length leaves room for the terminating NUL only
+        let empty = nsca::Nsca::new(String::from("localhost")).output_length(0).packet("db01", "disk", &cr, "text", 0).unwrap();
+        assert_eq!(&empty[206..208], &[0, 0]);
+
+        let iv: Vec<u8> = (0..128).collect();
+        let mut xored = packet.clone();
+        nsca::encrypt(&mut xored, nsca::Encryption::Xor, &iv, b"secret");
+        assert_ne!(xored, packet);
+        nsca::encrypt(&mut xored, nsca::Encryption::Xor, &iv, b"secret");
+        assert_eq!(xored, packet);
+
+        // the first CFB byte is the first byte of the encrypted IV, here a DES test vector
+        let mut iv = vec![0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        iv.resize(128, 0);
+        let mut data = [0u8; 1];
+        nsca::encrypt(&mut data, nsca::Encryption::Des, &iv, &[0x13, 0x34, 0x57, 0x79, 0x9B, 0xBC, 0xDF, 0xF1]);
+        assert_eq!(data, [0x85]);
+    }
+
+    #[cfg(feature = "nrpe")]
+    #[test]
+    fn nrpe_query() {
+        use std::io::Write;
+
+        for version in [nrpe::Version::V2, nrpe::Version::V3] {
+            let packet = nrpe::Packet { version, packet_type: nrpe::QUERY_PACKET, result_code: 0,
+                buffer: String::from("check_disk!/var") };
+            let encoded = packet.encode();
+            assert_eq!(encoded.len(), if version == nrpe::Version::V2 { 1036 } else { 1040 });
+            assert_eq!(nrpe::Packet::decode(&mut encoded.as_slice()).unwrap(), packet);
+
+            let mut corrupted = encoded.clone();
+            corrupted[20] = b'x';
+            assert!(nrpe::Packet::decode(&mut corrupted.as_slice()).is_err());
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = nrpe::Packet::decode(&mut stream).unwrap();
+            let response = nrpe::Packet { version: query.version, packet_type: nrpe::RESPONSE_PACKET,
+                result_code: 1, buffer: format!("WARNING - {} | a=1", query.buffer) };
+            stream.write_all(&response.encode()).unwrap();
+        });
+
+        let client = nrpe::Nrpe::new(String::from("127.0.0.1")).port(port);
+        let cr = client.check("check_disk", &["/var"]).unwrap();
+        assert_eq!(cr.to_string(), "WARNING - check_disk!/var | 'a'=1;;;; ");
+        server.join().unwrap();
+
+        assert!(client.query("check_disk", &["a!b"]).is_err());
+    }
+
+    #[cfg(feature = "nrpe")]
+    #[test]
+    fn nrpe_server() {
+        let ok = || -> Result<check::CheckResult, runner::Error> {
+            Ok(check::CheckResult::new(check::State::Critical).set_info(String::from("disk full"))
+                .add_long_output(String::from("/var")))
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let slow = || -> Result<check::CheckResult, runner::Error> {
+            std::thread::sleep(std::time::Duration::from_secs(5));
+            Ok(check::CheckResult::from(0))
+        };
+        let server = nrpe::NrpeServer::new().command("check_disk", ok).command("check_slow", slow)
+            .command_timeout(std::time::Duration::from_millis(50));
+        std::thread::spawn(move || server.serve_listener(listener));
+
+        let client = nrpe::Nrpe::new(String::from("127.0.0.1")).port(port);
+        for version in [nrpe::Version::V2, nrpe::Version::V3] {
+            let client = client.clone().version(version);
+            let response = client.query("check_disk", &[]).unwrap();
+            assert_eq!(response, nrpe::NrpeResponse { result_code: 2, output: String::from("CRITICAL - disk full\n/var") });
+            assert_eq!(client.query("check_foo", &[]).unwrap().output, "NRPE: Command 'check_foo' not defined");
+            assert_eq!(client.query("check_disk", &["/home"]).unwrap().result_code, 3);
+            assert!(client.query("_NRPE_CHECK", &[]).unwrap().output.contains("NRPE"));
+        }
+        // the timeout set after registering the command applies
+        assert_eq!(client.query("check_slow", &[]).unwrap().output, "UNKNOWN - check timed out after 0.05s");
+    }
+
+    #[test]
+    fn spool_file() {
+        let cr = check::CheckResult::new(check::State::Warning).set_info(String::from("load is 4.2"))
+            .add_long_output(String::from("4 cores"));
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_250);
+        assert_eq!(spool::contents("db01", Some("load"), &cr, time), "### Passive Check Result File ###\n\
+            file_time=1700000000\n\n### Nagios Service Check Result ###\nhost_name=db01\n\
+            service_description=load\ncheck_type=1\ncheck_options=0\nscheduled_check=0\n\
+            reschedule_check=0\nlatency=0.0\nstart_time=1700000000.250000\n\
+            finish_time=1700000000.250000\nearly_timeout=0\nexited_ok=1\nreturn_code=1\n\
+            output=WARNING - load is 4.2\\n4 cores\n");
+
+        let dir = std::env::temp_dir().join(format!("icingaplugin-spool-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let writer = spool::SpoolWriter::new(dir.clone());
+        let first = writer.write("db01", None, &cr).unwrap();
+        let second = writer.write("db01", None, &cr).unwrap();
+        assert_ne!(first, second);
+        let err = writer.write("db01\nreturn_code=0", None, &cr).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(writer.write("db01", Some("load\r\noutput=OK"), &cr).is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+        assert!(std::fs::read_to_string(&first).unwrap().contains("### Nagios Host Check Result ###"));
+        assert!(dir.join(format!("{}.ok", first.file_name().unwrap().to_str().unwrap())).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prometheus_export() {
+        let pd = check::PerfData::from_metrics(vec![
+            check::Metric::numeric("requests", 1234).uom(String::from("c")),
+            check::Metric::numeric("disk usage", 87.5).uom(String::from("%")),
+            check::Metric::numeric("1min load", 0.5),
+            check::Metric::numeric("size", 2).uom(String::from("MB")),
+            check::Metric::new(String::from("temp"), String::from("U")),
+        ]);
+        assert_eq!(pd.to_prometheus(), "# HELP requests requests\n# TYPE requests counter\nrequests 1234\n\
+            # HELP disk_usage_ratio disk usage\n# TYPE disk_usage_ratio gauge\ndisk_usage_ratio 0.875\n\
+            # HELP _1min_load 1min load\n# TYPE _1min_load gauge\n_1min_load 0.5\n\
+            # HELP size_bytes size\n# TYPE size_bytes gauge\nsize_bytes 2000000\n");
+    }
+
+    #[test]
+    fn openmetrics_export() {
+        let pd = check::PerfData::from_metrics(vec![
+            check::Metric::numeric("errors_total", 3).uom(String::from("c")),
+            check::Metric::numeric("free \"tmp\"", 50).uom(String::from("%")),
+        ]);
+        assert_eq!(pd.to_openmetrics(), "# TYPE errors counter\n# HELP errors errors_total\n\
+            errors_total 3\n# TYPE free__tmp__ratio gauge\n# UNIT free__tmp__ratio ratio\n\
+            # HELP free__tmp__ratio free \\\"tmp\\\"\nfree__tmp__ratio 0.5\n# EOF\n");
+        assert_eq!(check::PerfData::from_metrics(vec![]).to_openmetrics(), "# EOF\n");
+    }
+
+    #[test]
+    fn influx_export() {
+        let pd = check::PerfData::from_metrics(vec![
+            check::Metric::numeric("rta", 0.25).uom(String::from("ms")).warning(String::from("100"))
+                .critical(String::from("500")).min(String::from("0")),
+            check::Metric::numeric("a,b=c", 1),
+            check::Metric::new(String::from("temp"), String::from("U")),
+        ]);
+        assert_eq!(pd.to_influx("ping 4", &[("hostname", "db01"), ("service", "ping")]),
+            "ping\\ 4,hostname=db01,service=ping,metric=rta,unit=ms value=0.25,warn=100,crit=500,min=0\n\
+            ping\\ 4,hostname=db01,service=ping,metric=a\\,b\\=c value=1\n");
+    }
+
+    #[test]
+    fn graphite_export() {
+        let pd = check::PerfData::from_metrics(vec![
+            check::Metric::numeric("C:\\ used", 12).uom(String::from("GB")),
+            check::Metric::numeric("rx.bytes", 1.5),
+            check::Metric::new(String::from("temp"), String::from("U")),
+        ]);
+        assert_eq!(pd.to_graphite("win01.", 42), "win01.C___used 12 42\nwin01.rx_bytes 1.5 42\n");
+        assert_eq!(pd.to_graphite("", 42), "C___used 12 42\nrx_bytes 1.5 42\n");
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn otel_counters() {
+        assert_eq!(otel::instrument_name("disk /var"), "disk_/var");
+        assert_eq!(otel::instrument_name("1min"), "metric_1min");
+
+        let mut exporter = otel::OtelExporter::new(opentelemetry::global::meter("test"));
+        assert_eq!(exporter.increment(String::from("rx"), 100.0), 100.0);
+        assert_eq!(exporter.increment(String::from("rx"), 150.0), 50.0);
+        assert_eq!(exporter.increment(String::from("tx"), 10.0), 10.0);
+        assert_eq!(exporter.increment(String::from("rx"), 20.0), 20.0);
+
+        let pd = check::PerfData::from_metrics(vec![
+            check::Metric::numeric("rx", 1).uom(String::from("c")),
+            check::Metric::numeric("load", 0.5),
+        ]);
+        exporter.record(&pd, &[]);
+        exporter.record(&pd, &[]);
+    }
+
+    #[test]
+    fn state_ordering() {
+        use check::State;
+        let mut states = vec![State::Critical, State::OK, State::Unknown, State::Warning];
+        states.sort();
+        assert_eq!(states, [State::OK, State::Warning, State::Unknown, State::Critical]);
+        assert_eq!(states.iter().copied().max(), Some(State::Critical));
+    }
+
+    #[test]
+    fn worst_state() {
+        use check::{CheckResult, State};
+        assert_eq!(State::worst([State::Critical, State::Unknown].iter().copied()), State::Critical);
+        let results: Vec<CheckResult> = (0..4).map(CheckResult::from).collect();
+        assert_eq!(CheckResult::worst_of(results).state(), State::Critical);
+        let results = vec![CheckResult::from(0).set_info(String::from("a")), CheckResult::from(0)];
+        assert_eq!(CheckResult::worst_of(results).to_string(), "OK - a");
+    }
+
+    #[test]
+    fn state_from_str() {
+        use check::{State, StateError};
+        use std::convert::TryFrom;
+        for (s, state) in [("ok", State::OK), ("Warning", State::Warning), ("1", State::Warning),
+            ("crit", State::Critical), ("UNKN", State::Unknown), ("unknown", State::Unknown)].iter() {
+            assert_eq!(State::try_from(*s), Ok(*state));
+        }
+        assert_eq!("4".parse::<State>(), Err(StateError::InvalidName(String::from("4"))));
+        assert_eq!("".parse::<State>(), Err(StateError::InvalidName(String::new())));
+    }
+
+    #[test]
+    fn state_from_exit_code() {
+        use check::{State, StateError};
+        use std::convert::TryFrom;
+        for code in 0..4 {
+            assert_eq!(State::try_from(code).map(i32::from), Ok(code));
+        }
+        assert_eq!(State::try_from(-1), Err(StateError::InvalidCode(-1)));
+        assert_eq!(State::from_exit_code_lossy(255), State::Unknown);
+        assert_eq!(State::from_exit_code_lossy(1), State::Warning);
+    }
+
+    #[test]
+    fn guard_catches_panics() {
+        let cr = utils::guard(|| panic!("disk {} vanished", "/var"));
+        assert_eq!(cr.state(), check::State::Unknown);
+        assert!(cr.to_string().starts_with("UNKNOWN - check panicked: disk /var vanished (src/lib.rs:"));
+        assert_eq!(utils::guard(|| check::CheckResult::from(1)), check::CheckResult::from(1));
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| std::thread::spawn(move || {
+                for _ in 0..20 {
+                    let cr = utils::guard(|| panic!("thread {}", i));
+                    assert!(cr.to_string().starts_with(&format!("UNKNOWN - check panicked: thread {} (src/lib.rs:", i)));
+                }
+            }))
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn timeout_disarmed() {
+        let timeout = runner::Timeout::arm(std::time::Duration::from_millis(20));
+        timeout.disarm();
+        {
+            let _timeout = runner::Timeout::arm(std::time::Duration::from_millis(20));
+        }
+        // the process would have exited if one of the watchdogs fired
+        std::thread::sleep(std::time::Duration::from_millis(60));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_runner_timeouts_and_panics() {
+        use std::time::Duration;
+        async fn slow() -> Result<check::CheckResult, runner::Error> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(check::CheckResult::from(0))
+        }
+        async fn panicking() -> Result<check::CheckResult, runner::Error> {
+            panic!("no endpoints")
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let runner = async_runner::AsyncRunner::new().timeout(Duration::from_millis(20));
+        let cr = rt.block_on(runner.execute(slow()));
+        assert_eq!(cr.to_string(), "UNKNOWN - check timed out after 0.02s");
+        let cr = rt.block_on(runner.execute(panicking()));
+        assert!(cr.to_string().starts_with("UNKNOWN - check panicked: no endpoints (src/lib.rs:"));
+
+        // checks panicking after an await point keep their own locations
+        async fn panicking_after(millis: u64) -> Result<check::CheckResult, runner::Error> {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            match millis % 2 {
+                0 => panic!("{}", line!()),
+                _ => panic!("{}", line!()),
+            }
+        }
+        let results = rt.block_on(runner.timeout(Duration::from_secs(5)).execute_all((0..10).map(|i| panicking_after(10 - i))));
+        for cr in results {
+            let output = cr.to_string();
+            let line = output["UNKNOWN - check panicked: ".len()..].split(' ').next().unwrap().to_string();
+            assert!(output.contains(&format!("(src/lib.rs:{}:", line)), "{}", output);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_runner_timings() {
+        use std::time::Duration;
+        async fn sleep(millis: u64) -> Result<check::CheckResult, runner::Error> {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok(check::CheckResult::ok(format!("slept {}ms", millis)))
+        }
+
+        let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let runner = async_runner::AsyncRunner::new().timeout(Duration::from_millis(100));
+        let checks = vec![("slow", sleep(60)), ("fast", sleep(1)), ("hanging", sleep(5000))];
+        let (results, timings) = rt.block_on(runner.execute_timed(checks));
+        assert_eq!(results[2].state(), check::State::Unknown);
+        assert!(timings.get("fast").unwrap() < Duration::from_millis(60));
+        assert!(timings.get("slow").unwrap() >= Duration::from_millis(60));
+        assert!(timings.get("hanging").unwrap() >= Duration::from_millis(100));
+        assert_eq!(timings.iter().map(|(name, _)| name).collect::<Vec<_>>(), vec!["slow", "fast", "hanging"]);
+
+        let cr = timings.attach(check::CheckResult::ok("3 checks"));
+        let labels: Vec<_> = cr.perf_data().unwrap().iter().map(|m| m.label.to_string()).collect();
+        assert_eq!(labels, vec!["slow_time", "fast_time", "hanging_time"]);
+        assert_eq!(cr.clone().to_string().lines().count(), 1);
+        let output = cr.set_verbosity(1).to_string();
+        let table: Vec<_> = output.lines().skip(1).collect();
+        assert_eq!(table[0], "check          time");
+        assert!(table[2].starts_with("fast         0.0"), "{}", output);
+        assert_eq!(async_runner::Timings::default().attach(check::CheckResult::ok("none")).to_string(), "OK - none");
+    }
+
+    #[test]
+    fn retry_attempts() {
+        use std::time::Duration;
+        let mut calls = 0;
+        let cr = utils::retry(3, Duration::from_millis(1), || {
+            calls += 1;
+            check::CheckResult::from(3).set_info(String::from("timeout"))
+        });
+        assert_eq!(calls, 3);
+        assert_eq!(cr.to_string(), "UNKNOWN - timeout (failed on all 3 attempts)");
+
+        let mut calls = 0;
+        let cr = utils::retry(3, Duration::from_millis(1), || { calls += 1; check::CheckResult::from(2) });
+        assert_eq!(calls, 1);
+        assert_eq!(cr, check::CheckResult::from(2));
+
+        let mut calls = 0;
+        let cr = utils::retry(0, Duration::from_millis(1), || { calls += 1; check::CheckResult::from(3) });
+        assert_eq!(calls, 1);
+        assert_eq!(cr, check::CheckResult::from(3));
+        assert_eq!(utils::retry(3, Duration::from_secs(60), || check::CheckResult::from(0)), check::CheckResult::from(0));
+    }
+
+    #[test]
+    fn plugin_error_conversion() {
+        fn read_config() -> Result<check::CheckResult, error::PluginError> {
+            std::fs::read_to_string("/nonexistent/icingaplugin.conf")?;
+            Ok(check::CheckResult::from(0))
+        }
+        let e = read_config().unwrap_err();
+        assert!(std::error::Error::source(&e).is_some());
+        let states = error::ErrorStates::new().io(check::State::Warning);
+        assert_eq!(states.state(&e), check::State::Warning);
+        let cr = check::CheckResult::from(error::PluginError::from("no response"));
+        assert_eq!(cr.to_string(), "UNKNOWN - no response");
+    }
+
+    #[test]
+    fn run_converts_errors() {
+        let cr = execute(|| Err::<check::CheckResult, _>("host unreachable"));
+        assert_eq!(cr.to_string(), "UNKNOWN - host unreachable");
+        let cr = execute(|| -> Result<check::CheckResult, error::PluginError> { Ok(check::CheckResult::from(1)) });
+        assert_eq!(cr, check::CheckResult::from(1));
+        let cr = execute(|| -> Result<check::CheckResult, std::io::Error> { panic!("boom") });
+        assert!(cr.to_string().starts_with("UNKNOWN - check panicked: boom"));
+    }
+
+    #[test]
+    fn output_sanitized() {
+        let cr = check::CheckResult::new(check::State::Warning)
+            .set_info(String::from("2 of 3 | down\nweb01 down"))
+            .set_perf_data(check::PerfData::from_metric(check::Metric::numeric("up", 1)))
+            .add_long_output(String::from("web02|down"))
+            .add_debug(1, String::from("cmd: a | b"))
+            .set_verbosity(1);
+        assert_eq!(cr.to_string(), "WARNING - 2 of 3 \u{a6} down | 'up'=1;;;; \nweb01 down\nweb02\u{a6}down\ncmd: a \u{a6} b");
+        assert_eq!(cr.info(), Some("2 of 3 | down\nweb01 down"));
+    }
+
+    #[test]
+    fn metric_labels() {
+        let pd = check::PerfData::parse("'it''s'=1 'a b'=2").unwrap();
+        assert_eq!(pd.to_string(), "'it''s'=1;;;; 'a b'=2;;;; ");
+        assert_eq!(check::PerfData::parse(&pd.to_string()).unwrap(), pd);
+        assert!(check::Metric::try_new("line\nbreak", "1").is_err());
+        assert_eq!(check::sanitize_label("a=b"), "a_b");
+    }
+
+    #[test]
+    fn unknown_metric_values() {
+        let pd = check::PerfData::parse("a=U b=U%").unwrap();
+        assert_eq!(pd.metrics[0], check::Metric::unknown("a"));
+        assert_eq!(pd.metrics[1], check::Metric::unknown("b").uom(String::from("%")));
+        assert_eq!(check::PerfData::parse("c=NaNx"), Err(parse::ParseError::InvalidMetric(String::from("c=NaNx"))));
+        assert_eq!(check::PerfData::parse("a=1 b=abc"), Err(parse::ParseError::InvalidMetric(String::from("b=abc"))));
+        assert_eq!(check::Metric::numeric("nan", f64::NAN).to_string(), "'nan'=U;;;;");
+        assert_eq!(pd.to_prometheus().lines().nth(2), Some("a NaN"));
+        assert_eq!(pd.to_influx("m", &[]), "");
+    }
+
+    #[test]
+    fn counter_rates() {
+        use rate::{CounterWidth, RateError, Sample};
+        use std::time::Duration;
+        let t0 = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let t1 = t0 + Duration::from_millis(500);
+        let previous = Sample::at(u64::MAX, t0);
+        assert_eq!(rate::rate(&previous, &Sample::at(9, t1), CounterWidth::Bits64), Ok(20.0));
+        assert_eq!(rate::rate(&previous, &Sample::at(9, t1), CounterWidth::Bits32), Err(RateError::Reset));
+        assert_eq!(rate::rate(&previous, &Sample::at(9, t0), CounterWidth::Bits64), Err(RateError::NoTimeElapsed));
+        let sample: Sample = Sample::at(42, t1).to_string().parse().unwrap();
+        assert_eq!(sample, Sample::at(42, t1));
+    }
+
+    #[test]
+    fn output_sinks() {
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let cr = check::CheckResult::ok("fine").add_long_output("details");
+        let mut out = Vec::new();
+        assert_eq!(cr.write_to(&mut out).unwrap(), 0);
+        assert_eq!(out, b"OK - fine\ndetails\n");
+        assert!(cr.write_to(&mut Broken).is_err());
+
+        let plugin = || -> Result<check::CheckResult, runner::Error> { Ok(check::CheckResult::unknown("no data")) };
+        let runner = runner::Runner::new(plugin)
+            .info(runner::PluginInfo::new("check_sink", "1.0"))
+            .output_format(runner::OutputFormat::Json);
+        let mut out = Vec::new();
+        assert_eq!(runner.run_to(&mut out).unwrap(), 3);
+        assert!(String::from_utf8(out).unwrap().starts_with(r#"{"plugin":{"name":"check_sink","version":"1.0""#));
+        assert_eq!(runner.run_to(&mut Broken).unwrap_err().kind(), std::io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn mock_clock() {
+        use clock::{Clock, MockClock, SystemClock};
+        use std::time::Duration;
+        let clock = MockClock::default();
+        let start = clock.now();
+        let shared = clock.clone();
+        shared.advance(Duration::from_secs(90));
+        assert_eq!(clock.since(start), Duration::from_secs(90));
+        clock.set(start - Duration::from_secs(10));
+        assert_eq!(clock.since(start), Duration::from_secs(0));
+        assert_eq!(clock.until(start), Duration::from_secs(10));
+        assert!(SystemClock.now() > start);
+
+        #[cfg(feature = "persist")]
+        {
+            use rate::{CounterWidth, Sample};
+            let mut state = persist::StateFile::load_from(std::env::temp_dir().join("never_saved.json"))
+                .unwrap()
+                .clock(clock.clone());
+            let max_age = Duration::from_secs(300);
+            assert_eq!(state.rate("rx", Sample::at(1_000, clock.now()), CounterWidth::Bits64, max_age), Ok(None));
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(state.rate("rx", Sample::at(4_000, clock.now()), CounterWidth::Bits64, max_age), Ok(Some(50.0)));
+            clock.advance(Duration::from_secs(301));
+            assert_eq!(state.rate("rx", Sample::at(5_000, clock.now()), CounterWidth::Bits64, max_age), Ok(None));
+        }
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn baseline_thresholds() {
+        let mut state = persist::StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+        let baseline = baseline::Baseline::stddev(1.0, 2.0).learning(3).window(3).direction(utils::Direction::Above);
+
+        let evaluation = baseline.evaluate(&mut state, &check::Metric::numeric("rta", 100));
+        assert_eq!(evaluation.state(), check::State::OK);
+        assert_eq!(evaluation.hint(), Some("rta is learning its baseline (0 of 3 values)"));
+        assert_eq!(evaluation.describe(), "rta is learning its baseline (0 of 3 values)");
+        assert_eq!(evaluation.metric().to_string(), "'rta'=100;;;;");
+        baseline.evaluate(&mut state, &check::Metric::numeric("rta", 200));
+        assert_eq!(baseline.evaluate(&mut state, &check::Metric::unknown("rta")).state(), check::State::Unknown);
+        baseline.evaluate(&mut state, &check::Metric::numeric("rta", 300));
+
+        // mean 200, standard deviation 81.65
+        let evaluation = baseline.evaluate(&mut state, &check::Metric::numeric("rta", 20));
+        assert_eq!(evaluation.state(), check::State::OK);
+        assert_eq!(evaluation.hint(), None);
+        assert_eq!(evaluation.metric().to_string(), "'rta'=20;~:281.65;~:363.3;;");
+        assert_eq!(state.get("rta"), Some("200:OK 300:OK 20:OK"));
+
+        let evaluation = baseline.evaluate(&mut state, &check::Metric::numeric("rta", 500));
+        assert_eq!(evaluation.state(), check::State::Critical);
+        assert_eq!(state.get("rta"), Some("300:OK 20:OK 500:CRITICAL"));
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn history_ring_buffer() {
+        let mut state = persist::StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+        let mut history = history::History::new(3);
+        assert_eq!(history.annotate(check::CheckResult::ok("fine")).to_string(), "OK - fine");
+        for (value, s) in &[(1.0, check::State::OK), (2.0, check::State::OK), (8.0, check::State::Warning), (4.0, check::State::OK)] {
+            history.push(*value, *s);
+        }
+        history.push(f64::NAN, check::State::Unknown);
+        assert_eq!(history.values().collect::<Vec<_>>(), vec![2.0, 8.0, 4.0]);
+        assert_eq!(history.states().collect::<Vec<_>>(), vec![check::State::OK, check::State::Warning, check::State::OK]);
+        assert_eq!(history.delta().as_deref(), Some("-50% since last run"));
+        assert_eq!(history.sparkline(), "▁█▃");
+
+        history.store(&mut state, "load");
+        assert_eq!(state.get("load"), Some("2:OK 8:WARNING 4:OK"));
+        assert_eq!(history::History::load(&state, "load", 3), history);
+        assert_eq!(history::History::load(&state, "load", 2).values().collect::<Vec<_>>(), vec![8.0, 4.0]);
+        state.set("load", "garbage");
+        assert!(history::History::load(&state, "load", 3).is_empty());
+
+        let mut flat = history::History::new(5);
+        flat.push(-2.0, check::State::OK);
+        flat.push(-2.0, check::State::OK);
+        assert_eq!(flat.annotate(check::CheckResult::from(0)).to_string(), "OK - +0% since last run, trend ▁▁");
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn state_file() {
+        use std::time::Duration;
+        let dir = std::env::temp_dir().join(format!("icingaplugin_state_{}", std::process::id()));
+        let path = dir.join("check_test.json");
+        let mut state = persist::StateFile::load_from(path.clone()).unwrap();
+        state.set("a \"quoted\" key", "line\nbreak");
+        state.set("offset", 10);
+        state.save().unwrap();
+
+        let mut loaded = persist::StateFile::load_from(path.clone()).unwrap();
+        assert_eq!(loaded.get("a \"quoted\" key"), Some("line\nbreak"));
+        assert!(loaded.age("offset").unwrap() < Duration::from_secs(60));
+        assert_eq!(loaded.get_fresh("offset", Duration::from_secs(0)), None);
+        assert_eq!(loaded.remove("offset"), Some(String::from("10")));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::write(&path, "{\"offset\": 10}").unwrap();
+        assert!(persist::StateFile::load_from(path).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn lock_contention() {
+        use std::time::{Duration, Instant};
+        let path = std::env::temp_dir().join(format!("icingaplugin_lock_{}", std::process::id()));
+        let lock = persist::Lock::acquire(&path, persist::LockPolicy::Fail).unwrap();
+        let start = Instant::now();
+        let err = persist::Lock::acquire(&path, persist::LockPolicy::Wait(Duration::from_millis(50))).unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        let cr = check::CheckResult::from(error::PluginError::from(err));
+        assert_eq!(cr.to_string(), format!("UNKNOWN - {} is locked by another invocation", path.display()));
+
+        let waiter = {
+            let path = path.clone();
+            std::thread::spawn(move || persist::Lock::acquire(&path, persist::LockPolicy::Wait(Duration::from_secs(5))).is_ok())
+        };
+        std::thread::sleep(Duration::from_millis(30));
+        drop(lock);
+        assert!(waiter.join().unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn byte_units() {
+        assert_eq!(units::format_bytes(0), "0 B");
+        assert_eq!(units::format_bytes(1023), "1023 B");
+        assert_eq!(units::format_bytes(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(units::format_bytes(u64::MAX), "16.0 EiB");
+        assert_eq!(units::parse_bytes(" 2 kB "), Ok(2000));
+        assert!(units::parse_bytes("-1GB").is_err());
+        assert!(units::parse_bytes("GB").is_err());
+        assert!(units::parse_bytes("5s").is_err());
+    }
+
+    #[test]
+    fn duration_units() {
+        use std::time::Duration;
+        assert_eq!(units::parse_duration("1w 2d"), Ok(Duration::from_secs(777600)));
+        assert_eq!(units::parse_duration(" 0s "), Ok(Duration::from_secs(0)));
+        assert!(units::parse_duration("").is_err());
+        assert!(units::parse_duration("5 3").is_err());
+        assert!(units::parse_duration("1h 30").is_err());
+        assert!(units::parse_duration("0s 5").is_err());
+        assert!(units::parse_duration("-5m").is_err());
+        assert!(units::parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn evaluate_optional_thresholds() {
+        assert_eq!(utils::evaluate_opt(1, Some(2), Some(2)), Err(utils::EvalError::EqualThresholds));
+        assert_eq!(utils::evaluate_opt(f64::NAN, None::<f64>, None::<f64>), Err(utils::EvalError::NotANumber));
+        assert_eq!(utils::evaluate_opt(1, None::<f64>, Some(f64::NAN)), Err(utils::EvalError::NotANumber));
+        assert_eq!(utils::evaluate_opt(90, None::<i32>, Some(90)), Ok(check::CheckResult::from(2)));
+        // a single threshold is "above only", whatever the direction of the pair would be
+        assert_eq!(utils::evaluate_opt(15, Some(20), Some(10)).unwrap().state(), check::State::Warning);
+        assert_eq!(utils::evaluate_opt(15, Some(20), None::<i32>).unwrap().state(), check::State::OK);
+        assert_eq!(utils::evaluate_opt(15, None::<i32>, Some(10)).unwrap().state(), check::State::Critical);
+    }
+
+    #[test]
+    fn evaluate_directions() {
+        assert_eq!(utils::evaluate_above(5, 10, 10).state(), check::State::OK);
+        assert_eq!(utils::evaluate_above(10, 10, 10).state(), check::State::Critical);
+        assert_eq!(utils::evaluate_below(15, 20, 10).state(), check::State::Warning);
+        assert_eq!(utils::evaluate_below(25, 20, 10).state(), check::State::OK);
+        let cr = utils::evaluate_below(5, 20, f64::NAN);
+        assert_eq!(cr.to_string(), "UNKNOWN - threshold is not a number");
+        assert_eq!(utils::evaluate_above(5, f64::NAN, 10).state(), check::State::Unknown);
+    }
+
+    #[test]
+    fn evaluate_metric_set() {
+        let cr = utils::evaluate_metrics(&[]);
+        assert_eq!(cr.to_string(), "OK - 0 metrics ok");
+        let inside = thresholds::Thresholds::new().critical("@0:10".parse().unwrap());
+        let cr = utils::evaluate_metrics(&[
+            (check::Metric::unknown("a"), inside),
+            (check::Metric::numeric("b", 5), inside),
+        ]);
+        assert_eq!(cr.state(), check::State::Critical);
+        assert_eq!(cr.info(), Some("2 of 2 metrics alerting: b=5 (CRITICAL), a=U (UNKNOWN)"));
+        let cr = utils::evaluate_metrics(&[(check::Metric::numeric("b", 5), inside)]);
+        assert_eq!(cr.info(), Some("1 of 1 metric alerting: b=5 (CRITICAL)"));
+    }
+
+    #[test]
+    fn check_builder() {
+        assert_eq!(builder::CheckBuilder::new().finish().state(), check::State::Unknown);
+        let crit = thresholds::Thresholds::new().critical("10".parse().unwrap());
+        let cr = builder::CheckBuilder::new()
+            .value(check::Metric::numeric("a", 1), crit, "{label}={value} ({state}, crit {critical})")
+            .value(check::Metric::numeric("b", 2), crit, "{label}={value}")
+            .finish();
+        assert_eq!(cr.info(), Some("a=1 (OK, crit 10), b=2"));
+        assert_eq!(cr.long_output(), ["[OK] a=1 (OK, crit 10)", "[OK] b=2"]);
+    }
+
+    #[test]
+    fn perf_data_collection() {
+        let pd: check::PerfData = (1..=3).map(|i| check::Metric::numeric(format!("m{}", i), i)).collect();
+        let mut labels = Vec::new();
+        for metric in &pd {
+            labels.push(metric.to_string());
+        }
+        assert_eq!(labels.len(), 3);
+        assert_eq!(pd.clone().into_iter().count(), 3);
+        let cr = check::CheckResult::from(0).set_perf_data(pd).add_metric(check::Metric::numeric("m4", 4));
+        assert_eq!(cr.perf_data().map(check::PerfData::len), Some(4));
+        let pd: check::PerfData = (0..100).map(|i| check::Metric::numeric("m", i)).collect();
+        assert!(pd.capacity() >= 100 && pd.len() == 100);
+        let mut pd = check::PerfData::with_capacity(2);
+        pd.reserve(10);
+        assert!(pd.capacity() >= 10 && pd.is_empty());
+
+        let cr = check::CheckResult::from(0)
+            .add_metric(check::Metric::new("b", 1))
+            .add_metric(check::Metric::new("a", 2))
+            .add_metric(check::Metric::new("b", 3));
+        assert_eq!(cr.clone().sort_perf_data().to_string(), "OK | 'a'=2;;;; 'b'=1;;;; 'b'=3;;;; ");
+        assert_eq!(check::CheckResult::from(0).sort_perf_data().perf_data(), None);
+        assert!(matches!(check::Metric::from_static("static", "text").label, std::borrow::Cow::Borrowed(_)));
+        assert!(matches!(check::Metric::new("owned", 1).label, std::borrow::Cow::Owned(_)));
+        assert_eq!(runner::Runner::new(move || Ok(cr.clone())).execute().to_string(), "OK | 'b'=1;;;; 'a'=2;;;; 'b'=3;;;; ");
+    }
+
+    #[test]
+    fn metric_dedup() {
+        use check::{DedupPolicy, Metric, PerfData};
+        let mut pd = PerfData::from_metrics(vec![Metric::new("a", 1), Metric::new("a", 2), Metric::new("a_2", 3), Metric::new("a", 4)]);
+        pd.dedup(DedupPolicy::Suffix).unwrap();
+        assert_eq!(pd.to_string(), "'a'=1;;;; 'a_3'=2;;;; 'a_2'=3;;;; 'a_4'=4;;;; ");
+        assert_eq!(pd.dedup(DedupPolicy::Error), Ok(()));
+
+        let plugin = || -> Result<check::CheckResult, runner::Error> {
+            Ok(check::CheckResult::ok("fine").add_metric(Metric::new("b", 1)).add_metric(Metric::new("b", 2)))
+        };
+        let cr = runner::Runner::new(plugin).dedup_metrics(DedupPolicy::Error).execute();
+        assert_eq!(cr.state(), check::State::Unknown);
+        let cr = runner::Runner::new(plugin).dedup_metrics(DedupPolicy::KeepFirst).execute();
+        assert_eq!(cr.to_string(), "OK - fine | 'b'=1;;;; ");
+        assert_eq!(check::CheckResult::ok("no metrics").dedup_perf_data(DedupPolicy::Error).state(), check::State::OK);
+    }
+
+    #[test]
+    fn metric_groups() {
+        let pd: check::PerfData = ["eth0", "eth\"1"]
+            .iter()
+            .map(|i| group::group(*i).metric("rx", 2).uom("KB"))
+            .chain(Some(check::Metric::new("::odd", 1).uom("c")))
+            .collect();
+        assert_eq!(pd.iter().map(|m| m.group()).collect::<Vec<_>>(), [Some("eth0"), Some("eth\"1"), None]);
+        assert_eq!(pd.to_prometheus(), "# HELP rx_bytes rx\n# TYPE rx_bytes gauge\n\
+            rx_bytes{group=\"eth0\"} 2000\nrx_bytes{group=\"eth\\\"1\"} 2000\n\
+            # HELP ::odd ::odd\n# TYPE ::odd counter\n::odd 1\n");
+        assert!(pd.to_openmetrics().contains("rx_bytes{group=\"eth0\"} 2000\n"));
+        assert_eq!(pd.to_influx("ifaces", &[]).lines().next(), Some("ifaces,group=eth0,metric=rx,unit=KB value=2"));
+        assert_eq!(pd.iter().map(check::Metric::label).next_back(), Some("::odd"));
+    }
+
+    #[test]
+    fn service_prefix() {
+        let cr = check::CheckResult::critical("down").set_service("HTTP").add_metric(check::Metric::new("time", 1));
+        assert_eq!(cr.to_string(), "HTTP CRITICAL - down | 'time'=1;;;; ");
+        assert_eq!(cr.to_string().parse::<check::CheckResult>(), Ok(cr.clone()));
+        assert!(cr.to_json().starts_with(r#"{"state":"CRITICAL","exit_code":2"#));
+        assert_eq!("DISK fine".parse::<check::CheckResult>(),
+            Err(parse::ParseError::MissingState(String::from("DISK fine"))));
+    }
+
+    #[test]
+    fn perf_data_after_long_output() {
+        let metrics: check::PerfData = (0..3).map(|i| check::Metric::new(format!("m{}", i), i)).collect();
+        let cr = check::CheckResult::ok("fine").set_perf_data(metrics).set_first_line_limit(10);
+        assert_eq!(cr.to_string(), "OK - fine\n| 'm0'=0;;;;\n'm1'=1;;;;\n'm2'=2;;;;");
+        let parsed: check::CheckResult = cr.to_string().parse().unwrap();
+        assert_eq!(parsed.perf_data(), cr.perf_data());
+        assert!(parsed.long_output().is_empty());
+
+        let cr = cr.set_first_line_limit(1024).add_long_output("details");
+        assert_eq!(cr.to_string(), "OK - fine | 'm0'=0;;;; 'm1'=1;;;; 'm2'=2;;;; \ndetails");
+    }
+
+    #[test]
+    fn output_truncation() {
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}", i)).collect();
+        let cr = check::CheckResult::ok("fine")
+            .add_metric(check::Metric::new("lines", 100))
+            .set_long_output(lines.clone())
+            .set_max_output_bytes(200);
+        let output = cr.to_string();
+        assert!(output.len() <= 200);
+        assert!(output.starts_with("OK - fine | 'lines'=100;;;; \nline 0\n"));
+        assert!(output.ends_with("lines omitted)"));
+
+        // nothing is truncated if the output fits
+        let cr = cr.set_max_output_bytes(4096);
+        assert_eq!(cr.to_string().lines().count(), 101);
+
+        // the first line is kept even if it exceeds the limit
+        let cr = check::CheckResult::ok("fine").set_long_output(lines).set_max_output_bytes(5);
+        assert_eq!(cr.to_string(), "OK - fine\n(output truncated, 100 lines omitted)");
+
+        let plugin = || -> Result<check::CheckResult, runner::Error> {
+            Ok(check::CheckResult::ok("fine").set_long_output(vec![String::from("a"), String::from("b")]))
+        };
+        let cr = runner::Runner::new(plugin).max_output_bytes(12).execute();
+        assert_eq!(cr.to_string(), "OK - fine\n(output truncated, 2 lines omitted)");
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn captured_logs() {
+        use log::{Level, Log, Record};
+        let logger = logging::Logger::new().filter("my_check=debug").capture();
+        let record = |level, msg| {
+            logger.log(&Record::builder().target("my_check").level(level).args(format_args!("{}", msg)).build())
+        };
+        record(Level::Warn, "slow response");
+        record(Level::Debug, "GET /health");
+        record(Level::Trace, "headers: none");
+
+        let cr = logging::attach_captured(check::CheckResult::ok("fine"));
+        assert_eq!(cr.clone().to_string(), "OK - fine");
+        assert_eq!(cr.clone().set_verbosity(1).to_string(), "OK - fine\n[WARN my_check] slow response");
+        assert_eq!(cr.set_verbosity(3).to_string(), "OK - fine\n[WARN my_check] slow response\n[DEBUG my_check] GET /health");
+        assert_eq!(logging::attach_captured(check::CheckResult::ok("fine")).set_verbosity(3).to_string(), "OK - fine");
+
+        // concurrent checks only get their own records
+        let logger = std::sync::Arc::new(logger);
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let logger = std::sync::Arc::clone(&logger);
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        logger.log(&Record::builder().target("my_check").level(Level::Info).args(format_args!("thread {}", i)).build());
+                        let cr = logging::attach_captured(check::CheckResult::ok("fine")).set_verbosity(1);
+                        assert_eq!(cr.to_string(), format!("OK - fine\n[INFO my_check] thread {}", i));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn span_diagnostics() {
+        use tracing_subscriber::layer::SubscriberExt;
+        let diagnostics = diagnostics::Diagnostics::new();
+        let subscriber = tracing_subscriber::registry().with(diagnostics.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            for host in &["web01", "web02"] {
+                let _query = tracing::info_span!("query", host).entered();
+                tracing::warn!(target: "check_web", host, "slow response");
+            }
+            tracing::trace_span!("parse").in_scope(|| tracing::trace!(target: "check_web", lines = 3));
+        });
+
+        let cr = diagnostics.attach(check::CheckResult::ok("fine"));
+        let labels: Vec<_> = cr.perf_data().unwrap().iter().map(|m| &*m.label).collect();
+        assert_eq!(labels, vec!["query_duration", "parse_duration"]);
+        assert_eq!(cr.long_output().len(), 2);
+        let output = cr.set_verbosity(3).to_string();
+        assert!(output.contains("\n[WARN check_web] slow response host=web01\n[WARN check_web] slow response host=web02\n[TRACE check_web] lines=3"));
+        assert!(diagnostics.attach(check::CheckResult::ok("fine")).perf_data().is_none());
+    }
+
+    #[test]
+    fn colored_output() {
+        let cr = check::CheckResult::critical("2 disks full")
+            .set_service("DISK")
+            .add_metric(check::Metric::new("/", 95).uom("%").warning("80").critical("90"))
+            .add_metric(check::Metric::new("/var", 85).uom("%").warning("80").critical("90"))
+            .add_metric(check::Metric::new("/tmp", 5).uom("%").warning("80").critical("90"))
+            .add_long_output("/ and /var are filling up")
+            .set_first_line_limit(60);
+        assert_eq!(cr.to_colored_string(), "DISK \x1b[31mCRITICAL\x1b[0m - 2 disks full \
+            | \x1b[31m'/'=95%;80;90;;\x1b[0m \n/ and /var are filling up | \x1b[33m'/var'=85%;80;90;;\x1b[0m\n'/tmp'=5%;80;90;;");
+        let stripped = cr.to_colored_string().replace("\x1b[31m", "").replace("\x1b[33m", "").replace("\x1b[0m", "");
+        assert_eq!(stripped, cr.to_string());
+
+        // state names in the service, info text or labels are not colored
+        let cr = check::CheckResult::warning("count is OK\nreported 'x'=5;2;;; before")
+            .set_service("DISK WARNING")
+            .add_metric(check::Metric::new("CRITICAL", 1).warning("2"))
+            .add_metric(check::Metric::new("x", 5).warning("2"));
+        assert_eq!(cr.to_colored_string(), "DISK WARNING \x1b[33mWARNING\x1b[0m - count is OK \
+            | 'CRITICAL'=1;2;;; \x1b[33m'x'=5;2;;;\x1b[0m \nreported 'x'=5;2;;; before");
+    }
+
+    #[test]
+    fn extra_opts() {
+        let ini = std::env::temp_dir().join(format!("icingaplugin_extra_opts_{}.ini", std::process::id()));
+        std::fs::write(&ini, "# shared\n[defaults]\ntimeout=20\n[check_web]\nw = 1 \n\n[defaults]\nverbose\n").unwrap();
+        let args = |args: &[&str]| extra_opts::expand(args.iter().copied()).map(|a| {
+            a.into_iter().map(|a| a.into_string().unwrap()).collect::<Vec<_>>()
+        });
+
+        let spec = format!("--extra-opts=@{}", ini.display());
+        assert_eq!(args(&["/usr/lib/nagios/plugins/check_web", &spec, "-w", "2"]).unwrap(),
+            vec!["/usr/lib/nagios/plugins/check_web", "-w", "1", "-w", "2"]);
+        let spec = format!("--extra-opts=defaults@{}", ini.display());
+        assert_eq!(args(&["check_web", &spec, "-H", "web01"]).unwrap(),
+            vec!["check_web", "--timeout=20", "--verbose", "-H", "web01"]);
+        assert_eq!(args(&["check_web", "-H", "web01"]).unwrap(), vec!["check_web", "-H", "web01"]);
+
+        let spec = format!("--extra-opts=missing@{}", ini.display());
+        assert!(matches!(args(&["check_web", &spec]), Err(extra_opts::ExtraOptsError::MissingSection(..))));
+        #[cfg(feature = "cli")]
+        {
+            let spec = format!("--extra-opts=defaults@{}", ini.display());
+            let cmd = cli::standard_args(clap::Command::new("check_web"));
+            let (args, _) = cli::parse_from(cmd.clone(), ["check_web", &spec]);
+            assert_eq!(args.timeout, std::time::Duration::from_secs(20));
+            assert_eq!(args.verbose, 1);
+            let (args, _) = cli::parse_from(cmd, ["check_web", &spec, "-t", "5"]);
+            assert_eq!(args.timeout, std::time::Duration::from_secs(5));
+        }
+
+        std::fs::write(&ini, "[check_web]\n[broken\n").unwrap();
+        let spec = format!("--extra-opts=@{}", ini.display());
+        assert!(matches!(args(&["check_web", &spec]), Err(extra_opts::ExtraOptsError::Syntax(_, 2))));
+        std::fs::remove_file(ini).unwrap();
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn config_layers() {
+        #[derive(Debug, Default, serde::Deserialize, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct Config {
+            warning: Option<String>,
+            timeout: Option<u64>,
+        }
+
+        let path = std::env::temp_dir().join(format!("icingaplugin_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "warning = \"80\"\ntimeout = 30\n").unwrap();
+        let file: Config = config::load_optional(Some(&path), "ICINGAPLUGIN_TEST_CONFIG").unwrap();
+        assert_eq!(file, Config { warning: Some(String::from("80")), timeout: Some(30) });
+        let none: Config = config::load_optional(None, "ICINGAPLUGIN_TEST_CONFIG").unwrap();
+        assert_eq!(none, Config::default());
+
+        std::env::set_var("ICINGAPLUGIN_TEST_TIMEOUT", "20");
+        assert_eq!(config::layered(None, "ICINGAPLUGIN_TEST_TIMEOUT", file.timeout).unwrap(), Some(20));
+        assert_eq!(config::layered(Some(5), "ICINGAPLUGIN_TEST_TIMEOUT", file.timeout).unwrap(), Some(5));
+        assert_eq!(config::layered(None, "ICINGAPLUGIN_TEST_WARNING", file.warning).unwrap().as_deref(), Some("80"));
+
+        std::fs::write(&path, "critical = 90\n").unwrap();
+        let err = config::load::<Config>(&path).unwrap_err();
+        assert!(matches!(err, config::ConfigError::Parse(..)));
+        assert_eq!(check::CheckResult::from(error::PluginError::from(err)).state(), check::State::Unknown);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn cli_env_layer() {
+        std::env::set_var("CHECK_ENV_TEST_WARNING", "10:");
+        std::env::set_var("CHECK_ENV_TEST_CRITICAL", "everything");
+        std::env::set_var("NAGIOS_HOSTNAME", "web01");
+        let cmd = cli::standard_args(clap::Command::new("check_env-test"));
+        let matches = cmd.clone().try_get_matches_from(["check_env-test", "-c", "5:"]).unwrap();
+        let args = cli::StandardArgs::from_matches(&matches);
+        assert_eq!(args.warning.unwrap().to_string(), "10:");
+        assert_eq!(args.critical.unwrap().to_string(), "5:");
+        assert_eq!(args.hostname.as_deref(), Some("web01"));
+        assert!(cmd.try_get_matches_from(["check_env-test"]).is_err());
+    }
+
+    #[test]
+    fn secrets_redacted() {
+        let path = std::env::temp_dir().join(format!("icingaplugin_secret_{}", std::process::id()));
+        std::fs::write(&path, "s3cr3t-pa55\n").unwrap();
+        let password = secret::Secret::from_file(&path).unwrap();
+        assert_eq!(password.expose(), "s3cr3t-pa55");
+        assert_eq!(password.to_string(), "***");
+        std::fs::remove_file(path).unwrap();
+
+        let plugin = move || -> Result<check::CheckResult, runner::Error> {
+            Err(format!("authentication failed for user monitoring with password {}", password.expose()).into())
+        };
+        let cr = runner::Runner::new(plugin).execute();
+        assert_eq!(cr.to_string(), "UNKNOWN - authentication failed for user monitoring with password ***");
+        assert!(!cr.to_json().contains("s3cr3t-pa55"));
+
+        assert!(matches!(secret::Secret::from_env("ICINGAPLUGIN_TEST_UNSET"), Err(secret::SecretError::MissingVar(_))));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn icinga2_command_dump() {
+        let cmd = cli::standard_args(clap::Command::new("check_http-api"));
+        let dump = cli::icinga2_command(&cmd);
+        assert!(dump.starts_with("object CheckCommand \"http-api\" {\n\tcommand = [ PluginDir + \"/check_http-api\" ]\n"));
+        assert!(dump.contains("\t\t\"-c\" = {\n\t\t\tvalue = \"$http_api_critical$\"\n\t\t\tdescription = \"Critical threshold\"\n\t\t}\n"));
+        assert!(dump.contains("\t\t\"-v\" = {\n\t\t\tset_if = \"$http_api_verbose$\"\n"));
+        assert!(!dump.contains("extra"));
+        assert!(!dump.contains("dump"));
+    }
+
+    #[test]
+    fn plugin_info_from_cargo() {
+        let info = plugin_info!();
+        assert_eq!(info.name(), "icingaplugin-rs");
+        assert_eq!(info.license(), Some("GPL-2.0-or-later"));
+        assert_eq!(info.author(), None);
+        assert!(runner::version_requested(["-V"]));
+        assert!(runner::version_requested(["--version"]));
+        assert!(!runner::version_requested(["--label", "-V"]));
+        assert!(!runner::version_requested(Vec::<String>::new()));
+        assert_eq!(info.banner(), format!("icingaplugin-rs v{} (https://github.com/NETWAYS/rust-check)\n\
+            License: GPL-2.0-or-later", env!("CARGO_PKG_VERSION")));
+        assert!(info.to_json().starts_with("{\"name\":\"icingaplugin-rs\","));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_plugin_args() {
+        use cli::PluginArgs;
+
+        #[derive(Debug, PluginArgs)]
+        struct Args {
+            #[hostname]
+            host: String,
+            #[verbose]
+            verbose: u8,
+            /// Path to check
+            #[arg(long = "url-path", env = "CHECK_DERIVE_TEST_PATH")]
+            path: Option<String>,
+        }
+
+        std::env::set_var("CHECK_DERIVE_TEST_PATH", "/health");
+        let args = Args::try_parse_from(["check_web", "-H", "web01", "-vv"]).unwrap();
+        assert_eq!((args.host.as_str(), args.verbose, args.path.as_deref()), ("web01", 2, Some("/health")));
+        let args = Args::try_parse_from(["check_web", "-H", "web01", "--url-path", "/"]).unwrap();
+        assert_eq!(args.path.as_deref(), Some("/"));
+        assert!(Args::try_parse_from(["check_web", "-H", "web01", "--url-path"]).is_err());
+        assert!(cli::icinga2_command(&Args::command()).contains("\"--url-path\" = {\n\t\t\tvalue = \"$icingaplugin_rs_path$\""));
+    }
+
+    #[test]
+    fn pipeline_layers() {
+        use pipeline::{Context, Evaluation, Pipeline, Resource, Summary};
+
+        struct Mounts;
+
+        impl Resource for Mounts {
+            fn probe(&self) -> Result<Vec<check::Metric>, error::PluginError> {
+                Ok(vec![check::Metric::new("/", 42).uom("%"), check::Metric::new("/var", 97).uom("%")])
+            }
+        }
+
+        struct Usage;
+
+        impl Summary for Usage {
+            fn problem(&self, evaluations: &[Evaluation]) -> String {
+                let worst = check::State::worst(evaluations.iter().map(Evaluation::state));
+                format!("{} filesystems checked, worst is {}", evaluations.len(), worst)
+            }
+
+            fn verbose(&self, _evaluations: &[Evaluation]) -> Vec<String> {
+                Vec::new()
+            }
+        }
+
+        let thresholds = thresholds::Thresholds::new().critical("90".parse().unwrap());
+        assert_eq!(thresholds.evaluate(&check::Metric::new("/var", 97)).state(), check::State::Critical);
+        assert_eq!(Mounts.probe().unwrap().len(), 2);
+
+        let cr = Pipeline::new().resource(Mounts).default_context(thresholds).evaluate();
+        assert_eq!(cr.to_string(), "CRITICAL - 1 of 2 metrics alerting: /var=97% (CRITICAL) | '/'=42%;;90;; '/var'=97%;;90;; ");
+        assert_eq!(cr.set_verbosity(1).to_string().lines().skip(1).collect::<Vec<_>>(), vec!["[OK] /=42%", "[CRITICAL] /var=97%"]);
+        let cr = Pipeline::new().resource(Mounts).default_context(thresholds).summary(Usage).evaluate();
+        assert_eq!(cr.set_verbosity(3).to_string(), "CRITICAL - 2 filesystems checked, worst is CRITICAL | '/'=42%;;90;; '/var'=97%;;90;; ");
+
+        let failing = || -> Result<Vec<check::Metric>, error::PluginError> { Err(error::PluginError::Auth(String::from("bad token"))) };
+        let cr = Pipeline::new().resource(Mounts).resource(failing).evaluate();
+        assert_eq!(cr.to_string(), "UNKNOWN - authentication failed: bad token");
+
+        let cr = Pipeline::new().evaluate();
+        assert_eq!(cr.perf_data(), None);
+        assert_eq!(cr.to_string(), "OK - 0 metrics ok");
+        let cr = Pipeline::new().resource(|| Ok(Vec::new())).evaluate();
+        assert_eq!(cr.to_string(), "OK - 0 metrics ok");
+    }
+
+    #[test]
+    fn closure_contexts() {
+        use pipeline::Pipeline;
+
+        let read_only = |m: &check::Metric| if m.label == "/var" { check::State::Critical } else { check::State::OK };
+        let crit = thresholds::Thresholds::new().critical("90".parse().unwrap());
+        let cr = builder::CheckBuilder::new()
+            .value(check::Metric::numeric("/", 42), crit, "{label} {state}")
+            .value(check::Metric::numeric("/var", 12), crit, "{label} {state}")
+            .context("/var", |_| check::State::Warning)
+            .context("/var", read_only)
+            .finish();
+        assert_eq!(cr.state(), check::State::Critical);
+        assert_eq!(cr.long_output(), ["[OK] / OK", "[CRITICAL] /var CRITICAL"]);
+        let builder = builder::CheckBuilder::new().context("/", read_only);
+        assert_eq!(builder, builder.clone());
+        assert_ne!(builder, builder::CheckBuilder::new().context("/", read_only));
+        assert_eq!(format!("{:?}", builder), r#"CheckBuilder { items: [], contexts: ["/"], ok_message: None }"#);
+
+        let mounts = || Ok(vec![check::Metric::new("/", 42), check::Metric::new("/var", 12)]);
+        let cr = Pipeline::new().resource(mounts).default_context(crit).context("/var", read_only).evaluate();
+        assert_eq!(cr.to_string(), "CRITICAL - 1 of 2 metrics alerting: /var=12 (CRITICAL) | '/'=42;;90;; '/var'=12;;;; ");
+    }
+
+    #[test]
+    fn check_assertions() {
+        use testing::CheckAssert;
+
+        let check = || -> Result<check::CheckResult, runner::Error> {
+            Ok(check::CheckResult::warning("2 of 3 replicas lagging | with a pipe")
+                .add_metric(check::Metric::new("db02", 12).uom("s"))
+                .add_long_output("db02 lags 12s"))
+        };
+        let assert = assert_check!(check, check::State::Warning)
+            .first_line_contains("replicas lagging")
+            .long_output_contains("lags 12s")
+            .metric("db02", 12)
+            .metric_with("db02", |m| m.uom.as_deref() == Some("s"));
+        assert_eq!(assert.result().state(), check::State::Warning);
+        assert!(std::panic::catch_unwind(|| assert.clone().metric("db02", 12.0)).is_err());
+        assert!(std::panic::catch_unwind(|| assert.clone().exit_code(2)).is_err());
+        assert!(std::panic::catch_unwind(|| CheckAssert::from(check::CheckResult::ok("fine")).metric("db02", 12)).is_err());
+
+        let failing = || -> Result<check::CheckResult, runner::Error> { Err("connection refused".into()) };
+        assert_check!(failing, check::State::Unknown, "UNKNOWN - connection refused").exit_code(3);
+    }
+
+    #[cfg(feature = "windows-service")]
+    #[test]
+    fn windows_service_status() {
+        use probes::windows_service::{ServiceState, ServiceStatus, StartType, WindowsService};
+
+        assert_eq!(ServiceState::from_code(4), Some(ServiceState::Running));
+        assert_eq!(ServiceState::from_code(8), None);
+        assert_eq!(StartType::from_code(StartType::Disabled.code()), Some(StartType::Disabled));
+
+        let status = ServiceStatus {
+            name: String::from("W32Time"),
+            display_name: String::from("W32Time"),
+            state: ServiceState::StartPending,
+            start_type: StartType::Manual,
+            started: None,
+        };
+        testing::CheckAssert::new(status.to_check_result(&clock::MockClock::default()))
+            .state(check::State::Warning)
+            .first_line("WARNING - W32Time is starting, start type manual")
+            .metric("start_type", 3)
+            .metric("uptime", check::Value::Unknown);
+
+        #[cfg(not(windows))]
+        assert_check!(WindowsService::new("Spooler"), check::State::Unknown,
+            "UNKNOWN - the Windows service probe is not supported on this platform");
+    }
+
+    #[cfg(feature = "systemd")]
+    #[test]
+    fn systemd_unit_status() {
+        use probes::systemd::{ActiveState, SystemdUnit, UnitStatus};
+        use probes::ProbeError;
+
+        let failed: UnitStatus = "Id=backup.service\nDescription=backup.service\nLoadState=loaded\n\
+            ActiveState=failed\nSubState=failed\nNRestarts=0\nActiveEnterTimestamp=\n".parse().unwrap();
+        assert_eq!(failed.active_state, ActiveState::Failed);
+        assert_eq!(failed.active_since, None);
+        let unit = SystemdUnit::new("backup.service").clock(clock::MockClock::default());
+        testing::CheckAssert::new(unit.evaluate(&failed))
+            .state(check::State::Critical)
+            .first_line("CRITICAL - backup.service is failed (failed)")
+            .metric("restarts", 0)
+            .metric("uptime", check::Value::Unknown);
+
+        let recent: UnitStatus = "Id=sshd.service\nLoadState=loaded\nActiveState=active\nSubState=running\n\
+            ActiveEnterTimestamp=@1699999990\nUnknownProperty=1\n".parse().unwrap();
+        let unit = unit.uptime(thresholds::Thresholds::new().critical("60:".parse().unwrap()));
+        testing::CheckAssert::new(unit.evaluate(&recent))
+            .state(check::State::Critical)
+            .metric_with("uptime", |m| m.to_string() == "'uptime'=10s;;60:;0;");
+        let restarted: UnitStatus = "Id=sshd.service\nLoadState=loaded\nActiveState=active\nSubState=running\n\
+            NRestarts=1\n".parse().unwrap();
+        assert_eq!(unit.evaluate(&restarted).info(), Some("sshd.service is active (running), restarted 1 time"));
+
+        assert!(matches!("Id=x\nActiveState=active\n".parse::<UnitStatus>(), Err(ProbeError::Parse(_))));
+        assert!(matches!("Id=x\nLoadState=loaded\nActiveState=sleeping\n".parse::<UnitStatus>(), Err(ProbeError::Parse(_))));
+    }
+
+    #[cfg(feature = "process")]
+    #[test]
+    fn process_count() {
+        use probes::process::{processes, ProcessInfo, Processes};
+
+        let worker = |pid: u32, config: &str| ProcessInfo {
+            pid,
+            name: String::from("php-fpm8.2"),
+            args: vec![String::from("/usr/sbin/php-fpm8.2"), String::from("--fpm-config"), config.to_string()],
+            rss: 32 << 20,
+            cpu: 1.25,
+        };
+        let table = vec![worker(101, "/etc/php/www.conf"), worker(102, "/etc/php/www.conf"), worker(103, "/etc/php/api.conf")];
+        let check = Processes::new()
+            .name("php-fpm8.2")
+            .argument("www.conf")
+            .count(thresholds::Thresholds::new().warning("3:".parse().unwrap()));
+        let assert = testing::CheckAssert::new(check.evaluate(&table).set_verbosity(1))
+            .state(check::State::Warning)
+            .first_line("WARNING - 2 processes named 'php-fpm8.2' with argument 'www.conf', 64.0 MiB RSS")
+            .metric("procs", 2)
+            .metric("rss", 64u64 << 20)
+            .metric("cpu", 2.5);
+        assert!(assert.result().to_string().ends_with("\n102 php-fpm8.2: 32.0 MiB RSS, 1.2% CPU, /usr/sbin/php-fpm8.2 --fpm-config /etc/php/www.conf"));
+        assert_eq!(Processes::new().name("www.conf").evaluate(&table).state(), check::State::Critical);
+        let cr = Processes::new().name("php-fpm8.2").argument("api.conf").evaluate(&table);
+        assert_eq!(cr.info(), Some("1 process named 'php-fpm8.2' with argument 'api.conf', 32.0 MiB RSS"));
+
+        #[cfg(target_os = "linux")]
+        {
+            let table = processes().unwrap();
+            assert!(table.iter().any(|p| p.pid == std::process::id() && p.rss > 0));
+            let own = table.iter().find(|p| p.pid == std::process::id()).unwrap();
+            assert_eq!(Processes::new().name(own.name.clone()).evaluate(std::slice::from_ref(own)).state(), check::State::Critical);
+        }
+    }
+
+    #[cfg(feature = "disk")]
+    #[test]
+    fn disk_usage() {
+        use probes::disk::{Disks, FsUsage};
+
+        let usage = |mount: &str, used: u64, available: u64, inodes: u64| FsUsage {
+            mount: mount.into(),
+            fs_type: String::from("xfs"),
+            total: used + available,
+            used,
+            available,
+            inodes,
+            inodes_free: inodes / 2,
+        };
+        let usages = [usage("/", 6 << 30, 14 << 30, 1000), usage("/srv/data", 900 << 30, 100 << 30, 0)];
+        let disks = Disks::new().free(thresholds::Thresholds::new().warning(thresholds::Range::new(16e9, f64::INFINITY)));
+        testing::CheckAssert::new(disks.evaluate(&usages))
+            .state(check::State::Warning)
+            .first_line("WARNING - / 30.0% used, 14.0 GiB of 20.0 GiB free")
+            .long_output(&["[WARNING] / 30.0% used, 14.0 GiB of 20.0 GiB free, 50.0% inodes used",
+                "[OK] /srv/data 90.0% used, 100.0 GiB of 1000.0 GiB free"])
+            .metric_with("/::free", |m| m.to_string() == "'/::free'=15032385536B;16000000000:;;0;21474836480")
+            .no_metric("/srv/data::inodes_used_pct")
+            .metric("total::used", 906u64 << 30);
+        assert_eq!(Disks::new().evaluate(&usages).info(), Some("2 file systems, 906.0 GiB of 1020.0 GiB used"));
+
+        #[cfg(target_os = "linux")]
+        {
+            let root = Disks::new().mount("/").query().unwrap();
+            assert_eq!(root.len(), 1);
+            assert!(root[0].total > 0);
+            assert!(Disks::new().filter(filter::FilterSet::from_args(&[], &["*"], false).unwrap()).query().unwrap().is_empty());
+            let others = filter::FilterSet::from_args(&["/"], &[], false).unwrap();
+            assert!(Disks::new().filter(others).query().unwrap().iter().all(|u| u.mount == std::path::Path::new("/")));
+            assert!(matches!(Disks::new().mount("/does/not/exist").query(), Err(probes::ProbeError::NotFound(_))));
+        }
+    }
+
+    #[cfg(feature = "cpu")]
+    #[test]
+    fn cpu_usage() {
+        use probes::cpu::{Cpu, CpuTimes, CpuUsage};
+
+        let stat = "cpu  4705 150 1120 16250 520 0 30 10 0 0\ncpu0 2350 75 560 8125 260 0 15 5 0 0\nintr 1\n";
+        let first: CpuTimes = stat.parse().unwrap();
+        assert_eq!(first, CpuTimes { user: 4705, nice: 150, system: 1120, idle: 16250, iowait: 520, irq: 0, softirq: 30, steal: 10 });
+        let second = CpuTimes { user: 5105, nice: 250, system: 1220, idle: 16350, iowait: 520, steal: 110, ..first };
+        let usage = first.usage(&second);
+        assert_eq!((usage.user, usage.system, usage.idle, usage.steal), (62.5, 12.5, 12.5, 12.5));
+        assert_eq!(first.usage(&first), CpuUsage::default());
+        assert_eq!("cpu  1 2 3 4".parse::<CpuTimes>().map(|t| (t.idle, t.steal)).unwrap(), (4, 0));
+        assert!("cpu  1 x 3".parse::<CpuTimes>().is_err());
+
+        let cpu = Cpu::new().total(thresholds::Thresholds::new().critical("80".parse().unwrap()));
+        testing::CheckAssert::new(cpu.evaluate(&usage))
+            .state(check::State::Critical)
+            .first_line("CRITICAL - 87.5% CPU used, 0.0% iowait, 12.5% steal")
+            .metric_with("total", |m| m.to_string() == "'total'=87.5%;;80;0;100");
+
+        #[cfg(target_os = "linux")]
+        assert!(Cpu::new().interval(std::time::Duration::from_millis(20)).query().unwrap().total() <= 100.0);
+    }
+
+    #[cfg(feature = "uptime")]
+    #[test]
+    fn uptime_modes() {
+        use probes::uptime::{uptime, Uptime};
+        use std::time::Duration;
+
+        let day = |days: u64| Duration::from_secs(days * 86400);
+        let check = Uptime::new().rebooted(Some(Duration::from_secs(600)), None).up_longer(Some(day(30)), Some(day(60)));
+        testing::CheckAssert::new(check.evaluate(Duration::from_secs(59)))
+            .state(check::State::Warning)
+            .first_line("WARNING - up 59s")
+            .metric_with("uptime", |m| m.to_string() == "'uptime'=59s;600:2592000;5184000;0;");
+        assert_eq!(check.evaluate(day(1)).state(), check::State::OK);
+        assert_eq!(check.evaluate(day(45)).state(), check::State::Warning);
+        assert_eq!(check.evaluate(day(61)).state(), check::State::Critical);
+        assert_eq!(Uptime::new().evaluate(Duration::ZERO).state(), check::State::OK);
+
+        #[cfg(target_os = "linux")]
+        assert!(uptime().unwrap() > Duration::ZERO);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn interface_rates() {
+        use clock::Clock;
+        use probes::net::{InterfaceCounters, Interfaces};
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("interface_rates_{}", std::process::id()));
+        let clock = clock::MockClock::default();
+        let check = Interfaces::new().speed(10_000_000).state_file(dir.join("state.json")).clock(clock.clone());
+        let mut counters = vec![
+            InterfaceCounters { name: String::from("eth0"), ..Default::default() },
+            InterfaceCounters { name: String::from("eth1"), ..Default::default() },
+        ];
+
+        let mut state = persist::StateFile::load_from(dir.join("state.json")).unwrap().clock(clock.clone());
+        assert_eq!(check.evaluate(&counters, clock.now(), &mut state).perf_data(), None);
+        clock.advance(Duration::from_secs(10));
+        counters[0].rx_bytes = 1_250_000;
+        counters[1].tx_packets = 500;
+        counters[1].tx_errors = 20;
+        testing::CheckAssert::new(check.evaluate(&counters, clock.now(), &mut state))
+            .state(check::State::OK)
+            .first_line("OK - 2 interfaces ok")
+            .long_output(&["[OK] eth0 in 1.0 Mbit/s, out 0 bit/s, 10.0% utilization",
+                "[OK] eth1 in 0 bit/s, out 0 bit/s, 0.0% utilization, 2.0 errors/s"])
+            .metric("eth1::out_pps", 50.0)
+            .metric("eth1::out_errors", 2.0);
+
+        // a reset counter yields no rate until the next invocation
+        clock.advance(Duration::from_secs(10));
+        counters[0].rx_bytes = 0;
+        testing::CheckAssert::new(check.evaluate(&counters, clock.now(), &mut state))
+            .no_metric("eth0::in_bps")
+            .metric("eth1::in_bps", 0.0)
+            .first_line("OK - eth1 in 0 bit/s, out 0 bit/s, 0.0% utilization");
+
+        assert!(InterfaceCounters::parse_table("a\nb\n eth0: 1 2 x\n").is_err());
+        #[cfg(target_os = "linux")]
+        {
+            let cr = check.clone().interface("lo").check().unwrap();
+            assert_eq!(cr.state(), check::State::OK);
+            assert!(matches!(check.clone().interface("nonexistent0").check(), Err(probes::ProbeError::NotFound(_))));
+            let none = check.filter(filter::FilterSet::from_args(&["nonexistent*"], &[], false).unwrap());
+            assert!(none.query().unwrap().is_empty());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn file_checks() {
+        use probes::file::{FileCount, FileExists, FileSize, NewestFile};
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("file_checks_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("subdir.gz")).unwrap();
+        std::fs::write(dir.join("db.sql.gz"), vec![0; 100]).unwrap();
+        std::fs::write(dir.join("db.sql"), "").unwrap();
+        let modified = std::fs::metadata(dir.join("db.sql")).unwrap().modified().unwrap();
+
+        let clock = clock::MockClock::new(modified + Duration::from_secs(7200));
+        let check = NewestFile::new(&dir).age(thresholds::Thresholds::new().warning("3600".parse().unwrap())).clock(clock);
+        testing::CheckAssert::new(check.check().unwrap())
+            .state(check::State::Warning)
+            .metric("age", 7200u64)
+            .metric("files", 2u64);
+        testing::CheckAssert::new(check.clone().pattern("*.tar").check().unwrap())
+            .state(check::State::Critical)
+            .metric("files", 0u64);
+        assert!(matches!(NewestFile::new(dir.join("missing")).check(), Err(probes::ProbeError::NotFound(_))));
+
+        let cr = FileSize::new(dir.join("db.sql.gz")).size(thresholds::Thresholds::new().critical("1024:".parse().unwrap())).check().unwrap();
+        testing::CheckAssert::new(cr).state(check::State::Critical).metric("size", 100u64);
+
+        // directories do not count, even if their name matches
+        testing::CheckAssert::new(FileCount::new(dir.join("*.gz")).check().unwrap()).metric("files", 1u64);
+        testing::CheckAssert::new(FileCount::new(dir.join("db.*")).check().unwrap()).metric("files", 2u64);
+
+        assert_eq!(FileExists::new(dir.join("subdir.gz")).check().state(), check::State::OK);
+        assert_eq!(FileExists::new(dir.join("missing")).check().state(), check::State::Critical);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = dir.join("db.sql");
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert_eq!(FileExists::new(&path).mode(0o644).check().state(), check::State::OK);
+            assert_eq!(FileExists::new(&path).mode(0o600).check().to_string(),
+                format!("CRITICAL - file {} has mode 0644, expected 0600 | 'size'=0B;;;0; ", path.display()));
+        }
+
+        testing::CheckAssert::new(FileCount::new(dir.join("db.sq[a-m]*")).check().unwrap()).metric("files", 2u64);
+        testing::CheckAssert::new(FileCount::new(dir.join("db.sql[!.]*")).check().unwrap()).metric("files", 0u64);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "logfile")]
+    #[test]
+    fn logfile_rotation() {
+        use probes::logfile::LogFile;
+        use regex::Regex;
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("logfile_rotation_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&path).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+        let check = LogFile::new(&path)
+            .warning(Regex::new("WARN").unwrap())
+            .critical(Regex::new("ERROR").unwrap())
+            .exclude(Regex::new("harmless").unwrap())
+            .state_file(dir.join("state.json"));
+
+        assert!(matches!(check.check(), Err(probes::ProbeError::NotFound(_))));
+        append("ERROR before the first check\n");
+        testing::CheckAssert::new(check.check().unwrap()).state(check::State::OK).no_metric("lines");
+
+        // the line still being written is scanned by the next invocation
+        append("WARN low memory\nERROR harmless\nERROR disk");
+        testing::CheckAssert::new(check.check().unwrap())
+            .state(check::State::Warning)
+            .first_line("WARNING - 1 warning line in 2 new lines: WARN low memory")
+            .metric("lines", 2u64)
+            .metric("critical", 0u64);
+        append(" failed\n");
+        testing::CheckAssert::new(check.check().unwrap())
+            .state(check::State::Critical)
+            .long_output(&["[CRITICAL] ERROR disk failed"]);
+        testing::CheckAssert::new(check.check().unwrap()).state(check::State::OK).metric("lines", 0u64);
+
+        // the rest of the rotated file is scanned before the new one
+        append("WARN before rotation\n");
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        append("ERROR after rotation\n");
+        testing::CheckAssert::new(check.check().unwrap())
+            .first_line("CRITICAL - 1 critical line and 1 warning line in 2 new lines: ERROR after rotation")
+            .long_output(&["[WARNING] WARN before rotation", "[CRITICAL] ERROR after rotation"]);
+
+        // a truncated file is scanned from the beginning
+        std::fs::write(&path, "WARN\n").unwrap();
+        testing::CheckAssert::new(check.check().unwrap()).metric("lines", 1u64).metric("warning", 1u64);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn tcp_connect() {
+        use probes::tcp::Tcp;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        // echoes the payload, then closes the connection
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut buf = [0; 64];
+                let read = stream.read(&mut buf).unwrap();
+                stream.write_all(&buf[..read]).unwrap();
+            }
+        });
+        let check = Tcp::new("127.0.0.1", port).timeout(Duration::from_secs(5)).send("PING\r\n");
+        testing::CheckAssert::new(check.clone().expect("PING").check().unwrap())
+            .state(check::State::OK)
+            .metric_with("time", |m| m.to_string().ends_with("s;;;0;"));
+        testing::CheckAssert::new(check.expect("PONG").check().unwrap())
+            .state(check::State::Critical)
+            .first_line_contains(&format!("CRITICAL - connected to 127.0.0.1:{} in ", port))
+            .first_line_contains(", but response 'PING' does not contain 'PONG'");
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        testing::CheckAssert::new(Tcp::new("127.0.0.1", port).check().unwrap())
+            .state(check::State::Critical)
+            .no_metric("time");
+        assert!(matches!(Tcp::new("nonexistent.invalid", 22).check(), Err(probes::ProbeError::NotFound(_))));
+    }
+
+    #[cfg(feature = "proptest")]
+    mod round_trips {
+        use crate::check::{CheckResult, Metric, PerfData};
+        use crate::thresholds::Range;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn range(range: Range) {
+                prop_assert_eq!(range.to_string().parse::<Range>(), Ok(range));
+            }
+
+            #[test]
+            fn metric(metric: Metric) {
+                prop_assert_eq!(metric.to_string().parse::<Metric>(), Ok(metric));
+            }
+
+            #[test]
+            fn perf_data(pd: PerfData) {
+                prop_assert_eq!(pd.to_string().parse::<PerfData>(), Ok(pd));
+            }
+
+            #[test]
+            fn check_result(cr: CheckResult) {
+                prop_assert_eq!(cr.to_string().parse::<CheckResult>(), Ok(cr));
+            }
+        }
+    }
 }
@@ -0,0 +1,240 @@
+use crate::check::CheckResult;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::cell::RefCell;
+use std::mem;
+use std::sync::Mutex;
+
+thread_local! {
+    /// The records captured by a `Logger` in capture mode on this thread, with the verbosity
+    /// required to display them
+    static CAPTURED: RefCell<Vec<(u8, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A `log` backend for check plugins. Plugins must only ever write their `CheckResult` to stdout,
+/// so all log records are written to stderr and, if configured, appended to a file.
+#[derive(Debug)]
+pub struct Logger {
+    /// the directives deciding which records are emitted
+    filter: Filter,
+    /// an optional file every emitted record is appended to
+    file: Option<Mutex<File>>,
+    /// whether emitted records are captured for the long output instead of written to stderr
+    capture: bool,
+}
+
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Logger {
+    /// Creates a new `Logger` with its filter read from the `RUST_LOG` environment variable,
+    /// falling back to `warn` if it is not set
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::logging::Logger;
+    /// let logger = Logger::new();
+    /// ```
+    pub fn new() -> Self {
+        let spec = env::var("RUST_LOG").unwrap_or_else(|_| String::from("warn"));
+        Self {
+            filter: Filter::parse(&spec),
+            file: None,
+            capture: false,
+        }
+    }
+
+    /// Replaces the filter of the `Logger` using `RUST_LOG` syntax: a comma separated list of
+    /// `level`, `target` or `target=level` directives. The most specific target wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - the filter directives, e.g. `"warn,my_check=debug"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::logging::Logger;
+    /// use log::{Level, Log, MetadataBuilder};
+    /// let logger = Logger::new().filter("warn,my_check::net=trace,my_check::net::dns=off");
+    /// let meta = |target, level| MetadataBuilder::new().target(target).level(level).build();
+    /// assert!(logger.enabled(&meta("other", Level::Error)));
+    /// assert!(!logger.enabled(&meta("other", Level::Info)));
+    /// assert!(logger.enabled(&meta("my_check::net::tcp", Level::Trace)));
+    /// assert!(!logger.enabled(&meta("my_check::net::dns", Level::Error)));
+    /// assert!(!logger.enabled(&meta("my_check::network", Level::Info)));
+    /// ```
+    pub fn filter(mut self, spec: &str) -> Self {
+        self.filter = Filter::parse(spec);
+        self
+    }
+
+    /// Additionally appends every emitted record to the file at `path`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the log file, created if it does not exist
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.file = Some(Mutex::new(file));
+        Ok(self)
+    }
+
+    /// Captures emitted records instead of writing them to stderr, so `attach_captured()` can add
+    /// them to the `CheckResult` as debug lines. Records logged by library code used by the
+    /// plugin then show up in the verbose output: `error`, `warn` and `info` records with `-v`,
+    /// `debug` records with `-vv` and `trace` records with `-vvv`. The `Runner` and `run()`
+    /// attach the captured records automatically. Records are captured per thread, so checks
+    /// running concurrently only get their own records, and records logged by threads a check
+    /// spawns itself are not attached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::logging::{attach_captured, Logger};
+    /// use icingaplugin_rs::runner::{Error, Runner};
+    ///
+    /// Logger::new().filter("check_db=debug").capture().init().unwrap();
+    /// log::info!(target: "check_db", "connected to db01");
+    /// log::debug!(target: "check_db", "running SELECT 1");
+    ///
+    /// let cr = attach_captured(CheckResult::ok("database is up")).set_verbosity(1);
+    /// assert_eq!(cr.to_string(), "OK - database is up\n[INFO check_db] connected to db01");
+    ///
+    /// let plugin = || -> Result<CheckResult, Error> {
+    ///     log::warn!(target: "check_db", "slow query");
+    ///     Ok(CheckResult::ok("database is up"))
+    /// };
+    /// let cr = Runner::new(plugin).verbosity(1).execute();
+    /// assert_eq!(cr.to_string(), "OK - database is up\n[WARN check_db] slow query");
+    /// ```
+    pub fn capture(mut self) -> Self {
+        self.capture = true;
+        self
+    }
+
+    /// Installs the `Logger` as the global `log` backend. Fails if a backend is already installed.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.filter.max_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("[{} {}] {}\n", record.level(), record.target(), record.args());
+        if self.capture {
+            let verbosity = match record.level() {
+                Level::Error | Level::Warn | Level::Info => 1,
+                Level::Debug => 2,
+                Level::Trace => 3,
+            };
+            CAPTURED.with(|captured| captured.borrow_mut().push((verbosity, line.trim_end().to_string())));
+        } else {
+            let _ = io::stderr().write_all(line.as_bytes());
+        }
+        if let Some(file) = &self.file {
+            if let Ok(mut f) = file.lock() {
+                let _ = f.write_all(line.as_bytes());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+        if let Some(file) = &self.file {
+            if let Ok(mut f) = file.lock() {
+                let _ = f.flush();
+            }
+        }
+    }
+}
+
+
+/// Adds the records captured on the current thread since the last call as debug lines to `cr`,
+/// see `Logger::capture()`
+///
+/// # Arguments
+///
+/// * `cr` - the result of the check
+pub fn attach_captured(cr: CheckResult) -> CheckResult {
+    let captured = CAPTURED.with(|captured| mem::take(&mut *captured.borrow_mut()));
+    captured.into_iter().fold(cr, |cr, (verbosity, line)| cr.add_debug(verbosity, line))
+}
+
+
+/// A parsed set of `RUST_LOG` style directives
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Filter {
+    /// the level applied to targets without a matching directive
+    default: LevelFilter,
+    /// target specific levels
+    directives: Vec<(String, LevelFilter)>,
+}
+
+
+impl Filter {
+    /// Parses `spec`, silently ignoring directives with an unknown level
+    fn parse(spec: &str) -> Self {
+        let mut filter = Filter {
+            default: LevelFilter::Off,
+            directives: Vec::new(),
+        };
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.directives.push((target.trim().to_string(), level));
+                    }
+                }
+                None => match directive.parse() {
+                    Ok(level) => filter.default = level,
+                    Err(_) => filter.directives.push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+
+        filter
+    }
+
+    /// Returns the level of the most specific directive matching `target`
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix
+                    || (target.starts_with(prefix.as_str()) && target[prefix.len()..].starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
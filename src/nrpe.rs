@@ -0,0 +1,673 @@
+use crate::check::{CheckResult, State};
+use crate::runner::{Plugin, Runner};
+use crate::utils::crc32;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(feature = "nrpe-tls")]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The packet type of requests
+pub(crate) const QUERY_PACKET: i16 = 1;
+/// The packet type of responses
+pub(crate) const RESPONSE_PACKET: i16 = 2;
+/// The size of the fixed buffer of version 2 packets
+const V2_BUFFER_SIZE: usize = 1024;
+/// The size of a version 3 header, up to and including the buffer length
+const V3_HEADER_SIZE: usize = 16;
+/// The command `check_nrpe` sends when invoked without `-c`, to check that the daemon is alive
+const VERSION_COMMAND: &str = "_NRPE_CHECK";
+/// The largest buffer accepted in version 3 packets, to guard against garbage lengths
+const V3_MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The versions of the NRPE protocol
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Version {
+    /// Fixed 1024 byte packets, spoken by all NRPE daemons and NSClient++
+    V2,
+    /// Variable length packets, supported since NRPE 3.0
+    V3,
+}
+
+
+/// A decoded NRPE packet
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Packet {
+    pub(crate) version: Version,
+    pub(crate) packet_type: i16,
+    pub(crate) result_code: i16,
+    pub(crate) buffer: String,
+}
+
+
+impl Packet {
+    /// Encodes the packet including its CRC32. Version 2 buffers longer than 1023 bytes are
+    /// truncated.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let data = self.buffer.as_bytes();
+        let mut packet = match self.version {
+            Version::V2 => {
+                let len = data.len().min(V2_BUFFER_SIZE - 1);
+                // the C struct is padded to a multiple of 4 bytes
+                let mut packet = vec![0u8; 10 + V2_BUFFER_SIZE + 2];
+                packet[10..10 + len].copy_from_slice(&data[..len]);
+                packet
+            }
+            Version::V3 => {
+                // NRPE 3 daemons expect at least the size of a version 2 buffer
+                let len = (data.len() + 1).max(V2_BUFFER_SIZE);
+                let mut packet = vec![0u8; V3_HEADER_SIZE + len];
+                packet[12..16].copy_from_slice(&(len as i32).to_be_bytes());
+                packet[V3_HEADER_SIZE..V3_HEADER_SIZE + data.len()].copy_from_slice(data);
+                packet
+            }
+        };
+
+        let version: i16 = match self.version {
+            Version::V2 => 2,
+            Version::V3 => 3,
+        };
+        packet[0..2].copy_from_slice(&version.to_be_bytes());
+        packet[2..4].copy_from_slice(&self.packet_type.to_be_bytes());
+        packet[8..10].copy_from_slice(&self.result_code.to_be_bytes());
+        let crc = crc32(&packet);
+        packet[4..8].copy_from_slice(&crc.to_be_bytes());
+        packet
+    }
+
+    /// Reads and verifies a single packet from `reader`
+    pub(crate) fn decode<R: Read>(reader: &mut R) -> Result<Packet, NrpeError> {
+        let mut packet = vec![0u8; 10];
+        reader.read_exact(&mut packet)?;
+        let version = match i16::from_be_bytes([packet[0], packet[1]]) {
+            2 => Version::V2,
+            3 => Version::V3,
+            v => return Err(NrpeError::Protocol(format!("unsupported packet version {}", v))),
+        };
+
+        let buffer_start = match version {
+            Version::V2 => {
+                packet.resize(10 + V2_BUFFER_SIZE + 2, 0);
+                reader.read_exact(&mut packet[10..])?;
+                10
+            }
+            Version::V3 => {
+                packet.resize(V3_HEADER_SIZE, 0);
+                reader.read_exact(&mut packet[10..])?;
+                let len = i32::from_be_bytes([packet[12], packet[13], packet[14], packet[15]]);
+                if len < 0 || len as usize > V3_MAX_BUFFER_SIZE {
+                    return Err(NrpeError::Protocol(format!("invalid buffer length {}", len)));
+                }
+                packet.resize(V3_HEADER_SIZE + len as usize, 0);
+                reader.read_exact(&mut packet[V3_HEADER_SIZE..])?;
+                V3_HEADER_SIZE
+            }
+        };
+
+        let crc = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        packet[4..8].copy_from_slice(&[0; 4]);
+        if crc32(&packet) != crc {
+            return Err(NrpeError::Protocol(String::from("CRC mismatch")));
+        }
+
+        let buffer = &packet[buffer_start..];
+        let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        Ok(Packet {
+            version,
+            packet_type: i16::from_be_bytes([packet[2], packet[3]]),
+            result_code: i16::from_be_bytes([packet[8], packet[9]]),
+            buffer: String::from_utf8_lossy(&buffer[..end]).into_owned(),
+        })
+    }
+}
+
+
+/// Queries NRPE daemons like `check_nrpe` does, e.g. to build proxy plugins that evaluate the
+/// output of remote checks locally
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Nrpe {
+    /// the host the daemon runs on
+    host: String,
+    /// the port the daemon listens on, 5666 by default
+    port: u16,
+    /// the protocol version used for requests
+    version: Version,
+    /// the time connecting and each read or write may take
+    timeout: Duration,
+    /// whether the connection is secured with TLS
+    #[cfg(feature = "nrpe-tls")]
+    tls: bool,
+    /// the CA the daemon's certificate is verified against
+    #[cfg(feature = "nrpe-tls")]
+    ca_file: Option<PathBuf>,
+    /// the client certificate and its key, if the daemon requires one
+    #[cfg(feature = "nrpe-tls")]
+    client_cert: Option<(PathBuf, PathBuf)>,
+    /// whether the daemon's certificate is accepted without verification
+    #[cfg(feature = "nrpe-tls")]
+    insecure: bool,
+}
+
+
+impl Nrpe {
+    /// Creates a new `Nrpe` client for the daemon on `host`, port 5666, using protocol version 3
+    /// without TLS
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host name or address of the NRPE daemon
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::nrpe::{Nrpe, Version};
+    ///
+    /// let nrpe = Nrpe::new(String::from("db01.example.com")).version(Version::V2);
+    /// let cr = nrpe.check("check_load", &[]).unwrap();
+    /// ```
+    pub fn new<S: Into<String>>(host: S) -> Self {
+        Self {
+            host: host.into(),
+            port: 5666,
+            version: Version::V3,
+            timeout: Duration::from_secs(10),
+            #[cfg(feature = "nrpe-tls")]
+            tls: false,
+            #[cfg(feature = "nrpe-tls")]
+            ca_file: None,
+            #[cfg(feature = "nrpe-tls")]
+            client_cert: None,
+            #[cfg(feature = "nrpe-tls")]
+            insecure: false,
+        }
+    }
+
+    /// Sets the port the daemon listens on
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - the `server_port` of the daemon
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the protocol version. Daemons older than NRPE 3.0 and NSClient++ only understand
+    /// version 2.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - the protocol version used for requests
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the time connecting and each read or write may take
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the network timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Secures the connection with TLS, verifying the daemon's certificate against `ca_file`.
+    /// Anonymous Diffie-Hellman, which NRPE uses without certificates, is not supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `ca_file` - a PEM file containing the CA certificates
+    #[cfg(feature = "nrpe-tls")]
+    pub fn tls(mut self, ca_file: PathBuf) -> Self {
+        self.tls = true;
+        self.ca_file = Some(ca_file);
+        self
+    }
+
+    /// Secures the connection with TLS without verifying the daemon's certificate, which is
+    /// often self-signed
+    #[cfg(feature = "nrpe-tls")]
+    pub fn tls_insecure(mut self) -> Self {
+        self.tls = true;
+        self.insecure = true;
+        self
+    }
+
+    /// Authenticates with a client certificate, if the daemon requires one
+    ///
+    /// # Arguments
+    ///
+    /// * `cert` - a PEM file containing the certificate (chain)
+    /// * `key` - a PEM file containing the private key
+    #[cfg(feature = "nrpe-tls")]
+    pub fn client_cert(mut self, cert: PathBuf, key: PathBuf) -> Self {
+        self.client_cert = Some((cert, key));
+        self
+    }
+
+    /// Runs `command` with `args` on the daemon and returns its response
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - the name of a command defined in the daemon's configuration
+    /// * `args` - the arguments passed to the command, requires `dont_blame_nrpe`
+    pub fn query(&self, command: &str, args: &[&str]) -> Result<NrpeResponse, NrpeError> {
+        if command.contains('!') || args.iter().any(|a| a.contains('!')) {
+            return Err(NrpeError::InvalidQuery(String::from("arguments must not contain '!'")));
+        }
+        let mut query = command.to_string();
+        for arg in args {
+            query.push('!');
+            query.push_str(arg);
+        }
+
+        let request = Packet { version: self.version, packet_type: QUERY_PACKET, result_code: 0, buffer: query };
+        let mut stream = self.connect()?;
+        stream.write_all(&request.encode())?;
+        stream.flush()?;
+
+        let response = Packet::decode(&mut stream)?;
+        if response.packet_type != RESPONSE_PACKET {
+            return Err(NrpeError::Protocol(format!("unexpected packet type {}", response.packet_type)));
+        }
+        Ok(NrpeResponse { result_code: response.result_code, output: response.buffer })
+    }
+
+    /// Runs `command` with `args` on the daemon and maps its response to a `CheckResult`, see
+    /// `NrpeResponse::into_check_result()`
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - the name of a command defined in the daemon's configuration
+    /// * `args` - the arguments passed to the command
+    pub fn check(&self, command: &str, args: &[&str]) -> Result<CheckResult, NrpeError> {
+        self.query(command, args).map(NrpeResponse::into_check_result)
+    }
+
+    fn connect(&self) -> Result<Box<dyn Stream>, NrpeError> {
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| NrpeError::Resolve(self.host.clone()))?;
+        let stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        #[cfg(feature = "nrpe-tls")]
+        {
+            if self.tls {
+                return Ok(Box::new(tls::connect(self, stream)?));
+            }
+        }
+        Ok(Box::new(stream))
+    }
+}
+
+
+/// The response of an NRPE daemon
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NrpeResponse {
+    /// the exit code of the remote command
+    pub result_code: i16,
+    /// the output of the remote command
+    pub output: String,
+}
+
+
+impl NrpeResponse {
+    /// Maps the response of a remote check plugin to a `CheckResult`. The state is taken from
+    /// the result code, codes outside of 0..=3 result in UNKNOWN. Output which cannot be parsed
+    /// becomes the info string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::nrpe::NrpeResponse;
+    /// let response = NrpeResponse { result_code: 2,
+    ///     output: String::from("CRITICAL - load is 12.1 | load1=12.1;4;8") };
+    /// assert_eq!(response.into_check_result().to_string(),
+    ///     "CRITICAL - load is 12.1 | 'load1'=12.1;4;8;; ");
+    ///
+    /// let response = NrpeResponse { result_code: 3,
+    ///     output: String::from("NRPE: Command 'check_foo' not defined") };
+    /// assert_eq!(response.into_check_result().to_string(),
+    ///     "UNKNOWN - NRPE: Command 'check_foo' not defined");
+    /// ```
+    pub fn into_check_result(self) -> CheckResult {
+        let state = State::from_exit_code_lossy(i32::from(self.result_code));
+
+        if let Ok(parsed) = self.output.parse::<CheckResult>() {
+            return parsed.set_state(state);
+        }
+
+        match self.output.trim().lines().next() {
+            Some(line) => CheckResult::new(state).set_info(line.to_string()),
+            None => CheckResult::new(state),
+        }
+    }
+}
+
+
+/// A check registered with a `NrpeServer`, executed with the given command timeout
+type Command = Box<dyn Fn(Duration) -> CheckResult + Send + Sync>;
+
+
+/// A minimal NRPE daemon answering requests with `Plugin`s executed in-process, which turns a
+/// binary built with this crate into an agent `check_nrpe` can query. Like NRPE with
+/// `dont_blame_nrpe=0`, commands are only looked up by name and requests carrying arguments are
+/// rejected. Connections are not encrypted.
+pub struct NrpeServer {
+    /// the registered commands by name
+    commands: HashMap<String, Command>,
+    /// the addresses allowed to connect, everyone if empty
+    allowed_hosts: Vec<IpAddr>,
+    /// the time each read or write may take
+    timeout: Duration,
+    /// the time each plugin is given to produce a result
+    command_timeout: Duration,
+}
+
+
+impl NrpeServer {
+    /// Creates a new `NrpeServer` without any commands, accepting connections from everywhere
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::nrpe::NrpeServer;
+    /// use icingaplugin_rs::runner::Error;
+    ///
+    /// let check_answer = || -> Result<CheckResult, Error> {
+    ///     Ok(CheckResult::new(State::OK).set_info(String::from("the answer is 42")))
+    /// };
+    /// NrpeServer::new()
+    ///     .command("check_answer", check_answer)
+    ///     .allowed_hosts(vec!["192.0.2.10".parse().unwrap()])
+    ///     .serve("0.0.0.0:5666")
+    ///     .unwrap();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            commands: HashMap::new(),
+            allowed_hosts: Vec::new(),
+            timeout: Duration::from_secs(10),
+            command_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Registers `plugin` under `name`. It is executed by a `Runner` with the `command_timeout()`
+    /// for every request, so errors, panics and timeouts are answered with UNKNOWN.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the command name requested by `check_nrpe -c`
+    /// * `plugin` - the plugin answering the command
+    pub fn command<P>(mut self, name: &str, plugin: P) -> Self where P: Plugin + Send + Sync + 'static {
+        let plugin = Arc::new(plugin);
+        let command = move |timeout| {
+            let plugin = Arc::clone(&plugin);
+            Runner::new(move || plugin.run()).timeout(timeout).execute()
+        };
+        self.commands.insert(name.to_string(), Box::new(command));
+        self
+    }
+
+    /// Restricts connections to the given addresses, like NRPE's `allowed_hosts`
+    ///
+    /// # Arguments
+    ///
+    /// * `hosts` - the addresses allowed to connect
+    pub fn allowed_hosts(mut self, hosts: Vec<IpAddr>) -> Self {
+        self.allowed_hosts = hosts;
+        self
+    }
+
+    /// Sets the time each read or write on a connection may take
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the network timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the time each plugin is given to produce a result, like NRPE's `command_timeout`,
+    /// 60 seconds by default. Applies to all commands, also the ones registered before.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the timeout of commands
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
+    }
+
+    /// Listens on `addr` and answers requests until the process ends. Only returns if binding
+    /// fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - the address to listen on, usually port 5666
+    pub fn serve<A: ToSocketAddrs>(self, addr: A) -> io::Result<()> {
+        self.serve_listener(TcpListener::bind(addr)?)
+    }
+
+    /// Answers requests on connections accepted by `listener`, each in its own thread
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - a bound listener
+    pub fn serve_listener(self, listener: TcpListener) -> io::Result<()> {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                // e.g. the client reset the connection before it was accepted
+                Err(_) => continue,
+            };
+            let allowed = match stream.peer_addr() {
+                Ok(peer) => server.allowed_hosts.is_empty() || server.allowed_hosts.contains(&peer.ip()),
+                Err(_) => false,
+            };
+            if !allowed {
+                continue;
+            }
+
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                let _ = stream.set_read_timeout(Some(server.timeout));
+                let _ = stream.set_write_timeout(Some(server.timeout));
+                let mut stream = stream;
+                // errors only affect the single connection, there is nobody to report them to
+                let _ = server.handle(&mut stream);
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads a single request from `stream` and writes the response
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - a connection to an NRPE client
+    pub fn handle<S: Read + Write>(&self, stream: &mut S) -> Result<(), NrpeError> {
+        let query = Packet::decode(stream)?;
+        if query.packet_type != QUERY_PACKET {
+            return Err(NrpeError::Protocol(format!("unexpected packet type {}", query.packet_type)));
+        }
+
+        let (result_code, output) = self.answer(&query.buffer);
+        let response = Packet { version: query.version, packet_type: RESPONSE_PACKET, result_code, buffer: output };
+        stream.write_all(&response.encode())?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Executes the command requested by `query` and returns the result code and output
+    fn answer(&self, query: &str) -> (i16, String) {
+        if query == VERSION_COMMAND {
+            return (0, format!("icingaplugin-rs NRPE listener {}", env!("CARGO_PKG_VERSION")));
+        }
+
+        let (name, has_args) = match query.split_once('!') {
+            Some((name, _)) => (name, true),
+            None => (query, false),
+        };
+        match self.commands.get(name) {
+            _ if has_args => (3, String::from("NRPE: Command arguments are not allowed")),
+            Some(command) => {
+                let cr = command(self.command_timeout);
+                (i32::from(cr.state()) as i16, cr.to_string())
+            }
+            None => (3, format!("NRPE: Command '{}' not defined", name)),
+        }
+    }
+}
+
+
+impl Default for NrpeServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// An error occurring while speaking the NRPE protocol
+#[derive(Debug)]
+pub enum NrpeError {
+    /// The daemon's host name did not resolve to any address
+    Resolve(String),
+    /// Connecting to or talking with the daemon failed
+    Io(io::Error),
+    /// TLS could not be set up
+    Tls(String),
+    /// The command or its arguments cannot be sent
+    InvalidQuery(String),
+    /// The daemon sent an invalid packet
+    Protocol(String),
+}
+
+
+impl fmt::Display for NrpeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NrpeError::Resolve(host) => write!(f, "could not resolve {}", host),
+            NrpeError::Io(e) => write!(f, "NRPE connection failed: {}", e),
+            NrpeError::Tls(e) => write!(f, "TLS setup failed: {}", e),
+            NrpeError::InvalidQuery(e) => write!(f, "invalid NRPE query: {}", e),
+            NrpeError::Protocol(e) => write!(f, "invalid NRPE packet: {}", e),
+        }
+    }
+}
+
+
+impl Error for NrpeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NrpeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+
+impl From<io::Error> for NrpeError {
+    fn from(e: io::Error) -> Self {
+        NrpeError::Io(e)
+    }
+}
+
+
+/// A connection to an NRPE daemon, either plain or TLS
+trait Stream: Read + Write {}
+
+
+impl<T: Read + Write> Stream for T {}
+
+
+#[cfg(feature = "nrpe-tls")]
+mod tls {
+    use super::{Nrpe, NrpeError};
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::{ring, CryptoProvider};
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+    use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme, StreamOwned};
+    use std::convert::TryFrom;
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    pub(super) fn connect(nrpe: &Nrpe, stream: TcpStream)
+        -> Result<StreamOwned<ClientConnection, TcpStream>, NrpeError> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| NrpeError::Tls(e.to_string()))?;
+
+        let builder = if nrpe.insecure {
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(NoVerification(provider)))
+        } else {
+            let path = nrpe.ca_file.as_ref().ok_or_else(|| NrpeError::Tls(String::from("no CA file")))?;
+            let mut roots = RootCertStore::empty();
+            for cert in certs(path)? {
+                roots.add(cert).map_err(|e| NrpeError::Tls(format!("{}: {}", path.display(), e)))?;
+            }
+            builder.with_root_certificates(roots)
+        };
+
+        let config = match &nrpe.client_cert {
+            Some((cert, key)) => {
+                let key = PrivateKeyDer::from_pem_file(key)
+                    .map_err(|e| NrpeError::Tls(format!("{}: {}", key.display(), e)))?;
+                builder.with_client_auth_cert(certs(cert)?, key).map_err(|e| NrpeError::Tls(e.to_string()))?
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let name = ServerName::try_from(nrpe.host.clone()).map_err(|e| NrpeError::Tls(e.to_string()))?;
+        let conn = ClientConnection::new(Arc::new(config), name).map_err(|e| NrpeError::Tls(e.to_string()))?;
+        Ok(StreamOwned::new(conn, stream))
+    }
+
+    fn certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, NrpeError> {
+        CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| NrpeError::Tls(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Accepts any server certificate, but still checks the handshake signatures
+    #[derive(Debug)]
+    struct NoVerification(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for NoVerification {
+        fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>,
+            _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+            -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+        }
+
+        fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+            -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+}
+
@@ -0,0 +1,283 @@
+use crate::check::CheckResult;
+use crate::utils::crc32;
+use des::cipher::generic_array::GenericArray;
+use des::cipher::{BlockEncrypt, KeyInit};
+use des::Des;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// The version of the data packet format spoken by NSCA 2.x
+const PACKET_VERSION: i16 = 3;
+/// The size of the initialization vector sent by the daemon
+const IV_SIZE: usize = 128;
+/// The maximum length of host names, including the terminating NUL
+const HOST_LENGTH: usize = 64;
+/// The maximum length of service descriptions, including the terminating NUL
+const SERVICE_LENGTH: usize = 128;
+/// The offset of the host name in the data packet, following version, padding, CRC, timestamp
+/// and return code
+const HOST_OFFSET: usize = 14;
+
+/// The encryption methods of `send_nsca`, which have to match the `decryption_method` of the
+/// daemon
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Encryption {
+    /// No encryption (method 0)
+    None,
+    /// Simple XOR with the IV and the password (method 1), which only obfuscates the packet
+    Xor,
+    /// DES in 8 bit CFB mode, keyed with the password (method 2)
+    Des,
+}
+
+
+/// Submits passive check results to an NSCA daemon like `send_nsca` does, using the classic NSCA
+/// 2.x protocol
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Nsca {
+    /// the host the daemon runs on
+    host: String,
+    /// the port the daemon listens on, 5667 by default
+    port: u16,
+    /// the shared password
+    password: String,
+    /// the encryption method
+    encryption: Encryption,
+    /// the time connecting and sending may take
+    timeout: Duration,
+    /// the size of the plugin output field, 4096 since NSCA 2.9 and 512 before
+    output_length: usize,
+}
+
+
+impl Nsca {
+    /// Creates a new `Nsca` sender for the daemon on `host`, port 5667, without encryption
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host name or address of the NSCA daemon
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::nsca::{Encryption, Nsca};
+    ///
+    /// let nsca = Nsca::new(String::from("icinga.example.com"))
+    ///     .encryption(Encryption::Des)
+    ///     .password(String::from("secret"));
+    /// let cr = CheckResult::new(State::OK).set_info(String::from("backup finished"));
+    /// nsca.send("db01", Some("backup"), &cr).unwrap();
+    /// ```
+    pub fn new<S: Into<String>>(host: S) -> Self {
+        Self {
+            host: host.into(),
+            port: 5667,
+            password: String::new(),
+            encryption: Encryption::None,
+            timeout: Duration::from_secs(10),
+            output_length: 4096,
+        }
+    }
+
+    /// Sets the port the daemon listens on
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - the `server_port` of the daemon
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the password shared with the daemon
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - the `password` of the daemon
+    pub fn password<S: Into<String>>(mut self, password: S) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Sets the encryption method
+    ///
+    /// # Arguments
+    ///
+    /// * `encryption` - the `decryption_method` of the daemon
+    pub fn encryption(mut self, encryption: Encryption) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Sets the time connecting, reading and writing may take each
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the network timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the size of the plugin output field, which has to match the daemon. NSCA 2.9 and
+    /// later use 4096 bytes, older versions 512.
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - the maximum plugin output length, including the terminating NUL, at least 1
+    pub fn output_length(mut self, length: usize) -> Self {
+        self.output_length = length.max(1);
+        self
+    }
+
+    /// Sends `result` as passive check result for the given host or service. Long output is
+    /// transmitted with escaped newlines, which NSCA 2.9 unescapes.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the name of the host
+    /// * `service` - the name of the service, `None` for a host check result
+    /// * `result` - the check result
+    pub fn send(&self, host: &str, service: Option<&str>, result: &CheckResult) -> Result<(), NscaError> {
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| NscaError::Resolve(self.host.clone()))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut init = [0u8; IV_SIZE + 4];
+        stream.read_exact(&mut init)?;
+        let iv = &init[..IV_SIZE];
+        let timestamp = u32::from_be_bytes([init[IV_SIZE], init[IV_SIZE + 1], init[IV_SIZE + 2], init[IV_SIZE + 3]]);
+
+        let output = result.to_string().replace('\\', "\\\\").replace('\n', "\\n");
+        let mut packet = self.packet(host, service.unwrap_or(""), result, &output, timestamp)?;
+        encrypt(&mut packet, self.encryption, iv, self.password.as_bytes());
+        stream.write_all(&packet)?;
+        Ok(())
+    }
+
+    /// Builds the unencrypted data packet including its CRC32
+    pub(crate) fn packet(&self, host: &str, service: &str, result: &CheckResult, output: &str, timestamp: u32)
+        -> Result<Vec<u8>, NscaError> {
+        let output_offset = HOST_OFFSET + HOST_LENGTH + SERVICE_LENGTH;
+        // the C struct is padded to a multiple of 4 bytes
+        let size = (output_offset + self.output_length).div_ceil(4) * 4;
+        let mut packet = vec![0u8; size];
+
+        packet[0..2].copy_from_slice(&PACKET_VERSION.to_be_bytes());
+        packet[8..12].copy_from_slice(&timestamp.to_be_bytes());
+        packet[12..14].copy_from_slice(&(i32::from(result.state()) as i16).to_be_bytes());
+        copy_field(&mut packet[HOST_OFFSET..HOST_OFFSET + HOST_LENGTH], "host name", host)?;
+        copy_field(&mut packet[HOST_OFFSET + HOST_LENGTH..output_offset], "service description", service)?;
+        copy_field(&mut packet[output_offset..output_offset + self.output_length], "plugin output",
+            truncate(output, self.output_length - 1))?;
+
+        let crc = crc32(&packet);
+        packet[4..8].copy_from_slice(&crc.to_be_bytes());
+        Ok(packet)
+    }
+}
+
+
+/// An error occurring while sending a check result to an NSCA daemon
+#[derive(Debug)]
+pub enum NscaError {
+    /// The daemon's host name did not resolve to any address
+    Resolve(String),
+    /// Connecting to or talking with the daemon failed
+    Io(io::Error),
+    /// A host or service name exceeds the limits of the protocol
+    TooLong(&'static str),
+}
+
+
+impl fmt::Display for NscaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NscaError::Resolve(host) => write!(f, "could not resolve {}", host),
+            NscaError::Io(e) => write!(f, "failed to send check result to NSCA: {}", e),
+            NscaError::TooLong(field) => write!(f, "{} is too long for NSCA", field),
+        }
+    }
+}
+
+
+impl Error for NscaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NscaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+
+impl From<io::Error> for NscaError {
+    fn from(e: io::Error) -> Self {
+        NscaError::Io(e)
+    }
+}
+
+
+/// Copies `value` into a NUL terminated, fixed size field of the packet
+fn copy_field(field: &mut [u8], name: &'static str, value: &str) -> Result<(), NscaError> {
+    if value.len() >= field.len() {
+        return Err(NscaError::TooLong(name));
+    }
+    field[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(())
+}
+
+
+/// Cuts `s` to at most `max` bytes without splitting a character
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+
+/// Encrypts `packet` in place the way `send_nsca` does
+pub(crate) fn encrypt(packet: &mut [u8], method: Encryption, iv: &[u8], password: &[u8]) {
+    match method {
+        Encryption::None => {}
+        Encryption::Xor => {
+            for (i, b) in packet.iter_mut().enumerate() {
+                *b ^= iv[i % iv.len()];
+            }
+            if !password.is_empty() {
+                for (i, b) in packet.iter_mut().enumerate() {
+                    *b ^= password[i % password.len()];
+                }
+            }
+        }
+        Encryption::Des => {
+            // the key is the password, cut or zero padded to 8 bytes
+            let mut key = [0u8; 8];
+            let len = password.len().min(key.len());
+            key[..len].copy_from_slice(&password[..len]);
+            let cipher = Des::new(GenericArray::from_slice(&key));
+
+            // 8 bit CFB, as libmcrypt's "cfb" mode
+            let mut register = [0u8; 8];
+            register.copy_from_slice(&iv[..8]);
+            for b in packet.iter_mut() {
+                let mut block = GenericArray::clone_from_slice(&register);
+                cipher.encrypt_block(&mut block);
+                *b ^= block[0];
+                register.copy_within(1.., 0);
+                register[7] = *b;
+            }
+        }
+    }
+}
+
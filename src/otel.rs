@@ -0,0 +1,126 @@
+use crate::check::PerfData;
+use crate::export::{as_f64, convert, Unit};
+use opentelemetry::metrics::{Counter, Gauge, Meter};
+use opentelemetry::KeyValue;
+use std::collections::HashMap;
+
+/// An instrument created for a `Metric`
+enum Instrument {
+    Gauge(Gauge<f64>),
+    Counter(Counter<f64>),
+}
+
+
+/// Records performance data into an OpenTelemetry `Meter`, so check executions also feed an
+/// OTLP pipeline. Values are converted to their base unit. Metrics with the UOM `c` are recorded
+/// by a counter, all others by a gauge.
+pub struct OtelExporter {
+    /// the meter instruments are created with
+    meter: Meter,
+    /// the instruments created so far, by name
+    instruments: HashMap<String, Instrument>,
+    /// the last value of each counter and attribute set, as plugins report running totals but
+    /// OpenTelemetry counters are incremented
+    totals: HashMap<String, f64>,
+}
+
+
+impl OtelExporter {
+    /// Creates a new `OtelExporter` recording into `meter`
+    ///
+    /// # Arguments
+    ///
+    /// * `meter` - the meter, e.g. from `opentelemetry::global::meter("check_disk")`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, PerfData};
+    /// use icingaplugin_rs::otel::OtelExporter;
+    /// use opentelemetry::KeyValue;
+    ///
+    /// let mut exporter = OtelExporter::new(opentelemetry::global::meter("check_disk"));
+    /// let pd = PerfData::from_metric(Metric::numeric("/var", 87.5).uom(String::from("%")));
+    /// exporter.record(&pd, &[KeyValue::new("host.name", "db01")]);
+    /// ```
+    pub fn new(meter: Meter) -> Self {
+        Self {
+            meter,
+            instruments: HashMap::new(),
+            totals: HashMap::new(),
+        }
+    }
+
+    /// Records all numeric metrics of `pd`. Counters are incremented by the difference to the
+    /// value recorded last with the same attributes, or by the whole value on the first call and
+    /// after the counter was reset.
+    ///
+    /// # Arguments
+    ///
+    /// * `pd` - the performance data of a check
+    /// * `attributes` - the attributes attached to every measurement, e.g. the host name
+    pub fn record(&mut self, pd: &PerfData, attributes: &[KeyValue]) {
+        for metric in &pd.metrics {
+            let (unit, value) = match as_f64(&metric.value) {
+                Some(v) if v.is_finite() => convert(v, metric.uom.as_deref().unwrap_or("")),
+                _ => continue,
+            };
+            let name = instrument_name(&metric.label);
+
+            if unit == Unit::Counter {
+                let key = format!("{}{:?}", name, attributes);
+                let increment = self.increment(key, value);
+                if let Instrument::Counter(c) = self.instrument(&name, &metric.label, unit) {
+                    c.add(increment, attributes);
+                }
+            } else if let Instrument::Gauge(g) = self.instrument(&name, &metric.label, unit) {
+                g.record(value, attributes);
+            }
+        }
+    }
+
+    /// Returns the difference between `total` and the total last recorded for `key`
+    pub(crate) fn increment(&mut self, key: String, total: f64) -> f64 {
+        match self.totals.insert(key, total) {
+            Some(last) if last <= total => total - last,
+            _ => total.max(0.0),
+        }
+    }
+
+    /// Returns the instrument for `name`, creating it on first use
+    fn instrument(&mut self, name: &str, label: &str, unit: Unit) -> &Instrument {
+        let meter = &self.meter;
+        self.instruments.entry(name.to_string()).or_insert_with(|| {
+            let description = label.to_string();
+            let ucum = match unit {
+                Unit::Seconds => "s",
+                Unit::Bytes => "By",
+                Unit::Ratio => "1",
+                Unit::Counter => return Instrument::Counter(meter.f64_counter(name.to_string())
+                    .with_description(description)
+                    .build()),
+                Unit::None => "",
+            };
+            Instrument::Gauge(meter.f64_gauge(name.to_string())
+                .with_description(description)
+                .with_unit(ucum)
+                .build())
+        })
+    }
+}
+
+
+/// Turns a label into a valid instrument name, which has to start with a letter and may only
+/// contain letters, digits, `_`, `.`, `-` and `/`
+pub(crate) fn instrument_name(label: &str) -> String {
+    let mut name: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "_.-/".contains(c) { c } else { '_' })
+        .take(255)
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "metric_");
+        name.truncate(255);
+    }
+    name
+}
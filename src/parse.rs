@@ -0,0 +1,272 @@
+use crate::check::{CheckResult, Metric, PerfData, State};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An error occurring while parsing plugin output
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The output was empty
+    Empty,
+    /// The first line of the output does not start with a state
+    MissingState(String),
+    /// A performance data entry could not be parsed
+    InvalidMetric(String),
+}
+
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty plugin output"),
+            ParseError::MissingState(line) => write!(f, "no state found in '{}'", line),
+            ParseError::InvalidMetric(m) => write!(f, "invalid performance data '{}'", m),
+        }
+    }
+}
+
+
+impl Error for ParseError {}
+
+
+impl FromStr for CheckResult {
+    type Err = ParseError;
+
+    /// Parses plugin output, e.g. of an existing check plugin, back into a `CheckResult`. The
+    /// first line has to start with the state, optionally preceded by a single word naming the
+    /// service and followed by `-` or `:` and the info string. Performance data is read from the
+    /// first line and from the long output section following the first `|` in it, as described
+    /// in the Nagios plugin API.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// let cr: CheckResult = "WARNING - load is high | 'load1'=4.2;4;8;0; load5=3.1\n\
+    ///     4 cores".parse().unwrap();
+    /// assert_eq!(cr.state(), State::Warning);
+    /// assert_eq!(cr.long_output(), ["4 cores"]);
+    /// assert_eq!(cr.to_string(), "WARNING - load is high | 'load1'=4.2;4;8;0; 'load5'=3.1;;;; \n4 cores");
+    ///
+    /// let cr: CheckResult = "CRITICAL: 3 of 5 services down".parse().unwrap();
+    /// assert_eq!(cr.to_string(), "CRITICAL - 3 of 5 services down");
+    ///
+    /// let cr: CheckResult = "DISK OK - all disks fine".parse().unwrap();
+    /// assert_eq!(cr.service(), Some("DISK"));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        let first = match lines.next() {
+            Some(line) if !line.trim().is_empty() => line,
+            _ => return Err(ParseError::Empty),
+        };
+
+        let (text, perf) = match first.split_once('|') {
+            Some((text, perf)) => (text, perf),
+            None => (first, ""),
+        };
+        let text = text.trim();
+        let (service, (state, info)) = match parse_state(text) {
+            Some(parsed) => (None, parsed),
+            None => text
+                .split_once(char::is_whitespace)
+                .and_then(|(service, rest)| Some((Some(service), parse_state(rest.trim_start())?)))
+                .ok_or_else(|| ParseError::MissingState(first.to_string()))?,
+        };
+        let mut metrics = parse_metrics(perf)?;
+
+        let mut long_output = Vec::new();
+        let mut in_perf_data = false;
+        for line in lines {
+            if in_perf_data {
+                metrics.extend(parse_metrics(line)?);
+            } else if let Some((text, perf)) = line.split_once('|') {
+                if !text.trim().is_empty() {
+                    long_output.push(text.trim_end().to_string());
+                }
+                metrics.extend(parse_metrics(perf)?);
+                in_perf_data = true;
+            } else {
+                long_output.push(line.to_string());
+            }
+        }
+
+        let mut cr = CheckResult::new(state).set_long_output(long_output);
+        if let Some(service) = service {
+            cr = cr.set_service(service);
+        }
+        if !info.is_empty() {
+            cr = cr.set_info(info.to_string());
+        }
+        if !metrics.is_empty() {
+            cr = cr.set_perf_data(PerfData::from_metrics(metrics));
+        }
+        Ok(cr)
+    }
+}
+
+
+impl PerfData {
+    /// Parses performance data in the `'label'=value[UOM];[warn];[crit];[min];[max]` format,
+    /// e.g. the part following the `|` in another plugin's output. Labels may be quoted with
+    /// single quotes, quotes inside of labels are escaped as `''`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - whitespace separated performance data entries
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::PerfData;
+    /// let pd = PerfData::parse("'rta'=0.25ms;100;500;0 'pl'=0%;20;60;; 'it''s'=1").unwrap();
+    /// assert_eq!(pd.to_string(), "'rta'=0.25ms;100;500;0; 'pl'=0%;20;60;; 'it''s'=1;;;; ");
+    /// assert!(PerfData::parse("rta=").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<PerfData, ParseError> {
+        parse_metrics(s).map(PerfData::from_metrics)
+    }
+}
+
+
+impl FromStr for PerfData {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PerfData::parse(s)
+    }
+}
+
+
+impl FromStr for Metric {
+    type Err = ParseError;
+
+    /// Parses a single performance data entry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, Value};
+    /// let metric: Metric = "'/var'=1024MB;2048;4096;0;8192".parse().unwrap();
+    /// assert_eq!(metric.value(), &Value::Integer(1024));
+    /// assert!("a=1 b=2".parse::<Metric>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut metrics = parse_metrics(s)?;
+        match metrics.len() {
+            1 => Ok(metrics.remove(0)),
+            _ => Err(ParseError::InvalidMetric(s.to_string())),
+        }
+    }
+}
+
+
+/// Splits the state off the text part of the first line, returning the state and the remaining
+/// info string
+fn parse_state(text: &str) -> Option<(State, &str)> {
+    let end = text.find(|c: char| c.is_whitespace() || c == ':').unwrap_or(text.len());
+    let state = match text[..end].to_uppercase().as_str() {
+        "OK" => State::OK,
+        "WARNING" => State::Warning,
+        "CRITICAL" => State::Critical,
+        "UNKNOWN" => State::Unknown,
+        _ => return None,
+    };
+
+    let rest = text[end..].trim_start();
+    let rest = rest
+        .strip_prefix('-')
+        .or_else(|| rest.strip_prefix(':'))
+        .unwrap_or(rest);
+    Some((state, rest.trim()))
+}
+
+
+/// Parses whitespace separated performance data entries in the
+/// `'label'=value[UOM];[warn];[crit];[min];[max]` format
+fn parse_metrics(s: &str) -> Result<Vec<Metric>, ParseError> {
+    let mut metrics = Vec::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        let (label, after_label) = match rest.strip_prefix('\'') {
+            Some(quoted) => parse_quoted_label(quoted).ok_or_else(|| invalid(rest))?,
+            None => {
+                let end = rest.find('=').ok_or_else(|| invalid(rest))?;
+                if rest[..end].contains(char::is_whitespace) {
+                    return Err(invalid(rest));
+                }
+                (rest[..end].to_string(), &rest[end..])
+            }
+        };
+
+        let after_eq = after_label.strip_prefix('=').ok_or_else(|| invalid(rest))?;
+        let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+        let entry = &rest[..rest.len() - after_eq.len() + end];
+        metrics.push(parse_metric(label, &after_eq[..end]).ok_or_else(|| invalid(entry))?);
+        rest = after_eq[end..].trim_start();
+    }
+
+    Ok(metrics)
+}
+
+
+/// Reads a label enclosed in single quotes, where `''` is an escaped quote. `s` starts after the
+/// opening quote. Returns the label and the text following the closing quote.
+fn parse_quoted_label(s: &str) -> Option<(String, &str)> {
+    let mut label = String::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if c == '\'' {
+            if let Some((_, '\'')) = chars.peek() {
+                label.push('\'');
+                chars.next();
+            } else {
+                return Some((label, &s[i + 1..]));
+            }
+        } else {
+            label.push(c);
+        }
+    }
+    None
+}
+
+
+/// Builds a `Metric` from its label and the `value[UOM];[warn];[crit];[min];[max]` part. The
+/// value has to be numeric or `U`, text values are not valid performance data.
+fn parse_metric(label: String, s: &str) -> Option<Metric> {
+    let mut fields = s.split(';');
+    let value = fields.next().filter(|v| !v.is_empty())?;
+    let mut metric = match Metric::try_new(label.clone(), value) {
+        Ok(m) => m,
+        Err(_) => match value.strip_prefix('U') {
+            Some("") => Metric::unknown(label),
+            Some(uom) if uom.chars().all(|c| c.is_alphabetic() || c == '%') => {
+                Metric::unknown(label).uom(uom.to_string())
+            }
+            _ => return None,
+        },
+    };
+
+    let thresholds: Vec<&str> = fields.collect();
+    if thresholds.len() > 4 {
+        return None;
+    }
+    for (i, field) in thresholds.iter().enumerate().filter(|(_, f)| !f.is_empty()) {
+        let field = field.to_string();
+        metric = match i {
+            0 => metric.warning(field),
+            1 => metric.critical(field),
+            2 => metric.min(field),
+            _ => metric.max(field),
+        };
+    }
+    Some(metric)
+}
+
+
+fn invalid(s: &str) -> ParseError {
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    ParseError::InvalidMetric(s[..end].to_string())
+}
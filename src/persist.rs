@@ -0,0 +1,440 @@
+use crate::clock::{Clock, SystemClock};
+use crate::error::PluginError;
+use crate::platform;
+use crate::rate::{rate, CounterWidth, RateError, Sample};
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A value kept between check invocations, with the time it was last set
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    value: String,
+    updated: SystemTime,
+}
+
+
+/// A key-value store persisted as JSON file between invocations of a plugin, e.g. for counter
+/// samples, log file offsets or the previous state. Every value remembers when it was set, so
+/// data left over from a long pause can be detected. Saving is atomic, the file is either
+/// replaced completely or not at all.
+#[derive(Clone, Debug)]
+pub struct StateFile {
+    /// the path of the JSON file
+    path: PathBuf,
+    /// the values, by key
+    entries: BTreeMap<String, Entry>,
+    /// the source of the time values are set at and aged by
+    clock: Arc<dyn Clock>,
+}
+
+
+impl PartialEq for StateFile {
+    /// State files are equal if their paths and values are, regardless of their clocks
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.entries == other.entries
+    }
+}
+
+
+impl StateFile {
+    /// Loads the state file of `plugin` from `cache_dir()`. A missing file results in an empty
+    /// `StateFile`.
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - the name of the plugin, used as file name. Plugins monitoring several
+    ///   objects should include the object, e.g. `check_interface_eth0`.
+    pub fn load(plugin: &str) -> io::Result<Self> {
+        Self::load_from(cache_dir().join(format!("{}.json", plugin)))
+    }
+
+    /// Loads the state file at `path`. A missing file results in an empty `StateFile`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the JSON file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::persist::StateFile;
+    ///
+    /// let path = std::env::temp_dir().join(format!("state_doctest_{}.json", std::process::id()));
+    /// let mut state = StateFile::load_from(path.clone()).unwrap();
+    /// assert_eq!(state.get("offset"), None);
+    /// state.set("offset", 4096);
+    /// state.save().unwrap();
+    ///
+    /// let state = StateFile::load_from(path.clone()).unwrap();
+    /// assert_eq!(state.get("offset"), Some("4096"));
+    /// std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn load_from(path: PathBuf) -> io::Result<Self> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => parse(&contents).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}: invalid state file", path.display()))
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path, entries, clock: Arc::new(SystemClock) })
+    }
+
+    /// Replaces the `SystemClock` the times values are set at and aged by are taken from, e.g.
+    /// with a `MockClock` in tests
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::clock::MockClock;
+    /// use icingaplugin_rs::persist::StateFile;
+    /// use std::time::Duration;
+    ///
+    /// let clock = MockClock::default();
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap().clock(clock.clone());
+    /// state.set("last_state", "CRITICAL");
+    /// clock.advance(Duration::from_secs(601));
+    /// assert_eq!(state.age("last_state"), Some(Duration::from_secs(601)));
+    /// assert_eq!(state.get_fresh("last_state", Duration::from_secs(600)), None);
+    /// ```
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the path of the JSON file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the value stored for `key`
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|e| e.value.as_str())
+    }
+
+    /// Returns the value stored for `key` if it was set no longer than `max_age` ago. Stale
+    /// values, e.g. a counter sample taken before the plugin was not run for a week, should not
+    /// be used to calculate a rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key
+    /// * `max_age` - the maximum age of the value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::persist::StateFile;
+    /// use std::time::Duration;
+    ///
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+    /// state.set("last_state", "CRITICAL");
+    /// assert_eq!(state.get_fresh("last_state", Duration::from_secs(600)), Some("CRITICAL"));
+    /// ```
+    pub fn get_fresh(&self, key: &str, max_age: Duration) -> Option<&str> {
+        match self.age(key) {
+            Some(age) if age <= max_age => self.get(key),
+            _ => None,
+        }
+    }
+
+    /// Returns how long ago the value of `key` was set. Values set in the future, e.g. after
+    /// the clock was turned back, have an age of zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key
+    pub fn age(&self, key: &str) -> Option<Duration> {
+        self.entries
+            .get(key)
+            .map(|e| self.clock.since(e.updated))
+    }
+
+    /// Sets the value of `key`. The change is persisted by `save()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key
+    /// * `value` - the value, any type which can be displayed and parsed again
+    pub fn set<V: ToString>(&mut self, key: &str, value: V) {
+        let entry = Entry { value: value.to_string(), updated: self.clock.now() };
+        self.entries.insert(key.to_string(), entry);
+    }
+
+    /// Removes the value of `key` and returns it
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.entries.remove(key).map(|e| e.value)
+    }
+
+    /// Stores `current` for `key` and returns the per-second rate the counter increased by since
+    /// the sample stored by the previous invocation. Returns `None` on the first invocation and
+    /// if the previous sample is older than `max_age`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key the samples are stored under
+    /// * `current` - the sample taken now
+    /// * `width` - the range of the counter
+    /// * `max_age` - the maximum age of the previous sample
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::persist::StateFile;
+    /// use icingaplugin_rs::rate::{CounterWidth, Sample};
+    /// use std::time::Duration;
+    ///
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+    /// let first = state.rate("eth0_rx", Sample::new(1000), CounterWidth::Bits64, Duration::from_secs(3600));
+    /// assert_eq!(first, Ok(None));
+    /// assert_eq!(state.get("eth0_rx").map(|s| s.starts_with("1000@")), Some(true));
+    /// ```
+    pub fn rate(&mut self, key: &str, current: Sample, width: CounterWidth, max_age: Duration)
+        -> Result<Option<f64>, RateError> {
+        let previous = self.get_fresh(key, max_age).map(str::parse::<Sample>);
+        self.set(key, current);
+        match previous {
+            Some(previous) => rate(&previous?, &current, width).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the state file, creating its directory if necessary. The contents are written to
+    /// a temporary file first, which then replaces the state file, so a concurrent or aborted
+    /// invocation never sees a partially written file.
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", process::id()));
+        let tmp = PathBuf::from(tmp);
+
+        let result = File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(serialize(&self.entries).as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+}
+
+
+/// Returns the directory state files are stored in by default: `$ICINGAPLUGIN_CACHE_DIR` if
+/// set, else `icingaplugin-rs` within `$XDG_CACHE_HOME`, `$HOME/.cache` or the temporary
+/// directory, in this order
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("ICINGAPLUGIN_CACHE_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let base = env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").filter(|d| !d.is_empty()).map(|h| PathBuf::from(h).join(".cache")))
+        .unwrap_or_else(env::temp_dir);
+    base.join("icingaplugin-rs")
+}
+
+
+/// Serializes the entries as `{"key": {"value": "...", "updated": seconds}}`
+fn serialize(entries: &BTreeMap<String, Entry>) -> String {
+    let mut map = Map::new();
+    for (key, entry) in entries {
+        let updated = entry.updated.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        let mut e = Map::new();
+        e.insert(String::from("value"), Value::String(entry.value.clone()));
+        e.insert(String::from("updated"), Number::from_f64(updated).map_or(Value::Null, Value::Number));
+        map.insert(key.clone(), Value::Object(e));
+    }
+    Value::Object(map).to_string()
+}
+
+
+/// Parses the entries written by `serialize()`
+fn parse(contents: &str) -> Option<BTreeMap<String, Entry>> {
+    let mut entries = BTreeMap::new();
+    for (key, entry) in serde_json::from_str::<Value>(contents).ok()?.as_object()? {
+        let value = entry.get("value")?.as_str()?.to_string();
+        let updated = Duration::try_from_secs_f64(entry.get("updated")?.as_f64()?).ok()?;
+        entries.insert(key.clone(), Entry { value, updated: UNIX_EPOCH + updated });
+    }
+    Some(entries)
+}
+
+
+/// What `Lock::acquire()` does if another invocation holds the lock
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LockPolicy {
+    /// Wait at most the given time for the lock to be released
+    Wait(Duration),
+    /// Fail immediately, e.g. to report UNKNOWN instead of piling up waiting invocations
+    Fail,
+}
+
+
+/// An exclusive advisory lock on a file, preventing concurrent invocations of the same check
+/// from corrupting a shared `StateFile` or e.g. reading the same log lines twice. The lock is
+/// released when the `Lock` is dropped, also if the process crashes.
+#[derive(Debug)]
+pub struct Lock {
+    /// the locked file, closing it releases the lock
+    file: File,
+    /// the path of the locked file
+    path: PathBuf,
+}
+
+
+impl Lock {
+    /// Locks the file at `path`, creating it if necessary
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the lock file
+    /// * `policy` - whether to wait for a lock held by another invocation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::persist::{Lock, LockError, LockPolicy};
+    ///
+    /// let path = std::env::temp_dir().join(format!("lock_doctest_{}", std::process::id()));
+    /// let lock = Lock::acquire(&path, LockPolicy::Fail).unwrap();
+    /// assert!(matches!(Lock::acquire(&path, LockPolicy::Fail), Err(LockError::Contended(_))));
+    /// drop(lock);
+    /// assert!(Lock::acquire(&path, LockPolicy::Fail).is_ok());
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn acquire<P: AsRef<Path>>(path: P, policy: LockPolicy) -> Result<Self, LockError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| LockError::Io(path.clone(), e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| LockError::Io(path.clone(), e))?;
+
+        let deadline = match policy {
+            LockPolicy::Wait(timeout) => Instant::now() + timeout,
+            LockPolicy::Fail => Instant::now(),
+        };
+        loop {
+            match platform::try_lock_file(&file) {
+                Ok(true) => return Ok(Self { file, path }),
+                Ok(false) if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(20).min(deadline - Instant::now()));
+                }
+                Ok(false) => return Err(LockError::Contended(path)),
+                Err(e) => return Err(LockError::Io(path, e)),
+            }
+        }
+    }
+
+    /// Returns the path of the locked file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // closing the file releases the lock anyway
+        platform::unlock_file(&self.file);
+    }
+}
+
+
+impl StateFile {
+    /// Locks the state file against concurrent invocations until the returned `Lock` is dropped.
+    /// Load the `StateFile` again after acquiring the lock, so no change saved by another
+    /// invocation in the meantime is lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - whether to wait for a lock held by another invocation
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::persist::{LockPolicy, StateFile};
+    /// use std::time::Duration;
+    ///
+    /// let state = StateFile::load("check_logfile").unwrap();
+    /// let _lock = state.lock(LockPolicy::Wait(Duration::from_secs(5))).unwrap();
+    /// let mut state = StateFile::load("check_logfile").unwrap();
+    /// state.set("offset", 8192);
+    /// state.save().unwrap();
+    /// ```
+    pub fn lock(&self, policy: LockPolicy) -> Result<Lock, LockError> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".lock");
+        Lock::acquire(PathBuf::from(path), policy)
+    }
+}
+
+
+/// An error occurring while acquiring a `Lock`
+#[derive(Debug)]
+pub enum LockError {
+    /// Another invocation holds the lock on the given file
+    Contended(PathBuf),
+    /// The given lock file could not be opened or locked
+    Io(PathBuf, io::Error),
+}
+
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Contended(path) => write!(f, "{} is locked by another invocation", path.display()),
+            LockError::Io(path, e) => write!(f, "failed to lock {}: {}", path.display(), e),
+        }
+    }
+}
+
+
+impl Error for LockError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LockError::Io(_, e) => Some(e),
+            LockError::Contended(_) => None,
+        }
+    }
+}
+
+
+impl From<LockError> for PluginError {
+    fn from(e: LockError) -> Self {
+        PluginError::Other(Box::new(e))
+    }
+}
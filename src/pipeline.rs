@@ -0,0 +1,341 @@
+use crate::check::{CheckResult, Metric, PerfData, State};
+use crate::error::PluginError;
+use crate::runner::{self, Plugin};
+use crate::thresholds::Thresholds;
+use crate::utils::{apply_thresholds, summarize_metrics, worst_first};
+
+/// Acquires the metrics of a check, e.g. by querying a database or reading `/proc`. Resources
+/// only collect data, evaluating it is left to `Context`s.
+pub trait Resource: Send + Sync {
+    /// Returns the current metrics. Errors turn the check result UNKNOWN, or the state
+    /// configured for the kind of error, see `ErrorStates`.
+    fn probe(&self) -> Result<Vec<Metric>, PluginError>;
+}
+
+
+impl<F> Resource for F where F: Fn() -> Result<Vec<Metric>, PluginError> + Send + Sync {
+    fn probe(&self) -> Result<Vec<Metric>, PluginError> {
+        self()
+    }
+}
+
+
+/// Evaluates a metric into a state, e.g. by comparing it against thresholds
+pub trait Context: Send + Sync {
+    /// Evaluates `metric` and returns its state, the metric as it is reported as performance
+    /// data, e.g. with its thresholds filled in, and an optional hint describing the state
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - the metric to evaluate
+    fn evaluate(&self, metric: &Metric) -> Evaluation;
+}
+
+
+impl Context for Thresholds {
+    /// Evaluates `metric` against the thresholds and fills them into the performance data.
+    /// Metrics without a numeric value are UNKNOWN.
+    fn evaluate(&self, metric: &Metric) -> Evaluation {
+        let (state, metric) = apply_thresholds(metric, self);
+        Evaluation::new(metric, state)
+    }
+}
+
+
+impl<F> Context for F where F: Fn(&Metric) -> State + Send + Sync {
+    /// Evaluates `metric` into the state returned by the closure, e.g. for conditions which are
+    /// not numeric
+    fn evaluate(&self, metric: &Metric) -> Evaluation {
+        Evaluation::new(metric.clone(), self(metric))
+    }
+}
+
+
+/// Renders the text of a check from the evaluations of all its metrics, independently of how they
+/// were evaluated. All methods have default implementations, override them to customize the text.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{Metric, State};
+/// use icingaplugin_rs::pipeline::{Evaluation, Pipeline, Summary};
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// struct Replication;
+///
+/// impl Summary for Replication {
+///     fn ok(&self, evaluations: &[Evaluation]) -> String {
+///         format!("all {} replicas in sync", evaluations.len())
+///     }
+/// }
+///
+/// let lag = || Ok(vec![Metric::new("db02", 0), Metric::new("db03", 12)]);
+/// let pipeline = Pipeline::new().resource(lag).default_context(Thresholds::new()).summary(Replication);
+/// let cr = pipeline.evaluate();
+/// assert_eq!(cr.to_string(), "OK - all 2 replicas in sync | 'db02'=0;;;; 'db03'=12;;;; ");
+/// assert_eq!(cr.set_verbosity(1).to_string(), "OK - all 2 replicas in sync | 'db02'=0;;;; 'db03'=12;;;; \n\
+///     [OK] db02=0\n[OK] db03=12");
+/// ```
+pub trait Summary: Send + Sync {
+    /// Returns the info line if no metric raises an alert, `N metrics ok` by default
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluations` - the evaluated metrics, in the order they were probed
+    fn ok(&self, evaluations: &[Evaluation]) -> String {
+        summarize_metrics::<&str>(evaluations.len(), &[])
+    }
+
+    /// Returns the info line if a metric raises an alert. By default, these are the alerting
+    /// metrics with their hint or value and state, worst first.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluations` - the evaluated metrics, in the order they were probed
+    fn problem(&self, evaluations: &[Evaluation]) -> String {
+        let alerting = worst_first(evaluations.iter().map(|e| (e.state, e)));
+        let names: Vec<_> = alerting.iter().map(|e| e.describe()).collect();
+        summarize_metrics(evaluations.len(), &names)
+    }
+
+    /// Returns the lines displayed below the info line with `-v`, by default every metric
+    /// prefixed by its state
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluations` - the evaluated metrics, in the order they were probed
+    fn verbose(&self, evaluations: &[Evaluation]) -> Vec<String> {
+        evaluations.iter().map(|e| format!("[{}] {}", e.state, e.describe_value())).collect()
+    }
+}
+
+
+/// The `Summary` used by default, see the default implementations of `Summary`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultSummary;
+
+
+impl Summary for DefaultSummary {}
+
+
+/// The result of evaluating a metric in a `Context`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    /// the metric as it is reported as performance data
+    metric: Metric,
+    /// the state of the metric
+    state: State,
+    /// a description of the state, if any
+    hint: Option<String>,
+}
+
+
+impl Evaluation {
+    /// Creates a new `Evaluation` of `metric` with `state`
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - the metric as it is reported as performance data
+    /// * `state` - the state of the metric
+    pub fn new(metric: Metric, state: State) -> Self {
+        Self { metric, state, hint: None }
+    }
+
+    /// Sets a description of the state, used by the `DefaultSummary` instead of the value
+    ///
+    /// # Arguments
+    ///
+    /// * `hint` - the description, e.g. `"/var is mounted read-only"`
+    pub fn set_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Returns the evaluated metric
+    pub fn metric(&self) -> &Metric {
+        &self.metric
+    }
+
+    /// Returns the state of the metric
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns the description of the state, if any
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// Returns the hint, or `label=valueuom (STATE)` if there is none
+    pub fn describe(&self) -> String {
+        match &self.hint {
+            Some(hint) => hint.clone(),
+            None => format!("{} ({})", self.describe_value(), self.state),
+        }
+    }
+
+    /// Returns the hint, or `label=valueuom` if there is none
+    pub fn describe_value(&self) -> String {
+        match &self.hint {
+            Some(hint) => hint.clone(),
+            None => format!("{}={}{}", self.metric.label, self.metric.formatted_value(),
+                            self.metric.uom.as_deref().unwrap_or("")),
+        }
+    }
+}
+
+
+/// A check composed of layers which can be tested on their own, like the Python `nagiosplugin`
+/// library: `Resource`s acquire metrics, `Context`s evaluate each metric into a state and a
+/// `Summary` renders the info line and the verbose output. The state of the check is the worst
+/// state of all metrics, all metrics are reported as performance data.
+///
+/// A metric is evaluated in the context registered for its label, or in the default context.
+/// Metrics without a context are reported, but do not influence the state.
+///
+/// `Pipeline` implements `Plugin`, so it can be executed by a `Runner`.
+pub struct Pipeline {
+    /// the resources in the order they are probed
+    resources: Vec<Box<dyn Resource>>,
+    /// the contexts by metric label
+    contexts: Vec<(String, Box<dyn Context>)>,
+    /// the context of metrics without a context of their own
+    default_context: Option<Box<dyn Context>>,
+    /// renders the info line and the verbose output
+    summary: Box<dyn Summary>,
+}
+
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Pipeline {
+    /// Creates a new `Pipeline` without resources and contexts, using the `DefaultSummary`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{Metric, State};
+    /// use icingaplugin_rs::error::PluginError;
+    /// use icingaplugin_rs::pipeline::Pipeline;
+    /// use icingaplugin_rs::runner::Runner;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let load = || -> Result<Vec<Metric>, PluginError> {
+    ///     Ok(vec![Metric::new("load1", 3.2), Metric::new("load5", 1.1), Metric::new("cores", 4)])
+    /// };
+    /// let thresholds = Thresholds::new().warning("3".parse().unwrap()).critical("6".parse().unwrap());
+    ///
+    /// let pipeline = Pipeline::new()
+    ///     .resource(load)
+    ///     .context("load1", thresholds)
+    ///     .context("load5", thresholds);
+    /// let cr = Runner::new(pipeline).execute();
+    /// assert_eq!(cr.state(), State::Warning);
+    /// assert_eq!(cr.to_string(), "WARNING - 1 of 3 metrics alerting: load1=3.2 (WARNING) \
+    ///     | 'load1'=3.2;3;6;; 'load5'=1.1;3;6;; 'cores'=4;;;; ");
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+            contexts: Vec::new(),
+            default_context: None,
+            summary: Box::new(DefaultSummary),
+        }
+    }
+
+    /// Adds a resource, probed in the order it was added
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - the resource
+    pub fn resource<R: Resource + 'static>(mut self, resource: R) -> Self {
+        self.resources.push(Box::new(resource));
+        self
+    }
+
+    /// Registers the context evaluating the metrics labeled `label`, replacing a context
+    /// registered before
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label of the metrics
+    /// * `context` - the context, e.g. `Thresholds`
+    pub fn context<L: Into<String>, C: Context + 'static>(mut self, label: L, context: C) -> Self {
+        let label = label.into();
+        self.contexts.retain(|(l, _)| *l != label);
+        self.contexts.push((label, Box::new(context)));
+        self
+    }
+
+    /// Sets the context evaluating the metrics without a context of their own
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - the context
+    pub fn default_context<C: Context + 'static>(mut self, context: C) -> Self {
+        self.default_context = Some(Box::new(context));
+        self
+    }
+
+    /// Replaces the `DefaultSummary` rendering the info line and the verbose output
+    ///
+    /// # Arguments
+    ///
+    /// * `summary` - the summary
+    pub fn summary<S: Summary + 'static>(mut self, summary: S) -> Self {
+        self.summary = Box::new(summary);
+        self
+    }
+
+    /// Probes all resources, evaluates their metrics and returns the resulting `CheckResult`.
+    /// If a resource fails, the result describes the error instead.
+    pub fn evaluate(&self) -> CheckResult {
+        let mut evaluations = Vec::new();
+        for resource in &self.resources {
+            let metrics = match resource.probe() {
+                Ok(metrics) => metrics,
+                Err(e) => return CheckResult::from(e),
+            };
+            evaluations.extend(metrics.iter().map(|m| self.evaluate_metric(m)));
+        }
+
+        let state = State::worst(evaluations.iter().map(Evaluation::state));
+        let info = match state {
+            State::OK => self.summary.ok(&evaluations),
+            _ => self.summary.problem(&evaluations),
+        };
+        let verbose = self.summary.verbose(&evaluations);
+        let metrics: Vec<_> = evaluations.into_iter().map(|e| e.metric).collect();
+        let mut cr = CheckResult::new(state).set_info(info);
+        if !metrics.is_empty() {
+            cr = cr.set_perf_data(PerfData::from_metrics(metrics));
+        }
+        verbose.into_iter().fold(cr, |cr, line| cr.add_debug(1, line))
+    }
+
+    /// Evaluates `metric` in its context
+    fn evaluate_metric(&self, metric: &Metric) -> Evaluation {
+        let context = self
+            .contexts
+            .iter()
+            .find(|(label, _)| *label == metric.label)
+            .map(|(_, context)| context)
+            .or(self.default_context.as_ref());
+        match context {
+            Some(context) => context.evaluate(metric),
+            None => Evaluation::new(metric.clone(), State::OK),
+        }
+    }
+}
+
+
+impl Plugin for Pipeline {
+    fn run(&self) -> Result<CheckResult, runner::Error> {
+        Ok(self.evaluate())
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+use std::sync::Once;
+
+/// The exit code of an interrupted check, UNKNOWN
+const EXIT_INTERRUPTED: i32 = 3;
+
+
+/// Prepares the process for printing a plugin result, once per process. On Windows, the console
+/// output code page is switched to UTF-8, so callers decoding the output with the console's code
+/// page, like PowerShell and thus Icinga for Windows, read non-ASCII characters correctly. Output
+/// to a console window is converted to UTF-16 by the standard library. Ctrl+C, Ctrl+Break and
+/// closing the console print `UNKNOWN - check interrupted` and exit with UNKNOWN (3), instead of
+/// terminating with `STATUS_CONTROL_C_EXIT`. Does nothing on other platforms, where Icinga2
+/// handles signals itself.
+pub(crate) fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(imp::init);
+}
+
+
+/// Flushes stdout and exits with `code`. The Monitoring Plugins exit codes are used as is on all
+/// platforms.
+pub(crate) fn exit(code: i32) -> ! {
+    let _ = io::stdout().flush();
+    process::exit(code)
+}
+
+
+/// Redirects stdout to stderr until the returned guard is dropped, so output of the check, e.g.
+/// a stray `println!` in a dependency, cannot corrupt the plugin output. Data the check left in
+/// the buffer of `io::stdout()` is flushed to stderr as well. Does nothing if stdout cannot be
+/// redirected.
+pub(crate) fn redirect_stdout() -> StdoutGuard {
+    let _ = io::stdout().flush();
+    StdoutGuard { saved: imp::redirect_stdout() }
+}
+
+
+/// Restores stdout when dropped, see `redirect_stdout()`
+pub(crate) struct StdoutGuard {
+    /// the original stdout, if it was redirected
+    saved: Option<imp::Saved>,
+}
+
+
+impl Drop for StdoutGuard {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            let _ = io::stdout().flush();
+            imp::restore_stdout(saved);
+        }
+    }
+}
+
+
+/// Locks `file` exclusively without waiting, like `flock(2)`. Returns `false` if the lock is held
+/// through another open file, also by the same process. The lock is released by
+/// `unlock_file()`, or when the file is closed.
+#[cfg_attr(not(feature = "persist"), allow(dead_code))]
+pub(crate) fn try_lock_file(file: &File) -> io::Result<bool> {
+    imp::try_lock_file(file)
+}
+
+
+/// Releases the lock on `file` acquired by `try_lock_file()`
+#[cfg_attr(not(feature = "persist"), allow(dead_code))]
+pub(crate) fn unlock_file(file: &File) {
+    imp::unlock_file(file)
+}
+
+
+/// Prints the result of an interrupted check and exits
+#[cfg_attr(not(windows), allow(dead_code))]
+fn interrupted() -> ! {
+    let _ = writeln!(io::stdout(), "UNKNOWN - check interrupted");
+    exit(EXIT_INTERRUPTED)
+}
+
+
+#[cfg(windows)]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    type Bool = i32;
+    type Handle = *mut std::ffi::c_void;
+
+    const CP_UTF8: u32 = 65001;
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    const CTRL_CLOSE_EVENT: u32 = 2;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const LOCKFILE_FAIL_IMMEDIATELY: u32 = 1;
+    const LOCKFILE_EXCLUSIVE_LOCK: u32 = 2;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    /// The original stdout handle
+    pub(super) struct Saved(Handle);
+
+    /// The `OVERLAPPED` structure, passing the offset of the locked region
+    #[repr(C)]
+    struct Overlapped {
+        internal: usize,
+        internal_high: usize,
+        offset: u32,
+        offset_high: u32,
+        event: Handle,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleOutputCP(code_page: u32) -> Bool;
+        fn SetConsoleCtrlHandler(handler: Option<unsafe extern "system" fn(u32) -> Bool>, add: Bool) -> Bool;
+        fn GetStdHandle(std_handle: u32) -> Handle;
+        fn SetStdHandle(std_handle: u32, handle: Handle) -> Bool;
+        fn LockFileEx(file: Handle, flags: u32, reserved: u32, bytes_low: u32, bytes_high: u32,
+                      overlapped: *mut Overlapped) -> Bool;
+        fn UnlockFile(file: Handle, offset_low: u32, offset_high: u32, bytes_low: u32, bytes_high: u32) -> Bool;
+    }
+
+    /// Handles console control events, called by Windows on a new thread, so exiting is safe
+    unsafe extern "system" fn handle_ctrl(event: u32) -> Bool {
+        match event {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT => super::interrupted(),
+            // logoff and shutdown events are left to the default handler
+            _ => 0,
+        }
+    }
+
+    pub(super) fn init() {
+        // both fail without a console, e.g. when started by a service, which is fine
+        unsafe {
+            SetConsoleOutputCP(CP_UTF8);
+            SetConsoleCtrlHandler(Some(handle_ctrl), 1);
+        }
+    }
+
+    // the standard library looks up the stdout handle on every write
+    pub(super) fn redirect_stdout() -> Option<Saved> {
+        unsafe {
+            let stdout = GetStdHandle(STD_OUTPUT_HANDLE);
+            match SetStdHandle(STD_OUTPUT_HANDLE, GetStdHandle(STD_ERROR_HANDLE)) {
+                0 => None,
+                _ => Some(Saved(stdout)),
+            }
+        }
+    }
+
+    pub(super) fn restore_stdout(saved: Saved) {
+        unsafe {
+            SetStdHandle(STD_OUTPUT_HANDLE, saved.0);
+        }
+    }
+
+    // the whole file is locked, from offset 0 on
+    pub(super) fn try_lock_file(file: &File) -> io::Result<bool> {
+        let mut overlapped =
+            Overlapped { internal: 0, internal_high: 0, offset: 0, offset_high: 0, event: std::ptr::null_mut() };
+        let flags = LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY;
+        match unsafe { LockFileEx(file.as_raw_handle(), flags, 0, u32::MAX, u32::MAX, &mut overlapped) } {
+            0 => match io::Error::last_os_error() {
+                e if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION) => Ok(false),
+                e => Err(e),
+            },
+            _ => Ok(true),
+        }
+    }
+
+    pub(super) fn unlock_file(file: &File) {
+        unsafe {
+            UnlockFile(file.as_raw_handle(), 0, 0, u32::MAX, u32::MAX);
+        }
+    }
+}
+
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+
+    const STDOUT: c_int = 1;
+    const STDERR: c_int = 2;
+    const LOCK_EX: c_int = 2;
+    const LOCK_NB: c_int = 4;
+    const LOCK_UN: c_int = 8;
+
+    /// A duplicate of the original stdout file descriptor
+    pub(super) struct Saved(c_int);
+
+    extern "C" {
+        fn dup(fd: c_int) -> c_int;
+        fn dup2(src: c_int, dst: c_int) -> c_int;
+        fn close(fd: c_int) -> c_int;
+        fn flock(fd: c_int, operation: c_int) -> c_int;
+    }
+
+    pub(super) fn init() {}
+
+    pub(super) fn redirect_stdout() -> Option<Saved> {
+        unsafe {
+            let saved = dup(STDOUT);
+            if saved < 0 {
+                return None;
+            }
+            if dup2(STDERR, STDOUT) < 0 {
+                close(saved);
+                return None;
+            }
+            Some(Saved(saved))
+        }
+    }
+
+    pub(super) fn restore_stdout(saved: Saved) {
+        unsafe {
+            dup2(saved.0, STDOUT);
+            close(saved.0);
+        }
+    }
+
+    pub(super) fn try_lock_file(file: &File) -> io::Result<bool> {
+        loop {
+            if unsafe { flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } == 0 {
+                return Ok(true);
+            }
+            match io::Error::last_os_error() {
+                e if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                e if e.kind() == io::ErrorKind::Interrupted => {}
+                e => return Err(e),
+            }
+        }
+    }
+
+    pub(super) fn unlock_file(file: &File) {
+        unsafe {
+            flock(file.as_raw_fd(), LOCK_UN);
+        }
+    }
+}
+
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::fs::File;
+    use std::io;
+
+    pub(super) struct Saved;
+
+    pub(super) fn init() {}
+
+    pub(super) fn redirect_stdout() -> Option<Saved> {
+        None
+    }
+
+    pub(super) fn restore_stdout(_saved: Saved) {}
+
+    pub(super) fn try_lock_file(_file: &File) -> io::Result<bool> {
+        Err(io::Error::new(io::ErrorKind::Other, "file locks are not supported on this platform"))
+    }
+
+    pub(super) fn unlock_file(_file: &File) {}
+}
@@ -0,0 +1,268 @@
+use crate::check::{CheckResult, Metric, State};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// Checks the CPU utilization of all cores, measured between two samples of `/proc/stat`. The
+/// busy time, everything but idle and iowait, and the iowait time can be evaluated against
+/// thresholds. The user, system, iowait and steal times are reported as performance data, in
+/// percent of the time of all cores.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::cpu::Cpu;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+/// use std::time::Duration;
+///
+/// let check = Cpu::new()
+///     .interval(Duration::from_secs(5))
+///     .total(Thresholds::new().warning("80".parse().unwrap()).critical("95".parse().unwrap()))
+///     .iowait(Thresholds::new().warning("20".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cpu {
+    /// the time between the two samples
+    interval: Duration,
+    /// the thresholds of the busy time in percent
+    total: Thresholds,
+    /// the thresholds of the iowait time in percent
+    iowait: Thresholds,
+}
+
+
+impl Cpu {
+    /// Creates a new `Cpu` probe sampling over one second, without thresholds
+    pub fn new() -> Self {
+        Self { interval: Duration::from_secs(1), total: Thresholds::new(), iowait: Thresholds::new() }
+    }
+
+    /// Sets the time between the two samples. Longer intervals smooth out short spikes, but
+    /// count against the timeout of the check.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - the time between the samples
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the thresholds of the busy time in percent, the time not spent idle or waiting for
+    /// I/O
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `80`
+    pub fn total(mut self, thresholds: Thresholds) -> Self {
+        self.total = thresholds;
+        self
+    }
+
+    /// Sets the thresholds of the time spent waiting for I/O in percent
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `20`
+    pub fn iowait(mut self, thresholds: Thresholds) -> Self {
+        self.iowait = thresholds;
+        self
+    }
+
+    /// Takes two samples of the CPU times, `interval` apart, and returns the utilization
+    /// between them. Fails with `ProbeError::Unsupported` on other platforms than Linux.
+    pub fn query(&self) -> Result<CpuUsage, ProbeError> {
+        let first = CpuTimes::read()?;
+        thread::sleep(self.interval);
+        let second = CpuTimes::read()?;
+        Ok(first.usage(&second))
+    }
+
+    /// Samples the CPU times and returns the `CheckResult` for the utilization, see
+    /// `evaluate()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.evaluate(&self.query()?))
+    }
+
+    /// Returns the `CheckResult` for `usage`, the worst of the busy and the iowait time against
+    /// their thresholds, with the metrics `total`, `user`, `system`, `iowait` and `steal` in
+    /// percent
+    ///
+    /// # Arguments
+    ///
+    /// * `usage` - the utilization of the CPUs
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::cpu::{Cpu, CpuTimes};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let first: CpuTimes = "cpu  1000 0 500 8000 100 0 0 0 0 0".parse().unwrap();
+    /// let second: CpuTimes = "cpu  1600 0 700 8800 400 0 0 0 0 0".parse().unwrap();
+    /// let cpu = Cpu::new().iowait(Thresholds::new().warning("10".parse().unwrap()));
+    /// assert_eq!(cpu.evaluate(&first.usage(&second)).to_string(),
+    ///     "WARNING - 42.1% CPU used, 15.8% iowait | 'total'=42.1%;;;0;100 'user'=31.6%;;;0;100 \
+    ///     'system'=10.5%;;;0;100 'iowait'=15.8%;10;;0;100 'steal'=0.0%;;;0;100 ");
+    /// ```
+    pub fn evaluate(&self, usage: &CpuUsage) -> CheckResult {
+        let state = State::worst([self.total.state(usage.total()), self.iowait.state(usage.iowait)]);
+        let percent = |label: &'static str, value: f64| Metric::new(label, value).uom("%").precision(1).min("0").max("100");
+        CheckResult::new(state)
+            .set_info(usage.to_string())
+            .add_metric(with_thresholds(percent("total", usage.total()), &self.total))
+            .add_metric(percent("user", usage.user))
+            .add_metric(percent("system", usage.system))
+            .add_metric(with_thresholds(percent("iowait", usage.iowait), &self.iowait))
+            .add_metric(percent("steal", usage.steal))
+    }
+}
+
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Plugin for Cpu {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The accumulated times all cores spent in each mode since boot, in clock ticks, as listed in
+/// the `cpu` line of `/proc/stat`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CpuTimes {
+    /// time spent in user mode
+    pub user: u64,
+    /// time spent in user mode with low priority
+    pub nice: u64,
+    /// time spent in kernel mode
+    pub system: u64,
+    /// time spent idle
+    pub idle: u64,
+    /// time spent idle while waiting for I/O
+    pub iowait: u64,
+    /// time spent servicing interrupts
+    pub irq: u64,
+    /// time spent servicing softirqs
+    pub softirq: u64,
+    /// time stolen by the hypervisor for other virtual machines
+    pub steal: u64,
+}
+
+
+impl CpuTimes {
+    /// Reads the current times from `/proc/stat`. Fails with `ProbeError::Unsupported` on
+    /// other platforms than Linux.
+    pub fn read() -> Result<Self, ProbeError> {
+        if cfg!(target_os = "linux") {
+            std::fs::read_to_string("/proc/stat")?.parse()
+        } else {
+            Err(ProbeError::Unsupported("CPU"))
+        }
+    }
+
+    /// Returns the sum of all times
+    fn sum(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle + self.iowait + self.irq + self.softirq + self.steal
+    }
+
+    /// Returns the utilization between these times and the `later` ones, in percent of the
+    /// time passed. Counters running backwards, e.g. after a CPU was taken offline, count as
+    /// zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `later` - the times sampled later
+    pub fn usage(&self, later: &CpuTimes) -> CpuUsage {
+        let elapsed = later.sum().saturating_sub(self.sum());
+        let percent = |earlier: u64, later: u64| match elapsed {
+            0 => 0.0,
+            elapsed => 100.0 * later.saturating_sub(earlier) as f64 / elapsed as f64,
+        };
+        CpuUsage {
+            user: percent(self.user + self.nice, later.user + later.nice),
+            system: percent(self.system + self.irq + self.softirq, later.system + later.irq + later.softirq),
+            idle: percent(self.idle, later.idle),
+            iowait: percent(self.iowait, later.iowait),
+            steal: percent(self.steal, later.steal),
+        }
+    }
+}
+
+
+impl FromStr for CpuTimes {
+    type Err = ProbeError;
+
+    /// Parses the `cpu` line of the contents of `/proc/stat`, older kernels without all
+    /// columns report zero for the missing ones
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = s
+            .lines()
+            .find(|l| l.starts_with("cpu "))
+            .ok_or_else(|| ProbeError::Parse(String::from("/proc/stat without cpu line")))?;
+        let mut columns = line.split_whitespace().skip(1).map(|c| c.parse::<u64>());
+        let mut next = || match columns.next() {
+            Some(Ok(ticks)) => Ok(ticks),
+            None => Ok(0),
+            Some(Err(_)) => Err(ProbeError::Parse(format!("/proc/stat: {}", line))),
+        };
+        Ok(Self {
+            user: next()?,
+            nice: next()?,
+            system: next()?,
+            idle: next()?,
+            iowait: next()?,
+            irq: next()?,
+            softirq: next()?,
+            steal: next()?,
+        })
+    }
+}
+
+
+/// The CPU utilization over an interval, in percent of the time of all cores
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CpuUsage {
+    /// time spent in user mode, including low priority processes
+    pub user: f64,
+    /// time spent in kernel mode, including interrupts
+    pub system: f64,
+    /// time spent idle
+    pub idle: f64,
+    /// time spent idle while waiting for I/O
+    pub iowait: f64,
+    /// time stolen by the hypervisor
+    pub steal: f64,
+}
+
+
+impl CpuUsage {
+    /// Returns the busy time, everything but idle and iowait
+    pub fn total(&self) -> f64 {
+        self.user + self.system + self.steal
+    }
+}
+
+
+impl fmt::Display for CpuUsage {
+    /// Formats the utilization, e.g. `42.1% CPU used, 15.8% iowait`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}% CPU used, {:.1}% iowait", self.total(), self.iowait)?;
+        if self.steal >= 0.05 {
+            write!(f, ", {:.1}% steal", self.steal)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+use crate::check::{CheckResult, State};
+use crate::filter::FilterSet;
+use crate::group::group;
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use crate::units::format_bytes;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The file system types without disk space, never checked unless given explicitly
+const PSEUDO_FS_TYPES: [&str; 21] = [
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "rpc_pipefs",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tracefs",
+];
+
+
+/// Checks the space and inodes used on file systems, like `check_disk`. Without explicit mount
+/// points, all mounted file systems with disk space are checked. The used space is evaluated in
+/// percent of the space available to unprivileged users, like `df` reports it, or as free
+/// bytes. Every file system reports its metrics in its own `MetricGroup`, the group `total`
+/// sums up the space of all of them.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::filter::FilterSet;
+/// use icingaplugin_rs::probes::disk::Disks;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// let check = Disks::new()
+///     .filter(FilterSet::from_args(&[], &["/boot/efi", "/snap/*"], false).unwrap())
+///     .exclude_type("tmpfs")
+///     .used(Thresholds::new().warning("80".parse().unwrap()).critical("90".parse().unwrap()))
+///     .inodes(Thresholds::new().critical("95".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Disks {
+    /// the mount points checked, all with disk space if empty
+    mounts: Vec<PathBuf>,
+    /// the filter selecting mount points
+    filter: FilterSet,
+    /// the file system types excluded
+    exclude_types: Vec<String>,
+    /// the thresholds of the used space in percent
+    used: Thresholds,
+    /// the thresholds of the free space in bytes
+    free: Thresholds,
+    /// the thresholds of the used inodes in percent
+    inodes: Thresholds,
+}
+
+
+impl Disks {
+    /// Creates a new `Disks` probe checking all mounted file systems without thresholds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the file system mounted at `path`, instead of all file systems. Can be called
+    /// multiple times.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the mount point, or any path on the file system
+    pub fn mount<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.mounts.push(path.into());
+        self
+    }
+
+    /// Checks only the file systems whose mount point is selected by `filter`, e.g. built from
+    /// `--include`/`--exclude` options. Patterns match the whole mount point, so `/snap/*`
+    /// excludes the file systems mounted below `/snap`, but not `/snap` itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - the filter selecting mount points
+    pub fn filter(mut self, filter: FilterSet) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Excludes file systems of `fs_type`, e.g. `tmpfs` or `nfs4`. Can be called multiple
+    /// times.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs_type` - the file system type as listed in `/proc/mounts`
+    pub fn exclude_type<S: Into<String>>(mut self, fs_type: S) -> Self {
+        self.exclude_types.push(fs_type.into());
+        self
+    }
+
+    /// Sets the thresholds of the used space in percent
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `90`
+    pub fn used(mut self, thresholds: Thresholds) -> Self {
+        self.used = thresholds;
+        self
+    }
+
+    /// Sets the thresholds of the free space in bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `1073741824:` to alert below
+    ///   1 GiB free
+    pub fn free(mut self, thresholds: Thresholds) -> Self {
+        self.free = thresholds;
+        self
+    }
+
+    /// Sets the thresholds of the used inodes in percent, not evaluated for file systems
+    /// without a fixed number of inodes like btrfs
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `90`
+    pub fn inodes(mut self, thresholds: Thresholds) -> Self {
+        self.inodes = thresholds;
+        self
+    }
+
+    /// Returns whether the file system at `path` of `fs_type` is excluded
+    fn excluded(&self, path: &Path, fs_type: &str) -> bool {
+        !self.filter.matches(&path.to_string_lossy()) || self.exclude_types.iter().any(|t| t == fs_type)
+    }
+
+    /// Returns the usage of the file systems checked. Discovering the mounted file systems
+    /// fails with `ProbeError::Unsupported` on other platforms than Linux, querying them on
+    /// other platforms than Unix.
+    pub fn query(&self) -> Result<Vec<FsUsage>, ProbeError> {
+        let mut usages = Vec::new();
+        if self.mounts.is_empty() {
+            for (path, fs_type) in imp::mounts()? {
+                if PSEUDO_FS_TYPES.contains(&fs_type.as_str()) || self.excluded(&path, &fs_type) {
+                    continue;
+                }
+                match imp::usage(&path, fs_type) {
+                    // file systems without disk space, and the ones of other users, e.g. FUSE
+                    Ok(usage) if usage.total == 0 => {}
+                    Err(ProbeError::Io(e)) if e.kind() == std::io::ErrorKind::PermissionDenied => {}
+                    usage => usages.push(usage?),
+                }
+            }
+        } else {
+            for path in &self.mounts {
+                let usage = imp::usage(path, String::new())?;
+                if !self.excluded(path, &usage.fs_type) {
+                    usages.push(usage);
+                }
+            }
+        }
+        Ok(usages)
+    }
+
+    /// Queries the file systems and returns the `CheckResult` for their usage, see
+    /// `evaluate()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.evaluate(&self.query()?))
+    }
+
+    /// Returns the state of one file system, the worst of its usage against all thresholds
+    ///
+    /// # Arguments
+    ///
+    /// * `usage` - the usage of the file system
+    pub fn state(&self, usage: &FsUsage) -> State {
+        State::worst([
+            self.used.state(usage.used_percent()),
+            self.free.state(usage.available as f64),
+            usage.inodes_used_percent().map_or(State::OK, |p| self.inodes.state(p)),
+        ])
+    }
+
+    /// Returns the `CheckResult` for `usages`, in the worst state of all file systems. Every
+    /// file system is listed in the long output and reports the metrics `used` and `free` in
+    /// bytes, `used_pct` and, if it has a fixed number of inodes, `inodes_used_pct` in its
+    /// `MetricGroup` named like its mount point. The group `total` holds the sum of `used` and
+    /// `free` of all file systems.
+    ///
+    /// # Arguments
+    ///
+    /// * `usages` - the usage of the checked file systems
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::disk::{Disks, FsUsage};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let var = FsUsage {
+    ///     mount: "/var".into(),
+    ///     fs_type: String::from("ext4"),
+    ///     total: 100 << 30,
+    ///     used: 92 << 30,
+    ///     available: 3 << 30,
+    ///     inodes: 6_553_600,
+    ///     inodes_free: 6_000_000,
+    /// };
+    /// let disks = Disks::new().used(Thresholds::new().critical("90".parse().unwrap()));
+    /// let cr = disks.evaluate(&[var]);
+    /// assert_eq!(cr.to_string().lines().next(), Some(
+    ///     "CRITICAL - /var 96.8% used, 3.0 GiB of 100.0 GiB free | '/var::used'=98784247808B;;;0;107374182400 \
+    ///     '/var::free'=3221225472B;;;0;107374182400 '/var::used_pct'=96.8%;;90;0;100 '/var::inodes_used_pct'=8.4%;;;0;100 \
+    ///     'total::used'=98784247808B;;;0;107374182400 'total::free'=3221225472B;;;0;107374182400 "));
+    /// assert_eq!(cr.long_output(), ["[CRITICAL] /var 96.8% used, 3.0 GiB of 100.0 GiB free, 8.4% inodes used"]);
+    /// ```
+    pub fn evaluate(&self, usages: &[FsUsage]) -> CheckResult {
+        let states: Vec<State> = usages.iter().map(|u| self.state(u)).collect();
+        let state = State::worst(states.iter().copied());
+        let info = if state == State::OK {
+            let (used, total) = usages.iter().fold((0, 0), |(used, total), u| (used + u.used, total + u.total));
+            format!("{} file systems, {} of {} used", usages.len(), format_bytes(used), format_bytes(total))
+        } else {
+            let problems: Vec<String> = usages
+                .iter()
+                .zip(&states)
+                .filter(|(_, s)| **s == state)
+                .map(|(u, _)| format!("{} {:.1}% used, {} of {} free", u.mount.display(), u.used_percent(),
+                    format_bytes(u.available), format_bytes(u.total)))
+                .collect();
+            problems.join(", ")
+        };
+
+        let mut cr = CheckResult::new(state).set_info(info);
+        for (usage, state) in usages.iter().zip(&states) {
+            cr = cr.add_long_output(format!("[{}] {}", state, usage));
+            let g = group(usage.mount.to_string_lossy());
+            let total = usage.total.to_string();
+            cr = cr
+                .add_metric(g.metric("used", usage.used).uom("B").min("0").max(total.clone()))
+                .add_metric(with_thresholds(g.metric("free", usage.available), &self.free).uom("B").min("0").max(total))
+                .add_metric(with_thresholds(g.metric("used_pct", usage.used_percent()), &self.used).uom("%")
+                    .precision(1).min("0").max("100"));
+            if let Some(percent) = usage.inodes_used_percent() {
+                cr = cr.add_metric(with_thresholds(g.metric("inodes_used_pct", percent), &self.inodes).uom("%")
+                    .precision(1).min("0").max("100"));
+            }
+        }
+        let g = group("total");
+        let (used, free, total) = usages.iter().fold((0, 0, 0), |(used, free, total), u| {
+            (used + u.used, free + u.available, total + u.total)
+        });
+        cr.add_metric(g.metric("used", used).uom("B").min("0").max(total.to_string()))
+            .add_metric(g.metric("free", free).uom("B").min("0").max(total.to_string()))
+    }
+}
+
+
+impl Plugin for Disks {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The usage of a file system
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FsUsage {
+    /// the mount point, or the path the file system was queried with
+    pub mount: PathBuf,
+    /// the file system type, empty if unknown
+    pub fs_type: String,
+    /// the size in bytes
+    pub total: u64,
+    /// the used space in bytes
+    pub used: u64,
+    /// the space available to unprivileged users in bytes, without the blocks reserved for
+    /// root
+    pub available: u64,
+    /// the number of inodes, zero if the file system allocates them dynamically
+    pub inodes: u64,
+    /// the number of free inodes
+    pub inodes_free: u64,
+}
+
+
+impl FsUsage {
+    /// Returns the used space in percent of the space usable by unprivileged users, as `df`
+    /// reports it
+    pub fn used_percent(&self) -> f64 {
+        match self.used + self.available {
+            0 => 0.0,
+            usable => 100.0 * self.used as f64 / usable as f64,
+        }
+    }
+
+    /// Returns the used inodes in percent, `None` if the file system allocates inodes
+    /// dynamically
+    pub fn inodes_used_percent(&self) -> Option<f64> {
+        match self.inodes {
+            0 => None,
+            inodes => Some(100.0 * inodes.saturating_sub(self.inodes_free) as f64 / inodes as f64),
+        }
+    }
+}
+
+
+impl fmt::Display for FsUsage {
+    /// Formats the usage, e.g. `/var 96.8% used, 3.0 GiB of 100.0 GiB free, 8.4% inodes used`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:.1}% used, {} of {} free", self.mount.display(), self.used_percent(),
+            format_bytes(self.available), format_bytes(self.total))?;
+        if let Some(percent) = self.inodes_used_percent() {
+            write!(f, ", {:.1}% inodes used", percent)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Returns the usage of the file system `path` is on. Fails with `ProbeError::NotFound` if
+/// `path` does not exist and with `ProbeError::Unsupported` on other platforms than Unix.
+///
+/// # Arguments
+///
+/// * `path` - the mount point, or any path on the file system
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use icingaplugin_rs::probes::disk::usage;
+/// let root = usage("/").unwrap();
+/// assert!(root.used <= root.total);
+/// # }
+/// ```
+pub fn usage<P: AsRef<Path>>(path: P) -> Result<FsUsage, ProbeError> {
+    imp::usage(path.as_ref(), String::new())
+}
+
+
+#[cfg(unix)]
+mod imp {
+    use super::FsUsage;
+    use crate::probes::ProbeError;
+    use std::ffi::CString;
+    use std::io;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    pub(super) fn usage(path: &Path, fs_type: String) -> Result<FsUsage, ProbeError> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| ProbeError::NotFound(path.display().to_string()))?;
+        let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            let e = io::Error::last_os_error();
+            return Err(match e.kind() {
+                io::ErrorKind::NotFound => ProbeError::NotFound(path.display().to_string()),
+                _ => ProbeError::Io(e),
+            });
+        }
+        let fragment = stat.f_frsize as u64;
+        Ok(FsUsage {
+            mount: path.to_path_buf(),
+            fs_type,
+            total: stat.f_blocks as u64 * fragment,
+            used: (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * fragment,
+            available: stat.f_bavail as u64 * fragment,
+            inodes: stat.f_files as u64,
+            inodes_free: stat.f_ffree as u64,
+        })
+    }
+
+    /// Returns the mount points and file system types of the mounted file systems, the last
+    /// mount of a mount point hiding the earlier ones
+    #[cfg(target_os = "linux")]
+    pub(super) fn mounts() -> Result<Vec<(PathBuf, String)>, ProbeError> {
+        let table = std::fs::read_to_string("/proc/self/mounts")?;
+        let mut mounts: Vec<(PathBuf, String)> = Vec::new();
+        for line in table.lines() {
+            let mut fields = line.split_whitespace().skip(1);
+            let (path, fs_type) = match (fields.next(), fields.next()) {
+                (Some(path), Some(fs_type)) => (PathBuf::from(unescape(path)), fs_type.to_string()),
+                _ => return Err(ProbeError::Parse(format!("/proc/self/mounts: {}", line))),
+            };
+            mounts.retain(|(p, _)| *p != path);
+            mounts.push((path, fs_type));
+        }
+        Ok(mounts)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn mounts() -> Result<Vec<(PathBuf, String)>, ProbeError> {
+        Err(ProbeError::Unsupported("disk discovery"))
+    }
+
+    /// Decodes the octal escapes of spaces, tabs, newlines and backslashes in `/proc/mounts`
+    #[cfg(target_os = "linux")]
+    fn unescape(field: &str) -> String {
+        field.replace("\\040", " ").replace("\\011", "\t").replace("\\012", "\n").replace("\\134", "\\")
+    }
+}
+
+
+#[cfg(not(unix))]
+mod imp {
+    use super::FsUsage;
+    use crate::probes::ProbeError;
+    use std::path::{Path, PathBuf};
+
+    pub(super) fn usage(_path: &Path, _fs_type: String) -> Result<FsUsage, ProbeError> {
+        Err(ProbeError::Unsupported("disk"))
+    }
+
+    pub(super) fn mounts() -> Result<Vec<(PathBuf, String)>, ProbeError> {
+        Err(ProbeError::Unsupported("disk"))
+    }
+}
@@ -0,0 +1,400 @@
+use crate::check::{CheckResult, Metric};
+use crate::clock::{Clock, SystemClock};
+use crate::filter::{FilterSet, Pattern};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use crate::units::{format_bytes, format_duration};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Checks the age of the newest file in a directory, e.g. to alert if no backup was written
+/// recently. Only regular files directly in the directory are considered, optionally only the
+/// ones whose name matches a glob pattern. An empty directory is CRITICAL.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::file::NewestFile;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// // a dump is written every night
+/// let check = NewestFile::new("/var/backups/postgres")
+///     .pattern("*.sql.gz")
+///     .age(Thresholds::new().warning("93600".parse().unwrap()).critical("180000".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct NewestFile {
+    /// the directory
+    dir: PathBuf,
+    /// the glob pattern file names have to match, if any
+    pattern: Option<String>,
+    /// the thresholds of the age of the newest file in seconds
+    age: Thresholds,
+    /// the clock the age is measured with
+    clock: Arc<dyn Clock>,
+}
+
+
+impl NewestFile {
+    /// Creates a new `NewestFile` probe for the files in `dir`, without thresholds
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - the directory
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into(), pattern: None, age: Thresholds::new(), clock: Arc::new(SystemClock) }
+    }
+
+    /// Considers files whose name matches `pattern` only
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the glob pattern, see `filter::Pattern::Glob`
+    pub fn pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// Sets the thresholds of the age of the newest file in seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `86400` for one day
+    pub fn age(mut self, thresholds: Thresholds) -> Self {
+        self.age = thresholds;
+        self
+    }
+
+    /// Sets the clock the age is measured with, `SystemClock` by default
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Finds the newest file and returns the `CheckResult` for its age, with the metrics `age`
+    /// in seconds and `files`, the number of files considered. Fails with
+    /// `ProbeError::NotFound` if the directory does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::probes::file::NewestFile;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("newest_file_doctest_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("backup.tar"), "data").unwrap();
+    /// let check = NewestFile::new(&dir).age(Thresholds::new().critical("3600".parse().unwrap()));
+    /// assert_eq!(check.check().unwrap().state(), State::OK);
+    /// assert_eq!(check.pattern("*.gz").check().unwrap().state(), State::Critical);
+    /// std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let files = list(&self.dir, self.pattern.as_deref())?;
+        let newest = files.iter().max_by_key(|(_, metadata)| metadata.modified().ok());
+        let (path, modified) = match newest {
+            Some((path, metadata)) => (path, metadata.modified()?),
+            None => {
+                return Ok(CheckResult::critical(format!("no files in {}", self.describe()))
+                    .add_metric(Metric::unknown("age").uom("s"))
+                    .add_metric(Metric::new("files", 0).min("0")))
+            }
+        };
+        let age = self.clock.since(modified);
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+        Ok(CheckResult::new(self.age.state(age.as_secs_f64()))
+            .set_info(format!("newest file {} in {} is {} old", name, self.describe(), format_duration(age)))
+            .add_metric(with_thresholds(Metric::new("age", age.as_secs()).uom("s"), &self.age).min("0"))
+            .add_metric(Metric::new("files", files.len() as u64).min("0")))
+    }
+
+    /// Describes the directory and pattern, e.g. `/var/backups/*.sql.gz`
+    fn describe(&self) -> String {
+        match &self.pattern {
+            Some(pattern) => self.dir.join(pattern).display().to_string(),
+            None => self.dir.display().to_string(),
+        }
+    }
+}
+
+
+impl Plugin for NewestFile {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// Checks the size of a file, e.g. to alert if a log file grows too large or a database dump is
+/// suspiciously small
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::probes::file::FileSize;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// let path = std::env::temp_dir().join(format!("file_size_doctest_{}", std::process::id()));
+/// std::fs::write(&path, vec![0; 2048]).unwrap();
+/// let cr = FileSize::new(&path).size(Thresholds::new().warning("1024".parse().unwrap())).check().unwrap();
+/// assert_eq!(cr.state(), State::Warning);
+/// assert!(cr.to_string().ends_with("is 2.0 KiB | 'size'=2048B;1024;;0; "));
+/// std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSize {
+    /// the file
+    path: PathBuf,
+    /// the thresholds of the size in bytes
+    size: Thresholds,
+}
+
+
+impl FileSize {
+    /// Creates a new `FileSize` probe for `path`, without thresholds
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into(), size: Thresholds::new() }
+    }
+
+    /// Sets the thresholds of the size in bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `1048576:` to alert below
+    ///   1 MiB
+    pub fn size(mut self, thresholds: Thresholds) -> Self {
+        self.size = thresholds;
+        self
+    }
+
+    /// Returns the `CheckResult` for the size of the file, with the metric `size` in bytes.
+    /// Fails with `ProbeError::NotFound` if the file does not exist.
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let size = metadata(&self.path)?.len();
+        Ok(CheckResult::new(self.size.state(size as f64))
+            .set_info(format!("{} is {}", self.path.display(), format_bytes(size)))
+            .add_metric(with_thresholds(Metric::new("size", size).uom("B"), &self.size).min("0")))
+    }
+}
+
+
+impl Plugin for FileSize {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// Checks the number of files matching a glob pattern, e.g. to alert if a spool directory fills
+/// up or expected exports are missing. Wildcards are supported in the file name only, e.g.
+/// `/var/spool/mail/queue/*.msg`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::file::FileCount;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// let check = FileCount::new("/var/spool/postfix/deferred/*")
+///     .count(Thresholds::new().warning("100".parse().unwrap()).critical("500".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileCount {
+    /// the glob pattern
+    pattern: PathBuf,
+    /// the thresholds of the number of matching files
+    count: Thresholds,
+}
+
+
+impl FileCount {
+    /// Creates a new `FileCount` probe for the files matching `pattern`, without thresholds
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the path with a glob pattern as file name, see `filter::Pattern::Glob`
+    pub fn new<P: Into<PathBuf>>(pattern: P) -> Self {
+        Self { pattern: pattern.into(), count: Thresholds::new() }
+    }
+
+    /// Sets the thresholds of the number of matching files
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `1:` if at least one file is
+    ///   expected
+    pub fn count(mut self, thresholds: Thresholds) -> Self {
+        self.count = thresholds;
+        self
+    }
+
+    /// Returns the `CheckResult` for the number of matching regular files, with the metric
+    /// `files`. Fails with `ProbeError::NotFound` if the directory does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::file::FileCount;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("file_count_doctest_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// for name in ["a.msg", "b.msg", "c.tmp"] {
+    ///     std::fs::write(dir.join(name), "").unwrap();
+    /// }
+    /// let cr = FileCount::new(dir.join("*.msg")).count(Thresholds::new().critical("1".parse().unwrap())).check().unwrap();
+    /// assert!(cr.to_string().starts_with("CRITICAL - 2 files match "));
+    /// assert!(cr.to_string().ends_with(" | 'files'=2;;1;0; "));
+    /// std::fs::remove_dir_all(dir).unwrap();
+    /// ```
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let (dir, pattern) = match (self.pattern.parent(), self.pattern.file_name()) {
+            (Some(dir), Some(pattern)) => (if dir.as_os_str().is_empty() { Path::new(".") } else { dir }, pattern),
+            _ => return Err(ProbeError::NotFound(self.pattern.display().to_string())),
+        };
+        let files = list(dir, Some(&pattern.to_string_lossy()))?.len();
+        Ok(CheckResult::new(self.count.state(files as f64))
+            .set_info(format!("{} files match {}", files, self.pattern.display()))
+            .add_metric(with_thresholds(Metric::new("files", files as u64), &self.count).min("0")))
+    }
+}
+
+
+impl Plugin for FileCount {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// Checks that a file or directory exists, and optionally that its permissions are exactly the
+/// expected ones, e.g. that a private key is not readable by others. A missing file and
+/// unexpected permissions are CRITICAL.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::probes::file::FileExists;
+///
+/// let path = std::env::temp_dir().join(format!("file_exists_doctest_{}", std::process::id()));
+/// assert_eq!(FileExists::new(&path).check().state(), State::Critical);
+/// std::fs::write(&path, "secret").unwrap();
+/// assert_eq!(FileExists::new(&path).check().state(), State::OK);
+/// std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileExists {
+    /// the file or directory
+    path: PathBuf,
+    /// the expected permission bits, if any
+    mode: Option<u32>,
+}
+
+
+impl FileExists {
+    /// Creates a new `FileExists` probe for `path`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file or directory
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into(), mode: None }
+    }
+
+    /// Expects the permission bits of the file to be `mode`, e.g. `0o600`. Only checked on
+    /// Unix, where the set-id and sticky bits are compared as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - the expected permission bits
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Returns the `CheckResult` for the file, with the metric `size` in bytes if it exists.
+    /// A file which cannot be accessed is reported as missing, with the reason.
+    pub fn check(&self) -> CheckResult {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return CheckResult::critical(format!("{} does not exist", self.path.display()))
+            }
+            Err(e) => return CheckResult::critical(format!("{} is not accessible: {}", self.path.display(), e)),
+        };
+        let kind = if metadata.is_dir() { "directory" } else { "file" };
+        let mut cr = CheckResult::ok(format!("{} {} exists", kind, self.path.display()));
+        #[cfg(unix)]
+        if let Some(expected) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = metadata.permissions().mode() & 0o7777;
+            if mode != expected {
+                cr = CheckResult::critical(format!("{} {} has mode {:04o}, expected {:04o}", kind,
+                    self.path.display(), mode, expected));
+            }
+        }
+        if metadata.is_file() {
+            cr = cr.add_metric(Metric::new("size", metadata.len()).uom("B").min("0"));
+        }
+        cr
+    }
+}
+
+
+impl Plugin for FileExists {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check())
+    }
+}
+
+
+/// Returns the metadata of `path`, failing with `ProbeError::NotFound` if it does not exist
+fn metadata(path: &Path) -> Result<fs::Metadata, ProbeError> {
+    fs::metadata(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ProbeError::NotFound(path.display().to_string()),
+        _ => ProbeError::Io(e),
+    })
+}
+
+
+/// Returns the regular files directly in `dir` whose name matches `pattern`, if any, with their
+/// metadata. Files deleted while the directory is read are skipped.
+fn list(dir: &Path, pattern: Option<&str>) -> Result<Vec<(PathBuf, fs::Metadata)>, ProbeError> {
+    metadata(dir)?;
+    let filter = match pattern {
+        Some(p) => FilterSet::new().include(Pattern::Glob(p.to_string())),
+        None => FilterSet::new(),
+    };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !filter.matches(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_file() => files.push((entry.path(), metadata)),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(files)
+}
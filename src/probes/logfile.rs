@@ -0,0 +1,314 @@
+use crate::check::{CheckResult, Metric, State};
+use crate::persist::{LockPolicy, StateFile};
+use crate::probes::ProbeError;
+use crate::runner::{Error, Plugin};
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The name of the state file the offsets are persisted in by default
+const STATE_FILE: &str = "check_logfile";
+
+/// The maximum number of matched lines listed in the long output
+const MAX_LINES: usize = 10;
+
+
+/// Scans a log file for lines matching warning or critical patterns, a replacement for
+/// `check_logfiles`. Only the lines appended since the previous invocation are read: the
+/// offset scanned up to and the inode of the file are persisted in a `StateFile`. A rotated
+/// file, detected by its inode changing or its size shrinking, is read from the beginning,
+/// after the rest of the old file if it was renamed to `<path>.1`. The first invocation starts
+/// at the end of the file, so old messages are not reported.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::logfile::LogFile;
+/// use icingaplugin_rs::runner::Runner;
+/// use regex::Regex;
+///
+/// let check = LogFile::new("/var/log/syslog")
+///     .warning(Regex::new("(?i)warn").unwrap())
+///     .critical(Regex::new("Out of memory|I/O error").unwrap());
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct LogFile {
+    /// the log file
+    path: PathBuf,
+    /// the patterns of lines counted as warnings
+    warning: Vec<Regex>,
+    /// the patterns of lines counted as critical
+    critical: Vec<Regex>,
+    /// the patterns of lines ignored even if they match another pattern
+    exclude: Vec<Regex>,
+    /// the state file the offsets are persisted in, `check_logfile` in the cache directory if
+    /// not set
+    state_file: Option<PathBuf>,
+}
+
+
+impl LogFile {
+    /// Creates a new `LogFile` probe for `path`, without patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the log file
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into(), warning: Vec::new(), critical: Vec::new(), exclude: Vec::new(), state_file: None }
+    }
+
+    /// Counts lines matching `regex` as warnings. Can be called multiple times.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - the pattern
+    pub fn warning(mut self, regex: Regex) -> Self {
+        self.warning.push(regex);
+        self
+    }
+
+    /// Counts lines matching `regex` as critical, even if they match a warning pattern. Can be
+    /// called multiple times.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - the pattern
+    pub fn critical(mut self, regex: Regex) -> Self {
+        self.critical.push(regex);
+        self
+    }
+
+    /// Ignores lines matching `regex`, e.g. known harmless errors. Can be called multiple times.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - the pattern
+    pub fn exclude(mut self, regex: Regex) -> Self {
+        self.exclude.push(regex);
+        self
+    }
+
+    /// Sets the state file the offsets are persisted in. Checks of different log files share
+    /// the default state file, as the offsets are stored by path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the JSON file
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Scans the lines appended since the previous invocation, see `scan()`. The state file is
+    /// locked while it is updated.
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let load = || match &self.state_file {
+            Some(path) => StateFile::load_from(path.clone()),
+            None => StateFile::load(STATE_FILE),
+        };
+        let _lock = load()?
+            .lock(LockPolicy::Wait(Duration::from_secs(5)))
+            .map_err(|e| ProbeError::Io(io::Error::other(e.to_string())))?;
+        let mut state = load()?;
+        let result = self.scan(&mut state)?;
+        state.save()?;
+        Ok(result)
+    }
+
+    /// Scans the lines appended since the position stored in `state` and stores the new one.
+    /// The result is CRITICAL if any line matches a critical pattern and WARNING if any matches
+    /// a warning pattern, with the metrics `lines`, the number of lines scanned, `warning` and
+    /// `critical`. The last matching lines are listed in the long output. A line still being
+    /// written, without a trailing newline, is scanned by the next invocation. Fails with
+    /// `ProbeError::NotFound` if the file does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state the position is persisted in
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::persist::StateFile;
+    /// use icingaplugin_rs::probes::logfile::LogFile;
+    /// use regex::Regex;
+    /// use std::io::Write;
+    ///
+    /// let path = std::env::temp_dir().join(format!("logfile_doctest_{}.log", std::process::id()));
+    /// std::fs::write(&path, "old error\n").unwrap();
+    /// let check = LogFile::new(&path).critical(Regex::new("error").unwrap());
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap();
+    /// assert_eq!(check.scan(&mut state).unwrap().state().to_string(), "OK");
+    ///
+    /// let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    /// file.write_all(b"disk error\nall fine\n").unwrap();
+    /// assert_eq!(check.scan(&mut state).unwrap().to_string(),
+    ///     "CRITICAL - 1 critical line in 2 new lines: disk error \
+    ///     | 'lines'=2;;;0; 'warning'=0;;;0; 'critical'=1;;;0; \n[CRITICAL] disk error");
+    /// std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn scan(&self, state: &mut StateFile) -> Result<CheckResult, ProbeError> {
+        let key = self.path.display().to_string();
+        let metadata = fs::metadata(&self.path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ProbeError::NotFound(key.clone()),
+            _ => ProbeError::Io(e),
+        })?;
+        let current = Position { inode: inode(&metadata), offset: 0 };
+        let previous = match state.get(&key).and_then(Position::parse) {
+            Some(previous) => previous,
+            None => {
+                state.set(&key, Position { offset: metadata.len(), ..current });
+                return Ok(CheckResult::ok(format!("scanning {} from the next check on", key)));
+            }
+        };
+
+        let mut matches = Matches::default();
+        let offset = if previous.inode == current.inode && previous.offset <= metadata.len() {
+            previous.offset
+        } else {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            let rotated = PathBuf::from(rotated);
+            if fs::metadata(&rotated).is_ok_and(|m| inode(&m) == previous.inode) {
+                read_lines(&rotated, previous.offset, true, |line| self.classify(line, &mut matches))?;
+            }
+            0
+        };
+        let offset = read_lines(&self.path, offset, false, |line| self.classify(line, &mut matches))?;
+        state.set(&key, Position { offset, ..current });
+        Ok(matches.to_check_result())
+    }
+
+    /// Counts `line` in `matches` and remembers it if it matches a pattern
+    fn classify(&self, line: &str, matches: &mut Matches) {
+        matches.lines += 1;
+        if self.exclude.iter().any(|r| r.is_match(line)) {
+            return;
+        }
+        let state = if self.critical.iter().any(|r| r.is_match(line)) {
+            State::Critical
+        } else if self.warning.iter().any(|r| r.is_match(line)) {
+            State::Warning
+        } else {
+            return;
+        };
+        match state {
+            State::Critical => matches.critical += 1,
+            _ => matches.warning += 1,
+        }
+        if matches.last.len() == MAX_LINES {
+            matches.last.remove(0);
+        }
+        matches.last.push((state, line.to_string()));
+    }
+}
+
+
+impl Plugin for LogFile {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The position in a log file scanned up to, persisted as `<inode>:<offset>`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Position {
+    /// the inode of the file, zero on platforms without inodes
+    inode: u64,
+    /// the offset in bytes
+    offset: u64,
+}
+
+
+impl Position {
+    /// Parses a persisted position, `None` if it is invalid
+    fn parse(s: &str) -> Option<Self> {
+        let (inode, offset) = s.split_once(':')?;
+        Some(Self { inode: inode.parse().ok()?, offset: offset.parse().ok()? })
+    }
+}
+
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.inode, self.offset)
+    }
+}
+
+
+/// The lines matched by one scan
+#[derive(Debug, Default)]
+struct Matches {
+    /// the number of lines scanned
+    lines: u64,
+    /// the number of lines matching a warning pattern
+    warning: u64,
+    /// the number of lines matching a critical pattern
+    critical: u64,
+    /// the last matching lines with their state, at most `MAX_LINES`
+    last: Vec<(State, String)>,
+}
+
+
+impl Matches {
+    /// Returns the `CheckResult` for the matches
+    fn to_check_result(&self) -> CheckResult {
+        let plural = |n: u64, what: &str| format!("{} {} line{}", n, what, if n == 1 { "" } else { "s" });
+        let counts = match (self.critical, self.warning) {
+            (0, 0) => String::from("no matches"),
+            (0, warning) => plural(warning, "warning"),
+            (critical, 0) => plural(critical, "critical"),
+            (critical, warning) => format!("{} and {}", plural(critical, "critical"), plural(warning, "warning")),
+        };
+        let mut info = format!("{} in {} new line{}", counts, self.lines, if self.lines == 1 { "" } else { "s" });
+        let state = if self.critical > 0 { State::Critical } else if self.warning > 0 { State::Warning } else { State::OK };
+        if let Some((_, line)) = self.last.iter().rev().find(|(s, _)| *s == state) {
+            info = format!("{}: {}", info, line);
+        }
+        let mut cr = CheckResult::new(state).set_info(info);
+        for (state, line) in &self.last {
+            cr = cr.add_long_output(format!("[{}] {}", state, line));
+        }
+        cr.add_metric(Metric::new("lines", self.lines).min("0"))
+            .add_metric(Metric::new("warning", self.warning).min("0"))
+            .add_metric(Metric::new("critical", self.critical).min("0"))
+    }
+}
+
+
+/// Calls `f` with each line of `path` from `offset` on, without the line ending, and returns the
+/// offset after the last line. A trailing line without a newline is skipped unless `partial`.
+fn read_lines<F: FnMut(&str)>(path: &Path, mut offset: u64, partial: bool, mut f: F) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 || (!partial && buf.last() != Some(&b'\n')) {
+            return Ok(offset);
+        }
+        offset += read as u64;
+        let line = String::from_utf8_lossy(&buf);
+        f(line.trim_end_matches(['\n', '\r']));
+    }
+}
+
+
+/// Returns the inode of a file, zero on platforms without inodes, where rotation is only
+/// detected by the file shrinking
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
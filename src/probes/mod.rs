@@ -0,0 +1,95 @@
+use crate::check::Metric;
+use crate::error::PluginError;
+use crate::thresholds::Thresholds;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[cfg(feature = "cpu")]
+pub mod cpu;
+#[cfg(feature = "disk")]
+pub mod disk;
+#[cfg(feature = "file")]
+pub mod file;
+#[cfg(feature = "logfile")]
+pub mod logfile;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "process")]
+pub mod process;
+#[cfg(feature = "systemd")]
+pub mod systemd;
+#[cfg(feature = "tcp")]
+pub mod tcp;
+#[cfg(feature = "uptime")]
+pub mod uptime;
+#[cfg(feature = "windows-service")]
+pub mod windows_service;
+
+
+/// An error occurring while a probe gathers the state of the monitored object
+#[derive(Debug)]
+pub enum ProbeError {
+    /// Reading the state failed
+    Io(io::Error),
+    /// The monitored object does not exist, containing its name
+    NotFound(String),
+    /// The state could not be parsed, containing a description
+    Parse(String),
+    /// The probe is not available on this platform, containing its name
+    Unsupported(&'static str),
+}
+
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Io(e) => write!(f, "{}", e),
+            ProbeError::NotFound(name) => write!(f, "{} does not exist", name),
+            ProbeError::Parse(msg) => write!(f, "unexpected output: {}", msg),
+            ProbeError::Unsupported(probe) => write!(f, "the {} probe is not supported on this platform", probe),
+        }
+    }
+}
+
+
+impl Error for ProbeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProbeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+
+impl From<io::Error> for ProbeError {
+    fn from(e: io::Error) -> Self {
+        ProbeError::Io(e)
+    }
+}
+
+
+impl From<ProbeError> for PluginError {
+    fn from(e: ProbeError) -> Self {
+        match e {
+            ProbeError::Io(e) => PluginError::Io(e),
+            ProbeError::Parse(msg) => PluginError::Parse(msg),
+            e => PluginError::Config(e.to_string()),
+        }
+    }
+}
+
+
+/// Adds the ranges of `thresholds` to `metric`, so the performance data shows what the probe
+/// evaluated
+#[cfg_attr(not(any(feature = "cpu", feature = "disk", feature = "file", feature = "net", feature = "process", feature = "systemd", feature = "tcp", feature = "uptime")), allow(dead_code))]
+pub(crate) fn with_thresholds(mut metric: Metric, thresholds: &Thresholds) -> Metric {
+    if let Some(warning) = thresholds.warning_range() {
+        metric = metric.warning(warning.to_string());
+    }
+    if let Some(critical) = thresholds.critical_range() {
+        metric = metric.critical(critical.to_string());
+    }
+    metric
+}
@@ -0,0 +1,425 @@
+use crate::check::{CheckResult, State};
+use crate::clock::{Clock, SystemClock};
+use crate::filter::FilterSet;
+use crate::group::group;
+use crate::persist::{LockPolicy, StateFile};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::rate::{CounterWidth, Sample};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The name of the state file the counters are persisted in by default
+const STATE_FILE: &str = "check_interfaces";
+
+
+/// Checks the traffic of network interfaces. The byte, packet and error counters are
+/// persisted between invocations in a `StateFile`, so the per-second rates since the previous
+/// invocation can be reported, from the second invocation on. With a configured link speed,
+/// the utilization of the busier direction is evaluated against thresholds.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::net::Interfaces;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// let check = Interfaces::new()
+///     .interface("eth0")
+///     .speed(1_000_000_000)
+///     .utilization(Thresholds::new().warning("70".parse().unwrap()).critical("90".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Interfaces {
+    /// the interfaces checked, all but the loopback interface if empty
+    interfaces: Vec<String>,
+    /// the filter selecting interfaces if none are given explicitly
+    filter: FilterSet,
+    /// the link speed in bits per second, if known
+    speed: Option<u64>,
+    /// the thresholds of the utilization in percent of the link speed
+    utilization: Thresholds,
+    /// the state file the counters are persisted in, `check_interfaces` in the cache
+    /// directory if not set
+    state_file: Option<PathBuf>,
+    /// the maximum age of persisted counters to calculate rates from
+    max_age: Duration,
+    /// the clock the counters are sampled with
+    clock: Arc<dyn Clock>,
+}
+
+
+impl Interfaces {
+    /// Creates a new `Interfaces` probe checking all interfaces but the loopback interface,
+    /// without link speed and thresholds
+    pub fn new() -> Self {
+        Self {
+            interfaces: Vec::new(),
+            filter: FilterSet::new(),
+            speed: None,
+            utilization: Thresholds::new(),
+            state_file: None,
+            max_age: Duration::from_secs(3600),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Checks the interface `name` instead of all interfaces. Can be called multiple times.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the interface, e.g. `eth0`
+    pub fn interface<S: Into<String>>(mut self, name: S) -> Self {
+        self.interfaces.push(name.into());
+        self
+    }
+
+    /// Checks only the interfaces selected by `filter`, e.g. built from `--include`/`--exclude`
+    /// options, instead of all interfaces. Does not apply to interfaces given by `interface()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - the filter selecting interfaces, e.g. excluding `veth*`
+    pub fn filter(mut self, filter: FilterSet) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the link speed of the interfaces, required to report their utilization
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_second` - the link speed, e.g. `1_000_000_000` for Gigabit Ethernet
+    pub fn speed(mut self, bits_per_second: u64) -> Self {
+        self.speed = Some(bits_per_second);
+        self
+    }
+
+    /// Sets the thresholds of the utilization in percent of the link speed, of the busier
+    /// direction. Only evaluated if the link speed is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `70`
+    pub fn utilization(mut self, thresholds: Thresholds) -> Self {
+        self.utilization = thresholds;
+        self
+    }
+
+    /// Sets the state file the counters are persisted in. Checks of different interfaces
+    /// share the default state file, as the counters are stored by interface.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the JSON file
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Sets the maximum age of the persisted counters, one hour by default. Rates over longer
+    /// periods, e.g. after the check was paused, are not reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - the maximum age
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Sets the clock the counters are sampled with, `SystemClock` by default
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the current counters of the interfaces checked. Fails with
+    /// `ProbeError::NotFound` if a configured interface does not exist and with
+    /// `ProbeError::Unsupported` on other platforms than Linux.
+    pub fn query(&self) -> Result<Vec<InterfaceCounters>, ProbeError> {
+        let all = counters()?;
+        if self.interfaces.is_empty() {
+            return Ok(all.into_iter().filter(|c| c.name != "lo" && self.filter.matches(&c.name)).collect());
+        }
+        self.interfaces
+            .iter()
+            .map(|name| {
+                all.iter()
+                    .find(|c| c.name == *name)
+                    .cloned()
+                    .ok_or_else(|| ProbeError::NotFound(format!("interface {}", name)))
+            })
+            .collect()
+    }
+
+    /// Reads the counters and returns the `CheckResult` for their rates since the previous
+    /// invocation, see `evaluate()`. The state file is locked while it is updated.
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let load = || match &self.state_file {
+            Some(path) => StateFile::load_from(path.clone()),
+            None => StateFile::load(STATE_FILE),
+        };
+        let _lock = load()?
+            .lock(LockPolicy::Wait(Duration::from_secs(5)))
+            .map_err(|e| ProbeError::Io(io::Error::other(e.to_string())))?;
+        let mut state = load()?;
+        let result = self.evaluate(&self.query()?, self.clock.now(), &mut state);
+        state.save()?;
+        Ok(result)
+    }
+
+    /// Returns the `CheckResult` for `counters` sampled at `time`, storing them in `state` and
+    /// calculating their rates from the ones stored by the previous invocation. Each interface
+    /// is listed in the long output and reports the metrics `in_bps` and `out_bps` in bits,
+    /// `in_pps`, `out_pps`, `in_errors` and `out_errors` per second and, with a link speed,
+    /// `utilization` in percent in its `MetricGroup`. Its state is the one of the utilization
+    /// of the busier direction against the thresholds.
+    ///
+    /// # Arguments
+    ///
+    /// * `counters` - the counters of the interfaces checked
+    /// * `time` - the time the counters were read
+    /// * `state` - the state holding the counters of the previous invocation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::clock::{Clock, MockClock};
+    /// use icingaplugin_rs::persist::StateFile;
+    /// use icingaplugin_rs::probes::net::{InterfaceCounters, Interfaces};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    /// use std::time::Duration;
+    ///
+    /// let clock = MockClock::default();
+    /// let mut state = StateFile::load_from(std::env::temp_dir().join("never_saved.json")).unwrap().clock(clock.clone());
+    /// let check = Interfaces::new().speed(100_000_000).utilization(Thresholds::new().warning("50".parse().unwrap()));
+    /// let mut eth0 = InterfaceCounters { name: String::from("eth0"), ..Default::default() };
+    /// assert_eq!(check.evaluate(&[eth0.clone()], clock.now(), &mut state).info(),
+    ///     Some("1 interface, rates are available from the next check on"));
+    ///
+    /// clock.advance(Duration::from_secs(60));
+    /// eth0.rx_bytes = 450_000_000;
+    /// eth0.tx_bytes = 7_500_000;
+    /// let cr = check.evaluate(&[eth0], clock.now(), &mut state);
+    /// assert_eq!(cr.to_string().lines().next(), Some(
+    ///     "WARNING - eth0 in 60.0 Mbit/s, out 1.0 Mbit/s, 60.0% utilization | 'eth0::in_bps'=60000000;;;0; \
+    ///     'eth0::out_bps'=1000000;;;0; 'eth0::in_pps'=0.0;;;0; 'eth0::out_pps'=0.0;;;0; 'eth0::in_errors'=0.0;;;0; \
+    ///     'eth0::out_errors'=0.0;;;0; 'eth0::utilization'=60.0%;50;;0;100 "));
+    /// ```
+    pub fn evaluate(&self, counters: &[InterfaceCounters], time: SystemTime, state: &mut StateFile) -> CheckResult {
+        let mut rates = Vec::with_capacity(counters.len());
+        for counter in counters {
+            let mut rate = |name: &str, value: u64| {
+                let key = group(counter.name.as_str()).label(name);
+                // the 64 bit counters practically never wrap, a decreasing one was reset, e.g. by a
+                // reboot, and yields a rate from the next invocation on
+                state.rate(&key, Sample::at(value, time), CounterWidth::Unbounded, self.max_age).ok().flatten()
+            };
+            let rx = [rate("rx_bytes", counter.rx_bytes), rate("rx_packets", counter.rx_packets), rate("rx_errors", counter.rx_errors)];
+            let tx = [rate("tx_bytes", counter.tx_bytes), rate("tx_packets", counter.tx_packets), rate("tx_errors", counter.tx_errors)];
+            rates.push(match (rx, tx) {
+                ([Some(in_bytes), Some(in_packets), Some(in_errors)], [Some(out_bytes), Some(out_packets), Some(out_errors)]) => {
+                    Some(InterfaceRates {
+                        in_bps: in_bytes * 8.0,
+                        out_bps: out_bytes * 8.0,
+                        in_pps: in_packets,
+                        out_pps: out_packets,
+                        in_errors,
+                        out_errors,
+                    })
+                }
+                _ => None,
+            });
+        }
+
+        let states: Vec<State> = rates.iter().map(|r| r.as_ref().map_or(State::OK, |r| self.state(r))).collect();
+        let state = State::worst(states.iter().copied());
+        let mut descriptions = Vec::new();
+        let mut cr = CheckResult::new(state);
+        for ((counter, rates), state) in counters.iter().zip(&rates).zip(&states) {
+            let rates = match rates {
+                Some(rates) => rates,
+                None => continue,
+            };
+            let description = format!("{} {}", counter.name, self.describe(rates));
+            cr = cr.add_long_output(format!("[{}] {}", state, description));
+            descriptions.push((*state, description));
+            let g = group(counter.name.as_str());
+            cr = cr
+                .add_metric(g.metric("in_bps", rates.in_bps.round()).min("0"))
+                .add_metric(g.metric("out_bps", rates.out_bps.round()).min("0"))
+                .add_metric(g.metric("in_pps", rates.in_pps).precision(1).min("0"))
+                .add_metric(g.metric("out_pps", rates.out_pps).precision(1).min("0"))
+                .add_metric(g.metric("in_errors", rates.in_errors).precision(1).min("0"))
+                .add_metric(g.metric("out_errors", rates.out_errors).precision(1).min("0"));
+            if let Some(utilization) = self.utilization_of(rates) {
+                let metric = g.metric("utilization", utilization).uom("%").precision(1);
+                cr = cr.add_metric(with_thresholds(metric, &self.utilization).min("0").max("100"));
+            }
+        }
+
+        let plural = if counters.len() == 1 { "" } else { "s" };
+        let info = match descriptions.as_slice() {
+            [] => format!("{} interface{}, rates are available from the next check on", counters.len(), plural),
+            [(_, description)] => description.clone(),
+            _ if state == State::OK => format!("{} interfaces ok", descriptions.len()),
+            _ => {
+                let worst: Vec<&str> = descriptions.iter().filter(|(s, _)| *s == state).map(|(_, d)| d.as_str()).collect();
+                worst.join(", ")
+            }
+        };
+        cr.set_info(info)
+    }
+
+    /// Returns the utilization of the busier direction in percent of the link speed, if known
+    fn utilization_of(&self, rates: &InterfaceRates) -> Option<f64> {
+        match self.speed {
+            Some(speed) if speed > 0 => Some(100.0 * rates.in_bps.max(rates.out_bps) / speed as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the state of an interface, the one of its utilization against the thresholds
+    fn state(&self, rates: &InterfaceRates) -> State {
+        self.utilization_of(rates).map_or(State::OK, |u| self.utilization.state(u))
+    }
+
+    /// Describes the rates of an interface, e.g. `in 60.0 Mbit/s, out 1.0 Mbit/s`
+    fn describe(&self, rates: &InterfaceRates) -> String {
+        let mut description = format!("in {}, out {}", format_bits(rates.in_bps), format_bits(rates.out_bps));
+        if let Some(utilization) = self.utilization_of(rates) {
+            description.push_str(&format!(", {:.1}% utilization", utilization));
+        }
+        if rates.in_errors + rates.out_errors > 0.0 {
+            description.push_str(&format!(", {:.1} errors/s", rates.in_errors + rates.out_errors));
+        }
+        description
+    }
+}
+
+
+impl Default for Interfaces {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Plugin for Interfaces {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The per-second rates of an interface between two invocations
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct InterfaceRates {
+    in_bps: f64,
+    out_bps: f64,
+    in_pps: f64,
+    out_pps: f64,
+    in_errors: f64,
+    out_errors: f64,
+}
+
+
+/// Formats a rate in bits per second, scaled to the largest decimal unit it reaches
+fn format_bits(bps: f64) -> String {
+    const UNITS: [&str; 4] = ["kbit/s", "Mbit/s", "Gbit/s", "Tbit/s"];
+    if bps < 1000.0 {
+        return format!("{:.0} bit/s", bps);
+    }
+    let mut value = bps / 1000.0;
+    let mut unit = 0;
+    while value >= 999.95 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+
+/// The counters of a network interface since it was brought up
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InterfaceCounters {
+    /// the name of the interface
+    pub name: String,
+    /// the bytes received
+    pub rx_bytes: u64,
+    /// the packets received
+    pub rx_packets: u64,
+    /// the receive errors
+    pub rx_errors: u64,
+    /// the bytes sent
+    pub tx_bytes: u64,
+    /// the packets sent
+    pub tx_packets: u64,
+    /// the transmit errors
+    pub tx_errors: u64,
+}
+
+
+impl InterfaceCounters {
+    /// Parses the interface table of `/proc/net/dev`
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - the contents of `/proc/net/dev`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::net::InterfaceCounters;
+    /// let table = "Inter-|   Receive |  Transmit\n face |bytes packets errs drop fifo frame compressed multicast|bytes packets errs\n\
+    ///     \x20 eth0: 9120 80 1 0 0 0 0 0 5400 45 0 0 0 0 0 0\n";
+    /// let counters = InterfaceCounters::parse_table(table).unwrap();
+    /// assert_eq!((counters[0].name.as_str(), counters[0].rx_bytes, counters[0].rx_errors, counters[0].tx_packets), ("eth0", 9120, 1, 45));
+    /// ```
+    pub fn parse_table(table: &str) -> Result<Vec<Self>, ProbeError> {
+        let mut counters = Vec::new();
+        for line in table.lines().skip(2) {
+            let invalid = || ProbeError::Parse(format!("/proc/net/dev: {}", line.trim()));
+            let (name, columns) = line.split_once(':').ok_or_else(invalid)?;
+            let columns = columns
+                .split_whitespace()
+                .map(str::parse::<u64>)
+                .collect::<Result<Vec<u64>, _>>()
+                .map_err(|_| invalid())?;
+            if columns.len() < 11 {
+                return Err(invalid());
+            }
+            counters.push(Self {
+                name: name.trim().to_string(),
+                rx_bytes: columns[0],
+                rx_packets: columns[1],
+                rx_errors: columns[2],
+                tx_bytes: columns[8],
+                tx_packets: columns[9],
+                tx_errors: columns[10],
+            });
+        }
+        Ok(counters)
+    }
+}
+
+
+/// Returns the counters of all network interfaces, read from `/proc/net/dev`. Fails with
+/// `ProbeError::Unsupported` on other platforms than Linux.
+pub fn counters() -> Result<Vec<InterfaceCounters>, ProbeError> {
+    if cfg!(target_os = "linux") {
+        InterfaceCounters::parse_table(&std::fs::read_to_string("/proc/net/dev")?)
+    } else {
+        Err(ProbeError::Unsupported("network interface"))
+    }
+}
@@ -0,0 +1,315 @@
+use crate::check::{CheckResult, Metric};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::{Range, Thresholds};
+use crate::units::format_bytes;
+use std::fmt;
+
+/// Checks the number of running processes matching a name, an argument and a regular expression,
+/// like `check_procs`. By default at least one matching process has to run. The resident memory
+/// and CPU usage of all matching processes are reported as performance data. The process of the
+/// check itself never matches.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::process::Processes;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// // between 2 and 8 nginx processes serving the production site
+/// let check = Processes::new()
+///     .name("nginx")
+///     .argument("/etc/nginx/production.conf")
+///     .count(Thresholds::new().critical("2:8".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Processes {
+    /// the name matching processes have, if any
+    name: Option<String>,
+    /// a text one of the arguments of matching processes contains, if any
+    argument: Option<String>,
+    /// the regular expression the command line of matching processes matches, if any
+    #[cfg(feature = "regex")]
+    regex: Option<regex::Regex>,
+    /// the thresholds of the number of matching processes
+    count: Thresholds,
+}
+
+
+impl Processes {
+    /// Creates a new `Processes` probe matching every process, CRITICAL unless at least one
+    /// matches
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            argument: None,
+            #[cfg(feature = "regex")]
+            regex: None,
+            count: Thresholds::new().critical(Range::new(1.0, f64::INFINITY)),
+        }
+    }
+
+    /// Matches processes named `name` only, either by the name of their executable or by their
+    /// first argument without the path, so e.g. `python3 script.py` matches `python3`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the process name, e.g. `nginx`
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Matches processes with an argument containing `argument` only, e.g. a configuration
+    /// file or a script
+    ///
+    /// # Arguments
+    ///
+    /// * `argument` - the text an argument contains
+    pub fn argument<S: Into<String>>(mut self, argument: S) -> Self {
+        self.argument = Some(argument.into());
+        self
+    }
+
+    /// Matches processes whose command line, the arguments joined by spaces, matches `regex`
+    /// only
+    ///
+    /// # Arguments
+    ///
+    /// * `regex` - the regular expression, e.g. `^java .*-jar /opt/app/`
+    #[cfg(feature = "regex")]
+    pub fn regex(mut self, regex: regex::Regex) -> Self {
+        self.regex = Some(regex);
+        self
+    }
+
+    /// Sets the thresholds of the number of matching processes, replacing the default critical
+    /// range of `1:`
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a critical range of `1:1` for exactly one process
+    pub fn count(mut self, thresholds: Thresholds) -> Self {
+        self.count = thresholds;
+        self
+    }
+
+    /// Returns whether `process` matches all filters
+    ///
+    /// # Arguments
+    ///
+    /// * `process` - the process
+    pub fn matches(&self, process: &ProcessInfo) -> bool {
+        if let Some(name) = &self.name {
+            let arg0 = process.args.first().map(|a| a.rsplit('/').next().unwrap_or(a));
+            if process.name != *name && arg0 != Some(name.as_str()) {
+                return false;
+            }
+        }
+        if let Some(argument) = &self.argument {
+            if !process.args.iter().skip(1).any(|a| a.contains(argument.as_str())) {
+                return false;
+            }
+        }
+        #[cfg(feature = "regex")]
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&process.args.join(" ")) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Scans the process table and returns the `CheckResult` for the matching processes, see
+    /// `evaluate()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.evaluate(&processes()?))
+    }
+
+    /// Returns the `CheckResult` for the processes of `table` matching all filters, with the
+    /// state of their number against the thresholds and the metrics `procs`, `rss` in bytes and
+    /// `cpu` in percent of a core. Each matching process is described on verbosity level 1.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - the running processes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::process::{ProcessInfo, Processes};
+    ///
+    /// let table = vec![
+    ///     ProcessInfo { pid: 1, name: String::from("systemd"), args: vec![String::from("/sbin/init")], rss: 12 << 20, cpu: 0.1 },
+    ///     ProcessInfo { pid: 812, name: String::from("sshd"), args: vec![String::from("sshd: /usr/sbin/sshd -D")], rss: 8 << 20, cpu: 0.0 },
+    /// ];
+    /// assert_eq!(Processes::new().name("nginx").evaluate(&table).to_string(),
+    ///     "CRITICAL - 0 processes named 'nginx' | 'procs'=0;;1:;0; 'rss'=0B;;;0; 'cpu'=0.0%;;;0; ");
+    /// ```
+    pub fn evaluate(&self, table: &[ProcessInfo]) -> CheckResult {
+        let own = std::process::id();
+        let matching: Vec<&ProcessInfo> = table.iter().filter(|p| p.pid != own && self.matches(p)).collect();
+        let rss: u64 = matching.iter().map(|p| p.rss).sum();
+        let cpu = matching.iter().fold(0.0, |sum, p| sum + p.cpu);
+
+        let procs = with_thresholds(Metric::new("procs", matching.len() as u64), &self.count).min("0");
+        let plural = if matching.len() == 1 { "" } else { "es" };
+        let mut info = format!("{} process{}{}", matching.len(), plural, self);
+        if !matching.is_empty() {
+            info.push_str(&format!(", {} RSS", format_bytes(rss)));
+        }
+        let mut cr = CheckResult::new(self.count.state(matching.len() as f64))
+            .set_info(info)
+            .add_metric(procs)
+            .add_metric(Metric::new("rss", rss).uom("B").min("0"))
+            .add_metric(Metric::new("cpu", cpu).uom("%").precision(1).min("0"));
+        for process in matching {
+            cr = cr.add_debug(1, process.to_string());
+        }
+        cr
+    }
+}
+
+
+impl Default for Processes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl fmt::Display for Processes {
+    /// Formats the filters, e.g. ` named 'nginx' with argument 'production.conf'`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, " named '{}'", name)?;
+        }
+        if let Some(argument) = &self.argument {
+            write!(f, " with argument '{}'", argument)?;
+        }
+        #[cfg(feature = "regex")]
+        if let Some(regex) = &self.regex {
+            write!(f, " matching '{}'", regex)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl Plugin for Processes {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// A running process
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessInfo {
+    /// the process id
+    pub pid: u32,
+    /// the name of the executable, truncated to 15 bytes on Linux
+    pub name: String,
+    /// the arguments, starting with the program, empty for kernel threads
+    pub args: Vec<String>,
+    /// the resident memory in bytes
+    pub rss: u64,
+    /// the CPU usage in percent of a core, averaged over the lifetime of the process like `ps`
+    /// does
+    pub cpu: f64,
+}
+
+
+impl fmt::Display for ProcessInfo {
+    /// Formats the process, e.g. `812 sshd: 8.0 MiB RSS, 0.1% CPU, sshd: /usr/sbin/sshd -D`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {} RSS, {:.1}% CPU", self.pid, self.name, format_bytes(self.rss), self.cpu)?;
+        if !self.args.is_empty() {
+            write!(f, ", {}", self.args.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Returns all running processes, read from `/proc`. Processes exiting while the table is read
+/// are skipped. Fails with `ProbeError::Unsupported` on other platforms than Linux.
+pub fn processes() -> Result<Vec<ProcessInfo>, ProbeError> {
+    imp::processes()
+}
+
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::ProcessInfo;
+    use crate::probes::ProbeError;
+    use std::fs;
+    use std::path::Path;
+
+    /// The clock ticks per second of the times in `/proc`, `USER_HZ`, which is 100 on all
+    /// architectures
+    const USER_HZ: f64 = 100.0;
+
+    pub(super) fn processes() -> Result<Vec<ProcessInfo>, ProbeError> {
+        let uptime = fs::read_to_string("/proc/uptime")?;
+        let uptime: f64 = uptime
+            .split_whitespace()
+            .next()
+            .and_then(|u| u.parse().ok())
+            .ok_or_else(|| ProbeError::Parse(format!("/proc/uptime: {}", uptime.trim())))?;
+
+        let mut table = Vec::new();
+        for entry in fs::read_dir("/proc")? {
+            let entry = entry?;
+            let pid = match entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            // the process may exit at any time, its files vanish then
+            if let Some(process) = read_process(pid, &entry.path(), uptime) {
+                table.push(process);
+            }
+        }
+        Ok(table)
+    }
+
+    /// Reads the process `pid` from its directory `dir`, `None` if it exited meanwhile
+    fn read_process(pid: u32, dir: &Path, uptime: f64) -> Option<ProcessInfo> {
+        let stat = fs::read_to_string(dir.join("stat")).ok()?;
+        let cmdline = fs::read(dir.join("cmdline")).ok()?;
+        let status = fs::read_to_string(dir.join("status")).ok()?;
+
+        // the name is enclosed in parentheses and may contain any character itself
+        let (name, fields) = stat.split_once(" (").and_then(|(_, rest)| rest.rsplit_once(") "))?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let ticks = |field: usize| fields.get(field - 3).and_then(|f| f.parse::<f64>().ok());
+        let cpu_time = (ticks(14)? + ticks(15)?) / USER_HZ;
+        let elapsed = uptime - ticks(22)? / USER_HZ;
+        let cpu = if elapsed > 0.0 { 100.0 * cpu_time / elapsed } else { 0.0 };
+
+        let rss = status
+            .lines()
+            .find_map(|l| l.strip_prefix("VmRSS:"))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map_or(0, |kb| kb * 1024);
+        let args = cmdline
+            .split(|b| *b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+        Some(ProcessInfo { pid, name: name.to_string(), args, rss, cpu })
+    }
+}
+
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::ProcessInfo;
+    use crate::probes::ProbeError;
+
+    pub(super) fn processes() -> Result<Vec<ProcessInfo>, ProbeError> {
+        Err(ProbeError::Unsupported("process"))
+    }
+}
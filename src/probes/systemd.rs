@@ -0,0 +1,376 @@
+use crate::check::{CheckResult, Metric, State};
+use crate::clock::{Clock, SystemClock};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use std::fmt;
+use std::io;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The unit properties queried with `systemctl show`
+const PROPERTIES: [&str; 7] = [
+    "Id",
+    "Description",
+    "LoadState",
+    "ActiveState",
+    "SubState",
+    "NRestarts",
+    "ActiveEnterTimestamp",
+];
+
+
+/// Checks a systemd unit using `systemctl show`. The unit is OK while active, WARNING while
+/// changing its state and CRITICAL if inactive or failed. The number of automatic restarts and
+/// the time since the unit was last started are reported as performance data and can be
+/// evaluated against thresholds.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::systemd::SystemdUnit;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+///
+/// // warn on any restart, and if nginx was started less than 5 minutes ago
+/// let unit = SystemdUnit::new("nginx.service")
+///     .restarts(Thresholds::new().warning("0".parse().unwrap()))
+///     .uptime(Thresholds::new().warning("300:".parse().unwrap()));
+/// Runner::new(unit).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SystemdUnit {
+    /// the name of the unit, e.g. `nginx.service`
+    unit: String,
+    /// whether the unit is managed by the user's service manager
+    user: bool,
+    /// the thresholds of the restart counter
+    restarts: Thresholds,
+    /// the thresholds of the seconds since the unit was started
+    uptime: Thresholds,
+    /// the clock the uptime is measured with
+    clock: Arc<dyn Clock>,
+}
+
+
+impl SystemdUnit {
+    /// Creates a new `SystemdUnit` probe for `unit` of the system's service manager
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - the name of the unit, e.g. `nginx.service`
+    pub fn new<S: Into<String>>(unit: S) -> Self {
+        Self {
+            unit: unit.into(),
+            user: false,
+            restarts: Thresholds::new(),
+            uptime: Thresholds::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets whether the unit is managed by the service manager of the calling user instead of
+    /// the system's, like `systemctl --user`
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - whether to query the user's service manager
+    pub fn user(mut self, user: bool) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Sets the thresholds of the number of automatic restarts, `NRestarts`
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `0` to alert on any restart
+    pub fn restarts(mut self, thresholds: Thresholds) -> Self {
+        self.restarts = thresholds;
+        self
+    }
+
+    /// Sets the thresholds of the seconds since the unit was last started, only evaluated
+    /// while it is active
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `300:` to alert on recent
+    ///   starts
+    pub fn uptime(mut self, thresholds: Thresholds) -> Self {
+        self.uptime = thresholds;
+        self
+    }
+
+    /// Sets the clock the uptime is measured with, `SystemClock` by default
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the `systemctl` command querying the unit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::systemd::SystemdUnit;
+    /// let cmd = SystemdUnit::new("nginx.service").user(true).command();
+    /// let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+    /// assert_eq!(args.last().map(String::as_str), Some("nginx.service"));
+    /// assert!(args.contains(&String::from("--user")));
+    /// ```
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("systemctl");
+        cmd.arg("show").arg("--timestamp=unix");
+        if self.user {
+            cmd.arg("--user");
+        }
+        for property in &PROPERTIES {
+            cmd.arg("-p").arg(property);
+        }
+        cmd.arg("--").arg(&self.unit);
+        cmd
+    }
+
+    /// Queries the current status of the unit. Fails with `ProbeError::NotFound` if the unit
+    /// is not loaded and with `ProbeError::Io` if `systemctl` fails, e.g. without systemd.
+    pub fn query(&self) -> Result<UnitStatus, ProbeError> {
+        let output = self.command().output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ProbeError::Io(io::Error::other(format!("systemctl failed: {}", stderr.trim()))));
+        }
+        let status: UnitStatus = String::from_utf8_lossy(&output.stdout).parse()?;
+        if status.load_state == "not-found" {
+            return Err(ProbeError::NotFound(format!("unit {}", self.unit)));
+        }
+        Ok(status)
+    }
+
+    /// Queries the unit and returns the `CheckResult` for its status, see `evaluate()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.evaluate(&self.query()?))
+    }
+
+    /// Returns the `CheckResult` for `status`, the worst of the state of its `ActiveState`
+    /// and of the restarts and uptime against their thresholds, with the metrics `restarts` and
+    /// `uptime` in seconds, unknown unless the unit is active
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - the status of the unit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::clock::MockClock;
+    /// use icingaplugin_rs::probes::systemd::{SystemdUnit, UnitStatus};
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    ///
+    /// let status: UnitStatus = "Id=nginx.service\nDescription=nginx web server\nLoadState=loaded\n\
+    ///     ActiveState=active\nSubState=running\nNRestarts=2\nActiveEnterTimestamp=@1699999880\n"
+    ///     .parse().unwrap();
+    /// let unit = SystemdUnit::new("nginx.service")
+    ///     .restarts(Thresholds::new().warning("1".parse().unwrap()))
+    ///     .clock(MockClock::default());
+    /// assert_eq!(unit.evaluate(&status).to_string(),
+    ///     "WARNING - nginx.service (nginx web server) is active (running), restarted 2 times \
+    ///     | 'restarts'=2c;1;;0; 'uptime'=120s;;;0; ");
+    /// ```
+    pub fn evaluate(&self, status: &UnitStatus) -> CheckResult {
+        let restarts = status.restarts.unwrap_or(0);
+        let uptime = match status.active_since {
+            Some(since) if status.active_state == ActiveState::Active => Some(self.clock.since(since)),
+            _ => None,
+        };
+        let state = State::worst([
+            status.active_state.check_state(),
+            self.restarts.state(restarts as f64),
+            uptime.map_or(State::OK, |u| self.uptime.state(u.as_secs_f64())),
+        ]);
+
+        let mut info = format!("{} is {} ({})", status.name(), status.active_state, status.sub_state);
+        if restarts > 0 {
+            let plural = if restarts == 1 { "" } else { "s" };
+            info.push_str(&format!(", restarted {} time{}", restarts, plural));
+        }
+        let restarts = with_thresholds(Metric::new("restarts", restarts).uom("c"), &self.restarts).min("0");
+        let uptime = match uptime {
+            Some(uptime) => Metric::new("uptime", uptime.as_secs()).uom("s").min("0"),
+            None => Metric::unknown("uptime").uom("s"),
+        };
+        CheckResult::new(state)
+            .set_info(info)
+            .add_metric(restarts)
+            .add_metric(with_thresholds(uptime, &self.uptime))
+    }
+}
+
+
+impl Plugin for SystemdUnit {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The status of a systemd unit, parsed from the output of `systemctl show`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnitStatus {
+    /// the name of the unit
+    pub id: String,
+    /// the description of the unit, e.g. `A high performance web server`
+    pub description: String,
+    /// whether the unit definition was loaded, e.g. `loaded` or `not-found`
+    pub load_state: String,
+    /// the high-level state of the unit
+    pub active_state: ActiveState,
+    /// the type specific state of the unit, e.g. `running` or `exited`
+    pub sub_state: String,
+    /// how often the service was restarted automatically, only reported for services
+    pub restarts: Option<u64>,
+    /// when the unit last entered the active state, if it ever did
+    pub active_since: Option<SystemTime>,
+}
+
+
+impl UnitStatus {
+    /// Returns the name of the unit followed by its description, if it has one
+    fn name(&self) -> String {
+        if self.description.is_empty() || self.description == self.id {
+            self.id.clone()
+        } else {
+            format!("{} ({})", self.id, self.description)
+        }
+    }
+}
+
+
+impl FromStr for UnitStatus {
+    type Err = ProbeError;
+
+    /// Parses the `Key=value` lines printed by `systemctl show --timestamp=unix`, ignoring
+    /// unknown properties
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut id, mut description, mut load_state, mut sub_state) = (None, String::new(), None, String::new());
+        let (mut active_state, mut restarts, mut active_since) = (None, None, None);
+        for line in s.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some(property) => property,
+                None => continue,
+            };
+            match key {
+                "Id" => id = Some(value.to_string()),
+                "Description" => description = value.to_string(),
+                "LoadState" => load_state = Some(value.to_string()),
+                "ActiveState" => active_state = Some(value.parse()?),
+                "SubState" => sub_state = value.to_string(),
+                "NRestarts" => {
+                    restarts = Some(value.parse().map_err(|_| ProbeError::Parse(format!("NRestarts={}", value)))?)
+                }
+                "ActiveEnterTimestamp" => active_since = parse_timestamp(value)?,
+                _ => {}
+            }
+        }
+        let missing = |property: &str| ProbeError::Parse(format!("missing property {}", property));
+        Ok(Self {
+            id: id.ok_or_else(|| missing("Id"))?,
+            description,
+            load_state: load_state.ok_or_else(|| missing("LoadState"))?,
+            active_state: active_state.ok_or_else(|| missing("ActiveState"))?,
+            sub_state,
+            restarts,
+            active_since,
+        })
+    }
+}
+
+
+/// Parses a timestamp printed with `--timestamp=unix`, e.g. `@1700000000`, empty if unset
+fn parse_timestamp(value: &str) -> Result<Option<SystemTime>, ProbeError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.strip_prefix('@').map(str::parse::<u64>) {
+        Some(Ok(0)) => Ok(None),
+        Some(Ok(secs)) => Ok(Some(UNIX_EPOCH + Duration::from_secs(secs))),
+        _ => Err(ProbeError::Parse(format!("timestamp {}", value))),
+    }
+}
+
+
+/// The high-level state of a systemd unit, `ActiveState`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ActiveState {
+    /// started, bound, plugged in, depending on the unit type
+    Active,
+    /// active and reloading its configuration
+    Reloading,
+    /// stopped
+    Inactive,
+    /// stopped after a failure, e.g. a non-zero exit code or a crash
+    Failed,
+    /// starting
+    Activating,
+    /// stopping
+    Deactivating,
+    /// stopped for maintenance, e.g. while the runtime directories are cleaned
+    Maintenance,
+    /// active and refreshing its extension images
+    Refreshing,
+}
+
+
+impl ActiveState {
+    /// Returns the state of the check, OK if active, CRITICAL if inactive or failed and
+    /// WARNING otherwise
+    pub fn check_state(self) -> State {
+        match self {
+            ActiveState::Active | ActiveState::Reloading | ActiveState::Refreshing => State::OK,
+            ActiveState::Inactive | ActiveState::Failed => State::Critical,
+            _ => State::Warning,
+        }
+    }
+}
+
+
+impl FromStr for ActiveState {
+    type Err = ProbeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(ActiveState::Active),
+            "reloading" => Ok(ActiveState::Reloading),
+            "inactive" => Ok(ActiveState::Inactive),
+            "failed" => Ok(ActiveState::Failed),
+            "activating" => Ok(ActiveState::Activating),
+            "deactivating" => Ok(ActiveState::Deactivating),
+            "maintenance" => Ok(ActiveState::Maintenance),
+            "refreshing" => Ok(ActiveState::Refreshing),
+            _ => Err(ProbeError::Parse(format!("ActiveState={}", s))),
+        }
+    }
+}
+
+
+impl fmt::Display for ActiveState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ActiveState::Active => "active",
+            ActiveState::Reloading => "reloading",
+            ActiveState::Inactive => "inactive",
+            ActiveState::Failed => "failed",
+            ActiveState::Activating => "activating",
+            ActiveState::Deactivating => "deactivating",
+            ActiveState::Maintenance => "maintenance",
+            ActiveState::Refreshing => "refreshing",
+        };
+        f.write_str(s)
+    }
+}
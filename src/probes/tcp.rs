@@ -0,0 +1,197 @@
+use crate::check::{CheckResult, Metric};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::Thresholds;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// The maximum number of bytes of the response read to find the expected text
+const MAX_RESPONSE: usize = 4096;
+
+
+/// Checks that a TCP port accepts connections, like `check_tcp`. Optionally a payload is sent
+/// and the response, e.g. the banner of an SSH or SMTP server, has to contain an expected
+/// text. The time to connect is reported in seconds and can be evaluated against thresholds.
+/// A refused or timed out connection and an unexpected response are CRITICAL.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::tcp::Tcp;
+/// use icingaplugin_rs::runner::Runner;
+/// use icingaplugin_rs::thresholds::Thresholds;
+/// use std::time::Duration;
+///
+/// let check = Tcp::new("mail.example.com", 25)
+///     .timeout(Duration::from_secs(5))
+///     .expect("220 ")
+///     .time(Thresholds::new().warning("0.5".parse().unwrap()).critical("2".parse().unwrap()));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tcp {
+    /// the host name or address
+    host: String,
+    /// the port
+    port: u16,
+    /// the timeout of connecting and of each read and write
+    timeout: Duration,
+    /// the payload sent after connecting, if any
+    send: Option<Vec<u8>>,
+    /// the text the response has to contain, if any
+    expect: Option<String>,
+    /// the thresholds of the time to connect in seconds
+    time: Thresholds,
+}
+
+
+impl Tcp {
+    /// Creates a new `Tcp` probe for `host` and `port` with a timeout of 10 seconds, without
+    /// thresholds
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host name or address
+    /// * `port` - the port
+    pub fn new<S: Into<String>>(host: S, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            timeout: Duration::from_secs(10),
+            send: None,
+            expect: None,
+            time: Thresholds::new(),
+        }
+    }
+
+    /// Sets the timeout of connecting and of each read and write
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends `payload` after connecting, e.g. a request the expected response answers
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - the bytes sent, e.g. `"PING\r\n"`
+    pub fn send<B: Into<Vec<u8>>>(mut self, payload: B) -> Self {
+        self.send = Some(payload.into());
+        self
+    }
+
+    /// Expects the response to contain `text` within its first 4 KiB. The response is read
+    /// until it does, the server closes the connection or the timeout expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the expected text, e.g. `SSH-2.0-` for the banner of an SSH server
+    pub fn expect<S: Into<String>>(mut self, text: S) -> Self {
+        self.expect = Some(text.into());
+        self
+    }
+
+    /// Sets the thresholds of the time to connect in seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `thresholds` - the thresholds, e.g. a warning range of `0.5`
+    pub fn time(mut self, thresholds: Thresholds) -> Self {
+        self.time = thresholds;
+        self
+    }
+
+    /// Connects to the port and returns the `CheckResult`, with the metric `time`, the time to
+    /// connect in seconds, if the connection was established. Fails with
+    /// `ProbeError::NotFound` if the host name cannot be resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::probes::tcp::Tcp;
+    /// use std::io::Write;
+    /// use std::net::TcpListener;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let port = listener.local_addr().unwrap().port();
+    /// std::thread::spawn(move || {
+    ///     let (mut stream, _) = listener.accept().unwrap();
+    ///     stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+    /// });
+    /// let cr = Tcp::new("127.0.0.1", port).expect("SSH-2.0-").check().unwrap();
+    /// assert_eq!(cr.state(), State::OK);
+    /// assert!(cr.to_string().contains(", response 'SSH-2.0-OpenSSH_9.6' | 'time'="));
+    /// ```
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        let target = format!("{}:{}", self.host, self.port);
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| ProbeError::NotFound(format!("host {}", self.host)))?;
+        let start = Instant::now();
+        let mut stream = match TcpStream::connect_timeout(&addr, self.timeout) {
+            Ok(stream) => stream,
+            Err(e) => return Ok(CheckResult::critical(format!("connection to {} failed: {}", target, e))),
+        };
+        let time = start.elapsed();
+        let metric = with_thresholds(Metric::new("time", time.as_secs_f64()).uom("s").precision(6), &self.time).min("0");
+        let info = format!("connected to {} in {:.3}s", target, time.as_secs_f64());
+        let cr = match self.converse(&mut stream) {
+            Ok(None) => CheckResult::new(self.time.state(time.as_secs_f64())).set_info(info),
+            Ok(Some(response)) => CheckResult::new(self.time.state(time.as_secs_f64()))
+                .set_info(format!("{}, response '{}'", info, response)),
+            Err(e) => CheckResult::critical(format!("{}, but {}", info, e)),
+        };
+        Ok(cr.add_metric(metric))
+    }
+
+    /// Sends the payload and reads the response until it contains the expected text. Returns
+    /// the first line of the response, if one is expected, or a description of the failure.
+    fn converse(&self, stream: &mut TcpStream) -> Result<Option<String>, String> {
+        stream.set_read_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+        if let Some(payload) = &self.send {
+            stream.write_all(payload).map_err(|e| format!("sending failed: {}", e))?;
+        }
+        let expect = match &self.expect {
+            Some(expect) => expect,
+            None => return Ok(None),
+        };
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        let found = |response: &[u8]| String::from_utf8_lossy(response).contains(expect.as_str());
+        while response.len() < MAX_RESPONSE && !found(&response) {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => response.extend_from_slice(&buf[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                Err(e) => return Err(format!("receiving failed: {}", e)),
+            }
+        }
+        response.truncate(MAX_RESPONSE);
+        let response = String::from_utf8_lossy(&response);
+        let first_line = response.lines().next().unwrap_or_default().to_string();
+        if response.contains(expect.as_str()) {
+            Ok(Some(first_line))
+        } else if response.is_empty() {
+            Err(format!("no response containing '{}' received", expect))
+        } else {
+            Err(format!("response '{}' does not contain '{}'", first_line, expect))
+        }
+    }
+}
+
+
+impl Plugin for Tcp {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
@@ -0,0 +1,187 @@
+use crate::check::{CheckResult, Metric};
+use crate::probes::{with_thresholds, ProbeError};
+use crate::runner::{Error, Plugin};
+use crate::thresholds::{Range, Thresholds};
+use crate::units::format_duration;
+use std::time::Duration;
+
+/// Checks the time since the system booted. It can alert if the system was rebooted recently,
+/// e.g. to notice unplanned reboots, and if it has been up for too long, e.g. to notice pending
+/// kernel updates. The uptime is reported in seconds as performance data.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::uptime::Uptime;
+/// use icingaplugin_rs::runner::Runner;
+/// use std::time::Duration;
+///
+/// const DAY: u64 = 86400;
+/// let check = Uptime::new()
+///     .rebooted(Some(Duration::from_secs(900)), None)
+///     .up_longer(Some(Duration::from_secs(90 * DAY)), Some(Duration::from_secs(180 * DAY)));
+/// Runner::new(check).run();
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Uptime {
+    /// the uptime below which the check is WARNING, if any
+    rebooted_warning: Option<Duration>,
+    /// the uptime below which the check is CRITICAL, if any
+    rebooted_critical: Option<Duration>,
+    /// the uptime above which the check is WARNING, if any
+    up_warning: Option<Duration>,
+    /// the uptime above which the check is CRITICAL, if any
+    up_critical: Option<Duration>,
+}
+
+
+impl Uptime {
+    /// Creates a new `Uptime` probe which is always OK
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Alerts if the system was rebooted less than `warning` or `critical` ago
+    ///
+    /// # Arguments
+    ///
+    /// * `warning` - the uptime below which the check is WARNING, if any
+    /// * `critical` - the uptime below which the check is CRITICAL, if any
+    pub fn rebooted(mut self, warning: Option<Duration>, critical: Option<Duration>) -> Self {
+        self.rebooted_warning = warning;
+        self.rebooted_critical = critical;
+        self
+    }
+
+    /// Alerts if the system has been up longer than `warning` or `critical`
+    ///
+    /// # Arguments
+    ///
+    /// * `warning` - the uptime above which the check is WARNING, if any
+    /// * `critical` - the uptime above which the check is CRITICAL, if any
+    pub fn up_longer(mut self, warning: Option<Duration>, critical: Option<Duration>) -> Self {
+        self.up_warning = warning;
+        self.up_critical = critical;
+        self
+    }
+
+    /// Returns the thresholds of the uptime in seconds, combining both modes into one range
+    /// per state, e.g. `900:7776000`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::uptime::Uptime;
+    /// use std::time::Duration;
+    /// let uptime = Uptime::new().rebooted(Some(Duration::from_secs(900)), None).up_longer(None, Some(Duration::from_secs(86400)));
+    /// let thresholds = uptime.thresholds();
+    /// assert_eq!(thresholds.warning_range().map(ToString::to_string), Some(String::from("900:")));
+    /// assert_eq!(thresholds.critical_range().map(ToString::to_string), Some(String::from("86400")));
+    /// ```
+    pub fn thresholds(&self) -> Thresholds {
+        let mut thresholds = Thresholds::new();
+        if let Some(range) = range(self.rebooted_warning, self.up_warning) {
+            thresholds = thresholds.warning(range);
+        }
+        if let Some(range) = range(self.rebooted_critical, self.up_critical) {
+            thresholds = thresholds.critical(range);
+        }
+        thresholds
+    }
+
+    /// Reads the uptime and returns the `CheckResult` for it, see `evaluate()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.evaluate(uptime()?))
+    }
+
+    /// Returns the `CheckResult` for `uptime` against the thresholds, with the metric `uptime`
+    /// in seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `uptime` - the time since the system booted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::probes::uptime::Uptime;
+    /// use std::time::Duration;
+    /// let check = Uptime::new().rebooted(Some(Duration::from_secs(900)), Some(Duration::from_secs(300)));
+    /// assert_eq!(check.evaluate(Duration::from_secs(754)).to_string(),
+    ///     "WARNING - up 12m 34s | 'uptime'=754s;900:;300:;0; ");
+    /// ```
+    pub fn evaluate(&self, uptime: Duration) -> CheckResult {
+        let thresholds = self.thresholds();
+        CheckResult::new(thresholds.state(uptime.as_secs_f64()))
+            .set_info(format!("up {}", format_duration(uptime)))
+            .add_metric(with_thresholds(Metric::new("uptime", uptime.as_secs()).uom("s"), &thresholds).min("0"))
+    }
+}
+
+
+impl Plugin for Uptime {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// Returns the range of uptimes within `min` and `max`, if either is set
+fn range(min: Option<Duration>, max: Option<Duration>) -> Option<Range> {
+    if min.is_none() && max.is_none() {
+        return None;
+    }
+    let min = min.map_or(0.0, |d| d.as_secs() as f64);
+    Some(Range::new(min, max.map_or(f64::INFINITY, |d| d.as_secs() as f64)))
+}
+
+
+/// Returns the time since the system booted, including the time it was suspended. Fails with
+/// `ProbeError::Unsupported` on other platforms than Linux and Windows.
+pub fn uptime() -> Result<Duration, ProbeError> {
+    imp::uptime()
+}
+
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use crate::probes::ProbeError;
+    use std::time::Duration;
+
+    pub(super) fn uptime() -> Result<Duration, ProbeError> {
+        let uptime = std::fs::read_to_string("/proc/uptime")?;
+        uptime
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse().ok())
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| ProbeError::Parse(format!("/proc/uptime: {}", uptime.trim())))
+    }
+}
+
+
+#[cfg(windows)]
+mod imp {
+    use crate::probes::ProbeError;
+    use std::time::Duration;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount64() -> u64;
+    }
+
+    pub(super) fn uptime() -> Result<Duration, ProbeError> {
+        Ok(Duration::from_millis(unsafe { GetTickCount64() }))
+    }
+}
+
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod imp {
+    use crate::probes::ProbeError;
+    use std::time::Duration;
+
+    pub(super) fn uptime() -> Result<Duration, ProbeError> {
+        Err(ProbeError::Unsupported("uptime"))
+    }
+}
@@ -0,0 +1,462 @@
+use crate::check::{CheckResult, Metric, State};
+use crate::clock::{Clock, SystemClock};
+use crate::probes::ProbeError;
+use crate::runner::{Error, Plugin};
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Checks a service registered with the Windows Service Control Manager, like `check_service` of
+/// Icinga for Windows. The service is CRITICAL if stopped, WARNING while paused or changing its
+/// state and OK while running. Its start type and uptime are reported as performance data.
+///
+/// # Examples
+///
+/// ```no_run
+/// use icingaplugin_rs::probes::windows_service::WindowsService;
+/// use icingaplugin_rs::runner::Runner;
+/// Runner::new(WindowsService::new("Spooler")).run();
+/// ```
+#[derive(Clone, Debug)]
+pub struct WindowsService {
+    /// the name of the service, not its display name
+    name: String,
+    /// the clock the uptime is measured with
+    clock: Arc<dyn Clock>,
+}
+
+
+impl WindowsService {
+    /// Creates a new `WindowsService` probe for the service `name`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the service, e.g. `Spooler`, not its display name
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self { name: name.into(), clock: Arc::new(SystemClock) }
+    }
+
+    /// Sets the clock the uptime is measured with, `SystemClock` by default
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Queries the current status of the service. Fails with `ProbeError::NotFound` if there
+    /// is no such service and with `ProbeError::Unsupported` on other platforms than Windows.
+    pub fn query(&self) -> Result<ServiceStatus, ProbeError> {
+        imp::query(&self.name)
+    }
+
+    /// Queries the service and returns the `CheckResult` for its status, see
+    /// `ServiceStatus::to_check_result()`
+    pub fn check(&self) -> Result<CheckResult, ProbeError> {
+        Ok(self.query()?.to_check_result(self.clock.as_ref()))
+    }
+}
+
+
+impl Plugin for WindowsService {
+    fn run(&self) -> Result<CheckResult, Error> {
+        Ok(self.check()?)
+    }
+}
+
+
+/// The status of a Windows service
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ServiceStatus {
+    /// the name of the service
+    pub name: String,
+    /// the display name of the service, e.g. `Print Spooler`
+    pub display_name: String,
+    /// the current state
+    pub state: ServiceState,
+    /// when the service is started
+    pub start_type: StartType,
+    /// when the process of the service was created, if it is running and the process could be
+    /// queried. Services sharing a process, e.g. in `svchost.exe`, report the creation of the
+    /// shared process.
+    pub started: Option<SystemTime>,
+}
+
+
+impl ServiceStatus {
+    /// Returns the `CheckResult` for the status, in the state of `ServiceState::check_state()`,
+    /// with the metrics `start_type`, the code of the `StartType`, and `uptime` in seconds,
+    /// unknown if the service is not running
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock the uptime is measured with
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::clock::{Clock, MockClock};
+    /// use icingaplugin_rs::probes::windows_service::{ServiceState, ServiceStatus, StartType};
+    /// use std::time::Duration;
+    ///
+    /// let clock = MockClock::default();
+    /// let status = ServiceStatus {
+    ///     name: String::from("Spooler"),
+    ///     display_name: String::from("Print Spooler"),
+    ///     state: ServiceState::Running,
+    ///     start_type: StartType::Automatic,
+    ///     started: Some(clock.now() - Duration::from_secs(3600)),
+    /// };
+    /// assert_eq!(status.to_check_result(&clock).to_string(),
+    ///     "OK - Print Spooler (Spooler) is running, start type automatic | 'start_type'=2;;;0;4 'uptime'=3600s;;;0; ");
+    ///
+    /// let stopped = ServiceStatus { state: ServiceState::Stopped, started: None, ..status };
+    /// assert_eq!(stopped.to_check_result(&clock).state(), State::Critical);
+    /// ```
+    pub fn to_check_result(&self, clock: &dyn Clock) -> CheckResult {
+        let name = if self.display_name.is_empty() || self.display_name == self.name {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.display_name, self.name)
+        };
+        let uptime = match self.started {
+            Some(started) => Metric::new("uptime", clock.since(started).as_secs()).uom("s").min("0"),
+            None => Metric::unknown("uptime").uom("s"),
+        };
+        CheckResult::new(self.state.check_state())
+            .set_info(format!("{} is {}, start type {}", name, self.state, self.start_type))
+            .add_metric(Metric::new("start_type", self.start_type.code()).min("0").max("4"))
+            .add_metric(uptime)
+    }
+}
+
+
+/// The state of a Windows service, `dwCurrentState` of `SERVICE_STATUS`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServiceState {
+    /// not running
+    Stopped,
+    /// starting
+    StartPending,
+    /// stopping
+    StopPending,
+    /// running
+    Running,
+    /// resuming from pause
+    ContinuePending,
+    /// pausing
+    PausePending,
+    /// paused
+    Paused,
+}
+
+
+impl ServiceState {
+    /// Returns the `ServiceState` for a `SERVICE_*` state code, if it is known
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - the state code, e.g. 4 for `SERVICE_RUNNING`
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(ServiceState::Stopped),
+            2 => Some(ServiceState::StartPending),
+            3 => Some(ServiceState::StopPending),
+            4 => Some(ServiceState::Running),
+            5 => Some(ServiceState::ContinuePending),
+            6 => Some(ServiceState::PausePending),
+            7 => Some(ServiceState::Paused),
+            _ => None,
+        }
+    }
+
+    /// Returns the state of the check, OK if running, CRITICAL if stopped and WARNING otherwise
+    pub fn check_state(self) -> State {
+        match self {
+            ServiceState::Running => State::OK,
+            ServiceState::Stopped => State::Critical,
+            _ => State::Warning,
+        }
+    }
+}
+
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ServiceState::Stopped => "stopped",
+            ServiceState::StartPending => "starting",
+            ServiceState::StopPending => "stopping",
+            ServiceState::Running => "running",
+            ServiceState::ContinuePending => "continuing",
+            ServiceState::PausePending => "pausing",
+            ServiceState::Paused => "paused",
+        };
+        f.write_str(s)
+    }
+}
+
+
+/// When a Windows service is started, `dwStartType` of `QUERY_SERVICE_CONFIG`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StartType {
+    /// a driver loaded by the boot loader
+    Boot,
+    /// a driver loaded during kernel initialization
+    System,
+    /// started during system startup
+    Automatic,
+    /// started on demand
+    Manual,
+    /// cannot be started
+    Disabled,
+}
+
+
+impl StartType {
+    /// Returns the `StartType` for a `SERVICE_*_START` code, if it is known
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - the start type code, e.g. 2 for `SERVICE_AUTO_START`
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(StartType::Boot),
+            1 => Some(StartType::System),
+            2 => Some(StartType::Automatic),
+            3 => Some(StartType::Manual),
+            4 => Some(StartType::Disabled),
+            _ => None,
+        }
+    }
+
+    /// Returns the `SERVICE_*_START` code of the start type, reported as metric
+    pub fn code(self) -> u32 {
+        match self {
+            StartType::Boot => 0,
+            StartType::System => 1,
+            StartType::Automatic => 2,
+            StartType::Manual => 3,
+            StartType::Disabled => 4,
+        }
+    }
+}
+
+
+impl fmt::Display for StartType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            StartType::Boot => "boot",
+            StartType::System => "system",
+            StartType::Automatic => "automatic",
+            StartType::Manual => "manual",
+            StartType::Disabled => "disabled",
+        };
+        f.write_str(s)
+    }
+}
+
+
+#[cfg(windows)]
+mod imp {
+    use super::{ServiceState, ServiceStatus, StartType};
+    use crate::probes::ProbeError;
+    use std::ffi::{c_void, OsStr};
+    use std::io;
+    use std::mem;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use std::slice;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    type Bool = i32;
+    type Handle = *mut c_void;
+
+    const SC_MANAGER_CONNECT: u32 = 0x0001;
+    const SERVICE_QUERY_CONFIG: u32 = 0x0001;
+    const SERVICE_QUERY_STATUS: u32 = 0x0004;
+    const SC_STATUS_PROCESS_INFO: u32 = 0;
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const ERROR_INSUFFICIENT_BUFFER: i32 = 122;
+    const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+    /// The seconds between 1601-01-01, the epoch of `FILETIME`, and the Unix epoch
+    const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+    /// The `FILETIME` intervals of 100ns per second
+    const FILETIME_PER_SEC: u64 = 10_000_000;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ServiceStatusProcess {
+        service_type: u32,
+        current_state: u32,
+        controls_accepted: u32,
+        win32_exit_code: u32,
+        service_specific_exit_code: u32,
+        check_point: u32,
+        wait_hint: u32,
+        process_id: u32,
+        service_flags: u32,
+    }
+
+    #[repr(C)]
+    struct QueryServiceConfig {
+        service_type: u32,
+        start_type: u32,
+        error_control: u32,
+        binary_path_name: *const u16,
+        load_order_group: *const u16,
+        tag_id: u32,
+        dependencies: *const u16,
+        service_start_name: *const u16,
+        display_name: *const u16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenSCManagerW(machine: *const u16, database: *const u16, access: u32) -> Handle;
+        fn OpenServiceW(manager: Handle, name: *const u16, access: u32) -> Handle;
+        fn QueryServiceStatusEx(service: Handle, level: u32, buffer: *mut u8, size: u32, needed: *mut u32) -> Bool;
+        fn QueryServiceConfigW(service: Handle, config: *mut QueryServiceConfig, size: u32, needed: *mut u32) -> Bool;
+        fn CloseServiceHandle(handle: Handle) -> Bool;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: Bool, pid: u32) -> Handle;
+        fn GetProcessTimes(
+            process: Handle,
+            creation: *mut FileTime,
+            exit: *mut FileTime,
+            kernel: *mut FileTime,
+            user: *mut FileTime,
+        ) -> Bool;
+        fn CloseHandle(handle: Handle) -> Bool;
+    }
+
+    /// A handle of the service control manager or a service, closed when dropped
+    struct ScHandle(Handle);
+
+    impl ScHandle {
+        fn new(handle: Handle) -> io::Result<Self> {
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Self(handle))
+            }
+        }
+    }
+
+    impl Drop for ScHandle {
+        fn drop(&mut self) {
+            unsafe {
+                CloseServiceHandle(self.0);
+            }
+        }
+    }
+
+    pub(super) fn query(name: &str) -> Result<ServiceStatus, ProbeError> {
+        let wide_name: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+        let manager = ScHandle::new(unsafe { OpenSCManagerW(ptr::null(), ptr::null(), SC_MANAGER_CONNECT) })?;
+        let access = SERVICE_QUERY_STATUS | SERVICE_QUERY_CONFIG;
+        let service = ScHandle::new(unsafe { OpenServiceW(manager.0, wide_name.as_ptr(), access) })
+            .map_err(|e| match e.raw_os_error() {
+                Some(ERROR_SERVICE_DOES_NOT_EXIST) => ProbeError::NotFound(format!("service {}", name)),
+                _ => ProbeError::Io(e),
+            })?;
+
+        let mut status = ServiceStatusProcess::default();
+        let mut needed = 0;
+        let size = mem::size_of::<ServiceStatusProcess>() as u32;
+        let buffer = &mut status as *mut ServiceStatusProcess as *mut u8;
+        if unsafe { QueryServiceStatusEx(service.0, SC_STATUS_PROCESS_INFO, buffer, size, &mut needed) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let state = ServiceState::from_code(status.current_state)
+            .ok_or_else(|| ProbeError::Parse(format!("unknown service state {}", status.current_state)))?;
+        let (start_type, display_name) = query_config(&service)?;
+        let started = match state {
+            ServiceState::Stopped => None,
+            _ => process_created(status.process_id),
+        };
+        Ok(ServiceStatus { name: name.to_string(), display_name, state, start_type, started })
+    }
+
+    /// Returns the start type and display name of `service`
+    fn query_config(service: &ScHandle) -> Result<(StartType, String), ProbeError> {
+        let mut needed = 0;
+        unsafe { QueryServiceConfigW(service.0, ptr::null_mut(), 0, &mut needed) };
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(ERROR_INSUFFICIENT_BUFFER) {
+            return Err(e.into());
+        }
+        // the strings follow the struct in the buffer, u64 aligns it for the pointers
+        let mut buffer = vec![0u64; (needed as usize).div_ceil(8)];
+        let config = buffer.as_mut_ptr() as *mut QueryServiceConfig;
+        let size = (buffer.len() * mem::size_of::<u64>()) as u32;
+        if unsafe { QueryServiceConfigW(service.0, config, size, &mut needed) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let config = unsafe { &*config };
+        let start_type = StartType::from_code(config.start_type)
+            .ok_or_else(|| ProbeError::Parse(format!("unknown start type {}", config.start_type)))?;
+        Ok((start_type, unsafe { from_wide(config.display_name) }))
+    }
+
+    /// Returns when the process `pid` was created, if it can be queried
+    fn process_created(pid: u32) -> Option<SystemTime> {
+        // drivers and stopping services have no process
+        if pid == 0 {
+            return None;
+        }
+        let mut creation = FileTime::default();
+        let (mut exit, mut kernel, mut user) = (FileTime::default(), FileTime::default(), FileTime::default());
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let ok = GetProcessTimes(process, &mut creation, &mut exit, &mut kernel, &mut user);
+            CloseHandle(process);
+            if ok == 0 {
+                return None;
+            }
+        }
+        let intervals = (u64::from(creation.high) << 32) | u64::from(creation.low);
+        let nanos = (intervals % FILETIME_PER_SEC) as u32 * 100;
+        let secs = (intervals / FILETIME_PER_SEC).checked_sub(FILETIME_UNIX_OFFSET)?;
+        Some(UNIX_EPOCH + Duration::new(secs, nanos))
+    }
+
+    /// Copies a NUL-terminated UTF-16 string, an empty string if `s` is null
+    unsafe fn from_wide(s: *const u16) -> String {
+        if s.is_null() {
+            return String::new();
+        }
+        let mut len = 0;
+        while *s.add(len) != 0 {
+            len += 1;
+        }
+        String::from_utf16_lossy(slice::from_raw_parts(s, len))
+    }
+}
+
+
+#[cfg(not(windows))]
+mod imp {
+    use super::ServiceStatus;
+    use crate::probes::ProbeError;
+
+    pub(super) fn query(_name: &str) -> Result<ServiceStatus, ProbeError> {
+        Err(ProbeError::Unsupported("Windows service"))
+    }
+}
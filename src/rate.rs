@@ -0,0 +1,190 @@
+use crate::check::Metric;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A reading of a monotonically increasing counter, e.g. the bytes received by an interface,
+/// taken at a point in time. Samples are persisted between check invocations in their textual
+/// form `value@seconds`, see `Display` and `FromStr`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sample {
+    /// the counter value
+    value: u64,
+    /// the time the counter was read
+    time: SystemTime,
+}
+
+
+impl Sample {
+    /// Creates a new `Sample` of `value`, read now. Use `Sample::at(value, clock.now())` to take
+    /// the time from a `Clock` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the counter value
+    pub fn new(value: u64) -> Self {
+        Self::at(value, SystemTime::now())
+    }
+
+    /// Creates a new `Sample` of `value`, read at `time`
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the counter value
+    /// * `time` - the time the counter was read
+    pub fn at(value: u64, time: SystemTime) -> Self {
+        Self { value, time }
+    }
+
+    /// Returns the counter value
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the time the counter was read
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{}@{}.{:09}", self.value, since_epoch.as_secs(), since_epoch.subsec_nanos())
+    }
+}
+
+
+impl FromStr for Sample {
+    type Err = RateError;
+
+    /// Parses a `Sample` from its textual form `value@seconds`, where `seconds` is the time since
+    /// the epoch and may have a fractional part
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::rate::Sample;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let sample: Sample = "1024@1700000000.5".parse().unwrap();
+    /// assert_eq!(sample, Sample::at(1024, UNIX_EPOCH + Duration::from_millis(1700000000500)));
+    /// assert_eq!(sample.to_string(), "1024@1700000000.500000000");
+    /// assert!("1024".parse::<Sample>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RateError::InvalidSample(s.to_string());
+        let (value, time) = s.trim().split_once('@').ok_or_else(invalid)?;
+        let value = value.parse().map_err(|_| invalid())?;
+        let secs: f64 = time.parse().map_err(|_| invalid())?;
+        let time = Duration::try_from_secs_f64(secs).map_err(|_| invalid())?;
+        Ok(Sample::at(value, UNIX_EPOCH + time))
+    }
+}
+
+
+/// The range of a counter, deciding how a decreasing value is interpreted
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CounterWidth {
+    /// A 32 bit counter, e.g. SNMP's `ifInOctets`, wrapping around after `u32::MAX`
+    Bits32,
+    /// A 64 bit counter, e.g. SNMP's `ifHCInOctets` or `/proc/net/dev`, wrapping around after
+    /// `u64::MAX`
+    Bits64,
+    /// A counter which never wraps, so a decreasing value means it was reset
+    Unbounded,
+}
+
+
+/// Returns the per-second rate a counter increased by between `previous` and `current`. If the
+/// value decreased, the counter is assumed to have wrapped around once, as long as `width`
+/// allows it and `previous` fits into it.
+///
+/// # Arguments
+///
+/// * `previous` - the sample taken by the previous check invocation
+/// * `current` - the sample taken now
+/// * `width` - the range of the counter
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::rate::{rate, CounterWidth, RateError, Sample};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let t0 = UNIX_EPOCH + Duration::from_secs(1700000000);
+/// let t1 = t0 + Duration::from_secs(60);
+/// let previous = Sample::at(1_000, t0);
+/// assert_eq!(rate(&previous, &Sample::at(7_000, t1), CounterWidth::Bits64), Ok(100.0));
+///
+/// let previous = Sample::at(u64::from(u32::MAX) - 999, t0);
+/// assert_eq!(rate(&previous, &Sample::at(5_000, t1), CounterWidth::Bits32), Ok(100.0));
+/// assert_eq!(rate(&previous, &Sample::at(5_000, t1), CounterWidth::Unbounded), Err(RateError::Reset));
+/// ```
+pub fn rate(previous: &Sample, current: &Sample, width: CounterWidth) -> Result<f64, RateError> {
+    let elapsed = match current.time.duration_since(previous.time) {
+        Ok(d) if d > Duration::from_secs(0) => d.as_secs_f64(),
+        _ => return Err(RateError::NoTimeElapsed),
+    };
+
+    let delta = if current.value >= previous.value {
+        current.value - previous.value
+    } else {
+        match width {
+            CounterWidth::Bits32 if previous.value <= u64::from(u32::MAX) => {
+                u64::from(u32::MAX) - previous.value + current.value + 1
+            }
+            CounterWidth::Bits64 => current.value.wrapping_sub(previous.value),
+            _ => return Err(RateError::Reset),
+        }
+    };
+    Ok(delta as f64 / elapsed)
+}
+
+
+impl Metric {
+    /// Creates a new `Metric` struct for a monotonically increasing counter, with the UOM `c`.
+    /// Use `rate()` to report how fast it increases instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the name of this `Metric`
+    /// * `value` - the counter value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::Metric;
+    /// assert_eq!(Metric::counter("eth0_rx_errors", 12).to_string(), "'eth0_rx_errors'=12c;;;;");
+    /// ```
+    pub fn counter<L: Into<String>>(label: L, value: u64) -> Self {
+        Metric::numeric(label, value).uom(String::from("c"))
+    }
+}
+
+
+/// An error occurring while calculating a rate
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RateError {
+    /// The current sample is not newer than the previous one
+    NoTimeElapsed,
+    /// The counter decreased, e.g. because the device was restarted
+    Reset,
+    /// A persisted sample could not be parsed
+    InvalidSample(String),
+}
+
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateError::NoTimeElapsed => write!(f, "no time elapsed since the previous sample"),
+            RateError::Reset => write!(f, "counter was reset"),
+            RateError::InvalidSample(s) => write!(f, "invalid counter sample '{}'", s),
+        }
+    }
+}
+
+
+impl Error for RateError {}
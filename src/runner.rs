@@ -0,0 +1,844 @@
+use crate::check::{colorize, format_float, write_json_option, write_json_string, CheckResult, DedupPolicy, Metric, State};
+use crate::export::as_f64;
+use crate::error::PluginError;
+use crate::platform;
+use crate::units::parse_duration;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::thread;
+use std::time::Duration;
+
+/// The error type plugins may return, any error can be converted into it using `?`
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// The environment variable `Runner::from_env()` reads the timeout from, also read by the `cli`
+/// module
+pub const TIMEOUT_VAR: &str = "ICINGA_TIMEOUT";
+
+/// The environment variable `Runner::from_env()` reads the verbosity level from
+pub const VERBOSITY_VAR: &str = "ICINGA_VERBOSITY";
+
+/// The environment variable `Runner::from_env()` reads the output format from, also read by the
+/// `cli` module
+pub const OUTPUT_FORMAT_VAR: &str = "ICINGA_OUTPUT_FORMAT";
+
+thread_local! {
+    /// The number of `catch_panic()` calls running on this thread
+    static CATCHING: Cell<u32> = const { Cell::new(0) };
+    /// The location of the last panic caught by `catch_panic()` on this thread
+    static CAUGHT_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Whether the plugin running on this thread runs in dry-run mode, see `dry_run()`
+    static DRY_RUN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A check plugin, executed by a `Runner`
+pub trait Plugin {
+    /// Performs the check and returns its `CheckResult`. Errors are turned into UNKNOWN results
+    /// by the `Runner`.
+    fn run(&self) -> Result<CheckResult, Error>;
+}
+
+
+impl<F> Plugin for F where F: Fn() -> Result<CheckResult, Error> {
+    fn run(&self) -> Result<CheckResult, Error> {
+        self()
+    }
+}
+
+
+/// Executes a `Plugin`, enforcing a global timeout and converting errors and panics into UNKNOWN
+/// results, then prints the result and exits with the corresponding exit code
+pub struct Runner<P> {
+    /// the plugin to execute
+    plugin: Arc<P>,
+    /// the time the plugin is given to produce a result
+    timeout: Duration,
+    /// the verbosity level applied to the result
+    verbosity: u8,
+    /// the format the result is printed in
+    output_format: OutputFormat,
+    /// the maximum length of the printed result, if any
+    max_output_bytes: Option<usize>,
+    /// whether the metrics of the result are sorted by label
+    sort_metrics: bool,
+    /// how metrics of the result with the same label are resolved, if at all
+    dedup: Option<DedupPolicy>,
+    /// the metadata of the plugin, if known
+    info: Option<PluginInfo>,
+    /// the interval `run()` executes the plugin in, if it watches it
+    watch: Option<Duration>,
+    /// whether the plugin only reports the actions it would take
+    dry_run: bool,
+}
+
+
+impl<P> Runner<P> where P: Plugin + Send + Sync + 'static {
+    /// Creates a new `Runner` for `plugin` with the default timeout of 10 seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - the plugin to execute
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::runner::{Error, Plugin, Runner};
+    ///
+    /// struct CheckAnswer;
+    ///
+    /// impl Plugin for CheckAnswer {
+    ///     fn run(&self) -> Result<CheckResult, Error> {
+    ///         let answer: u8 = "42".parse()?;
+    ///         Ok(CheckResult::new(State::OK).set_info(format!("the answer is {}", answer)))
+    ///     }
+    /// }
+    ///
+    /// let cr = Runner::new(CheckAnswer).execute();
+    /// assert_eq!(cr.to_string(), "OK - the answer is 42");
+    /// ```
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin: Arc::new(plugin),
+            timeout: Duration::from_secs(10),
+            verbosity: 0,
+            output_format: OutputFormat::Text,
+            max_output_bytes: None,
+            sort_metrics: false,
+            dedup: None,
+            info: None,
+            watch: None,
+            dry_run: false,
+        }
+    }
+
+    /// Sets the time the plugin is given to produce a result
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the global timeout of the check
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the verbosity level (0-3) deciding which debug lines of the result are displayed,
+    /// usually the number of `-v` flags given
+    ///
+    /// # Arguments
+    ///
+    /// * `verbosity` - the verbosity level
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::runner::{Error, Runner};
+    /// let plugin = || -> Result<CheckResult, Error> {
+    ///     Ok(CheckResult::new(State::OK).add_debug(2, String::from("GET /health")))
+    /// };
+    /// assert_eq!(Runner::new(plugin).verbosity(2).execute().to_string(), "OK\nGET /health");
+    /// ```
+    pub fn verbosity(mut self, verbosity: u8) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Sets the format the result is printed in by `run()`
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - the output format
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Limits the length of the printed result, see `CheckResult::set_max_output_bytes()`
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the maximum length of the output
+    pub fn max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = Some(bytes);
+        self
+    }
+
+    /// Sorts the metrics of the result by label instead of keeping the order the plugin added
+    /// them in, e.g. for snapshot tests or diff-based tooling if the plugin collects metrics in
+    /// varying order, see `CheckResult::sort_perf_data()`
+    ///
+    /// # Arguments
+    ///
+    /// * `sort` - whether to sort the metrics
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// use icingaplugin_rs::runner::{Error, Runner};
+    /// let plugin = || -> Result<CheckResult, Error> {
+    ///     Ok(CheckResult::ok("2 disks").add_metric(Metric::new("sdb", 2)).add_metric(Metric::new("sda", 1)))
+    /// };
+    /// assert_eq!(Runner::new(plugin).sort_metrics(true).execute().to_string(), "OK - 2 disks | 'sda'=1;;;; 'sdb'=2;;;; ");
+    /// ```
+    pub fn sort_metrics(mut self, sort: bool) -> Self {
+        self.sort_metrics = sort;
+        self
+    }
+
+    /// Resolves metrics of the result with the same label according to `policy`, see
+    /// `CheckResult::dedup_perf_data()`
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - how duplicates are resolved
+    pub fn dedup_metrics(mut self, policy: DedupPolicy) -> Self {
+        self.dedup = Some(policy);
+        self
+    }
+
+    /// Sets the metadata of the plugin: `run()` prints its banner if the plugin is invoked with
+    /// `-V` or `--version`, and adds it to JSON output
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - the metadata of the plugin, see `plugin_info!()`
+    pub fn info(mut self, info: PluginInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Makes `run()` execute the plugin every `interval` instead of once, printing each result
+    /// followed by the changes of the state and the metric values since the previous run,
+    /// colored if stdout is a terminal. Meant for developing plugins against live systems,
+    /// `run()` never exits in this mode. Plugins using `cli::parse()` enable it with `--watch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - the time between the end of a run and the start of the next one
+    pub fn watch(mut self, interval: Duration) -> Self {
+        self.watch = Some(interval);
+        self
+    }
+
+    /// Runs the plugin in dry-run mode: the plugin can tell from `runner::dry_run()` that it
+    /// should only report the actions it would take, e.g. restarting a service, instead of
+    /// taking them, and the info line of the result is prefixed with `[dry run]`. Plugins using
+    /// `cli::parse()` enable it with `--dry-run`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - whether to run in dry-run mode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::runner::{dry_run, Error, Runner};
+    ///
+    /// let restart_nginx = || -> Result<CheckResult, Error> {
+    ///     if dry_run() {
+    ///         return Ok(CheckResult::ok("would restart nginx"));
+    ///     }
+    ///     // systemctl restart nginx
+    ///     Ok(CheckResult::ok("restarted nginx"))
+    /// };
+    /// assert_eq!(Runner::new(restart_nginx).execute().to_string(), "OK - restarted nginx");
+    /// assert_eq!(Runner::new(restart_nginx).dry_run(true).execute().to_string(), "OK - [dry run] would restart nginx");
+    /// ```
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Overrides the configuration with the environment variables which are set: the timeout
+    /// from `ICINGA_TIMEOUT` (e.g. `30` or `1m30s`), the verbosity level from
+    /// `ICINGA_VERBOSITY` and the output format from `ICINGA_OUTPUT_FORMAT`. Fails if a
+    /// variable has an invalid value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::runner::{Error, Runner};
+    /// let plugin = || -> Result<CheckResult, Error> { Ok(CheckResult::ok("fine").add_debug(1, "GET /health")) };
+    ///
+    /// std::env::set_var("ICINGA_VERBOSITY", "1");
+    /// assert_eq!(Runner::new(plugin).from_env().unwrap().execute().to_string(), "OK - fine\nGET /health");
+    ///
+    /// std::env::set_var("ICINGA_TIMEOUT", "soon");
+    /// let err = Runner::new(plugin).from_env().err().unwrap();
+    /// assert_eq!(err.to_string(), "invalid configuration: invalid duration 'soon' in ICINGA_TIMEOUT");
+    /// ```
+    pub fn from_env(mut self) -> Result<Self, PluginError> {
+        let invalid = |var: &str, e: &dyn fmt::Display| PluginError::Config(format!("{} in {}", e, var));
+        if let Ok(timeout) = env::var(TIMEOUT_VAR) {
+            self.timeout = parse_duration(&timeout).map_err(|e| invalid(TIMEOUT_VAR, &e))?;
+        }
+        if let Ok(verbosity) = env::var(VERBOSITY_VAR) {
+            self.verbosity = verbosity.trim().parse().map_err(|e| invalid(VERBOSITY_VAR, &e))?;
+        }
+        if let Ok(format) = env::var(OUTPUT_FORMAT_VAR) {
+            self.output_format = format.trim().parse().map_err(|e| invalid(OUTPUT_FORMAT_VAR, &e))?;
+        }
+        Ok(self)
+    }
+
+    /// Executes the plugin and returns its `CheckResult`. Errors, panics and timeouts result in
+    /// an UNKNOWN `CheckResult` describing the problem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::runner::{Error, Runner};
+    /// use std::time::Duration;
+    ///
+    /// let failing = || -> Result<CheckResult, Error> { Err("connection refused".into()) };
+    /// assert_eq!(Runner::new(failing).execute().to_string(), "UNKNOWN - connection refused");
+    ///
+    /// let hanging = || -> Result<CheckResult, Error> {
+    ///     std::thread::sleep(Duration::from_secs(5));
+    ///     Ok(CheckResult::new(State::OK))
+    /// };
+    /// let cr = Runner::new(hanging).timeout(Duration::from_millis(10)).execute();
+    /// assert_eq!(cr.to_string(), "UNKNOWN - check timed out after 0.01s");
+    /// ```
+    pub fn execute(&self) -> CheckResult {
+        let (tx, rx) = mpsc::channel();
+        let plugin = Arc::clone(&self.plugin);
+        let dry_run = self.dry_run;
+
+        thread::spawn(move || {
+            DRY_RUN.with(|d| d.set(dry_run));
+            let result = match catch_panic(|| plugin.run()) {
+                Ok(Ok(cr)) => cr,
+                Ok(Err(e)) => CheckResult::unknown(e.to_string()),
+                Err(panic) => CheckResult::unknown(panic),
+            };
+            // log records are captured on the thread the plugin runs on
+            #[cfg(feature = "log")]
+            let result = crate::logging::attach_captured(result);
+            // the receiver is gone if the check timed out, nobody is interested in the result
+            let _ = tx.send(result);
+        });
+
+        let result = rx
+            .recv_timeout(self.timeout)
+            .unwrap_or_else(|_| CheckResult::unknown(format!("check timed out after {}s", self.timeout.as_secs_f64())));
+        let mut result = result.set_verbosity(self.verbosity);
+        if self.dry_run {
+            let info = match result.info() {
+                Some(info) if !info.is_empty() => format!("[dry run] {}", info),
+                _ => String::from("[dry run]"),
+            };
+            result = result.set_info(info);
+        }
+        if let Some(policy) = self.dedup {
+            result = result.dedup_perf_data(policy);
+        }
+        if self.sort_metrics {
+            result = result.sort_perf_data();
+        }
+        match self.max_output_bytes {
+            Some(bytes) => result.set_max_output_bytes(bytes),
+            None => result,
+        }
+    }
+
+    /// Executes the plugin, prints its `CheckResult` and exits with the corresponding exit code.
+    /// While the plugin runs, stdout is redirected to stderr, so nothing but the `CheckResult`
+    /// reaches stdout, even if the plugin or one of its dependencies prints something. If the
+    /// metadata of the plugin is set and the plugin is invoked with `-V` or `--version` as its
+    /// only argument, prints its banner instead and exits with UNKNOWN (3), as the Monitoring
+    /// Plugins guidelines demand. Elsewhere on the command line `-V` may be the value of an
+    /// option, so plugins using `cli::parse()` leave it to clap.
+    pub fn run(&self) -> ! {
+        platform::init();
+        if let Some(info) = &self.info {
+            if version_requested(env::args().skip(1)) {
+                println!("{}", info.banner());
+                platform::exit(State::Unknown.into())
+            }
+        }
+        if let Some(interval) = self.watch {
+            self.run_watch(interval)
+        }
+        let cr = {
+            let _stdout = platform::redirect_stdout();
+            self.execute()
+        };
+        exit_with(cr, self.output_format, self.info.as_ref())
+    }
+
+    /// Executes the plugin every `interval` and prints the results with the changes since the
+    /// previous run, see `watch()`
+    fn run_watch(&self, interval: Duration) -> ! {
+        let colored = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        let mut previous: Option<CheckResult> = None;
+        for round in 1.. {
+            let cr = {
+                let _stdout = platform::redirect_stdout();
+                self.execute()
+            };
+            let mut out = io::stdout().lock();
+            let _ = writeln!(out, "--- run {} ---", round);
+            let _ = write_result(&mut out, &cr, self.output_format, self.info.as_ref(), colored);
+            if let Some(previous) = &previous {
+                let _ = writeln!(out, "{}", watch_diff(previous, &cr, colored));
+            }
+            drop(out);
+            previous = Some(cr);
+            thread::sleep(interval);
+        }
+        unreachable!("watching never ends")
+    }
+
+    /// Executes the plugin and writes its `CheckResult` in the output format to `out` instead of
+    /// stdout, without exiting. Returns the exit code the plugin would exit with, e.g. for
+    /// daemons executing checks repeatedly or tests capturing the output. The `Color` format is
+    /// colored unless `NO_COLOR` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - the writer the output is written to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::runner::{Error, OutputFormat, Runner};
+    ///
+    /// let plugin = || -> Result<CheckResult, Error> { Ok(CheckResult::critical("disk full")) };
+    /// let mut out = Vec::new();
+    /// assert_eq!(Runner::new(plugin).run_to(&mut out).unwrap(), 2);
+    /// assert_eq!(String::from_utf8(out).unwrap(), "CRITICAL - disk full\n");
+    ///
+    /// let mut out = Vec::new();
+    /// Runner::new(plugin).output_format(OutputFormat::Json).run_to(&mut out).unwrap();
+    /// assert!(out.starts_with(br#"{"state":"CRITICAL""#));
+    /// ```
+    pub fn run_to<W: Write>(&self, out: &mut W) -> io::Result<i32> {
+        let cr = self.execute();
+        let colored = env::var_os("NO_COLOR").is_none();
+        write_result(out, &cr, self.output_format, self.info.as_ref(), colored)?;
+        Ok(cr.state().into())
+    }
+}
+
+
+/// Returns whether the plugin runs in dry-run mode, see `Runner::dry_run()`. Plugins taking
+/// actions, e.g. event handlers or remediation plugins, should then only report the actions they
+/// would take. The mode is only known on the thread the `Runner` runs the plugin on, not on
+/// threads the plugin spawns itself.
+pub fn dry_run() -> bool {
+    DRY_RUN.with(Cell::get)
+}
+
+
+/// The metadata of a plugin, used for the `-V/--version` banner, JSON output and the
+/// `CheckCommand` export. Use `plugin_info!()` to take it from the plugin's `Cargo.toml`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PluginInfo {
+    /// the name of the plugin, e.g. `check_foo`
+    name: String,
+    /// the version of the plugin
+    version: String,
+    /// the author or copyright holder, if known
+    author: Option<String>,
+    /// the license, if known
+    license: Option<String>,
+    /// the homepage or repository, if known
+    url: Option<String>,
+}
+
+
+impl PluginInfo {
+    /// Creates a new `PluginInfo` for the plugin `name` in `version`
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the plugin, e.g. `check_foo`
+    /// * `version` - the version of the plugin
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::runner::PluginInfo;
+    /// let info = PluginInfo::new("check_foo", "1.2.0")
+    ///     .set_author("NETWAYS GmbH")
+    ///     .set_license("GPL-2.0-or-later")
+    ///     .set_url("https://github.com/NETWAYS/check_foo");
+    /// assert_eq!(info.banner(), "check_foo v1.2.0 (https://github.com/NETWAYS/check_foo)\n\
+    ///     Copyright (c) NETWAYS GmbH\nLicense: GPL-2.0-or-later");
+    /// assert_eq!(PluginInfo::new("check_foo", "1.2.0").banner(), "check_foo v1.2.0");
+    /// ```
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, version: V) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            author: None,
+            license: None,
+            url: None,
+        }
+    }
+
+    /// Sets the author or copyright holder of the plugin
+    ///
+    /// # Arguments
+    ///
+    /// * `author` - the author
+    pub fn set_author<S: Into<String>>(mut self, author: S) -> Self {
+        self.author = Some(author.into()).filter(|a| !a.is_empty());
+        self
+    }
+
+    /// Sets the license of the plugin
+    ///
+    /// # Arguments
+    ///
+    /// * `license` - the license, e.g. an SPDX identifier
+    pub fn set_license<S: Into<String>>(mut self, license: S) -> Self {
+        self.license = Some(license.into()).filter(|l| !l.is_empty());
+        self
+    }
+
+    /// Sets the homepage or repository of the plugin
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the URL
+    pub fn set_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.url = Some(url.into()).filter(|u| !u.is_empty());
+        self
+    }
+
+    /// Returns the name of the plugin
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the version of the plugin
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the author of the plugin, if known
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// Returns the license of the plugin, if known
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Returns the homepage or repository of the plugin, if known
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the banner printed for `-V/--version`: the name, version and URL, followed by the
+    /// copyright and license lines if known
+    pub fn banner(&self) -> String {
+        let mut banner = format!("{} v{}", self.name, self.version);
+        if let Some(url) = &self.url {
+            banner += &format!(" ({})", url);
+        }
+        if let Some(author) = &self.author {
+            banner += &format!("\nCopyright (c) {}", author);
+        }
+        if let Some(license) = &self.license {
+            banner += &format!("\nLicense: {}", license);
+        }
+        banner
+    }
+
+    /// Returns the metadata as a JSON object, unknown fields are `null`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::runner::PluginInfo;
+    /// assert_eq!(PluginInfo::new("check_foo", "1.2.0").set_license("MIT").to_json(),
+    ///     r#"{"name":"check_foo","version":"1.2.0","author":null,"license":"MIT","url":null}"#);
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"name\":");
+        write_json_string(&mut out, &self.name);
+        out += ",\"version\":";
+        write_json_string(&mut out, &self.version);
+        for (key, value) in [("author", &self.author), ("license", &self.license), ("url", &self.url)] {
+            out += &format!(",\"{}\":", key);
+            write_json_option(&mut out, value.as_deref());
+        }
+        out.push('}');
+        out
+    }
+}
+
+
+/// Creates a `PluginInfo` from the metadata in the `Cargo.toml` of the calling crate: the package
+/// name, version, authors, license and homepage, or repository if no homepage is set
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::plugin_info;
+/// let info = plugin_info!();
+/// assert_eq!(info.name(), env!("CARGO_PKG_NAME"));
+/// ```
+#[macro_export]
+macro_rules! plugin_info {
+    () => {
+        $crate::runner::PluginInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+            .set_author(env!("CARGO_PKG_AUTHORS").replace(':', ", "))
+            .set_license(env!("CARGO_PKG_LICENSE"))
+            .set_url(match env!("CARGO_PKG_HOMEPAGE") {
+                "" => env!("CARGO_PKG_REPOSITORY"),
+                homepage => homepage,
+            })
+    };
+}
+
+
+/// The formats a `Runner` can print results in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// The plugin output format understood by Icinga2
+    Text,
+    /// A JSON object, see `CheckResult::to_json()`
+    Json,
+    /// The `Text` format with ANSI colors if stdout is a terminal and `NO_COLOR` is not set, see
+    /// `CheckResult::to_colored_string()`. Otherwise, e.g. when run by Icinga2, the output is
+    /// identical to `Text`.
+    Color,
+}
+
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    /// Parses an `OutputFormat` from its name
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::runner::OutputFormat;
+    /// assert_eq!("json".parse(), Ok(OutputFormat::Json));
+    /// assert_eq!("color".parse(), Ok(OutputFormat::Color));
+    /// assert!("xml".parse::<OutputFormat>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "color" => Ok(OutputFormat::Color),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+
+/// A watchdog which prints `UNKNOWN - check timed out after Ns` and exits with UNKNOWN (3) if it
+/// is not disarmed before the deadline. Unlike the `Runner`, which abandons the plugin's thread,
+/// this also covers code running before or after the check, e.g. argument parsing or name
+/// resolution. The deadline is watched by a timer thread on all platforms, as exiting from a
+/// thread is safe where exiting from a signal handler is not.
+///
+/// The watchdog is disarmed when the `Timeout` is dropped.
+pub struct Timeout {
+    /// whether the watchdog was disarmed, and the condition variable waking the timer thread
+    disarmed: Arc<(Mutex<bool>, Condvar)>,
+}
+
+
+impl Timeout {
+    /// Arms a watchdog exiting the process once `timeout` has passed
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the time the check may take
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::runner::Timeout;
+    /// use std::time::Duration;
+    ///
+    /// let timeout = Timeout::arm(Duration::from_secs(10));
+    /// let cr = CheckResult::new(State::OK).set_info(String::from("responded in 12ms"));
+    /// timeout.disarm();
+    /// ```
+    pub fn arm(timeout: Duration) -> Self {
+        let disarmed = Arc::new((Mutex::new(false), Condvar::new()));
+        let watched = Arc::clone(&disarmed);
+
+        thread::spawn(move || {
+            let (lock, condvar) = &*watched;
+            let guard = match lock.lock() {
+                Ok(g) => g,
+                Err(_) => return,
+            };
+            let disarmed = match condvar.wait_timeout_while(guard, timeout, |d| !*d) {
+                Ok((d, _)) => *d,
+                Err(_) => return,
+            };
+            if !disarmed {
+                platform::exit(CheckResult::unknown(format!("check timed out after {}s", timeout.as_secs_f64())).promote());
+            }
+        });
+
+        Self { disarmed }
+    }
+
+    /// Disarms the watchdog. Call this before printing the result, so the output cannot be
+    /// interleaved with the timeout message.
+    pub fn disarm(self) {}
+}
+
+
+impl Drop for Timeout {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.disarmed;
+        if let Ok(mut disarmed) = lock.lock() {
+            *disarmed = true;
+        }
+        condvar.notify_all();
+    }
+}
+
+
+/// Returns whether the command line `args`, without the program name, ask for the version banner,
+/// i.e. consist of nothing but `-V` or `--version`
+pub(crate) fn version_requested<S: AsRef<str>, I: IntoIterator<Item = S>>(args: I) -> bool {
+    let mut args = args.into_iter();
+    match (args.next(), args.next()) {
+        (Some(arg), None) => arg.as_ref() == "-V" || arg.as_ref() == "--version",
+        _ => false,
+    }
+}
+
+
+/// Prints `cr` in `format` and exits with the corresponding exit code. JSON output includes the
+/// metadata of the plugin as `plugin` if given.
+pub(crate) fn exit_with(cr: CheckResult, format: OutputFormat, info: Option<&PluginInfo>) -> ! {
+    let colored = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let _ = write_result(&mut io::stdout().lock(), &cr, format, info, colored);
+    platform::exit(cr.state().into())
+}
+
+
+/// Describes the changes of the state and the metric values from `previous` to `current`, one
+/// per line, for `Runner::watch()`. Numeric changes include the difference. If `colored` is set,
+/// each line is colored by the new state of the state or metric.
+pub(crate) fn watch_diff(previous: &CheckResult, current: &CheckResult, colored: bool) -> String {
+    let paint = |s: String, state: State| match colored {
+        true => colorize(&s, state),
+        false => s,
+    };
+    let before = previous.perf_data().map_or(&[][..], |pd| &pd.metrics[..]);
+    let after = current.perf_data().map_or(&[][..], |pd| &pd.metrics[..]);
+    let display = |m: &Metric| format!("{}{}", m.formatted_value(), m.uom.as_deref().unwrap_or(""));
+
+    let mut lines = Vec::new();
+    if previous.state() != current.state() {
+        lines.push(paint(format!("state: {} -> {}", previous.state(), current.state()), current.state()));
+    }
+    for metric in after {
+        let line = match before.iter().find(|m| m.label == metric.label) {
+            None => format!("{}: new, {}", metric.label, display(metric)),
+            Some(old) if old.value != metric.value || old.uom != metric.uom => {
+                let values = [old.formatted_value(), metric.formatted_value()];
+                let delta = match (as_f64(&old.value), as_f64(&metric.value)) {
+                    (Some(a), Some(b)) if (b - a).is_finite() && old.uom == metric.uom => {
+                        // as many decimal places as the values are displayed with
+                        let decimals = values
+                            .iter()
+                            .map(|v| v.split_once('.').map_or(0, |(_, d)| d.len()))
+                            .max();
+                        let sign = if b >= a { "+" } else { "" };
+                        format!(" ({}{})", sign, format_float(b - a, decimals))
+                    }
+                    _ => String::new(),
+                };
+                format!("{}: {} -> {}{}", metric.label, display(old), display(metric), delta)
+            }
+            Some(_) => continue,
+        };
+        lines.push(paint(line, metric.threshold_state()));
+    }
+    for metric in before {
+        if !after.iter().any(|m| m.label == metric.label) {
+            lines.push(format!("{}: removed", metric.label));
+        }
+    }
+
+    match lines.is_empty() {
+        true => String::from("no changes"),
+        false => lines.join("\n"),
+    }
+}
+
+
+/// Writes `cr` in `format` to `out`, followed by a line break. The `Color` format is only
+/// colored if `colored` is set.
+fn write_result<W: Write>(out: &mut W, cr: &CheckResult, format: OutputFormat, info: Option<&PluginInfo>,
+                          colored: bool) -> io::Result<()> {
+    match (format, info) {
+        (OutputFormat::Text, _) => writeln!(out, "{}", cr)?,
+        (OutputFormat::Color, _) if colored => writeln!(out, "{}", cr.to_colored_string())?,
+        (OutputFormat::Color, _) => writeln!(out, "{}", cr)?,
+        (OutputFormat::Json, Some(info)) => writeln!(out, "{{\"plugin\":{},{}", info.to_json(), &cr.to_json()[1..])?,
+        (OutputFormat::Json, None) => writeln!(out, "{}", cr.to_json())?,
+    }
+    out.flush()
+}
+
+
+/// Runs `f`, catching a panic and describing it by its message and location. The panic hook is
+/// replaced once per process by one recording the location of panics on threads running
+/// `catch_panic()`, instead of printing them with a backtrace which would end up in the plugin
+/// output. Panics on other threads are passed to the previous hook.
+pub(crate) fn catch_panic<R, F: FnOnce() -> R>(f: F) -> Result<R, String> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(Cell::get) == 0 {
+                return previous_hook(info);
+            }
+            let location = info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+            CAUGHT_LOCATION.with(|caught| *caught.borrow_mut() = location);
+        }));
+    });
+
+    CATCHING.with(|c| c.set(c.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(c.get() - 1));
+    result.map_err(|payload| match CAUGHT_LOCATION.with(|caught| caught.borrow_mut().take()) {
+        Some(l) => format!("check panicked: {} ({})", panic_message(&payload), l),
+        None => format!("check panicked: {}", panic_message(&payload)),
+    })
+}
+
+
+/// Extracts the message from the payload of a panic
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
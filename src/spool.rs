@@ -0,0 +1,118 @@
+use crate::check::CheckResult;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Distinguishes files created by this process within the same nanosecond
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Drops passive check results into the `check_result_path` spool directory of Nagios, Icinga 1.x
+/// or Naemon, which reaps them without any network involved. Each result is written to its own
+/// `c??????` file, followed by the `.ok` file signalling that it is complete.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpoolWriter {
+    /// the spool directory
+    dir: PathBuf,
+}
+
+
+impl SpoolWriter {
+    /// Creates a new `SpoolWriter` for the spool directory `dir`
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - the `check_result_path` of the monitoring core
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use icingaplugin_rs::check::{CheckResult, State};
+    /// use icingaplugin_rs::spool::SpoolWriter;
+    /// use std::path::PathBuf;
+    ///
+    /// let spool = SpoolWriter::new(PathBuf::from("/var/spool/nagios/checkresults"));
+    /// let cr = CheckResult::new(State::OK).set_info(String::from("backup finished"));
+    /// spool.write("db01", Some("backup"), &cr).unwrap();
+    /// ```
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Writes `result` as passive check result for the given host or service and returns the
+    /// path of the result file. Fails with `InvalidInput` if the host or service name contains a
+    /// line break, which would inject further fields into the result file.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the name of the host
+    /// * `service` - the name of the service, `None` for a host check result
+    /// * `result` - the check result
+    pub fn write(&self, host: &str, service: Option<&str>, result: &CheckResult) -> io::Result<PathBuf> {
+        for name in std::iter::once(host).chain(service) {
+            if name.contains(['\n', '\r']) {
+                let message = format!("line break in host or service name {:?}", name);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        }
+        let contents = contents(host, service, result, SystemTime::now());
+        let (path, mut file) = self.create()?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+
+        let mut ok = path.clone().into_os_string();
+        ok.push(".ok");
+        File::create(ok)?;
+        Ok(path)
+    }
+
+    /// Creates a new, uniquely named result file
+    fn create(&self) -> io::Result<(PathBuf, File)> {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+        loop {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+            let mut seed = u64::from(nanos) ^ (u64::from(process::id()) << 32)
+                ^ u64::from(COUNTER.fetch_add(1, Ordering::Relaxed)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+
+            let mut name = String::from("c");
+            for _ in 0..6 {
+                name.push(CHARS[(seed % CHARS.len() as u64) as usize] as char);
+                seed /= CHARS.len() as u64;
+            }
+
+            let path = self.dir.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+
+/// Formats a check result file. Newlines in the output are escaped, as the core reads a single
+/// `output` line.
+pub(crate) fn contents(host: &str, service: Option<&str>, result: &CheckResult, time: SystemTime) -> String {
+    let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let output = result.to_string().replace('\\', "\\\\").replace('\n', "\\n");
+
+    let mut s = format!("### Passive Check Result File ###\nfile_time={}\n\n", time.as_secs());
+    match service {
+        Some(service) => {
+            s.push_str("### Nagios Service Check Result ###\n");
+            s.push_str(&format!("host_name={}\nservice_description={}\n", host, service));
+        }
+        None => {
+            s.push_str("### Nagios Host Check Result ###\n");
+            s.push_str(&format!("host_name={}\n", host));
+        }
+    }
+    s.push_str("check_type=1\ncheck_options=0\nscheduled_check=0\nreschedule_check=0\nlatency=0.0\n");
+    s.push_str(&format!("start_time={0}.{1:06}\nfinish_time={0}.{1:06}\n", time.as_secs(), time.subsec_micros()));
+    s.push_str("early_timeout=0\nexited_ok=1\n");
+    s.push_str(&format!("return_code={}\noutput={}\n", i32::from(result.state()), output));
+    s
+}
@@ -0,0 +1,298 @@
+use crate::check::{CheckResult, State};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// The exit code `ssh` uses for its own errors, e.g. failed connections or authentication
+const SSH_ERROR: i32 = 255;
+
+/// Runs commands, usually check plugins, on a remote host using the system's `ssh` client, like
+/// `check_by_ssh` does. Only key based authentication is supported, `ssh` never prompts for
+/// passwords.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ssh {
+    /// the remote host
+    host: String,
+    /// the remote user, if not the local default
+    user: Option<String>,
+    /// the remote port, if not the default
+    port: Option<u16>,
+    /// the private key used for authentication, if not the default
+    identity_file: Option<PathBuf>,
+    /// the time to wait for the connection to be established
+    connect_timeout: Duration,
+    /// the control socket of a multiplexed master connection, if connections are reused
+    control_path: Option<PathBuf>,
+    /// how long an idle master connection is kept open
+    control_persist: Duration,
+    /// additional `-o` options passed to `ssh`
+    options: Vec<String>,
+}
+
+
+impl Ssh {
+    /// Creates a new `Ssh` helper connecting to `host` with a connect timeout of 10 seconds
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the remote host name or address
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::ssh::Ssh;
+    /// let ssh = Ssh::new(String::from("db01.example.com"));
+    /// let args: Vec<_> = ssh.command("/usr/lib/nagios/plugins/check_load").get_args()
+    ///     .map(|a| a.to_string_lossy().into_owned()).collect();
+    /// assert_eq!(args, ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "--",
+    ///     "db01.example.com", "/usr/lib/nagios/plugins/check_load"]);
+    /// ```
+    pub fn new<S: Into<String>>(host: S) -> Self {
+        Self {
+            host: host.into(),
+            user: None,
+            port: None,
+            identity_file: None,
+            connect_timeout: Duration::from_secs(10),
+            control_path: None,
+            control_persist: Duration::from_secs(60),
+            options: Vec::new(),
+        }
+    }
+
+    /// Sets the remote user
+    ///
+    /// # Arguments
+    ///
+    /// * `user` - the user to log in as
+    pub fn user<S: Into<String>>(mut self, user: S) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Sets the remote port
+    ///
+    /// # Arguments
+    ///
+    /// * `port` - the port the remote `sshd` listens on
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Sets the private key used for authentication
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path to the private key
+    pub fn identity_file(mut self, path: PathBuf) -> Self {
+        self.identity_file = Some(path);
+        self
+    }
+
+    /// Sets the time to wait for the connection to be established. `ssh` only supports whole
+    /// seconds, so `timeout` is rounded up.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - the connect timeout
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Reuses a multiplexed master connection for consecutive commands, which saves the
+    /// handshake when a plugin runs many commands against the same host
+    ///
+    /// # Arguments
+    ///
+    /// * `control_path` - the socket of the master connection, e.g. `/run/icinga2/ssh-%C`
+    /// * `persist` - how long an idle master connection is kept open
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::ssh::Ssh;
+    /// use std::path::PathBuf;
+    /// use std::time::Duration;
+    /// let ssh = Ssh::new(String::from("db01")).user(String::from("icinga")).port(2222)
+    ///     .multiplex(PathBuf::from("/tmp/ssh-%C"), Duration::from_secs(300));
+    /// let args: Vec<_> = ssh.command("uptime").get_args()
+    ///     .map(|a| a.to_string_lossy().into_owned()).collect();
+    /// assert_eq!(args, ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "-p", "2222",
+    ///     "-o", "ControlMaster=auto", "-o", "ControlPath=/tmp/ssh-%C", "-o", "ControlPersist=300",
+    ///     "--", "icinga@db01", "uptime"]);
+    /// ```
+    pub fn multiplex(mut self, control_path: PathBuf, persist: Duration) -> Self {
+        self.control_path = Some(control_path);
+        self.control_persist = persist;
+        self
+    }
+
+    /// Adds an arbitrary `ssh` option
+    ///
+    /// # Arguments
+    ///
+    /// * `option` - an option in `ssh_config` syntax, e.g. `StrictHostKeyChecking=accept-new`
+    pub fn option<S: Into<String>>(mut self, option: S) -> Self {
+        self.options.push(option.into());
+        self
+    }
+
+    /// Returns the `Command` that runs `remote_command` on the remote host
+    ///
+    /// # Arguments
+    ///
+    /// * `remote_command` - the command line executed by the remote shell
+    pub fn command(&self, remote_command: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        let timeout = self.connect_timeout.as_secs() + u64::from(self.connect_timeout.subsec_nanos() > 0);
+        cmd.arg("-o").arg("BatchMode=yes");
+        cmd.arg("-o").arg(format!("ConnectTimeout={}", timeout));
+        if let Some(port) = self.port {
+            cmd.arg("-p").arg(port.to_string());
+        }
+        if let Some(identity) = &self.identity_file {
+            cmd.arg("-i").arg(identity);
+        }
+        if let Some(path) = &self.control_path {
+            cmd.arg("-o").arg("ControlMaster=auto");
+            cmd.arg("-o").arg(format!("ControlPath={}", path.display()));
+            cmd.arg("-o").arg(format!("ControlPersist={}", self.control_persist.as_secs()));
+        }
+        for option in &self.options {
+            cmd.arg("-o").arg(option);
+        }
+        // ends the options, so a host or user starting with `-` cannot be taken for one
+        cmd.arg("--");
+        match &self.user {
+            Some(user) => cmd.arg(format!("{}@{}", user, self.host)),
+            None => cmd.arg(&self.host),
+        };
+        cmd.arg(remote_command);
+        cmd
+    }
+
+    /// Runs `remote_command` on the remote host and returns its output
+    ///
+    /// # Arguments
+    ///
+    /// * `remote_command` - the command line executed by the remote shell
+    pub fn run(&self, remote_command: &str) -> Result<SshOutput, SshError> {
+        let output = self.command(remote_command).output().map_err(SshError::Spawn)?;
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+        match output.status.code() {
+            Some(SSH_ERROR) => Err(SshError::Connection(stderr)),
+            Some(code) => Ok(SshOutput {
+                exit_code: code,
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr,
+            }),
+            None => Err(SshError::Killed),
+        }
+    }
+
+    /// Runs the check plugin `remote_command` on the remote host and maps its output to a
+    /// `CheckResult`, see `SshOutput::into_check_result()`
+    ///
+    /// # Arguments
+    ///
+    /// * `remote_command` - the command line executed by the remote shell
+    pub fn check(&self, remote_command: &str) -> Result<CheckResult, SshError> {
+        self.run(remote_command).map(SshOutput::into_check_result)
+    }
+}
+
+
+/// The output of a command run by `Ssh`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SshOutput {
+    /// the exit code of the remote command
+    pub exit_code: i32,
+    /// everything the remote command wrote to stdout
+    pub stdout: String,
+    /// everything the remote command wrote to stderr
+    pub stderr: String,
+}
+
+
+impl SshOutput {
+    /// Maps the output of a remote check plugin to a `CheckResult`. The state is taken from the
+    /// exit code, exit codes outside of 0..=3 result in UNKNOWN. Plugin output is parsed into
+    /// info string, long output and performance data. Output which cannot be parsed, e.g. shell
+    /// errors, becomes the info string, falling back to stderr if stdout is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::ssh::SshOutput;
+    /// let output = SshOutput { exit_code: 127, stdout: String::new(),
+    ///     stderr: String::from("bash: check_foo: command not found") };
+    /// let cr = output.into_check_result();
+    /// assert_eq!(cr.state(), State::Unknown);
+    /// assert_eq!(cr.to_string(), "UNKNOWN - bash: check_foo: command not found");
+    ///
+    /// let output = SshOutput { exit_code: 1,
+    ///     stdout: String::from("WARNING - load is 4.2 | load1=4.2\n"), stderr: String::new() };
+    /// assert_eq!(output.into_check_result().to_string(), "WARNING - load is 4.2 | 'load1'=4.2;;;; ");
+    /// ```
+    pub fn into_check_result(self) -> CheckResult {
+        let state = State::from_exit_code_lossy(self.exit_code);
+
+        if let Ok(parsed) = self.stdout.parse::<CheckResult>() {
+            // the exit code is authoritative, not the state word printed by the plugin
+            return parsed.set_state(state);
+        }
+
+        let stdout = self.stdout.trim();
+        let info = match stdout.lines().next() {
+            Some(line) => line.to_string(),
+            None => self.stderr,
+        };
+
+        if info.is_empty() {
+            CheckResult::new(state)
+        } else {
+            CheckResult::new(state).set_info(info)
+        }
+    }
+}
+
+
+/// An error occurring while running a command over SSH
+#[derive(Debug)]
+pub enum SshError {
+    /// The `ssh` client could not be started
+    Spawn(io::Error),
+    /// `ssh` failed to connect or authenticate, containing its error message
+    Connection(String),
+    /// `ssh` was terminated by a signal
+    Killed,
+}
+
+
+impl fmt::Display for SshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SshError::Spawn(e) => write!(f, "failed to execute ssh: {}", e),
+            SshError::Connection(msg) if msg.is_empty() => write!(f, "ssh connection failed"),
+            SshError::Connection(msg) => write!(f, "ssh connection failed: {}", msg),
+            SshError::Killed => write!(f, "ssh was terminated by a signal"),
+        }
+    }
+}
+
+
+impl Error for SshError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SshError::Spawn(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::check::{CheckResult, Metric, PerfData, State, Value};
+use crate::thresholds::Range;
+use proptest::prelude::*;
+
+// All generated values survive a round-trip through their textual form, e.g.
+// `CheckResult::to_string()` and `str::parse()`, so plugins can property-test their formatting
+// and parsing.
+
+/// The units of measurement generated metrics have
+const UOMS: [&str; 8] = ["s", "ms", "us", "%", "B", "KB", "MB", "c"];
+
+
+/// Returns a strategy for metric labels, including spaces, slashes and single quotes
+pub fn label() -> impl Strategy<Value = String> {
+    "[a-zA-Z_/][a-zA-Z0-9_/.' -]{0,18}[a-zA-Z0-9_/']?"
+}
+
+
+/// Returns a strategy for numeric values and `Value::Unknown`. Floats always have a fractional
+/// part, as integral floats are displayed and thus parsed as integers.
+pub fn value() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        4 => any::<i64>().prop_map(Value::Integer),
+        4 => (-1e12..1e12f64).prop_filter("integral float", |f| f.fract() != 0.0).prop_map(Value::Float),
+        1 => Just(Value::Unknown),
+    ]
+}
+
+
+prop_compose! {
+    /// Returns a strategy for ranges, bounded or unbounded, alerting outside or inside
+    pub fn range()(a in -1e6..1e6f64, b in -1e6..1e6f64, kind in 0..4u8, inside in any::<bool>()) -> Range {
+        let (start, end) = if a <= b { (a, b) } else { (b, a) };
+        let (start, end) = match kind {
+            0 => (0.0, end.abs()),
+            1 => (f64::NEG_INFINITY, end),
+            2 => (start, f64::INFINITY),
+            _ => (start, end),
+        };
+        if inside { Range::inside(start, end) } else { Range::new(start, end) }
+    }
+}
+
+
+prop_compose! {
+    /// Returns a strategy for metrics with optional UOM, thresholds and bounds
+    pub fn metric()(
+        label in label(),
+        value in value(),
+        uom in proptest::option::of(proptest::sample::select(&UOMS[..])),
+        warning in proptest::option::of(range()),
+        critical in proptest::option::of(range()),
+        min in proptest::option::of(any::<i32>()),
+        max in proptest::option::of(any::<i32>()),
+    ) -> Metric {
+        let mut metric = Metric::new(label, value);
+        if let Some(uom) = uom {
+            metric = metric.uom(uom);
+        }
+        if let Some(warning) = warning {
+            metric = metric.warning(warning.to_string());
+        }
+        if let Some(critical) = critical {
+            metric = metric.critical(critical.to_string());
+        }
+        if let Some(min) = min {
+            metric = metric.min(min.to_string());
+        }
+        if let Some(max) = max {
+            metric = metric.max(max.to_string());
+        }
+        metric
+    }
+}
+
+
+/// Returns a strategy for performance data of up to 8 metrics
+pub fn perf_data() -> impl Strategy<Value = PerfData> {
+    proptest::collection::vec(metric(), 0..8).prop_map(PerfData::from_metrics)
+}
+
+
+/// Returns a strategy for the four states
+pub fn state() -> impl Strategy<Value = State> {
+    prop_oneof![Just(State::OK), Just(State::Warning), Just(State::Critical), Just(State::Unknown)]
+}
+
+
+prop_compose! {
+    /// Returns a strategy for check results with an optional service name, info line,
+    /// performance data and long output
+    pub fn check_result()(
+        state in state(),
+        service in proptest::option::of("[A-Z]{3,8}".prop_filter("state name", |s| s.parse::<State>().is_err())),
+        info in proptest::option::of("[a-zA-Z0-9]([a-zA-Z0-9 ,.%()]{0,38}[a-zA-Z0-9])?"),
+        metrics in proptest::collection::vec(metric(), 1..8),
+        with_perf_data in any::<bool>(),
+        long_output in proptest::collection::vec("[a-zA-Z0-9\\[][a-zA-Z0-9 ,.%()\\]]{0,40}", 0..4),
+    ) -> CheckResult {
+        let mut cr = CheckResult::new(state).set_long_output(long_output);
+        if let Some(service) = service {
+            cr = cr.set_service(service);
+        }
+        if let Some(info) = info {
+            cr = cr.set_info(info);
+        }
+        if with_perf_data {
+            cr = cr.set_perf_data(PerfData::from_metrics(metrics));
+        }
+        cr
+    }
+}
+
+
+impl Arbitrary for Range {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        range().boxed()
+    }
+}
+
+
+impl Arbitrary for Metric {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        metric().boxed()
+    }
+}
+
+
+impl Arbitrary for PerfData {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        perf_data().boxed()
+    }
+}
+
+
+impl Arbitrary for State {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        state().boxed()
+    }
+}
+
+
+impl Arbitrary for CheckResult {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        check_result().boxed()
+    }
+}
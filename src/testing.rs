@@ -0,0 +1,273 @@
+use crate::check::{CheckResult, Metric, State, Value};
+use crate::runner::{Plugin, Runner};
+
+/// Assertions on the `CheckResult` of a plugin, for unit tests of checks. Every assertion panics
+/// with the complete plugin output if it fails, and returns the `CheckAssert` so assertions can
+/// be chained.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{CheckResult, Metric, State};
+/// use icingaplugin_rs::runner::Error;
+/// use icingaplugin_rs::testing::CheckAssert;
+///
+/// let check = || -> Result<CheckResult, Error> {
+///     Ok(CheckResult::warning("load is 3.2")
+///         .add_metric(Metric::new("load1", 3.2).warning("3"))
+///         .add_long_output("4 cores"))
+/// };
+/// CheckAssert::run(check)
+///     .state(State::Warning)
+///     .exit_code(1)
+///     .first_line("WARNING - load is 3.2")
+///     .long_output(&["4 cores"])
+///     .metric("load1", 3.2)
+///     .no_metric("load5");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckAssert {
+    /// the result the assertions are made on
+    result: CheckResult,
+}
+
+
+impl CheckAssert {
+    /// Executes `plugin` with a `Runner`, like the plugin binary would, and returns the
+    /// assertions on its result
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - the plugin to execute
+    pub fn run<P: Plugin + Send + Sync + 'static>(plugin: P) -> Self {
+        Self::new(Runner::new(plugin).execute())
+    }
+
+    /// Returns the assertions on a `CheckResult` created directly
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - the result the assertions are made on
+    pub fn new(result: CheckResult) -> Self {
+        Self { result }
+    }
+
+    /// Returns the result the assertions are made on
+    pub fn result(&self) -> &CheckResult {
+        &self.result
+    }
+
+    /// Returns the first line of the output without the performance data, e.g.
+    /// `WARNING - load is 3.2`
+    pub fn get_first_line(&self) -> String {
+        let output = self.result.to_string();
+        let line = output.lines().next().unwrap_or_default();
+        line.split(" | ").next().unwrap_or_default().to_string()
+    }
+
+    /// Asserts that the result has `state`
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the expected state
+    #[track_caller]
+    pub fn state(self, state: State) -> Self {
+        if self.result.state() != state {
+            self.fail(&format!("expected state {}, got {}", state, self.result.state()));
+        }
+        self
+    }
+
+    /// Asserts that the plugin exits with `code`
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - the expected exit code
+    #[track_caller]
+    pub fn exit_code(self, code: i32) -> Self {
+        let actual = i32::from(self.result.state());
+        if actual != code {
+            self.fail(&format!("expected exit code {}, got {}", code, actual));
+        }
+        self
+    }
+
+    /// Asserts that the first line of the output, without the performance data, is `line`
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - the expected first line, e.g. `"OK - all disks healthy"`
+    #[track_caller]
+    pub fn first_line(self, line: &str) -> Self {
+        let actual = self.get_first_line();
+        if actual != line {
+            self.fail(&format!("expected first line '{}', got '{}'", line, actual));
+        }
+        self
+    }
+
+    /// Asserts that the first line of the output, without the performance data, matches the
+    /// regular expression `pattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the regular expression, e.g. `r"^CRITICAL - \d+ disks? failed$"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::CheckResult;
+    /// use icingaplugin_rs::testing::CheckAssert;
+    /// CheckAssert::new(CheckResult::critical("2 disks failed")).first_line_matches(r"^CRITICAL - \d+ disks? failed$");
+    /// ```
+    #[cfg(feature = "regex")]
+    #[track_caller]
+    pub fn first_line_matches(self, pattern: &str) -> Self {
+        let re = regex::Regex::new(pattern).unwrap_or_else(|e| panic!("invalid pattern: {}", e));
+        let actual = self.get_first_line();
+        if !re.is_match(&actual) {
+            self.fail(&format!("expected first line matching '{}', got '{}'", pattern, actual));
+        }
+        self
+    }
+
+    /// Asserts that the first line of the output contains `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the expected part of the first line
+    #[track_caller]
+    pub fn first_line_contains(self, text: &str) -> Self {
+        let actual = self.get_first_line();
+        if !actual.contains(text) {
+            self.fail(&format!("expected first line containing '{}', got '{}'", text, actual));
+        }
+        self
+    }
+
+    /// Asserts that the long output consists of exactly `lines`
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - the expected lines
+    #[track_caller]
+    pub fn long_output(self, lines: &[&str]) -> Self {
+        if self.result.long_output() != lines {
+            self.fail(&format!("expected long output {:?}, got {:?}", lines, self.result.long_output()));
+        }
+        self
+    }
+
+    /// Asserts that a line of the long output contains `text`
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - the expected part of a line
+    #[track_caller]
+    pub fn long_output_contains(self, text: &str) -> Self {
+        if !self.result.long_output().iter().any(|l| l.contains(text)) {
+            self.fail(&format!("expected long output containing '{}'", text));
+        }
+        self
+    }
+
+    /// Asserts that the performance data contains a metric labeled `label` with `value`
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label of the metric
+    /// * `value` - the expected value, an integer and a float are never equal
+    #[track_caller]
+    pub fn metric<V: Into<Value>>(self, label: &str, value: V) -> Self {
+        let value = value.into();
+        match self.find_metric(label) {
+            Some(metric) if *metric.value() == value => {}
+            Some(metric) => self.fail(&format!("expected {} to be {}, got {}", label, value, metric.value())),
+            None => self.fail(&format!("expected metric {}", label)),
+        }
+        self
+    }
+
+    /// Asserts that the performance data contains a metric labeled `label` satisfying
+    /// `predicate`, e.g. to check its thresholds or UOM
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label of the metric
+    /// * `predicate` - returns whether the metric is as expected
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::{CheckResult, Metric};
+    /// use icingaplugin_rs::testing::CheckAssert;
+    /// let cr = CheckResult::ok("fine").add_metric(Metric::new("used", 12).uom("%").critical("90"));
+    /// CheckAssert::new(cr).metric_with("used", |m| m.to_string() == "'used'=12%;;90;;");
+    /// ```
+    #[track_caller]
+    pub fn metric_with<F: FnOnce(&Metric) -> bool>(self, label: &str, predicate: F) -> Self {
+        match self.find_metric(label) {
+            Some(metric) if predicate(metric) => {}
+            Some(metric) => self.fail(&format!("unexpected metric {}", metric)),
+            None => self.fail(&format!("expected metric {}", label)),
+        }
+        self
+    }
+
+    /// Asserts that the performance data contains no metric labeled `label`
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - the label of the metric
+    #[track_caller]
+    pub fn no_metric(self, label: &str) -> Self {
+        if self.find_metric(label).is_some() {
+            self.fail(&format!("unexpected metric {}", label));
+        }
+        self
+    }
+
+    /// Returns the metric labeled `label`, if any
+    fn find_metric(&self, label: &str) -> Option<&Metric> {
+        self.result.perf_data()?.iter().find(|m| m.label == label)
+    }
+
+    /// Panics with `message` and the complete output
+    #[track_caller]
+    fn fail(&self, message: &str) -> ! {
+        panic!("{}\nplugin output:\n{}", message, self.result)
+    }
+}
+
+
+impl From<CheckResult> for CheckAssert {
+    fn from(result: CheckResult) -> Self {
+        Self::new(result)
+    }
+}
+
+
+/// Executes a `Plugin` and asserts its state and optionally the first line of its output without
+/// the performance data, see `CheckAssert`. Evaluates to the `CheckAssert`, so further assertions
+/// can be chained.
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::assert_check;
+/// use icingaplugin_rs::check::{CheckResult, State};
+/// use icingaplugin_rs::runner::Error;
+///
+/// let check = || -> Result<CheckResult, Error> { Ok(CheckResult::critical("/var is 97% full")) };
+/// assert_check!(check, State::Critical);
+/// assert_check!(check, State::Critical, "CRITICAL - /var is 97% full").exit_code(2);
+/// ```
+#[macro_export]
+macro_rules! assert_check {
+    ($plugin:expr, $state:expr) => {
+        $crate::testing::CheckAssert::run($plugin).state($state)
+    };
+    ($plugin:expr, $state:expr, $first_line:expr) => {
+        $crate::testing::CheckAssert::run($plugin).state($state).first_line($first_line)
+    };
+}
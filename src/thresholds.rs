@@ -0,0 +1,308 @@
+use crate::check::State;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A threshold range in the Monitoring Plugins syntax (`10`, `10:`, `~:10`, `10:20`, `@10:20`).
+/// By default a value raises an alert if it lies outside of the range, `@`-ranges raise an alert
+/// if the value lies inside of the range. Both ends are inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    /// the lower end, `f64::NEG_INFINITY` if unbounded
+    start: f64,
+    /// the upper end, `f64::INFINITY` if unbounded
+    end: f64,
+    /// whether values inside of the range raise an alert
+    inside: bool,
+}
+
+
+impl Range {
+    /// Creates a new `Range` raising an alert for values outside of `start..=end`
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - the lower end, may be `f64::NEG_INFINITY`
+    /// * `end` - the upper end, may be `f64::INFINITY`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::thresholds::Range;
+    /// assert_eq!(Range::new(0.0, 10.0).to_string(), "10");
+    /// assert_eq!(Range::new(5.0, f64::INFINITY).to_string(), "5:");
+    /// ```
+    pub fn new(start: f64, end: f64) -> Self {
+        Self {
+            start,
+            end,
+            inside: false,
+        }
+    }
+
+    /// Creates a new `Range` raising an alert for values inside of `start..=end`
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - the lower end, may be `f64::NEG_INFINITY`
+    /// * `end` - the upper end, may be `f64::INFINITY`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::thresholds::Range;
+    /// assert_eq!(Range::inside(10.0, 20.0).to_string(), "@10:20");
+    /// ```
+    pub fn inside(start: f64, end: f64) -> Self {
+        Self {
+            start,
+            end,
+            inside: true,
+        }
+    }
+
+    /// Returns the lower end of the `Range`
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// Returns the upper end of the `Range`
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    /// Returns whether values inside of the `Range` raise an alert
+    pub fn is_inside(&self) -> bool {
+        self.inside
+    }
+
+    /// Returns whether `value` lies within `start..=end`, regardless of the alert direction
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to test
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::thresholds::Range;
+    /// let range: Range = "@10:20".parse().unwrap();
+    /// assert!(range.contains(10));
+    /// assert!(!range.contains(20.5));
+    /// ```
+    pub fn contains<T: Into<f64>>(&self, value: T) -> bool {
+        let v_64 = value.into();
+        self.start <= v_64 && v_64 <= self.end
+    }
+
+    /// Returns whether `value` raises an alert according to this `Range`. NaN always raises an
+    /// alert, as it is neither inside nor outside of any range.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to test
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::thresholds::Range;
+    /// let range: Range = "10".parse().unwrap();
+    /// assert!(!range.alert(10));
+    /// assert!(range.alert(-1));
+    /// assert!(range.alert(10.1));
+    /// let range: Range = "@10:20".parse().unwrap();
+    /// assert!(range.alert(15));
+    /// assert!(!range.alert(9.9));
+    /// assert!(range.alert(f64::NAN));
+    /// ```
+    pub fn alert<T: Into<f64>>(&self, value: T) -> bool {
+        let v_64 = value.into();
+        v_64.is_nan() || self.contains(v_64) == self.inside
+    }
+}
+
+
+impl FromStr for Range {
+    type Err = RangeError;
+
+    /// Parses a `Range` from the Monitoring Plugins range syntax
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::thresholds::{Range, RangeError};
+    /// let range: Range = "~:10".parse().unwrap();
+    /// assert_eq!(range.start(), f64::NEG_INFINITY);
+    /// assert_eq!(range.end(), 10.0);
+    /// assert_eq!("20:10".parse::<Range>(), Err(RangeError::StartAfterEnd));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (inside, s) = match s.strip_prefix('@') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        if s.is_empty() {
+            return Err(RangeError::Empty);
+        }
+
+        let (start, end) = match s.split_once(':') {
+            Some((start, end)) => {
+                let start = match start {
+                    "~" => f64::NEG_INFINITY,
+                    "" => 0.0,
+                    _ => parse_number(start)?,
+                };
+                let end = if end.is_empty() { f64::INFINITY } else { parse_number(end)? };
+                (start, end)
+            }
+            None => (0.0, parse_number(s)?),
+        };
+
+        if start > end {
+            return Err(RangeError::StartAfterEnd);
+        }
+
+        Ok(Self { start, end, inside })
+    }
+}
+
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.inside {
+            write!(f, "@")?;
+        }
+        if self.start == 0.0 && self.end.is_finite() {
+            return write!(f, "{}", self.end);
+        }
+        if self.start == f64::NEG_INFINITY {
+            write!(f, "~:")?;
+        } else {
+            write!(f, "{}:", self.start)?;
+        }
+        if self.end.is_finite() {
+            write!(f, "{}", self.end)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// The optional warning and critical `Range` of a value, as given by `-w` and `-c`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Thresholds {
+    /// the warning range, if any
+    warning: Option<Range>,
+    /// the critical range, if any
+    critical: Option<Range>,
+}
+
+
+impl Thresholds {
+    /// Creates new `Thresholds` without any range, so every value is OK
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    /// let thresholds = Thresholds::new().warning("80".parse().unwrap()).critical("90".parse().unwrap());
+    /// assert_eq!(thresholds.state(85), State::Warning);
+    /// assert_eq!(Thresholds::new().state(1e9), State::OK);
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the warning range
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - the warning range
+    pub fn warning(mut self, range: Range) -> Self {
+        self.warning = Some(range);
+        self
+    }
+
+    /// Sets the critical range
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - the critical range
+    pub fn critical(mut self, range: Range) -> Self {
+        self.critical = Some(range);
+        self
+    }
+
+    /// Returns the warning range, if set
+    pub fn warning_range(&self) -> Option<&Range> {
+        self.warning.as_ref()
+    }
+
+    /// Returns the critical range, if set
+    pub fn critical_range(&self) -> Option<&Range> {
+        self.critical.as_ref()
+    }
+
+    /// Returns the `State` of `value`. The critical range is checked first, ranges which are
+    /// not set never raise an alert. NaN values are UNKNOWN, as they cannot be compared. This is
+    /// the evaluation used by everything accepting `Range`s, e.g. `utils::evaluate_range()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value to evaluate
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use icingaplugin_rs::check::State;
+    /// use icingaplugin_rs::thresholds::Thresholds;
+    /// assert_eq!(Thresholds::new().state(f64::NAN), State::Unknown);
+    /// ```
+    pub fn state<T: Copy + Into<f64>>(&self, value: T) -> State {
+        if value.into().is_nan() {
+            State::Unknown
+        } else if self.critical.is_some_and(|c| c.alert(value)) {
+            State::Critical
+        } else if self.warning.is_some_and(|w| w.alert(value)) {
+            State::Warning
+        } else {
+            State::OK
+        }
+    }
+}
+
+
+/// An error occurring while parsing a `Range`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RangeError {
+    /// The range was empty
+    Empty,
+    /// One of the range ends is not a number
+    InvalidNumber(String),
+    /// The lower end of the range is greater than the upper end
+    StartAfterEnd,
+}
+
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Empty => write!(f, "empty range"),
+            RangeError::InvalidNumber(n) => write!(f, "'{}' is not a number", n),
+            RangeError::StartAfterEnd => write!(f, "start of range must not be greater than its end"),
+        }
+    }
+}
+
+
+impl Error for RangeError {}
+
+
+fn parse_number(s: &str) -> Result<f64, RangeError> {
+    match s.parse::<f64>() {
+        Ok(n) if !n.is_nan() => Ok(n),
+        _ => Err(RangeError::InvalidNumber(s.to_string())),
+    }
+}
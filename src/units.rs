@@ -0,0 +1,181 @@
+use crate::export::{convert, Unit};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Formats a number of bytes for the info line, scaled to the largest binary unit it reaches
+/// and rounded to one decimal place. Performance data should keep the raw bytes.
+///
+/// # Arguments
+///
+/// * `bytes` - the number of bytes
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::units::format_bytes;
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(1536), "1.5 KiB");
+/// assert_eq!(format!("{} of {} used", format_bytes(13_314_398_618), format_bytes(21_474_836_480)),
+///     "12.4 GiB of 20.0 GiB used");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    // scale up values which would be displayed as 1024.0 after rounding
+    while value >= 1023.95 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+
+/// Parses a size like `10GB`, `512MiB` or `1.5 TB` into bytes, e.g. for thresholds given on the
+/// command line. Decimal (`kB`, `MB`, ...) and binary units (`KiB`, `MiB`, ...) up to peta are
+/// understood, a number without unit is taken as bytes.
+///
+/// # Arguments
+///
+/// * `s` - the size
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::units::{parse_bytes, UnitError};
+/// assert_eq!(parse_bytes("10GB"), Ok(10_000_000_000));
+/// assert_eq!(parse_bytes("512MiB"), Ok(536_870_912));
+/// assert_eq!(parse_bytes("1.5 KiB"), Ok(1536));
+/// assert_eq!(parse_bytes("4096"), Ok(4096));
+/// assert_eq!(parse_bytes("10 apples"), Err(UnitError::InvalidSize(String::from("10 apples"))));
+/// ```
+pub fn parse_bytes(s: &str) -> Result<u64, UnitError> {
+    let invalid = || UnitError::InvalidSize(s.to_string());
+    let (number, unit) = split_number(s).ok_or_else(invalid)?;
+    let bytes = match convert(number, if unit.is_empty() { "B" } else { unit }) {
+        (Unit::Bytes, bytes) if bytes >= 0.0 && bytes < u64::MAX as f64 => bytes,
+        _ => return Err(invalid()),
+    };
+    Ok(bytes.round() as u64)
+}
+
+
+/// Parses a duration like `30s`, `5m`, `2h`, `1d` or `1h30m` into a `Duration`, e.g. for
+/// thresholds of age-based checks given on the command line. Understood units are `ms`, `s`,
+/// `m`, `h`, `d` and `w`, a number without unit is taken as seconds. Use `as_secs_f64()` to
+/// compare the result with a `Range`.
+///
+/// # Arguments
+///
+/// * `s` - the duration
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::units::{parse_duration, UnitError};
+/// use std::time::Duration;
+/// assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+/// assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+/// assert_eq!(parse_duration("1.5d").unwrap().as_secs_f64(), 129600.0);
+/// assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("5y"), Err(UnitError::InvalidDuration(String::from("5y"))));
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, UnitError> {
+    let invalid = || UnitError::InvalidDuration(s.to_string());
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut secs = 0.0;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let number: f64 = rest[..split].parse().map_err(|_| invalid())?;
+        rest = rest[split..].trim_start();
+        let end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let factor = match &rest[..end] {
+            // a bare number is only allowed on its own
+            "" if rest.is_empty() && split == s.trim().len() => 1.0,
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            "w" => 604800.0,
+            _ => return Err(invalid()),
+        };
+        secs += number * factor;
+        rest = rest[end..].trim_start();
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| invalid())
+}
+
+
+/// Formats a duration for the info line in days, hours, minutes and seconds, omitting the
+/// larger units it does not reach, e.g. `3d 4h 5m 6s`. Fractions of a second are dropped. The
+/// result can be parsed by `parse_duration()`.
+///
+/// # Arguments
+///
+/// * `duration` - the duration
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::units::{format_duration, parse_duration};
+/// use std::time::Duration;
+/// assert_eq!(format_duration(Duration::from_secs(93784)), "1d 2h 3m 4s");
+/// assert_eq!(format_duration(Duration::from_secs(3600)), "1h 0m 0s");
+/// assert_eq!(format_duration(Duration::from_millis(999)), "0s");
+/// assert_eq!(parse_duration(&format_duration(Duration::from_secs(93784))), Ok(Duration::from_secs(93784)));
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+
+/// Splits `s` into its leading number and the trimmed rest
+fn split_number(s: &str) -> Option<(f64, &str)> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+')).unwrap_or(s.len());
+    let number = s[..split].parse::<f64>().ok().filter(|n| n.is_finite())?;
+    Some((number, s[split..].trim_start()))
+}
+
+
+/// An error occurring while parsing a quantity with a unit
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UnitError {
+    /// The size is not a non-negative number followed by an optional byte unit
+    InvalidSize(String),
+    /// The duration is not a sequence of non-negative numbers followed by time units
+    InvalidDuration(String),
+}
+
+
+impl fmt::Display for UnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitError::InvalidSize(s) => write!(f, "invalid size '{}'", s),
+            UnitError::InvalidDuration(s) => write!(f, "invalid duration '{}'", s),
+        }
+    }
+}
+
+
+impl Error for UnitError {}
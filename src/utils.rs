@@ -1,5 +1,14 @@
-use crate::check::CheckResult;
+use crate::check::{CheckResult, Metric, PerfData, State, Value};
+use crate::runner::catch_panic;
+use crate::thresholds::{Range, Thresholds};
+use std::cmp::Reverse;
 use std::convert::Into;
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "tokio")]
+use std::future::Future;
 
 /// A function evaluating a given `value` with provided `warn` and `crit` thresholds to a
 /// `CheckResult` with the corresponding `State`. Supports different value types per argument.
@@ -32,31 +41,531 @@ pub fn evaluate<T, U, V>(value: T, warn: U, crit: V) -> CheckResult where
 T: Copy + Into<f64>,
 U: Copy + Into<f64>,
 V: Copy + Into<f64>
+{
+    match try_evaluate(value, warn, crit) {
+        Ok(cr) => cr,
+        Err(e) => panic!("{}!", e),
+    }
+}
+
+
+/// The non-panicking variant of `evaluate`, returning an `EvalError` for invalid thresholds, so
+/// callers can turn them into a clean UNKNOWN result.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - a warning threshold
+/// * `crit` - a critical threshold
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::CheckResult;
+/// use icingaplugin_rs::utils::{try_evaluate, EvalError};
+/// assert_eq!(try_evaluate(5, 3.2, 7.00), Ok(CheckResult::from(1)));
+/// assert_eq!(try_evaluate(1, 2, 2), Err(EvalError::EqualThresholds));
+/// assert_eq!(try_evaluate(f64::NAN, 2, 3), Err(EvalError::NotANumber));
+/// ```
+pub fn try_evaluate<T, U, V>(value: T, warn: U, crit: V) -> Result<CheckResult, EvalError> where
+T: Copy + Into<f64>,
+U: Copy + Into<f64>,
+V: Copy + Into<f64>
 {
     let v_64: f64 = value.into();
     let w_64: f64 = warn.into();
     let c_64: f64 = crit.into();
 
+    if v_64.is_nan() || w_64.is_nan() || c_64.is_nan() {
+        return Err(EvalError::NotANumber);
+    }
+
     if w_64 == c_64 {
-        panic!("warning and critical threshold must not be equal!");
+        return Err(EvalError::EqualThresholds);
     }
 
 
     if w_64 < c_64 {
         if v_64 >= c_64 {
-            return CheckResult::from(2);
+            return Ok(CheckResult::from(2));
         } else if v_64 >= w_64 {
-            return CheckResult::from(1);
+            return Ok(CheckResult::from(1));
         } else {
-            return CheckResult::from(0);
+            return Ok(CheckResult::from(0));
         }
     }
 
     if v_64 <= c_64 {
-        return CheckResult::from(2);
+        Ok(CheckResult::from(2))
     } else if v_64 <= w_64 {
-        return CheckResult::from(1);
+        Ok(CheckResult::from(1))
+    } else {
+        Ok(CheckResult::from(0))
+    }
+}
+
+
+/// A variant of `try_evaluate` for plugins run with only one or no threshold, as configured e.g.
+/// in Icinga Director. With both thresholds it behaves like `try_evaluate`, guessing the
+/// direction from their order. A single threshold cannot tell the direction, so it always
+/// raises its state for values at or above it, even if the missing threshold would have turned
+/// the direction around. Use `Range`s, e.g. `10:`, for single thresholds where lower values are
+/// worse. Without thresholds the result is always OK, e.g. for plugins only gathering metrics.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - an optional warning threshold
+/// * `crit` - an optional critical threshold
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::utils::evaluate_opt;
+/// assert_eq!(evaluate_opt(95, None::<i32>, Some(90)).unwrap().state(), State::Critical);
+/// assert_eq!(evaluate_opt(85, None::<i32>, Some(90)).unwrap().state(), State::OK);
+/// assert_eq!(evaluate_opt(85, Some(80), None::<i32>).unwrap().state(), State::Warning);
+/// assert_eq!(evaluate_opt(85, None::<i32>, None::<i32>).unwrap().state(), State::OK);
+/// assert_eq!(evaluate_opt(3, Some(2), Some(1)).unwrap().state(), State::OK);
+/// // above only, without the warning threshold telling that lower values are worse
+/// assert_eq!(evaluate_opt(3, None::<i32>, Some(1)).unwrap().state(), State::Critical);
+/// ```
+pub fn evaluate_opt<T, U, V>(value: T, warn: Option<U>, crit: Option<V>) -> Result<CheckResult, EvalError> where
+T: Copy + Into<f64>,
+U: Copy + Into<f64>,
+V: Copy + Into<f64>
+{
+    let v_64: f64 = value.into();
+    let w_64: Option<f64> = warn.map(Into::into);
+    let c_64: Option<f64> = crit.map(Into::into);
+
+    match (w_64, c_64) {
+        (Some(w), Some(c)) => try_evaluate(v_64, w, c),
+        _ if v_64.is_nan() || w_64.is_some_and(f64::is_nan) || c_64.is_some_and(f64::is_nan) => {
+            Err(EvalError::NotANumber)
+        }
+        (None, Some(c)) if v_64 >= c => Ok(CheckResult::new(State::Critical)),
+        (Some(w), None) if v_64 >= w => Ok(CheckResult::new(State::Warning)),
+        _ => Ok(CheckResult::new(State::OK)),
+    }
+}
+
+
+/// The direction in which a value becomes a problem, see `evaluate_direction`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Values at or above a threshold raise an alert, e.g. disk usage or response times
+    Above,
+    /// Values at or below a threshold raise an alert, e.g. free space or days until a
+    /// certificate expires
+    Below,
+}
+
+
+/// Evaluates `value` against the `warn` and `crit` thresholds in the explicitly given
+/// `direction`, unlike `evaluate`, which guesses it from the order of the thresholds. The
+/// critical threshold is checked first. A NaN value or threshold results in UNKNOWN, as it
+/// cannot be compared.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - a warning threshold
+/// * `crit` - a critical threshold
+/// * `direction` - whether values above or below the thresholds raise an alert
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::utils::{evaluate_direction, Direction};
+/// assert_eq!(evaluate_direction(85, 80, 90, Direction::Above).state(), State::Warning);
+/// assert_eq!(evaluate_direction(15, 20, 10, Direction::Below).state(), State::Warning);
+/// assert_eq!(evaluate_direction(f64::NAN, 80, 90, Direction::Above).state(), State::Unknown);
+/// assert_eq!(evaluate_direction(95, f64::NAN, 90, Direction::Above).state(), State::Unknown);
+/// ```
+pub fn evaluate_direction<T, U, V>(value: T, warn: U, crit: V, direction: Direction) -> CheckResult where
+T: Copy + Into<f64>,
+U: Copy + Into<f64>,
+V: Copy + Into<f64>
+{
+    let v_64: f64 = value.into();
+    let w_64: f64 = warn.into();
+    let c_64: f64 = crit.into();
+    let reached = |threshold: f64| match direction {
+        Direction::Above => v_64 >= threshold,
+        Direction::Below => v_64 <= threshold,
+    };
+
+    if v_64.is_nan() {
+        CheckResult::new(State::Unknown).set_info(String::from("value is not a number"))
+    } else if w_64.is_nan() || c_64.is_nan() {
+        CheckResult::new(State::Unknown).set_info(String::from("threshold is not a number"))
+    } else if reached(c_64) {
+        CheckResult::new(State::Critical)
+    } else if reached(w_64) {
+        CheckResult::new(State::Warning)
     } else {
-        return CheckResult::from(0);
+        CheckResult::new(State::OK)
+    }
+}
+
+
+/// Evaluates `value` against the `warn` and `crit` thresholds, raising an alert for values at
+/// or above them. Shorthand for `evaluate_direction(value, warn, crit, Direction::Above)`.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - a warning threshold
+/// * `crit` - a critical threshold
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::utils::evaluate_above;
+/// assert_eq!(evaluate_above(250, 200, 500).state(), State::Warning);
+/// assert_eq!(evaluate_above(500, 200, 500).state(), State::Critical);
+/// ```
+pub fn evaluate_above<T, U, V>(value: T, warn: U, crit: V) -> CheckResult where
+T: Copy + Into<f64>,
+U: Copy + Into<f64>,
+V: Copy + Into<f64>
+{
+    evaluate_direction(value, warn, crit, Direction::Above)
+}
+
+
+/// Evaluates `value` against the `warn` and `crit` thresholds, raising an alert for values at
+/// or below them. Shorthand for `evaluate_direction(value, warn, crit, Direction::Below)`.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - a warning threshold
+/// * `crit` - a critical threshold
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::utils::evaluate_below;
+/// let days_left = 12;
+/// assert_eq!(evaluate_below(days_left, 30, 7).state(), State::Warning);
+/// assert_eq!(evaluate_below(3, 30, 7).state(), State::Critical);
+/// ```
+pub fn evaluate_below<T, U, V>(value: T, warn: U, crit: V) -> CheckResult where
+T: Copy + Into<f64>,
+U: Copy + Into<f64>,
+V: Copy + Into<f64>
+{
+    evaluate_direction(value, warn, crit, Direction::Below)
+}
+
+
+/// Evaluates every metric against its `Thresholds` and returns a single `CheckResult` with the
+/// worst state, all metrics as performance data, their thresholds filled in, and an info line
+/// naming the offending metrics, worst first. Metrics without a numeric value are UNKNOWN.
+///
+/// # Arguments
+///
+/// * `metrics` - the metrics with their thresholds
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{Metric, State};
+/// use icingaplugin_rs::thresholds::Thresholds;
+/// use icingaplugin_rs::utils::evaluate_metrics;
+///
+/// let thresholds = Thresholds::new().warning("80".parse().unwrap()).critical("90".parse().unwrap());
+/// let percent = String::from("%");
+/// let cr = evaluate_metrics(&[
+///     (Metric::numeric("/", 42).uom(percent.clone()), thresholds),
+///     (Metric::numeric("/var", 85).uom(percent.clone()), thresholds),
+///     (Metric::numeric("/home", 95).uom(percent), thresholds),
+/// ]);
+/// assert_eq!(cr.state(), State::Critical);
+/// assert_eq!(cr.to_string(), "CRITICAL - 2 of 3 metrics alerting: /home=95% (CRITICAL), /var=85% (WARNING) \
+///     | '/'=42%;80;90;; '/var'=85%;80;90;; '/home'=95%;80;90;; ");
+///
+/// let cr = evaluate_metrics(&[(Metric::numeric("load1", 0.5), thresholds)]);
+/// assert_eq!(cr.to_string(), "OK - 1 metric ok | 'load1'=0.5;80;90;; ");
+/// ```
+pub fn evaluate_metrics(metrics: &[(Metric, Thresholds)]) -> CheckResult {
+    let mut evaluated = Vec::with_capacity(metrics.len());
+    let mut alerting = Vec::new();
+
+    for (metric, thresholds) in metrics {
+        let (state, metric) = apply_thresholds(metric, thresholds);
+        if state != State::OK {
+            let uom = metric.uom.as_deref().unwrap_or("");
+            alerting.push((state, format!("{}={}{} ({})", metric.label, metric.formatted_value(), uom, state)));
+        }
+        evaluated.push(metric);
+    }
+
+    let state = State::worst(alerting.iter().map(|(s, _)| *s));
+    let info = summarize_metrics(evaluated.len(), &worst_first(alerting));
+    let cr = CheckResult::new(state).set_info(info);
+    match evaluated.is_empty() {
+        true => cr,
+        false => cr.set_perf_data(PerfData::from_metrics(evaluated)),
+    }
+}
+
+
+/// Returns the descriptions of the items which are not OK, worst first
+pub(crate) fn worst_first<T, I: IntoIterator<Item = (State, T)>>(items: I) -> Vec<T> {
+    let mut alerting: Vec<_> = items.into_iter().filter(|(s, _)| *s != State::OK).collect();
+    // stable, so items with the same state keep their order
+    alerting.sort_by_key(|(s, _)| Reverse(*s));
+    alerting.into_iter().map(|(_, description)| description).collect()
+}
+
+
+/// Returns the info line for `total` metrics of which the ones described by `alerting` raise an
+/// alert, e.g. `3 metrics ok` or `1 of 3 metrics alerting: /var=95% (CRITICAL)`
+pub(crate) fn summarize_metrics<S: AsRef<str>>(total: usize, alerting: &[S]) -> String {
+    let plural = if total == 1 { "" } else { "s" };
+    if alerting.is_empty() {
+        return format!("{} metric{} ok", total, plural);
+    }
+    let names: Vec<_> = alerting.iter().map(AsRef::as_ref).collect();
+    format!("{} of {} metric{} alerting: {}", names.len(), total, plural, names.join(", "))
+}
+
+
+/// Returns the `State` of `metric` and a copy of it with the ranges of `thresholds` filled in.
+/// Metrics without a numeric value are UNKNOWN.
+pub(crate) fn apply_thresholds(metric: &Metric, thresholds: &Thresholds) -> (State, Metric) {
+    let mut metric = metric.clone();
+    if let Some(w) = thresholds.warning_range() {
+        metric = metric.warning(w.to_string());
+    }
+    if let Some(c) = thresholds.critical_range() {
+        metric = metric.critical(c.to_string());
+    }
+
+    let state = match metric.value() {
+        Value::Integer(i) => thresholds.state(*i as f64),
+        Value::Float(f) => thresholds.state(*f),
+        _ => State::Unknown,
+    };
+    (state, metric)
+}
+
+
+/// An error occurring while evaluating a value against thresholds
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EvalError {
+    /// The warning and critical threshold are equal, so the alerting direction is ambiguous
+    EqualThresholds,
+    /// The value or one of the thresholds is NaN
+    NotANumber,
+}
+
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::EqualThresholds => write!(f, "warning and critical threshold must not be equal"),
+            EvalError::NotANumber => write!(f, "value and thresholds must not be NaN"),
+        }
+    }
+}
+
+
+impl Error for EvalError {}
+
+
+/// Returns the `State` of `value` for the `warn` and `crit` threshold `Range`s, honoring the
+/// inside/outside semantics of the Monitoring Plugins range syntax: a value raises an alert if
+/// it lies outside of a range, or inside of an `@`-range. The critical range is checked first.
+/// Unlike `evaluate`, the alerting direction is never guessed from the thresholds. NaN values
+/// are UNKNOWN. This is a shorthand for `Thresholds::state()`.
+///
+/// # Arguments
+///
+/// * `value` - a value gathered by e.g. a check
+/// * `warn` - a warning threshold range
+/// * `crit` - a critical threshold range
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::State;
+/// use icingaplugin_rs::thresholds::Range;
+/// use icingaplugin_rs::utils::evaluate_range;
+/// let warn: Range = "@10:20".parse().unwrap();
+/// let crit: Range = "@15:20".parse().unwrap();
+/// assert_eq!(evaluate_range(5, &warn, &crit), State::OK);
+/// assert_eq!(evaluate_range(12, &warn, &crit), State::Warning);
+/// assert_eq!(evaluate_range(17.5, &warn, &crit), State::Critical);
+/// assert_eq!(evaluate_range(25, &warn, &crit), State::OK);
+/// assert_eq!(evaluate_range(f64::NAN, &warn, &crit), State::Unknown);
+/// ```
+pub fn evaluate_range<T>(value: T, warn: &Range, crit: &Range) -> State where
+T: Copy + Into<f64>
+{
+    Thresholds::new().warning(*warn).critical(*crit).state(value)
+}
+
+
+/// Runs `check` and returns its `CheckResult`. If it panics, an UNKNOWN `CheckResult` containing
+/// the panic message and location is returned instead, and no backtrace is printed which would
+/// end up in the plugin output. Use `runner::Runner` to also enforce a timeout.
+///
+/// # Arguments
+///
+/// * `check` - the check to run
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{CheckResult, State};
+/// use icingaplugin_rs::utils::guard;
+/// let cr = guard(|| CheckResult::new(State::OK));
+/// assert_eq!(cr.state(), State::OK);
+///
+/// let cr = guard(|| {
+///     let disks: Vec<u64> = Vec::new();
+///     CheckResult::new(State::OK).set_info(format!("{} bytes free", disks[0]))
+/// });
+/// assert_eq!(cr.state(), State::Unknown);
+/// assert!(cr.to_string().starts_with("UNKNOWN - check panicked: index out of bounds"));
+/// ```
+pub fn guard<F: FnOnce() -> CheckResult>(check: F) -> CheckResult {
+    catch_panic(check).unwrap_or_else(|panic| CheckResult::new(State::Unknown).set_info(panic))
+}
+
+
+/// Runs `op` up to `attempts` times while it returns an UNKNOWN `CheckResult`, sleeping
+/// `backoff` before the first retry and doubling it before each further one, so transient network
+/// blips do not raise alerts. Only UNKNOWN results, i.e. failed probes, are retried. OK, WARNING
+/// and CRITICAL results are genuine states and returned right away. If a retry succeeds, its info
+/// is annotated with the attempt, otherwise the result of the last attempt is returned, annotated
+/// with the number of attempts.
+///
+/// # Arguments
+///
+/// * `attempts` - the maximum number of attempts, at least one attempt is always made
+/// * `backoff` - the delay before the first retry
+/// * `op` - the probe to run
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{CheckResult, State};
+/// use icingaplugin_rs::utils::retry;
+/// use std::time::Duration;
+///
+/// let mut calls = 0;
+/// let cr = retry(5, Duration::from_millis(1), || {
+///     calls += 1;
+///     match calls {
+///         3 => CheckResult::new(State::OK).set_info(String::from("host is reachable")),
+///         _ => CheckResult::new(State::Unknown).set_info(String::from("connection refused")),
+///     }
+/// });
+/// assert_eq!(cr.to_string(), "OK - host is reachable (succeeded on attempt 3/5)");
+///
+/// let cr = retry(2, Duration::from_millis(1), || CheckResult::new(State::Unknown));
+/// assert_eq!(cr.to_string(), "UNKNOWN - failed on all 2 attempts");
+///
+/// // a WARNING is a result, not a failure
+/// let cr = retry(2, Duration::from_millis(1), || CheckResult::new(State::Warning));
+/// assert_eq!(cr.to_string(), "WARNING");
+/// ```
+pub fn retry<F: FnMut() -> CheckResult>(attempts: u32, backoff: Duration, mut op: F) -> CheckResult {
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        let cr = op();
+        if cr.state() != State::Unknown || attempt == attempts {
+            return annotate_attempts(cr, attempt, attempts);
+        }
+        thread::sleep(delay);
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
+
+/// The async variant of `retry`, sleeping on the tokio runtime between attempts. Like `retry`,
+/// it only retries UNKNOWN results.
+///
+/// # Arguments
+///
+/// * `attempts` - the maximum number of attempts, at least one attempt is always made
+/// * `backoff` - the delay before the first retry
+/// * `op` - a function returning the probe to run
+///
+/// # Examples
+///
+/// ```
+/// use icingaplugin_rs::check::{CheckResult, State};
+/// use icingaplugin_rs::utils::retry_async;
+/// use std::time::Duration;
+///
+/// let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// let mut calls = 0;
+/// let cr = rt.block_on(retry_async(3, Duration::from_millis(1), || {
+///     calls += 1;
+///     let state = if calls == 2 { State::OK } else { State::Unknown };
+///     async move { CheckResult::new(state) }
+/// }));
+/// assert_eq!(cr.to_string(), "OK - succeeded on attempt 2/3");
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn retry_async<F, Fut>(attempts: u32, backoff: Duration, mut op: F) -> CheckResult
+where F: FnMut() -> Fut, Fut: Future<Output = CheckResult> {
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        let cr = op().await;
+        if cr.state() != State::Unknown || attempt == attempts {
+            return annotate_attempts(cr, attempt, attempts);
+        }
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2);
+        attempt += 1;
+    }
+}
+
+
+/// Appends the number of attempts `retry` needed to the info of `cr`. Results of a single
+/// attempt are returned unchanged.
+fn annotate_attempts(cr: CheckResult, attempt: u32, attempts: u32) -> CheckResult {
+    if attempt == 1 {
+        return cr;
+    }
+    let note = if cr.state() == State::Unknown {
+        format!("failed on all {} attempts", attempts)
+    } else {
+        format!("succeeded on attempt {}/{}", attempt, attempts)
+    };
+    let info = match cr.info() {
+        Some(info) if !info.is_empty() => format!("{} ({})", info, note),
+        _ => note,
+    };
+    cr.set_info(info)
+}
+
+
+/// Computes the CRC32 (IEEE 802.3) of `data`, as used by NSCA and NRPE to verify packets
+#[cfg(any(feature = "nsca", feature = "nrpe"))]
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
     }
+    !crc
 }